@@ -0,0 +1,99 @@
+//! Throughput of the server messages: JSON parse, middleware and command dispatch, response serialize
+//! The commands run on the backend without Steam (cargo bench --features bench)
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use remoteplay_inviter::bench::Pipeline;
+use std::hint::black_box;
+
+const ALICE: &str = r#""user":{"id":"123456789012345678","name":"alice"}"#;
+
+/// Requests by name: protocol version and JSON text
+fn requests() -> Vec<(&'static str, &'static str, String)> {
+    vec![
+        (
+            "v1/link",
+            "1",
+            format!(r#"{{"id":"4",{ALICE},"cmd":"link","game":480}}"#),
+        ),
+        (
+            "v2/link",
+            "2",
+            format!(
+                r#"{{"id":"4",{ALICE},"key":null,"cmd":"link","game":480,"max_uses":null,"kind":"player"}}"#
+            ),
+        ),
+        (
+            "v2/revoke",
+            "2",
+            format!(r#"{{"id":"7",{ALICE},"key":null,"cmd":"revoke","guest_id":42,"reason":"Spam"}}"#),
+        ),
+        (
+            "v2/game",
+            "2",
+            format!(r#"{{"id":"5",{ALICE},"key":null,"cmd":"game"}}"#),
+        ),
+        // Shown in the console, nothing is answered
+        (
+            "v2/chat",
+            "2",
+            format!(r#"{{"id":"8",{ALICE},"key":null,"cmd":"chat","from":null,"text":"Can I join?"}}"#),
+        ),
+    ]
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for (name, version, text) in requests() {
+        let pipeline = Pipeline::new(version).unwrap();
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &text, |b, text| {
+            b.iter(|| pipeline.decode(black_box(text)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn dispatch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dispatch");
+    for (name, version, text) in requests() {
+        let pipeline = Pipeline::new(version).unwrap();
+        let request = pipeline.decode(&text).unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(name), &request, |b, request| {
+            b.iter(|| pipeline.dispatch(black_box(request)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    for (name, version, text) in requests() {
+        let pipeline = Pipeline::new(version).unwrap();
+        let request = pipeline.decode(&text).unwrap();
+        let Some(response) = pipeline.dispatch(&request).unwrap() else {
+            continue;
+        };
+        group.bench_with_input(
+            BenchmarkId::from_parameter(name),
+            &response,
+            |b, response| b.iter(|| pipeline.encode(black_box(response)).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+/// The whole path a text message takes through a connection
+fn process(c: &mut Criterion) {
+    let mut group = c.benchmark_group("process");
+    for (name, version, text) in requests() {
+        let pipeline = Pipeline::new(version).unwrap();
+        group.throughput(Throughput::Elements(1));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &text, |b, text| {
+            b.iter(|| pipeline.process(black_box(text)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode, dispatch, encode, process);
+criterion_main!(benches);
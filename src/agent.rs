@@ -0,0 +1,341 @@
+use anyhow::{Context as _, Result};
+use futures::SinkExt;
+use futures_util::stream::StreamExt;
+use tokio::{
+    net::UdpSocket,
+    time::{self, timeout, Duration},
+};
+use tokio_tungstenite::tungstenite::{
+    http::{uri::Builder, HeaderMap, Uri},
+    protocol::{Message, WebSocketConfig},
+};
+
+use crate::{
+    config::{self, AgentConfig, Config},
+    connection::{self, ConnectionStateMachine, Input},
+    console::{self, Glyph},
+    doh,
+    endpoint::{self, ServerUrl},
+    error::{ClientError, ClientResult, ErrorKind as _, Recovery},
+    handlers,
+    models::{ClientCmd, ClientMessage, ErrorStatus, Protocol, ServerCmd, ServerMessage},
+    open,
+    platform::encode_query,
+    transport::{self, UNIX_SCHEME},
+    ws_error_handler::{close_action, handle_ws_error, retry_after, CloseAction},
+    WsStream, DEFAULT_URL, MAX_FRAME_SIZE, MAX_MESSAGE_SIZE, VERSION,
+};
+
+/// Magic packets sent per wake request (UDP may drop one)
+const PACKETS: usize = 3;
+
+/// Runs the Wake-on-LAN agent: stays registered with the server and wakes the gaming PC when a session is requested
+/// Only the primary server of the endpoint configuration is used
+pub async fn run(config: &Config, device_id: &str) -> Result<()> {
+    // Refuse to start with a MAC address that cannot be woken
+    if let Some(mac) = &config.agent.mac {
+        parse_mac(mac)?;
+    }
+    let (url, headers) = agent_url(config, device_id)?;
+    let mut url = ServerUrl::new(url);
+    let connector = transport::connector(config.tls.backend)?;
+    let resolver = doh::Resolver::new(&config.dns, config.timeouts.connect());
+    // Keep an absurd payload from exhausting the memory
+    let ws_config = WebSocketConfig {
+        max_message_size: Some(MAX_MESSAGE_SIZE),
+        max_frame_size: Some(MAX_FRAME_SIZE),
+        ..Default::default()
+    };
+    console::println!(
+        "{} Agent mode: {} is woken when the server requests a session",
+        Glyph::Ok,
+        config.agent.mac.as_deref().unwrap_or("the requested PC")
+    )?;
+
+    let mut machine = ConnectionStateMachine::new();
+    loop {
+        let result = match machine.state().clone() {
+            connection::State::Connecting { reconnect } => 'connect: {
+                if reconnect {
+                    console::println!("{} Reconnecting to the server...", Glyph::Retry)?;
+                }
+                let connect_result = match timeout(
+                    config.timeouts.connect(),
+                    open(
+                        url.as_str().to_owned(),
+                        &headers,
+                        ws_config,
+                        &config.keepalive,
+                        resolver.as_ref(),
+                        connector.clone(),
+                    ),
+                )
+                .await
+                .context("Connection timed out to the server")
+                .network()
+                {
+                    Ok(connect_result) => connect_result,
+                    Err(err) => {
+                        connect_failed(&mut url)?;
+                        break 'connect Err(err);
+                    }
+                };
+                match connect_result {
+                    Ok((ws_stream, _)) => {
+                        url.connected();
+                        machine.handle(Input::Opened);
+                        run_session(config, &mut machine, &mut url, ws_stream).await
+                    }
+                    Err(err) => {
+                        connect_failed(&mut url)?;
+                        // The server is overloaded or restarting
+                        if let Some(sec) = retry_after(&err) {
+                            machine.handle(Input::Hint(sec));
+                        }
+                        // If OK is returned, stop (the agent does not pair, even when unlinked)
+                        handle_ws_error(err).map(|_| Input::Stop)
+                    }
+                }
+            }
+            // Only reached through a session, which runs right after connecting
+            connection::State::Connected { .. } => Ok(Input::Lost { planned: false }),
+            connection::State::Backoff { wait, .. } => {
+                console::println!(
+                    "{} Connection lost. Reconnecting in {:.1} seconds...",
+                    Glyph::Retry,
+                    wait.as_secs_f32()
+                )?;
+                time::sleep(wait).await;
+                Ok(Input::Elapsed)
+            }
+            connection::State::Fatal => return Ok(()),
+            connection::State::GaveUp { retries } => {
+                return Err(connection::gave_up(retries).into())
+            }
+        };
+
+        let input = match result {
+            Ok(input) => input,
+            // Only connection problems are worth another try
+            Err(err) if err.recovery() == Recovery::Retry => {
+                console::eprintln!("{} {}", Glyph::Err, err)?;
+                Input::Lost { planned: false }
+            }
+            Err(err) => return Err(err.into()),
+        };
+        machine.handle(input);
+    }
+}
+
+/// URL of the primary server (registered as an agent) and the request headers
+fn agent_url(config: &Config, device_id: &str) -> Result<(String, HeaderMap)> {
+    let (url, headers) = match config::read_endpoint_config()? {
+        Some(e) => (
+            e.url.unwrap_or_else(|| DEFAULT_URL.to_owned()),
+            endpoint::request_headers(e.user_agent.as_deref(), &e.headers)?,
+        ),
+        None => (
+            DEFAULT_URL.to_owned(),
+            endpoint::request_headers(None, &Default::default())?,
+        ),
+    };
+    let path_and_query = format!(
+        "/ws?v={VERSION}&token={0}&device={1}&agent=1",
+        config.uuid,
+        encode_query(device_id)
+    );
+
+    // A server on the same host listening on a Unix domain socket
+    if let Some((socket, _)) = transport::unix_socket(&url) {
+        return Ok((format!("{UNIX_SCHEME}{socket}:{path_and_query}"), headers));
+    }
+    let uri: Uri = url.parse().context("Failed to parse URL")?;
+    let uri = Builder::from(uri)
+        .path_and_query(path_and_query)
+        .build()
+        .context("Failed to build URL")?;
+    Ok((uri.to_string(), headers))
+}
+
+/// Goes back to the configured server when the one the agent was moved to stays unreachable
+fn connect_failed(url: &mut ServerUrl) -> Result<()> {
+    if let Some(server) = url.connect_failed() {
+        console::println!("{} Moving back to {server}", Glyph::Retry)?;
+    }
+    Ok(())
+}
+
+/**
+ * Answers the wake requests of the server until the connection ends
+ * @return How the connection ended
+ */
+async fn run_session(
+    config: &Config,
+    machine: &mut ConnectionStateMachine,
+    url: &mut ServerUrl,
+    ws_stream: WsStream,
+) -> ClientResult<Input> {
+    let (mut write, mut read) = ws_stream.split();
+    console::println!("{} Connected to the server!", Glyph::Ok).console()?;
+
+    // Heartbeats keep the connection from timing out while no session is requested
+    let mut heartbeat = time::interval(Duration::from_secs(config.shaping.heartbeat_secs.max(1)));
+    heartbeat.reset();
+    loop {
+        let message = tokio::select! {
+            message = timeout(config.timeouts.read(), read.next()) => {
+                message.context("Connection timed out").network()?
+            }
+            _ = heartbeat.tick() => {
+                write
+                    .send(Message::Ping(Vec::new()))
+                    .await
+                    .context("Failed to send ping message to the server")
+                    .network()?;
+                continue;
+            }
+        };
+        // The stream ended without a close frame
+        let Some(message) = message else {
+            return Ok(Input::Lost { planned: false });
+        };
+        let message = message
+            .context("Failed to receive message from the server")
+            .network()?;
+
+        match message {
+            Message::Close(frame) => match close_action(frame.as_ref()) {
+                CloseAction::Reconnect { planned } => return Ok(Input::Lost { planned }),
+                CloseAction::Stop(reason) => {
+                    console::eprintln!("{} Disconnected by the server: {reason}", Glyph::Err)
+                        .console()?;
+                    return Ok(Input::Stop);
+                }
+                CloseAction::Redirect(new_url) => {
+                    match url.redirect(&new_url) {
+                        Ok(server) => {
+                            console::println!("{} Moving to {server}", Glyph::Retry).console()?;
+                            machine.handle(Input::Reset);
+                        }
+                        Err(err) => console::eprintln!("{} {err}", Glyph::Warn).console()?,
+                    }
+                    return Ok(Input::Lost { planned: true });
+                }
+            },
+            Message::Ping(ping) => {
+                write
+                    .send(Message::Pong(ping))
+                    .await
+                    .context("Failed to send pong message to the server")
+                    .network()?;
+                machine.handle(Input::Received);
+            }
+            Message::Text(text) => {
+                let msg: ServerMessage =
+                    serde_json::from_str(&text).map_err(|err| ClientError::Protocol(err.into()))?;
+                msg.check()
+                    .context("Invalid message from the server")
+                    .protocol()?;
+                machine.handle(Input::Received);
+
+                let request = msg.request_id();
+                let cmd = match msg.cmd {
+                    ServerCmd::WakeHost { .. } if !config.permissions.permits("wake_host") => {
+                        ClientCmd::Error {
+                            code: ErrorStatus::PermissionDenied,
+                        }
+                    }
+                    ServerCmd::WakeHost { mac } => {
+                        match wake(&config.agent, mac.as_deref()).await {
+                            Ok(mac) => {
+                                console::println!("{} Woke the gaming PC ({mac})", Glyph::Ok)
+                                    .console()?;
+                                ClientCmd::Waking { mac }
+                            }
+                            Err(err) => {
+                                console::eprintln!("{} {:#}", Glyph::Err, err).console()?;
+                                ClientCmd::Error {
+                                    code: ErrorStatus::WakeFailed,
+                                }
+                            }
+                        }
+                    }
+                    ServerCmd::Backoff { secs } => {
+                        machine.handle(Input::Hint(secs));
+                        continue;
+                    }
+                    ServerCmd::Exit => return Ok(Input::Stop),
+                    // The other requests are for the inviter on the gaming PC
+                    _ if !msg.id.is_empty() => ClientCmd::Error {
+                        code: ErrorStatus::InvalidCmd,
+                    },
+                    _ => continue,
+                };
+                handlers::send_message(
+                    &ClientMessage::reply(request, cmd),
+                    // Agents came with the second version
+                    Protocol::V2,
+                    &mut write,
+                )
+                .await?;
+            }
+            _ => (),
+        }
+    }
+}
+
+/**
+ * Sends Wake-on-LAN magic packets to the gaming PC
+ * @param requested MAC address requested by the server (only the configured one is woken if set)
+ * @return The MAC address that was woken
+ */
+async fn wake(agent: &AgentConfig, requested: Option<&str>) -> Result<String> {
+    let mac = match (requested, agent.mac.as_deref()) {
+        (Some(requested), Some(configured)) if parse_mac(requested)? != parse_mac(configured)? => {
+            anyhow::bail!(
+                "The server asked to wake {requested}, but only {configured} may be woken"
+            );
+        }
+        (_, Some(mac)) | (Some(mac), None) => mac,
+        (None, None) => {
+            anyhow::bail!("The MAC address of the gaming PC is not set (mac in [agent])")
+        }
+    };
+
+    // 6 bytes of 0xFF followed by the MAC address 16 times
+    let mut packet = vec![0xFF; 6];
+    for _ in 0..16 {
+        packet.extend_from_slice(&parse_mac(mac)?);
+    }
+    let socket = UdpSocket::bind(("0.0.0.0", 0))
+        .await
+        .context("Unable to open a UDP socket")?;
+    socket
+        .set_broadcast(true)
+        .context("Unable to enable broadcasts")?;
+    for _ in 0..PACKETS {
+        socket
+            .send_to(&packet, (agent.broadcast.as_str(), agent.port))
+            .await
+            .with_context(|| format!("Unable to send the magic packet to {}", agent.broadcast))?;
+    }
+    Ok(mac.to_owned())
+}
+
+/// Parses a MAC address (`01:23:45:67:89:ab` or `01-23-45-67-89-AB`)
+fn parse_mac(text: &str) -> Result<[u8; 6]> {
+    let mut mac = [0; 6];
+    let mut parts = text.split([':', '-']);
+    for byte in &mut mac {
+        let part = parts
+            .next()
+            .filter(|part| part.len() == 2)
+            .with_context(|| format!("Invalid MAC address: {text}"))?;
+        *byte =
+            u8::from_str_radix(part, 16).with_context(|| format!("Invalid MAC address: {text}"))?;
+    }
+    if parts.next().is_some() {
+        anyhow::bail!("Invalid MAC address: {text}");
+    }
+    Ok(mac)
+}
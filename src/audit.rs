@@ -0,0 +1,203 @@
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::{fs, io::Write as _, path::PathBuf};
+
+use crate::{
+    config,
+    console::{self, Glyph},
+    files,
+};
+
+/// Moderation actions
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// A guest was disconnected
+    Kick,
+    /// A guest was banned
+    Ban,
+    /// A ban was lifted
+    Unban,
+    /// A pending guest was approved
+    Approve,
+    /// A pending guest was denied
+    Deny,
+    /// A joining guest was disconnected automatically (banned or exhausted link)
+    Reject,
+    /// Invite creation was paused
+    Pause,
+    /// Invite creation was resumed
+    Resume,
+    /// An invite link was revoked
+    Revoke,
+    /// The recent log was sent to the server (diagnostics request)
+    ShareLogs,
+}
+
+/// Who initiated an action
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    /// The host at the console prompt
+    Console,
+    /// The host through the local control API (subcommands, integrations)
+    Api,
+    /// The server (Discord bot)
+    Server,
+    /// The host through a desktop notification button
+    #[cfg_attr(not(windows), allow(dead_code))]
+    Notification,
+    /// A user script
+    #[cfg_attr(not(feature = "scripting"), allow(dead_code))]
+    Script,
+    /// The client itself (timeouts, bans, usage limits)
+    Auto,
+}
+
+/// Who initiated an action and why
+#[derive(Debug, Clone)]
+pub struct Origin {
+    /// Initiator
+    pub source: Source,
+    /// Reason given by the initiator
+    pub reason: Option<String>,
+}
+
+impl Origin {
+    pub fn new(source: Source, reason: Option<String>) -> Self {
+        Self { source, reason }
+    }
+
+    /// Action taken by the client itself
+    pub fn auto(reason: &str) -> Self {
+        Self::new(Source::Auto, Some(reason.to_owned()))
+    }
+}
+
+/// Audit log entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    /// When the action was taken
+    pub time: DateTime<Utc>,
+    /// Action
+    pub action: Action,
+    /// Initiator
+    pub source: Source,
+    /// Reason given by the initiator
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Guest ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guest_id: Option<u64>,
+    /// Steam ID of the guest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub steam_id: Option<u64>,
+    /// Discord user associated with the guest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Additional details (e.g. ban expiry)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Entry {
+    pub fn new(action: Action, origin: &Origin) -> Self {
+        Self {
+            time: Utc::now(),
+            action,
+            source: origin.source,
+            reason: origin.reason.clone(),
+            guest_id: None,
+            steam_id: None,
+            name: None,
+            detail: None,
+        }
+    }
+
+    /// Sets the guest the action applies to
+    pub fn guest(
+        mut self,
+        guest_id: Option<u64>,
+        steam_id: Option<u64>,
+        name: Option<String>,
+    ) -> Self {
+        self.guest_id = guest_id;
+        self.steam_id = steam_id;
+        self.name = name;
+        self
+    }
+
+    /// Sets additional details
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
+/// Path of the audit log (one JSON object per line)
+fn audit_path() -> Result<PathBuf> {
+    Ok(config::get_exe_path()?.with_extension("audit.jsonl"))
+}
+
+/// Appends an entry to the audit log
+pub fn record(entry: Entry) {
+    let result: Result<()> = 'tryblock: {
+        let path = match audit_path() {
+            Ok(path) => path,
+            Err(err) => break 'tryblock Err(err),
+        };
+        let line = match serde_json::to_string(&entry).context("Unable to serialize audit entry") {
+            Ok(line) => line,
+            Err(err) => break 'tryblock Err(err),
+        };
+        files::append(&path)
+            .and_then(|mut file| writeln!(file, "{line}"))
+            .with_context(|| format!("Unable to write audit log: {:?}", path))
+    };
+    // The action itself already happened, so only report the failure
+    if let Err(err) = result {
+        let _ = console::eprintln!("{} {}", Glyph::Err, err);
+    }
+}
+
+/// `audit show` subcommand
+pub fn show(limit: usize) -> Result<()> {
+    let path = audit_path()?;
+    if !path.exists() {
+        console::println!("{} No moderation actions recorded yet", Glyph::Ok)?;
+        return Ok(());
+    }
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Unable to read audit log: {:?}", path))?;
+    let entries: Vec<Entry> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+
+    for entry in &entries[entries.len().saturating_sub(limit)..] {
+        let mut line = format!(
+            "{} {:<7} by {:<7}",
+            entry.time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+            format!("{:?}", entry.action).to_lowercase(),
+            format!("{:?}", entry.source).to_lowercase(),
+        );
+        if let Some(name) = &entry.name {
+            line.push_str(&format!(" claimer={name}"));
+        }
+        if let Some(guest_id) = entry.guest_id {
+            line.push_str(&format!(" guest_id={guest_id}"));
+        }
+        if let Some(steam_id) = entry.steam_id {
+            line.push_str(&format!(" steam_id={steam_id}"));
+        }
+        if let Some(detail) = &entry.detail {
+            line.push_str(&format!(" {detail}"));
+        }
+        if let Some(reason) = &entry.reason {
+            line.push_str(&format!(" reason=\"{reason}\""));
+        }
+        console::println!("{}", line.trim_end())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,193 @@
+use anyhow::{Context as _, Result};
+use serde::Deserialize;
+use std::{
+    cmp::Reverse,
+    ffi::OsStr,
+    fs,
+    io::Read as _,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tokio::task;
+
+use crate::{
+    config::{self, AvatarsConfig},
+    files,
+    logger::{self, Level},
+};
+
+/// Player summaries of the Steam Web API
+const SUMMARIES_API: &str = "https://api.steampowered.com/ISteamUser/GetPlayerSummaries/v2/";
+/// Largest avatar that is saved (the 64x64 JPEGs are a few KiB)
+const MAX_AVATAR_SIZE: u64 = 256 * 1024;
+/// Extension of the cached avatars
+const AVATAR_EXTENSION: &str = "jpg";
+
+#[derive(Deserialize)]
+struct SummariesResponse {
+    response: Summaries,
+}
+
+#[derive(Deserialize)]
+struct Summaries {
+    #[serde(default)]
+    players: Vec<PlayerSummary>,
+}
+
+#[derive(Deserialize)]
+struct PlayerSummary {
+    /// URL of the 64x64 avatar
+    avatarmedium: String,
+}
+
+/// Avatars of the guests' Steam accounts, cached on disk for the notifications
+pub struct Avatars {
+    config: AvatarsConfig,
+    /// Steam Web API key
+    api_key: String,
+    /// How long Steam may take to answer
+    timeout: Duration,
+    dir: PathBuf,
+}
+
+impl Avatars {
+    /// Avatar cache (None without an API key)
+    pub fn new(config: &AvatarsConfig, timeout: Duration) -> Option<Self> {
+        let api_key = config.api_key.clone()?;
+        let dir = avatar_dir().ok()?;
+        Some(Self {
+            config: config.clone(),
+            api_key,
+            timeout,
+            dir,
+        })
+    }
+
+    fn path(&self, steam_id: u64) -> PathBuf {
+        self.dir.join(format!("{steam_id}.{AVATAR_EXTENSION}"))
+    }
+
+    /// Cached avatar of a Steam user, even if outdated (no download)
+    pub fn cached(&self, steam_id: u64) -> Option<PathBuf> {
+        let path = self.path(steam_id);
+        path.is_file().then_some(path)
+    }
+
+    /**
+     * Avatar of a Steam user, downloaded if it is not cached or outdated
+     * @return The image file (the outdated one while Steam is unreachable)
+     */
+    pub async fn fetch(&self, steam_id: u64) -> Option<PathBuf> {
+        if steam_id == 0 {
+            return None;
+        }
+        let path = self.path(steam_id);
+        let max_age = Duration::from_secs(self.config.max_age_days * 24 * 60 * 60);
+        if age(&path).is_some_and(|age| age < max_age) {
+            return Some(path);
+        }
+
+        let api_key = self.api_key.clone();
+        let timeout = self.timeout;
+        let download = path.clone();
+        let result =
+            task::spawn_blocking(move || download_avatar(&api_key, steam_id, timeout, &download))
+                .await
+                .context("Avatar download task failed")
+                .and_then(|result| result);
+        match result {
+            Ok(()) => {
+                if let Err(err) = apply_size_limit(&self.dir, self.config.max_cache_kb * 1024) {
+                    logger::write(Level::Warn, format_args!("{err:#}"));
+                }
+                Some(path)
+            }
+            Err(err) => {
+                // Offline: keep showing the outdated avatar
+                logger::write(
+                    Level::Warn,
+                    format_args!("Unable to download the avatar of {steam_id}: {err:#}"),
+                );
+                self.cached(steam_id)
+            }
+        }
+    }
+}
+
+/// Directory containing the cached avatars
+fn avatar_dir() -> Result<PathBuf> {
+    Ok(config::get_exe_path()?.with_extension("avatars"))
+}
+
+/// Time since the file was written (None if it does not exist)
+fn age(path: &Path) -> Option<Duration> {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    Some(
+        SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default(),
+    )
+}
+
+/// Looks up the avatar URL of a Steam user and saves the image
+fn download_avatar(api_key: &str, steam_id: u64, timeout: Duration, path: &Path) -> Result<()> {
+    let summaries: SummariesResponse = ureq::get(SUMMARIES_API)
+        .query("key", api_key)
+        .query("steamids", &steam_id.to_string())
+        .timeout(timeout)
+        .call()
+        .context("Steam Web API request failed")?
+        .into_json()
+        .context("Invalid Steam Web API response")?;
+    let Some(player) = summaries.response.players.into_iter().next() else {
+        anyhow::bail!("Steam Web API has no profile for {steam_id}");
+    };
+
+    let mut image = Vec::new();
+    ureq::get(&player.avatarmedium)
+        .timeout(timeout)
+        .call()
+        .context("Avatar request failed")?
+        .into_reader()
+        .take(MAX_AVATAR_SIZE + 1)
+        .read_to_end(&mut image)
+        .context("Unable to read the avatar")?;
+    if image.len() as u64 > MAX_AVATAR_SIZE {
+        anyhow::bail!("Avatar is larger than {} KiB", MAX_AVATAR_SIZE / 1024);
+    }
+
+    let dir = path.parent().context("Invalid avatar path")?;
+    files::create_dir_all(dir)
+        .with_context(|| format!("Unable to create avatar directory: {:?}", dir))?;
+    files::write(path, image).with_context(|| format!("Unable to write avatar: {:?}", path))
+}
+
+/// Deletes the oldest avatars until the cache fits in the size limit
+fn apply_size_limit(dir: &Path, max_size: u64) -> Result<()> {
+    let mut avatars = Vec::new();
+    for entry in
+        fs::read_dir(dir).with_context(|| format!("Unable to read avatar directory: {:?}", dir))?
+    {
+        let entry = entry.context("Unable to read avatar directory entry")?;
+        let path = entry.path();
+        if path.extension() != Some(OsStr::new(AVATAR_EXTENSION)) {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        avatars.push((path, metadata.len(), modified));
+    }
+    // Newest first, the oldest ones past the limit are deleted
+    avatars.sort_by_key(|(_, _, modified)| Reverse(*modified));
+    let mut total = 0;
+    for (path, size, _) in avatars {
+        total += size;
+        if total > max_size {
+            fs::remove_file(&path)
+                .with_context(|| format!("Unable to delete avatar: {:?}", path))?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,237 @@
+use anyhow::{anyhow, bail, Context as _, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display},
+    fs,
+    path::PathBuf,
+    str::FromStr,
+};
+
+use crate::{config, files};
+
+/// How long a ban lasts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum BanDuration {
+    /// Never expires
+    Permanent,
+    /// Expires after the duration
+    For(Duration),
+}
+
+impl FromStr for BanDuration {
+    type Err = anyhow::Error;
+
+    /// Parses `permanent` or a number followed by `m`, `h` or `d` (e.g. `30m`, `7d`)
+    fn from_str(s: &str) -> Result<Self> {
+        if s.eq_ignore_ascii_case("permanent") {
+            return Ok(BanDuration::Permanent);
+        }
+        let invalid =
+            || anyhow!("Invalid ban duration: {s} (expected e.g. 30m, 12h, 7d or permanent)");
+        let (count, unit) = s.split_at(s.len().saturating_sub(1));
+        let count: i64 = count.parse().map_err(|_| invalid())?;
+        if count <= 0 {
+            return Err(invalid());
+        }
+        let duration = match unit {
+            "m" => Duration::try_minutes(count),
+            "h" => Duration::try_hours(count),
+            "d" => Duration::try_days(count),
+            _ => None,
+        };
+        duration.map(BanDuration::For).ok_or_else(invalid)
+    }
+}
+
+impl TryFrom<String> for BanDuration {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<BanDuration> for String {
+    fn from(duration: BanDuration) -> Self {
+        duration.to_string()
+    }
+}
+
+impl Display for BanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BanDuration::Permanent => f.write_str("permanent"),
+            BanDuration::For(duration) if duration.num_minutes() % (24 * 60) == 0 => {
+                write!(f, "{}d", duration.num_days())
+            }
+            BanDuration::For(duration) if duration.num_minutes() % 60 == 0 => {
+                write!(f, "{}h", duration.num_hours())
+            }
+            BanDuration::For(duration) => write!(f, "{}m", duration.num_minutes()),
+        }
+    }
+}
+
+/// A banned guest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ban {
+    /// Steam ID of the guest
+    pub steam_id: u64,
+    /// Discord user associated with the guest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// When the ban was issued
+    pub since: DateTime<Utc>,
+    /// When the ban expires (never if not set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Ban {
+    /// Whether the ban is still in effect
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.until.map(|until| until > now).unwrap_or(true)
+    }
+}
+
+/// Ban list persisted next to the executable
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BanList {
+    /// Banned guests
+    #[serde(default)]
+    pub bans: Vec<Ban>,
+}
+
+impl BanList {
+    /// Path of the ban list file
+    fn path() -> Result<PathBuf> {
+        Ok(config::get_exe_path()?.with_extension("bans.toml"))
+    }
+
+    /// Reads the ban list (empty if the file does not exist)
+    pub fn load() -> Result<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Unable to read ban list: {:?}", path))?;
+        let mut list: Self = toml::from_str(&content).context("Unable to parse ban list")?;
+        list.prune();
+        Ok(list)
+    }
+
+    /// Writes the ban list
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path()?;
+        let content = toml::to_string(self).context("Unable to serialize ban list")?;
+        files::write(&path, content)
+            .with_context(|| format!("Unable to write ban list: {:?}", path))
+    }
+
+    /// Drops the expired bans
+    pub fn prune(&mut self) {
+        let now = Utc::now();
+        self.bans.retain(|ban| ban.is_active(now));
+    }
+
+    /// Active ban of the guest
+    pub fn find(&self, steam_id: u64) -> Option<&Ban> {
+        let now = Utc::now();
+        self.bans
+            .iter()
+            .find(|ban| ban.steam_id == steam_id && ban.is_active(now))
+    }
+
+    /// Bans a guest (replacing any previous ban) and saves the list
+    pub fn ban(
+        &mut self,
+        steam_id: u64,
+        name: Option<String>,
+        duration: BanDuration,
+    ) -> Result<Ban> {
+        if steam_id == 0 {
+            bail!("The Steam ID of the guest is unknown");
+        }
+        let since = Utc::now();
+        let ban = Ban {
+            steam_id,
+            name,
+            since,
+            until: match duration {
+                BanDuration::Permanent => None,
+                BanDuration::For(duration) => Some(since + duration),
+            },
+        };
+        self.bans.retain(|ban| ban.steam_id != steam_id);
+        self.bans.push(ban.clone());
+        self.save()?;
+        Ok(ban)
+    }
+
+    /// Lifts the ban of a guest and saves the list
+    pub fn unban(&mut self, steam_id: u64) -> Result<Ban> {
+        let index = self
+            .bans
+            .iter()
+            .position(|ban| ban.steam_id == steam_id)
+            .with_context(|| format!("Guest is not banned: steam_id={steam_id}"))?;
+        let ban = self.bans.remove(index);
+        self.save()?;
+        Ok(ban)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ban(steam_id: u64, until: Option<DateTime<Utc>>) -> Ban {
+        Ban {
+            steam_id,
+            name: None,
+            since: Utc::now() - Duration::days(1),
+            until,
+        }
+    }
+
+    #[test]
+    fn bans_expire_at_their_end() {
+        let now = Utc::now();
+        assert!(ban(1, None).is_active(now));
+        assert!(ban(1, Some(now + Duration::minutes(1))).is_active(now));
+        assert!(!ban(1, Some(now)).is_active(now));
+        assert!(!ban(1, Some(now - Duration::minutes(1))).is_active(now));
+    }
+
+    #[test]
+    fn expired_bans_are_ignored_and_pruned() {
+        let mut list = BanList {
+            bans: vec![
+                ban(1, Some(Utc::now() - Duration::hours(1))),
+                ban(2, Some(Utc::now() + Duration::hours(1))),
+                ban(3, None),
+            ],
+        };
+        assert!(list.find(1).is_none());
+        assert!(list.find(2).is_some());
+        assert!(list.find(3).is_some());
+        list.prune();
+        let remaining: Vec<u64> = list.bans.iter().map(|ban| ban.steam_id).collect();
+        assert_eq!(remaining, [2, 3]);
+    }
+
+    #[test]
+    fn ban_durations_round_trip() {
+        for text in ["30m", "12h", "7d", "permanent"] {
+            assert_eq!(text.parse::<BanDuration>().unwrap().to_string(), text);
+        }
+        assert_eq!("90m".parse::<BanDuration>().unwrap().to_string(), "90m");
+        assert_eq!("120m".parse::<BanDuration>().unwrap().to_string(), "2h");
+        for text in ["", "0d", "-1h", "7w", "d"] {
+            assert!(text.parse::<BanDuration>().is_err(), "{text}");
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Message path of a server connection for the benchmarks in benches/ (not a stable API)
+
+use anyhow::{Context as _, Result};
+use std::sync::Arc;
+use steam_stuff::NoSteam;
+use tokio::{runtime::Runtime, sync::Mutex};
+use tokio_tungstenite::tungstenite::http::HeaderMap;
+
+use crate::{
+    bans::BanList,
+    commands::Outcome,
+    compat::CompatList,
+    config::{Config, PermissionsConfig},
+    console::{self, Verbosity},
+    endpoint::Endpoint,
+    handlers::Handler,
+    middleware::{self, Middleware, Verdict},
+    models::{ClientMessage, Protocol, ServerMessage},
+};
+
+/// Request of the server as the connection reads it
+pub struct Request(ServerMessage);
+
+/// Answer to a request
+pub struct Response(ClientMessage);
+
+/// Middleware stages and command handlers of a connection, on the backend without Steam
+pub struct Pipeline {
+    stages: Vec<Box<dyn Middleware>>,
+    handler: Handler,
+    endpoint: Arc<Endpoint>,
+    runtime: Runtime,
+}
+
+impl Pipeline {
+    /// Connection speaking a protocol version ("1" or "2")
+    pub fn new(version: &str) -> Result<Self> {
+        let protocol = Protocol::parse(version).context("Unknown protocol version")?;
+        // Only the log file gets the console messages (there is none while benchmarking)
+        console::set_verbosity(Verbosity::Silent);
+        let mut config = Config {
+            permissions: PermissionsConfig {
+                max_per_minute: 0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        // Commands must not wait for the Steam store
+        config.store.enabled = false;
+        let endpoint = Arc::new(Endpoint::new(
+            0,
+            "wss://bench.invalid/ws".to_owned(),
+            None,
+            HeaderMap::new(),
+        ));
+        endpoint.set_protocol(protocol);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .context("Unable to start the runtime")?;
+        let handler = {
+            let _runtime = runtime.enter();
+            Handler::new(
+                Arc::new(Mutex::new(NoSteam::new()?)),
+                &config,
+                BanList::default(),
+                CompatList::default(),
+                None,
+                vec![endpoint.clone()],
+            )
+        };
+        Ok(Self {
+            stages: middleware::chain(&config.permissions),
+            handler,
+            endpoint,
+            runtime,
+        })
+    }
+
+    /// Parses and checks a request
+    pub fn decode(&self, text: &str) -> Result<Request> {
+        let msg = self.endpoint.protocol().decode(text)?;
+        msg.check().context("Invalid message from the server")?;
+        Ok(Request(msg))
+    }
+
+    /**
+     * Passes a request through the middleware and runs its command
+     * @return The answer (None if there is nothing to answer)
+     */
+    pub fn dispatch(&self, request: &Request) -> Result<Option<Response>> {
+        let msg = &request.0;
+        match middleware::screen(&self.stages, msg, &self.endpoint)? {
+            Verdict::Continue => (),
+            Verdict::Reply(res) => return Ok(Some(Response(res))),
+            Verdict::Drop => return Ok(None),
+        }
+        let outcome = self
+            .runtime
+            .block_on(self.handler.run_command(msg, &self.endpoint))?;
+        Ok(match outcome {
+            Outcome::Reply(cmd) => Some(Response(ClientMessage::reply(msg.request_id(), cmd))),
+            Outcome::Done | Outcome::Exit => None,
+        })
+    }
+
+    /**
+     * Writes an answer
+     * @return The JSON text (None if this version has no such message)
+     */
+    pub fn encode(&self, response: &Response) -> Result<Option<String>> {
+        Ok(self.endpoint.protocol().encode(&response.0)?)
+    }
+
+    /// Parse, dispatch and serialize, like a connection does for each text message
+    pub fn process(&self, text: &str) -> Result<Option<String>> {
+        let request = self.decode(text)?;
+        match self.dispatch(&request)? {
+            Some(response) => self.encode(&response),
+            None => Ok(None),
+        }
+    }
+}
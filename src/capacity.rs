@@ -0,0 +1,86 @@
+// The limit only follows the load samples of the metrics feature
+#![cfg_attr(not(feature = "metrics"), allow(dead_code))]
+
+use crate::{config::WaitlistConfig, models::HostLoad};
+
+/// Guest limit that follows the host load
+/// The waitlist's slots are lowered one at a time while the host is overloaded
+/// and raised again when it has headroom (the guests who are playing are never disconnected)
+pub struct Capacity {
+    config: WaitlistConfig,
+    /// Slots the waitlist may fill now
+    limit: u32,
+    /// Samples in a row above the high mark
+    over: u32,
+    /// Samples in a row below the low mark
+    under: u32,
+}
+
+impl Capacity {
+    pub fn new(config: &WaitlistConfig) -> Self {
+        Self {
+            config: config.clone(),
+            limit: config.slots,
+            over: 0,
+            under: 0,
+        }
+    }
+
+    /// Slots the waitlist may fill now
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    /**
+     * Adjusts the limit to a load sample
+     * @return The new limit if it changed
+     */
+    pub fn update(&mut self, load: HostLoad) -> Option<u32> {
+        if !self.config.adaptive || self.config.slots == 0 {
+            return None;
+        }
+        // The busiest part of the host limits the stream
+        let busiest = [Some(load.cpu), load.gpu, load.encoder]
+            .into_iter()
+            .flatten()
+            .fold(0.0, f32::max);
+        if busiest >= self.config.high_percent {
+            self.over += 1;
+            self.under = 0;
+        } else if busiest <= self.config.low_percent {
+            self.under += 1;
+            self.over = 0;
+        } else {
+            self.over = 0;
+            self.under = 0;
+        }
+
+        let samples = self.config.samples.max(1);
+        // One guest is always allowed
+        if self.over >= samples && self.limit > 1 {
+            self.over = 0;
+            self.limit -= 1;
+            return Some(self.limit);
+        }
+        if self.under >= samples && self.limit < self.config.slots {
+            self.under = 0;
+            self.limit += 1;
+            return Some(self.limit);
+        }
+        None
+    }
+
+    /**
+     * Restores the configured slots (the session ended)
+     * @return The new limit if it changed
+     */
+    pub fn reset(&mut self) -> Option<u32> {
+        self.over = 0;
+        self.under = 0;
+        if self.limit == self.config.slots {
+            return None;
+        }
+        self.limit = self.config.slots;
+        Some(self.limit)
+    }
+}
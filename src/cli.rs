@@ -0,0 +1,696 @@
+use anyhow::{bail, Context as _, Result};
+use chrono::NaiveDate;
+use std::{path::PathBuf, time::Duration};
+
+use crate::{
+    bans::BanDuration,
+    console::Verbosity,
+    logger::Level,
+    schema::Direction,
+    stats::{ExportFormat, Period},
+    transport::IpFamily,
+};
+
+/// Command line arguments
+#[derive(Default)]
+pub struct Args {
+    /// Display the version of the program
+    pub version: bool,
+    /// Display the help message
+    pub help: bool,
+    /// Plain sequential output for screen readers
+    pub accessible: bool,
+    /// Use ASCII symbols instead of Unicode glyphs
+    pub ascii: bool,
+    /// Large minimal output for the Steam Deck
+    pub deck: bool,
+    /// How much of the output reaches the terminal (the higher of --quiet and --silent)
+    pub verbosity: Verbosity,
+    /// Print the status, invites and stats as JSON on stdout (the messages go to stderr)
+    pub json: bool,
+    /// Exit when the connection to a server is lost instead of reconnecting (for process supervisors)
+    pub exit_on_disconnect: bool,
+    /// Configuration from the environment, JSON log on stdout and no terminal (also REMOTEPLAY_INVITER_CONTAINER=1)
+    pub container: bool,
+    /// Exit after this many failed reconnections in a row (for process supervisors)
+    pub max_retries: Option<u32>,
+    /// Connect to the servers only over this IP version (for debugging)
+    pub only_family: Option<IpFamily>,
+    /// Answer the server commands that change something with simulated results (for testing bots)
+    pub dry_run: bool,
+    /// Write the Steam callbacks to this file (for reproducing bugs)
+    pub record_steam: Option<PathBuf>,
+    /// Take the Steam callbacks from this recording instead of Steam (for reproducing bugs)
+    pub replay_steam: Option<PathBuf>,
+    /// Fake guest joins per minute through the mock Steam layer (for load tests)
+    #[cfg(feature = "simulate")]
+    pub simulate_guests: Option<u32>,
+    /// Subcommand to run instead of the inviter
+    pub command: Option<Command>,
+}
+
+/// Subcommands
+pub enum Command {
+    /// Log file management
+    Logs(LogsCommand),
+    /// Show the state of the running instance
+    Status,
+    /// Invite management on the running instance
+    Invite(InviteCommand),
+    /// Guest management on the running instance
+    Guest(GuestCommand),
+    /// Show the recent moderation actions
+    Audit {
+        /// Number of entries to show
+        limit: usize,
+    },
+    /// Refuse (true) or accept (false) new invites on the running instance
+    Pause(bool),
+    /// Reply to the last chat message through the running instance
+    Chat(String),
+    /// Token management
+    Token(TokenCommand),
+    /// Check the network for Remote Play (NAT type and port mapping)
+    Diagnose,
+    /// Wake the gaming PC when the server requests a session (Wake-on-LAN agent)
+    Agent,
+    /// Print the JSON Schema of the server protocol (both directions if not set)
+    Schema(Option<Direction>),
+    /// Check that the protocol test vectors still round-trip
+    VerifyProtocol,
+    /// Recorded Remote Play sessions
+    Stats(StatsCommand),
+}
+
+/// `stats` subcommands
+pub enum StatsCommand {
+    /// Summarize the sessions by week or month
+    Show {
+        /// Length of the summarized periods
+        period: Period,
+    },
+    /// Print every session for a spreadsheet
+    Export {
+        format: ExportFormat,
+        /// Only the sessions started on this day or later
+        since: Option<NaiveDate>,
+    },
+}
+
+/// `invite` subcommands
+pub enum InviteCommand {
+    /// Create a new invite link (in the running instance, or directly with Steam)
+    New {
+        /// Game ID (defaults to the running game)
+        game: Option<u32>,
+        /// Copy the link to the clipboard
+        copy: bool,
+        /// Show the link as a QR code
+        qr: bool,
+        /// Revoke the link after this long (needs the running instance)
+        ttl: Option<Duration>,
+    },
+}
+
+/// `token` subcommands
+pub enum TokenCommand {
+    /// Show the token (redacted)
+    Show,
+    /// Write the token to a file (or print it) for moving to another PC
+    Export {
+        /// File to write
+        file: Option<PathBuf>,
+    },
+    /// Replace the token with one exported on another PC
+    Import {
+        /// Token, or a file containing it
+        source: String,
+    },
+    /// Replace the token with a new one
+    Rotate {
+        /// Ask the server to move the Discord link to the new token
+        relink: bool,
+    },
+    /// Encrypt the token in the config file with a key bound to this PC
+    Encrypt,
+    /// Store the token in the config file as plain text again
+    Decrypt,
+}
+
+/// `guest` subcommands
+pub enum GuestCommand {
+    /// Let a guest waiting for approval play
+    Approve {
+        /// Guest ID
+        guest_id: u64,
+    },
+    /// Disconnect a guest waiting for approval
+    Deny {
+        /// Guest ID
+        guest_id: u64,
+    },
+    /// Disconnect a guest from the session
+    Kick {
+        /// Guest ID
+        guest_id: u64,
+        /// Ban the guest for this long (defaults to the config)
+        ban: Option<BanDuration>,
+        /// Reason recorded in the audit log
+        reason: Option<String>,
+    },
+    /// List the banned guests
+    Bans,
+    /// Lift the ban of a guest
+    Unban {
+        /// Steam ID of the guest
+        steam_id: u64,
+    },
+}
+
+/// `logs` subcommands
+pub enum LogsCommand {
+    /// Delete rotated log files
+    Clean {
+        /// Delete all rotated log files, not only the expired ones
+        all: bool,
+    },
+    /// Follow the active log file
+    Tail {
+        /// Minimum level to display
+        level: Level,
+    },
+}
+
+impl Args {
+    /// Parses the command line arguments of the current process
+    pub fn parse() -> Result<Self> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    /// Parses the given command line arguments (without the program name)
+    pub fn parse_from(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut parsed = Args::default();
+        let mut rest = Vec::new();
+
+        // Global options can appear anywhere
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-v" | "--version" => parsed.version = true,
+                "-h" | "--help" => parsed.help = true,
+                "--accessible" => parsed.accessible = true,
+                "--ascii" => parsed.ascii = true,
+                "--deck" => parsed.deck = true,
+                "-q" | "--quiet" => parsed.verbosity = parsed.verbosity.max(Verbosity::Quiet),
+                "--silent" => parsed.verbosity = Verbosity::Silent,
+                "--json" => parsed.json = true,
+                "--exit-on-disconnect" => parsed.exit_on_disconnect = true,
+                "--container" => parsed.container = true,
+                "--max-retries" => {
+                    let value = args.next().context("Missing number for --max-retries")?;
+                    parsed.max_retries = Some(
+                        value
+                            .parse()
+                            .with_context(|| format!("Invalid number of retries: {value}"))?,
+                    );
+                }
+                "--dry-run" => parsed.dry_run = true,
+                "--ipv4-only" | "--ipv6-only" => {
+                    let family = if arg == "--ipv4-only" {
+                        IpFamily::V4
+                    } else {
+                        IpFamily::V6
+                    };
+                    if parsed.only_family.is_some_and(|only| only != family) {
+                        bail!("--ipv4-only and --ipv6-only cannot be combined");
+                    }
+                    parsed.only_family = Some(family);
+                }
+                "--record-steam" => {
+                    let path = args.next().context("Missing file for --record-steam")?;
+                    parsed.record_steam = Some(PathBuf::from(path));
+                }
+                "--replay-steam" => {
+                    let path = args.next().context("Missing file for --replay-steam")?;
+                    parsed.replay_steam = Some(PathBuf::from(path));
+                }
+                #[cfg(feature = "simulate")]
+                "--simulate-guests" => {
+                    let value = args.next().context("Missing rate for --simulate-guests")?;
+                    let per_minute: u32 = value
+                        .parse()
+                        .ok()
+                        .filter(|&per_minute| per_minute > 0)
+                        .with_context(|| {
+                        format!("Invalid number of guests per minute: {value}")
+                    })?;
+                    parsed.simulate_guests = Some(per_minute);
+                }
+                _ => rest.push(arg),
+            }
+        }
+        if parsed.record_steam.is_some() && parsed.replay_steam.is_some() {
+            bail!("--record-steam and --replay-steam cannot be combined");
+        }
+
+        // The first remaining argument selects the subcommand
+        parsed.command = match rest.first().map(String::as_str) {
+            None => None,
+            Some("logs") => Some(Command::Logs(LogsCommand::parse(&rest[1..])?)),
+            Some("status") => {
+                if let Some(arg) = rest.get(1) {
+                    bail!("Unexpected argument for status: {arg}");
+                }
+                Some(Command::Status)
+            }
+            Some("diagnose") => {
+                if let Some(arg) = rest.get(1) {
+                    bail!("Unexpected argument for diagnose: {arg}");
+                }
+                Some(Command::Diagnose)
+            }
+            Some("agent") => {
+                if let Some(arg) = rest.get(1) {
+                    bail!("Unexpected argument for agent: {arg}");
+                }
+                Some(Command::Agent)
+            }
+            Some("schema") => {
+                let direction = match rest.get(1).map(String::as_str) {
+                    None => None,
+                    Some("server") => Some(Direction::Server),
+                    Some("client") => Some(Direction::Client),
+                    Some(arg) => bail!("Unexpected argument for schema: {arg} (server or client)"),
+                };
+                if let Some(arg) = rest.get(2) {
+                    bail!("Unexpected argument for schema: {arg}");
+                }
+                Some(Command::Schema(direction))
+            }
+            Some("verify-protocol") => {
+                if let Some(arg) = rest.get(1) {
+                    bail!("Unexpected argument for verify-protocol: {arg}");
+                }
+                Some(Command::VerifyProtocol)
+            }
+            Some(command @ ("pause" | "resume")) => {
+                if let Some(arg) = rest.get(1) {
+                    bail!("Unexpected argument for {command}: {arg}");
+                }
+                Some(Command::Pause(command == "pause"))
+            }
+            Some("stats") => Some(Command::Stats(StatsCommand::parse(&rest[1..])?)),
+            Some("audit") => Some(Command::Audit {
+                limit: parse_audit(&rest[1..])?,
+            }),
+            Some("chat") => {
+                if rest.len() < 2 {
+                    bail!("Missing chat message");
+                }
+                Some(Command::Chat(rest[1..].join(" ")))
+            }
+            Some("guest") => Some(Command::Guest(GuestCommand::parse(&rest[1..])?)),
+            Some("invite") => Some(Command::Invite(InviteCommand::parse(&rest[1..])?)),
+            Some("token") => Some(Command::Token(TokenCommand::parse(&rest[1..])?)),
+            Some(arg) if arg.starts_with('-') => bail!("Unknown option: {arg}"),
+            Some(arg) => bail!("Unknown command: {arg}"),
+        };
+
+        Ok(parsed)
+    }
+}
+
+impl LogsCommand {
+    /// Parses the arguments following `logs`
+    fn parse(args: &[String]) -> Result<Self> {
+        let (action, options) = args
+            .split_first()
+            .context("Missing logs command (clean or tail)")?;
+        let mut options = options.iter();
+        match action.as_str() {
+            "clean" => {
+                let mut all = false;
+                for option in options {
+                    match option.as_str() {
+                        "--all" => all = true,
+                        _ => bail!("Unknown option for logs clean: {option}"),
+                    }
+                }
+                Ok(LogsCommand::Clean { all })
+            }
+            "tail" => {
+                let mut level = Level::Info;
+                while let Some(option) = options.next() {
+                    match option.as_str() {
+                        "--level" => {
+                            level = options
+                                .next()
+                                .context("Missing value for --level")?
+                                .parse()?;
+                        }
+                        _ => bail!("Unknown option for logs tail: {option}"),
+                    }
+                }
+                Ok(LogsCommand::Tail { level })
+            }
+            _ => bail!("Unknown logs command: {action}"),
+        }
+    }
+}
+
+impl InviteCommand {
+    /// Parses the arguments following `invite`
+    fn parse(args: &[String]) -> Result<Self> {
+        // `new` is the only invite command, so it may be left out
+        let options = match args.split_first() {
+            Some((action, options)) if action == "new" => options,
+            Some((action, _)) if !action.starts_with('-') => {
+                bail!("Unknown invite command: {action}")
+            }
+            _ => args,
+        };
+        let mut game = None;
+        let mut copy = false;
+        let mut qr = false;
+        let mut ttl = None;
+        let mut options = options.iter();
+        while let Some(option) = options.next() {
+            match option.as_str() {
+                "--game" => {
+                    let value = options.next().context("Missing value for --game")?;
+                    game = Some(
+                        value
+                            .parse()
+                            .with_context(|| format!("Invalid game ID: {value}"))?,
+                    );
+                }
+                "--ttl" => {
+                    let value = options.next().context("Missing value for --ttl")?;
+                    let minutes: u64 = value
+                        .parse()
+                        .ok()
+                        .filter(|&minutes| minutes > 0)
+                        .with_context(|| format!("Invalid number of minutes: {value}"))?;
+                    ttl = Some(Duration::from_secs(minutes * 60));
+                }
+                "--copy" => copy = true,
+                "--qr" => qr = true,
+                _ => bail!("Unknown option for invite: {option}"),
+            }
+        }
+        Ok(InviteCommand::New {
+            game,
+            copy,
+            qr,
+            ttl,
+        })
+    }
+}
+
+impl TokenCommand {
+    /// Parses the arguments following `token`
+    fn parse(args: &[String]) -> Result<Self> {
+        match args {
+            [action] if action == "show" => Ok(TokenCommand::Show),
+            [action] if action == "export" => Ok(TokenCommand::Export { file: None }),
+            [action, option, file] if action == "export" && option == "--file" => {
+                Ok(TokenCommand::Export {
+                    file: Some(PathBuf::from(file)),
+                })
+            }
+            [action, source] if action == "import" => Ok(TokenCommand::Import {
+                source: source.clone(),
+            }),
+            [action] if action == "rotate" => Ok(TokenCommand::Rotate { relink: false }),
+            [action, option] if action == "rotate" && option == "--relink" => {
+                Ok(TokenCommand::Rotate { relink: true })
+            }
+            [action] if action == "encrypt" => Ok(TokenCommand::Encrypt),
+            [action] if action == "decrypt" => Ok(TokenCommand::Decrypt),
+            [] => bail!("Missing token command (show, export, import, rotate, encrypt or decrypt)"),
+            [action, ..] => bail!("Invalid arguments for token {action} (see --help)"),
+        }
+    }
+}
+
+impl GuestCommand {
+    /// Parses the arguments following `guest`
+    fn parse(args: &[String]) -> Result<Self> {
+        let parse_id = |id: &String| {
+            id.parse::<u64>()
+                .with_context(|| format!("Invalid ID: {id}"))
+        };
+        match args {
+            [action] if action == "bans" => Ok(GuestCommand::Bans),
+            [action, guest_id] if action == "approve" => Ok(GuestCommand::Approve {
+                guest_id: parse_id(guest_id)?,
+            }),
+            [action, guest_id] if action == "deny" => Ok(GuestCommand::Deny {
+                guest_id: parse_id(guest_id)?,
+            }),
+            [action, steam_id] if action == "unban" => Ok(GuestCommand::Unban {
+                steam_id: parse_id(steam_id)?,
+            }),
+            [action, guest_id, options @ ..] if action == "kick" => {
+                let mut ban = None;
+                let mut reason = None;
+                let mut options = options.iter();
+                while let Some(option) = options.next() {
+                    match option.as_str() {
+                        "--ban" => {
+                            ban = Some(options.next().context("Missing value for --ban")?.parse()?);
+                        }
+                        "--reason" => {
+                            reason = Some(
+                                options
+                                    .next()
+                                    .context("Missing value for --reason")?
+                                    .clone(),
+                            );
+                        }
+                        _ => bail!("Unknown option for guest kick: {option}"),
+                    }
+                }
+                Ok(GuestCommand::Kick {
+                    guest_id: parse_id(guest_id)?,
+                    ban,
+                    reason,
+                })
+            }
+            [] => bail!("Missing guest command (approve, deny, kick, bans or unban)"),
+            [action, ..] => bail!("Invalid arguments for guest {action} (see --help)"),
+        }
+    }
+}
+
+impl StatsCommand {
+    /// Parses the arguments following `stats`
+    fn parse(args: &[String]) -> Result<Self> {
+        if args.first().is_some_and(|action| action == "export") {
+            let mut format = ExportFormat::Csv;
+            let mut since = None;
+            let mut options = args[1..].iter();
+            while let Some(option) = options.next() {
+                match option.as_str() {
+                    "--format" => {
+                        format = match options.next().map(String::as_str) {
+                            Some("csv") => ExportFormat::Csv,
+                            Some("json") => ExportFormat::Json,
+                            Some(value) => bail!("Invalid format: {value} (csv or json)"),
+                            None => bail!("Missing value for --format"),
+                        };
+                    }
+                    "--since" => {
+                        let value = options.next().context("Missing value for --since")?;
+                        since =
+                            Some(NaiveDate::parse_from_str(value, "%Y-%m-%d").with_context(
+                                || format!("Invalid date: {value} (e.g. 2024-07-01)"),
+                            )?);
+                    }
+                    _ => bail!("Unknown option for stats export: {option}"),
+                }
+            }
+            return Ok(StatsCommand::Export { format, since });
+        }
+
+        let mut period = Period::default();
+        for arg in args {
+            match arg.as_str() {
+                "week" => period = Period::Week,
+                "month" => period = Period::Month,
+                _ => bail!("Unexpected argument for stats: {arg}"),
+            }
+        }
+        Ok(StatsCommand::Show { period })
+    }
+}
+
+/// Parses the arguments following `audit`
+/// @return Number of entries to show
+fn parse_audit(args: &[String]) -> Result<usize> {
+    let (action, options) = args.split_first().context("Missing audit command (show)")?;
+    if action != "show" {
+        bail!("Unknown audit command: {action}");
+    }
+    let mut limit = 20;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--limit" => {
+                let value = options.next().context("Missing value for --limit")?;
+                limit = value
+                    .parse()
+                    .with_context(|| format!("Invalid limit: {value}"))?;
+            }
+            _ => bail!("Unknown option for audit show: {option}"),
+        }
+    }
+    Ok(limit)
+}
+
+/// Help message
+pub fn help_text(program: &str) -> String {
+    // Only in builds with the simulate feature
+    let simulate = if cfg!(feature = "simulate") {
+        "\n    --simulate-guests <n>  Fake n guest joins per minute through the mock Steam layer (for load tests)"
+    } else {
+        ""
+    };
+    indoc::formatdoc! {"
+        Usage: {program} [options] [command]
+
+        Options:
+            -v, --version    Display the version of the program
+            -h, --help       Display this help message
+            --accessible     Plain sequential output for screen readers
+            --ascii          Use ASCII symbols instead of Unicode glyphs
+            --deck           Large minimal output for the Steam Deck (auto-detected in Game Mode)
+            -q, --quiet      Print only the warnings and errors (no banner)
+            --silent         Print nothing, only write the log file
+            --json           Print the status, invite and stats as JSON on stdout
+                             (with --json, --quiet or --silent the messages go to stderr,
+                             so stdout only carries the JSON and the invite links)
+            --ipv4-only      Connect to the servers only over IPv4 (for debugging)
+            --ipv6-only      Connect to the servers only over IPv6 (for debugging)
+            --exit-on-disconnect   Exit when the connection to a server is lost instead of reconnecting
+            --max-retries <n>      Exit after n failed reconnections in a row
+                                   (both leave the restarts to systemd or pm2: exit code 75 when
+                                   a restart can help, 78 for a configuration problem or
+                                   when Steam is not running, 1 otherwise)
+            --container      Container mode (also REMOTEPLAY_INVITER_CONTAINER=1): the configuration
+                             comes from REMOTEPLAY_INVITER_CONFIG, REMOTEPLAY_INVITER_ENDPOINT and
+                             REMOTEPLAY_INVITER__<SECTION>__<KEY>, the log is written to stdout as JSON,
+                             GET /healthz is served on port 8731 and SIGTERM stops the client
+                             (keep the state files in a volume with REMOTEPLAY_INVITER_DATA_DIR)
+            --dry-run        Log the server commands that would call Steam or stop the client
+                             and answer them with simulated results (for testing bots)
+            --record-steam <file>  Write the Steam callbacks to a file (for reproducing bugs)
+            --replay-steam <file>  Take the Steam callbacks from a recording instead of Steam{simulate}
+
+        Commands:
+            logs clean [--all]           Delete expired (or all) rotated log files
+            logs tail [--level <level>]  Follow the active log file (info, warn, error)
+            status                       Show the state of the running instance
+            invite [--game <appid>] [--copy] [--qr] [--ttl <minutes>]
+                                         Create an invite link (the running game by default),
+                                         in the running instance or directly with Steam
+                                         (--ttl revokes it after a while in the running instance)
+            guest approve <guest_id>     Let a guest waiting for approval play
+            guest deny <guest_id>        Disconnect a guest waiting for approval
+            guest kick <guest_id> [--ban <duration>] [--reason <text>]
+                                         Disconnect a guest (ban for 30m, 12h, 7d or permanent)
+            guest bans                   List the banned guests
+            guest unban <steam_id>       Lift the ban of a guest
+            audit show [--limit <n>]     Show the recent moderation actions
+            stats [week|month]           Summarize the Remote Play sessions hosted on this PC
+            stats export [--format csv|json] [--since <yyyy-mm-dd>]
+                                         Print every recorded session for a spreadsheet
+            pause                        Refuse new invites in the running instance
+            resume                       Accept new invites again
+            chat <message>               Reply to the last chat message from Discord
+            token show                   Show the token that links the client with Discord
+            token export [--file <path>] Export the token for moving to another PC
+            token import <token|file>    Use a token exported on another PC
+            token rotate [--relink]      Replace the token (and move the Discord link to it)
+            token encrypt                Encrypt the token in the config file with a key bound to this PC
+            token decrypt                Store the token in the config file as plain text again
+            diagnose                     Check the NAT type and the port mapping of the router
+            agent                        Wake the gaming PC when the server requests a session
+                                         (run on a low-power device, set mac in [agent])
+            schema [server|client]       Print the JSON Schema of the messages exchanged with the server
+            verify-protocol              Check the messages against the protocol test vectors
+
+        While the inviter is running, type help for the console commands.
+    "}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Args> {
+        Args::parse_from(args.split_whitespace().map(str::to_owned))
+    }
+
+    fn error(args: &str) -> String {
+        match parse(args) {
+            Ok(_) => panic!("parsed: {args}"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn global_options_can_follow_the_command() {
+        let args = parse("invite --json --game 480 -q --ttl 5 --qr").unwrap();
+        assert!(args.json);
+        assert_eq!(args.verbosity, Verbosity::Quiet);
+        assert!(matches!(
+            args.command,
+            Some(Command::Invite(InviteCommand::New {
+                game: Some(480),
+                copy: false,
+                qr: true,
+                ttl: Some(ttl),
+            })) if ttl == Duration::from_secs(5 * 60)
+        ));
+    }
+
+    #[test]
+    fn no_arguments_run_the_inviter() {
+        let args = parse("--max-retries 3 --ipv4-only").unwrap();
+        assert!(args.command.is_none());
+        assert_eq!(args.max_retries, Some(3));
+        assert_eq!(args.only_family, Some(IpFamily::V4));
+    }
+
+    #[test]
+    fn subcommand_arguments_are_parsed() {
+        assert!(matches!(
+            parse("guest kick 7 --ban 12h --reason spam").unwrap().command,
+            Some(Command::Guest(GuestCommand::Kick {
+                guest_id: 7,
+                ban: Some(BanDuration::For(_)),
+                reason: Some(reason),
+            })) if reason == "spam"
+        ));
+        assert!(matches!(
+            parse("token rotate --relink").unwrap().command,
+            Some(Command::Token(TokenCommand::Rotate { relink: true }))
+        ));
+        assert!(matches!(
+            parse("pause").unwrap().command,
+            Some(Command::Pause(true))
+        ));
+    }
+
+    #[test]
+    fn conflicting_or_unknown_arguments_are_refused() {
+        assert!(error("--ipv4-only --ipv6-only").contains("cannot be combined"));
+        assert!(error("--record-steam a --replay-steam b").contains("cannot be combined"));
+        assert!(error("--max-retries many").contains("Invalid number of retries"));
+        assert!(error("invite --ttl 0").contains("Invalid number of minutes"));
+        assert!(error("status now").contains("Unexpected argument"));
+        assert!(error("--frobnicate").contains("Unknown option"));
+        assert!(error("frobnicate").contains("Unknown command"));
+    }
+}
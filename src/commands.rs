@@ -0,0 +1,119 @@
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+    time::Instant,
+};
+
+use crate::{
+    endpoint::Endpoint,
+    error::ClientResult,
+    handlers::Handler,
+    models::{ClientCmd, ErrorStatus, ServerMessage},
+};
+
+/// Running server command
+pub type CommandFuture<'a> = BoxFuture<'a, ClientResult<Outcome>>;
+
+/// Handler method that runs a server command
+pub type CommandFn = for<'a> fn(&'a Handler, &'a ServerMessage, &'a Endpoint) -> CommandFuture<'a>;
+
+/// What a command asks the connection to do
+pub enum Outcome {
+    /// Answer the server (with the ID of the request)
+    Reply(ClientCmd),
+    /// Nothing to answer
+    Done,
+    /// Stop the client
+    Exit,
+}
+
+/// Runs one kind of server command
+pub trait CommandHandler: Send + Sync {
+    fn run<'a>(
+        &'a self,
+        handler: &'a Handler,
+        msg: &'a ServerMessage,
+        endpoint: &'a Endpoint,
+    ) -> CommandFuture<'a>;
+}
+
+impl CommandHandler for CommandFn {
+    fn run<'a>(
+        &'a self,
+        handler: &'a Handler,
+        msg: &'a ServerMessage,
+        endpoint: &'a Endpoint,
+    ) -> CommandFuture<'a> {
+        self(handler, msg, endpoint)
+    }
+}
+
+/// How often a command ran and how long it took
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommandMetrics {
+    /// Command name on the wire
+    pub command: String,
+    /// Number of runs
+    pub calls: u64,
+    /// Runs that answered with an error or failed
+    pub errors: u64,
+    /// Time spent in all runs (milliseconds)
+    pub total_ms: u64,
+}
+
+/// Server commands by name on the wire
+/// Optional features and plugins register their own commands next to the built-in ones
+#[derive(Default)]
+pub struct Registry {
+    commands: HashMap<&'static str, Box<dyn CommandHandler>>,
+    metrics: Mutex<BTreeMap<&'static str, CommandMetrics>>,
+}
+
+impl Registry {
+    /// Adds a command (replacing the one registered with the same name)
+    pub fn register(&mut self, name: &'static str, command: impl CommandHandler + 'static) {
+        self.commands.insert(name, Box::new(command));
+    }
+
+    /**
+     * Runs the command of a server message
+     * @return Outcome of the command (an InvalidCmd error for an unknown command)
+     */
+    pub async fn run(
+        &self,
+        handler: &Handler,
+        msg: &ServerMessage,
+        endpoint: &Endpoint,
+    ) -> ClientResult<Outcome> {
+        let Some((name, command)) = msg
+            .cmd
+            .name()
+            .and_then(|name| self.commands.get_key_value(name))
+        else {
+            return Ok(Outcome::Reply(ClientCmd::Error {
+                code: ErrorStatus::InvalidCmd,
+            }));
+        };
+
+        let start = Instant::now();
+        let result = command.run(handler, msg, endpoint).await;
+        let failed = matches!(result, Err(_) | Ok(Outcome::Reply(ClientCmd::Error { .. })));
+        let mut metrics = self.metrics.lock().unwrap_or_else(|err| err.into_inner());
+        let entry = metrics.entry(name).or_insert_with(|| CommandMetrics {
+            command: name.to_string(),
+            ..Default::default()
+        });
+        entry.calls += 1;
+        entry.errors += u64::from(failed);
+        entry.total_ms += start.elapsed().as_millis() as u64;
+        result
+    }
+
+    /// Metrics of the commands that ran, by name
+    pub fn metrics(&self) -> Vec<CommandMetrics> {
+        let metrics = self.metrics.lock().unwrap_or_else(|err| err.into_inner());
+        metrics.values().cloned().collect()
+    }
+}
@@ -0,0 +1,80 @@
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf, sync::Arc, time::Duration};
+use tokio::{task::JoinHandle, time::interval};
+
+use crate::{config, handlers::Handler};
+
+/// How often the list is fetched from the server
+const REFRESH: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A game known to work poorly with Remote Play Together
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CompatEntry {
+    /// App ID of the game
+    pub app_id: u32,
+    /// Name of the game
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Known problems (e.g. "Only the first guest gets a controller")
+    #[serde(default)]
+    pub caveats: Vec<String>,
+}
+
+/// Game compatibility list cached next to the executable
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CompatList {
+    /// When the list was fetched from the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<DateTime<Utc>>,
+    /// Games with known problems
+    #[serde(default)]
+    pub games: Vec<CompatEntry>,
+}
+
+impl CompatList {
+    /// Path of the cached list
+    fn path() -> Result<PathBuf> {
+        Ok(config::get_exe_path()?.with_extension("compat.toml"))
+    }
+
+    /// Reads the cached list (empty if it was never fetched)
+    pub fn load() -> Result<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Unable to read compatibility list: {:?}", path))?;
+        toml::from_str(&content).context("Unable to parse compatibility list")
+    }
+
+    /// Replaces the list with the one from the server and saves it
+    pub fn replace(&mut self, games: Vec<CompatEntry>) -> Result<()> {
+        self.games = games;
+        self.updated = Some(Utc::now());
+        let path = Self::path()?;
+        let content = toml::to_string(self).context("Unable to serialize compatibility list")?;
+        fs::write(&path, content)
+            .with_context(|| format!("Unable to write compatibility list: {:?}", path))
+    }
+
+    /// Known problems of the game
+    pub fn find(&self, app_id: u32) -> Option<&CompatEntry> {
+        self.games.iter().find(|entry| entry.app_id == app_id)
+    }
+}
+
+/// Asks the servers for the latest list now and then once a day
+pub fn spawn_refresher(handler: Arc<Handler>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = interval(REFRESH);
+        loop {
+            // Queued until a server is connected
+            ticker.tick().await;
+            handler.request_compat().await;
+        }
+    })
+}
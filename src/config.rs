@@ -1,26 +1,1085 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+
+use crate::{bans::BanDuration, container, files, vault};
 use std::{
+    collections::BTreeMap,
     env, fs,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
+/// Whole configuration document in container mode
+const CONFIG_ENV: &str = "REMOTEPLAY_INVITER_CONFIG";
+/// Whole endpoint document in container mode
+const ENDPOINT_ENV: &str = "REMOTEPLAY_INVITER_ENDPOINT";
+/// Prefix of a single configuration value in container mode (sections and keys separated by __)
+const VALUE_ENV_PREFIX: &str = "REMOTEPLAY_INVITER__";
+/// Directory of the state files instead of the one of the executable
+const DATA_DIR_ENV: &str = "REMOTEPLAY_INVITER_DATA_DIR";
+
 /// Endpoint configuration
 #[derive(Serialize, Deserialize)]
 pub struct EndpointConfig {
-    /// Endpoint URL to connect to
+    /// Endpoint URL to connect to (the default server if omitted, ws+unix:///path/to.sock for a local socket)
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Name shown in the console, notifications and status (e.g. "community-A")
+    #[serde(default)]
+    pub label: Option<String>,
+    /// User-Agent of the connection request (the client name and version if omitted)
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// Extra HTTP headers of the connection request (e.g. credentials for a reverse proxy)
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// More servers to register with at the same time (e.g. another Discord community)
+    #[serde(default)]
+    pub extra: Vec<ExtraEndpointConfig>,
+}
+
+/// Additional server configuration
+#[derive(Serialize, Deserialize)]
+pub struct ExtraEndpointConfig {
+    /// Endpoint URL to connect to (ws+unix:///path/to.sock for a local socket)
     pub url: String,
+    /// Name shown in the console, notifications and status (e.g. "friends")
+    #[serde(default)]
+    pub label: Option<String>,
+    /// User-Agent of the connection request (the client name and version if omitted)
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// Extra HTTP headers of the connection request (e.g. credentials for a reverse proxy)
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
 }
 
 /// UUID configuration
-#[derive(Serialize, Deserialize)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct Config {
-    /// UUID
+    /// UUID (encrypted with a key bound to this PC after `token encrypt`)
     pub uuid: String,
+    /// Log file settings
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// Invite link settings
+    #[serde(default)]
+    pub invites: InvitesConfig,
+    /// Join instructions sent with the invites
+    #[serde(default)]
+    pub instructions: InstructionsConfig,
+    /// Ban settings
+    #[serde(default)]
+    pub bans: BansConfig,
+    /// Guest name settings
+    #[serde(default)]
+    pub personas: PersonasConfig,
+    /// Guest avatar settings
+    #[serde(default)]
+    pub avatars: AvatarsConfig,
+    /// Steam store metadata settings
+    #[serde(default)]
+    pub store: StoreConfig,
+    /// Guest approval settings
+    #[serde(default)]
+    pub approval: ApprovalConfig,
+    /// Priority access settings
+    #[serde(default)]
+    pub priority: PriorityConfig,
+    /// Playtime cap settings
+    #[serde(default)]
+    pub playtime: PlaytimeConfig,
+    /// Session recap settings
+    #[serde(default)]
+    pub recap: RecapConfig,
+    /// Waitlist settings
+    #[serde(default)]
+    pub waitlist: WaitlistConfig,
+    /// Server command permissions
+    #[serde(default)]
+    pub permissions: PermissionsConfig,
+    /// Steam Deck mode settings
+    #[serde(default)]
+    pub deck: DeckConfig,
+    /// Do-not-disturb settings
+    #[serde(default)]
+    pub do_not_disturb: DoNotDisturbConfig,
+    /// Quiet hours settings
+    #[serde(default)]
+    pub quiet_hours: QuietHoursConfig,
+    /// Traffic shaping settings
+    #[serde(default)]
+    pub shaping: ShapingConfig,
+    /// Keepalive settings (for aggressive NAT or proxy timeouts)
+    #[serde(default)]
+    pub keepalive: KeepaliveConfig,
+    /// Timeout settings (for slow networks such as satellite links or mobile hotspots)
+    #[serde(default)]
+    pub timeouts: TimeoutsConfig,
+    /// DNS settings (for ISPs that hijack DNS)
+    #[serde(default)]
+    pub dns: DnsConfig,
+    /// TLS settings (for PCs with a broken certificate store)
+    #[serde(default)]
+    pub tls: TlsConfig,
+    /// Device pairing settings
+    #[serde(default)]
+    pub pairing: PairingConfig,
+    /// This PC (tells the devices sharing the UUID apart)
+    #[serde(default)]
+    pub device: DeviceConfig,
+    /// LAN party page (no server needed)
+    #[serde(default)]
+    pub lan: LanConfig,
+    /// Health endpoint for container orchestrators
+    #[serde(default)]
+    pub health: HealthConfig,
+    /// Invite link shortener settings
+    #[serde(default)]
+    pub shortener: ShortenerConfig,
+    /// NAT traversal settings
+    #[serde(default)]
+    pub nat: NatConfig,
+    /// Resource usage check settings
+    #[serde(default)]
+    pub watchdog: WatchdogConfig,
+    /// Host load reporting settings
+    #[serde(default)]
+    pub load: LoadConfig,
+    /// Wake-on-LAN agent settings (agent command)
+    #[serde(default)]
+    pub agent: AgentConfig,
+    /// Sound alert settings
+    #[cfg(feature = "sounds")]
+    #[serde(default)]
+    pub sounds: SoundsConfig,
+    /// OBS integration settings
+    #[cfg(feature = "integration")]
+    #[serde(default)]
+    pub obs: ObsConfig,
+    /// Desktop notification settings
+    #[cfg(all(windows, feature = "notifications"))]
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
+    /// Notification area icon settings
+    #[cfg(all(windows, feature = "tray"))]
+    #[serde(default)]
+    pub tray: TrayConfig,
+    /// Discord rich presence settings
+    #[cfg(feature = "discord-rpc")]
+    #[serde(default)]
+    pub discord_rpc: DiscordRpcConfig,
+    /// macOS integration settings
+    #[cfg(all(target_os = "macos", feature = "macos"))]
+    #[serde(default)]
+    pub macos: MacosConfig,
+    /// User script settings
+    #[cfg(feature = "scripting")]
+    #[serde(default)]
+    pub scripts: ScriptsConfig,
+}
+
+/// Log file configuration
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Write the console output to a log file (off unless turned on)
+    pub enabled: bool,
+    /// Rotate the active log once it grows beyond this size (KiB)
+    pub max_size_kb: u64,
+    /// Delete rotated logs older than this many days
+    pub max_age_days: u64,
+    /// Keep at most this many rotated logs
+    pub max_files: usize,
+    /// Most minutes of log the server may ask for, each request needs the host's consent (0: refuse)
+    pub share_max_minutes: u64,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_size_kb: 1024,
+            max_age_days: 14,
+            max_files: 10,
+            share_max_minutes: 60,
+        }
+    }
+}
+
+/// Invite link configuration
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InvitesConfig {
+    /// Revoke a link after this many joins (unlimited if not set)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_uses: Option<u32>,
+    /// Create an invite and post it to the server when a supported game is started
+    pub auto_invite: bool,
+    /// Check that Steam still accepts the links this often and replace the broken ones (0: never)
+    pub health_check_secs: u64,
+    /// Answer repeated link requests for the same game with the last link for this many seconds (0: always a new link)
+    pub cooldown_secs: u64,
+}
+
+impl Default for InvitesConfig {
+    fn default() -> Self {
+        Self {
+            max_uses: None,
+            auto_invite: false,
+            health_check_secs: 60,
+            cooldown_secs: 30,
+        }
+    }
+}
+
+/// Join instructions configuration (sent with each invite, the server shows them to the guest)
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InstructionsConfig {
+    /// Send the instructions with each invite
+    pub enabled: bool,
+    /// Language of the built-in text
+    pub language: Language,
+    /// Own text instead of the built-in one ({join}, {game}, {controller} and {note} are replaced)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Settings of each game by app ID (e.g. [instructions.games.480])
+    pub games: BTreeMap<String, GameInstructions>,
+}
+
+impl Default for InstructionsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            language: Language::default(),
+            template: None,
+            games: BTreeMap::new(),
+        }
+    }
+}
+
+/// Languages of the built-in join instructions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    En,
+    Ja,
+}
+
+/// Join instructions of a game
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameInstructions {
+    /// Name shown to the guest (the name in the compatibility list if not set)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Whether the guest needs a controller
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub controller: Option<ControllerNeed>,
+    /// Extra note for the guest
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// Whether a game needs a controller
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ControllerNeed {
+    /// Guests cannot play with a keyboard and mouse
+    Required,
+    /// Keyboard and mouse work, but a controller plays better
+    Recommended,
+    /// Keyboard and mouse work as well
+    Optional,
+}
+
+/// Ban configuration
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BansConfig {
+    /// Ban kicked guests for this long (e.g. "30m", "7d", "permanent"; no ban if not set)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_kick: Option<BanDuration>,
+}
+
+/// Guest name configuration
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PersonasConfig {
+    /// Show the Steam names of the guests and send them to the servers
+    pub enabled: bool,
+    /// Look a name up again after this many seconds
+    pub cache_secs: u64,
+    /// Ask Steam for at most this many names per minute
+    pub lookups_per_minute: usize,
+}
+
+impl Default for PersonasConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            cache_secs: 3600,
+            lookups_per_minute: 30,
+        }
+    }
+}
+
+/// Guest avatar configuration
+/// Avatars are shown in the join notifications and kept next to the executable
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AvatarsConfig {
+    /// Steam Web API key (https://steamcommunity.com/dev/apikey; no avatars if not set)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// Delete the oldest avatars once the cache grows beyond this size (KiB)
+    pub max_cache_kb: u64,
+    /// Download an avatar again after this many days
+    pub max_age_days: u64,
+}
+
+impl Default for AvatarsConfig {
+    fn default() -> Self {
+        Self {
+            api_key: None,
+            max_cache_kb: 1024,
+            max_age_days: 7,
+        }
+    }
+}
+
+/// Steam store metadata configuration
+/// Remote Play Together and controller support of the games are cached next to the executable
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StoreConfig {
+    /// Look the games up on the Steam store
+    pub enabled: bool,
+    /// Refuse invites for games whose store page does not list Remote Play Together (warn only if false)
+    pub refuse_unsupported: bool,
+    /// Look a game up again after this many days
+    pub max_age_days: u64,
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            refuse_unsupported: false,
+            max_age_days: 7,
+        }
+    }
+}
+
+/// Guest approval configuration
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApprovalConfig {
+    /// Require the host's approval before a guest can play
+    pub enabled: bool,
+    /// Deny the guest if nobody answers within this many seconds
+    pub timeout_secs: u64,
+    /// Guests approved without asking (Steam IDs or Discord user names)
+    pub auto_approve: Vec<String>,
+}
+
+impl Default for ApprovalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout_secs: 120,
+            auto_approve: Vec::new(),
+        }
+    }
+}
+
+/// Priority access configuration
+/// Guests on the list skip the approval prompt, the waitlist and the playtime cap (bans still apply)
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PriorityConfig {
+    /// Steam IDs, Discord user names or Discord user IDs
+    pub guests: Vec<String>,
+}
+
+/// Playtime cap configuration (rotates the guests when more people want to play)
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlaytimeConfig {
+    /// Disconnect a guest after playing this many minutes (0: no cap)
+    pub max_minutes: u64,
+    /// Warn the guest this many minutes before (0: no warning)
+    pub warn_minutes: u64,
+}
+
+impl Default for PlaytimeConfig {
+    fn default() -> Self {
+        Self {
+            max_minutes: 0,
+            warn_minutes: 5,
+        }
+    }
+}
+
+/// Session recap configuration (a summary when the last guest leaves)
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecapConfig {
+    /// Print a summary of each Remote Play session when it ends
+    pub enabled: bool,
+    /// Send the summary to the servers as well (for their statistics)
+    pub send: bool,
+    /// Keep the stats of each session on this PC (stats command)
+    pub stats: bool,
+}
+
+impl Default for RecapConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            send: false,
+            stats: true,
+        }
+    }
+}
+
+/// Waitlist configuration (the server admits the next person in line when a slot is free)
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WaitlistConfig {
+    /// Guest slots the waitlist can fill (0: no waitlist)
+    pub slots: u32,
+    /// Offer fewer slots while the host is overloaded (needs [load])
+    pub adaptive: bool,
+    /// Lower the slots when the CPU, GPU or encoder is busier than this (percent)
+    pub high_percent: f32,
+    /// Raise them again when everything is below this (percent)
+    pub low_percent: f32,
+    /// Load samples in a row before a change
+    pub samples: u32,
+}
+
+impl Default for WaitlistConfig {
+    fn default() -> Self {
+        Self {
+            slots: 0,
+            adaptive: false,
+            high_percent: 90.0,
+            low_percent: 60.0,
+            samples: 3,
+        }
+    }
+}
+
+/// Server command permission configuration
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PermissionsConfig {
+    /// Server commands to honor ("*" for all): message, game, link, admit_next, revoke, chat, compat, host_request, handoff_ready, handoff_failed, request_diagnostics, wake_host, backoff, relinked, pair, paired, conflict, active, exit
+    pub allow: Vec<String>,
+    /// Server commands to refuse even if allowed
+    pub deny: Vec<String>,
+    /// Requests a server may send per minute (0 for unlimited, notifications are not counted)
+    pub max_per_minute: u32,
+    /// Requests a server may send at once after an idle time
+    pub burst: u32,
+}
+
+impl Default for PermissionsConfig {
+    fn default() -> Self {
+        Self {
+            allow: vec!["*".to_owned()],
+            deny: Vec::new(),
+            max_per_minute: 60,
+            burst: 10,
+        }
+    }
+}
+
+impl PermissionsConfig {
+    /// Whether the host allows the server command
+    pub fn permits(&self, command: &str) -> bool {
+        let listed = |list: &[String]| {
+            list.iter()
+                .any(|entry| entry == "*" || entry.eq_ignore_ascii_case(command))
+        };
+        listed(&self.allow) && !listed(&self.deny)
+    }
+}
+
+/// Steam Deck mode configuration
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeckConfig {
+    /// Large minimal output (auto-detected in Game Mode if not set)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// Only print errors, headlines and prompts (everything still goes to the log file)
+    pub critical_only: bool,
+}
+
+/// Do-not-disturb configuration
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DoNotDisturbConfig {
+    /// Hold back messages, bells and prompts while a fullscreen game has the focus
+    pub enabled: bool,
+    /// Interval between focus checks (seconds)
+    pub poll_secs: u64,
+}
+
+impl Default for DoNotDisturbConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            poll_secs: 2,
+        }
+    }
+}
+
+/// Quiet hours configuration
+/// Invites are paused and notifications are held back every day between the times (local time)
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuietHoursConfig {
+    /// Keep quiet hours every day
+    pub enabled: bool,
+    /// Start of the quiet hours (HH:MM)
+    pub start: String,
+    /// End of the quiet hours (HH:MM, the next day if before the start)
+    pub end: String,
+}
+
+impl Default for QuietHoursConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start: "22:00".to_owned(),
+            end: "07:00".to_owned(),
+        }
+    }
+}
+
+/// Traffic shaping configuration
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShapingConfig {
+    /// Hold back background messages while streaming saturates the upstream
+    pub enabled: bool,
+    /// Round trip time to the server above which the upstream counts as saturated (milliseconds)
+    pub saturation_rtt_ms: u64,
+    /// How long background messages are held back at most (seconds)
+    pub batch_secs: u64,
+    /// Interval between heartbeats (seconds)
+    pub heartbeat_secs: u64,
+    /// Interval between heartbeats while throttling (seconds)
+    pub throttled_heartbeat_secs: u64,
+}
+
+impl Default for ShapingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            saturation_rtt_ms: 300,
+            batch_secs: 10,
+            heartbeat_secs: 15,
+            throttled_heartbeat_secs: 60,
+        }
+    }
+}
+
+/// Keepalive configuration
+/// The WebSocket ping interval is [shaping] heartbeat_secs
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeepaliveConfig {
+    /// Idle time before TCP keepalive probes are sent (seconds, 0 disables)
+    pub tcp_secs: u64,
+    /// Suggest shorter intervals when the connection keeps dropping after being idle
+    pub detect_idle_drops: bool,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            tcp_secs: 30,
+            detect_idle_drops: true,
+        }
+    }
+}
+
+/// DNS configuration
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DnsConfig {
+    /// DNS-over-HTTPS provider that resolves the servers: "cloudflare", "google"
+    /// or the URL of a DNS JSON API (the system resolver if not set, e.g. for NAT64 networks)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doh: Option<String>,
+    /// Use the system resolver when the provider fails (off: the connection attempt fails)
+    pub system_fallback: bool,
+}
+
+/// TLS configuration
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsConfig {
+    /// TLS implementation of the server connection
+    pub backend: TlsBackend,
+}
+
+/// TLS implementations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TlsBackend {
+    /// rustls with the bundled root certificates (works with a broken system certificate store)
+    #[default]
+    Rustls,
+    /// TLS and certificate store of the operating system (needs the native-tls feature)
+    Native,
+}
+
+/// Timeout configuration (seconds)
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeoutsConfig {
+    /// Connecting to the server
+    pub connect_secs: u64,
+    /// Waiting for a message from the server (longer than heartbeat_secs in [shaping])
+    pub read_secs: u64,
+    /// Steam creating an invite link
+    pub invite_secs: u64,
+    /// Requests to the link shortener
+    pub http_secs: u64,
+    /// Answers of the router and the STUN servers
+    pub gateway_secs: u64,
+    /// Browsers sending their request to the LAN page
+    pub lan_secs: u64,
+    /// Steam fetching the name of a guest
+    pub persona_secs: u64,
+}
+
+impl Default for TimeoutsConfig {
+    fn default() -> Self {
+        Self {
+            connect_secs: 10,
+            read_secs: 60,
+            invite_secs: 10,
+            http_secs: 5,
+            gateway_secs: 3,
+            lan_secs: 5,
+            persona_secs: 3,
+        }
+    }
+}
+
+impl TimeoutsConfig {
+    /// Checks that each timeout is within its bounds
+    pub fn validate(&self, shaping: &ShapingConfig) -> Result<()> {
+        let bounds = [
+            ("connect_secs", self.connect_secs, 1, 120),
+            ("read_secs", self.read_secs, 10, 600),
+            ("invite_secs", self.invite_secs, 1, 120),
+            ("http_secs", self.http_secs, 1, 60),
+            ("gateway_secs", self.gateway_secs, 1, 30),
+            ("lan_secs", self.lan_secs, 1, 60),
+            ("persona_secs", self.persona_secs, 1, 30),
+        ];
+        for (name, value, min, max) in bounds {
+            if !(min..=max).contains(&value) {
+                anyhow::bail!(
+                    "{name} in [timeouts] must be between {min} and {max} seconds (got {value})"
+                );
+            }
+        }
+        // The heartbeats keep an idle connection from timing out
+        if self.read_secs <= shaping.heartbeat_secs {
+            anyhow::bail!(
+                "read_secs in [timeouts] must be longer than heartbeat_secs in [shaping] ({})",
+                shaping.heartbeat_secs
+            );
+        }
+        Ok(())
+    }
+
+    pub fn connect(&self) -> Duration {
+        Duration::from_secs(self.connect_secs)
+    }
+
+    pub fn read(&self) -> Duration {
+        Duration::from_secs(self.read_secs)
+    }
+
+    pub fn invite(&self) -> Duration {
+        Duration::from_secs(self.invite_secs)
+    }
+
+    pub fn http(&self) -> Duration {
+        Duration::from_secs(self.http_secs)
+    }
+
+    pub fn gateway(&self) -> Duration {
+        Duration::from_secs(self.gateway_secs)
+    }
+
+    pub fn lan(&self) -> Duration {
+        Duration::from_secs(self.lan_secs)
+    }
+
+    pub fn persona(&self) -> Duration {
+        Duration::from_secs(self.persona_secs)
+    }
+}
+
+/// Device configuration
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceConfig {
+    /// Identifies this PC among the devices sharing the UUID (generated on the first start)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Name shown by the bot and to the other devices (the host name if not set)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Do not tell the servers the name of this PC
+    pub anonymize: bool,
+}
+
+/// Device pairing configuration
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PairingConfig {
+    /// Link the client with Discord by entering a short code shown in the console
+    pub enabled: bool,
+}
+
+/// Invite link shortener configuration
+/// The API receives POST {"url": "..."} and answers {"url": "<short URL>", "id": "<ID>"}
+/// Short links are deleted with DELETE <api>/<ID> when the invite is revoked
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShortenerConfig {
+    /// Shorten the invite URLs before sharing them
+    pub enabled: bool,
+    /// Shortener API (defaults to /shorten on the server the client registers with)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api: Option<String>,
+    /// Bearer token of the shortener API
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+/// NAT traversal configuration
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NatConfig {
+    /// Open the Remote Play ports on the router with UPnP or NAT-PMP
+    pub port_mapping: bool,
+    /// STUN servers that classify the NAT at startup (none: no probe)
+    pub stun_servers: Vec<String>,
+}
+
+impl Default for NatConfig {
+    fn default() -> Self {
+        Self {
+            port_mapping: false,
+            stun_servers: vec![
+                "stun.l.google.com:19302".to_owned(),
+                "stun.cloudflare.com:3478".to_owned(),
+            ],
+        }
+    }
+}
+
+/// Resource usage check configuration
+/// Task and queue counts are written to the log file and a warning is shown when one keeps growing
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchdogConfig {
+    /// Seconds between checks (0: never)
+    pub interval_secs: u64,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self { interval_secs: 300 }
+    }
+}
+
+/// Wake-on-LAN agent configuration (the agent command runs on a low-power device next to the gaming PC)
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AgentConfig {
+    /// MAC address of the gaming PC (e.g. "01:23:45:67:89:ab"), the only one the server may wake
+    pub mac: Option<String>,
+    /// Address the magic packets are sent to (the broadcast address of the LAN)
+    pub broadcast: String,
+    /// UDP port of the magic packets (usually 7 or 9)
+    pub port: u16,
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            mac: None,
+            broadcast: "255.255.255.255".to_owned(),
+            port: 9,
+        }
+    }
+}
+
+/// Host load reporting configuration (CPU, GPU and video encoder while guests are playing)
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoadConfig {
+    /// Seconds between samples while streaming (0: never)
+    pub interval_secs: u64,
+    /// Warn when the video encoder is busier than this (percent)
+    pub encoder_warn_percent: f32,
+    /// Samples in a row above the limit before warning
+    pub warn_after: u32,
+}
+
+impl Default for LoadConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 10,
+            encoder_warn_percent: 90.0,
+            warn_after: 3,
+        }
+    }
+}
+
+/// LAN party page configuration
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LanConfig {
+    /// Serve a page with the invite link and a join counter on the local network
+    pub enabled: bool,
+    /// Port of the page
+    pub port: u16,
+    /// Only serve the page and do not connect to any server
+    pub offline: bool,
+}
+
+impl Default for LanConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 8730,
+            offline: false,
+        }
+    }
+}
+
+/// Health endpoint configuration
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthConfig {
+    /// Serve GET /healthz (only in container mode if not set)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// Port of the endpoint
+    pub port: u16,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: None,
+            port: 8731,
+        }
+    }
+}
+
+/// Sound alerts configuration
+#[cfg(feature = "sounds")]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoundsConfig {
+    /// Play sound alerts
+    pub enabled: bool,
+    /// Volume (0.0 - 1.0)
+    pub volume: f32,
+    /// Alert when a guest joins
+    pub join: SoundConfig,
+    /// Alert when a guest leaves
+    pub leave: SoundConfig,
+    /// Alert when the connection to the server is lost
+    pub disconnect: SoundConfig,
+}
+
+#[cfg(feature = "sounds")]
+impl Default for SoundsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            volume: 0.5,
+            join: SoundConfig::default(),
+            leave: SoundConfig::default(),
+            disconnect: SoundConfig::default(),
+        }
+    }
+}
+
+/// Sound alert configuration of a single event
+#[cfg(feature = "sounds")]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoundConfig {
+    /// Play this alert
+    pub enabled: bool,
+    /// Sound file (wav, ogg or mp3) to play instead of the built-in chime,
+    /// relative to the executable directory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+}
+
+#[cfg(feature = "sounds")]
+impl Default for SoundConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            file: None,
+        }
+    }
+}
+
+/// OBS integration configuration
+#[cfg(feature = "integration")]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ObsConfig {
+    /// Connect to OBS
+    pub enabled: bool,
+    /// obs-websocket server URL
+    pub url: String,
+    /// obs-websocket server password
+    pub password: Option<String>,
+    /// Scene to switch to when a guest joins
+    pub join_scene: Option<String>,
+    /// Scene to switch to when the last guest leaves
+    pub leave_scene: Option<String>,
+}
+
+#[cfg(feature = "integration")]
+impl Default for ObsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: "ws://127.0.0.1:4455".to_owned(),
+            password: None,
+            join_scene: None,
+            leave_scene: None,
+        }
+    }
+}
+
+/// Desktop notification configuration
+#[cfg(all(windows, feature = "notifications"))]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    /// Show a toast with Kick / Ban buttons when a guest joins
+    pub enabled: bool,
+    /// How long the Ban button bans the guest
+    pub ban_duration: BanDuration,
+}
+
+#[cfg(all(windows, feature = "notifications"))]
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ban_duration: BanDuration::Permanent,
+        }
+    }
+}
+
+/// Notification area icon configuration
+#[cfg(all(windows, feature = "tray"))]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrayConfig {
+    /// Show the connection state and guest count in the notification area
+    pub enabled: bool,
+}
+
+#[cfg(all(windows, feature = "tray"))]
+impl Default for TrayConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Discord rich presence configuration
+#[cfg(feature = "discord-rpc")]
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiscordRpcConfig {
+    /// Show the connection state and guest count as the host's Discord activity
+    pub enabled: bool,
+    /// ID of the Discord application the activity is shown for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+}
+
+/// macOS integration configuration
+#[cfg(all(target_os = "macos", feature = "macos"))]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MacosConfig {
+    /// Show the connection state and guest count in the menu bar
+    pub menu_bar: bool,
+    /// Post notifications when a guest joins or the connection is lost
+    pub notifications: bool,
+    /// Reconnect to the server when the Mac wakes from sleep
+    pub reconnect_on_wake: bool,
+}
+
+#[cfg(all(target_os = "macos", feature = "macos"))]
+impl Default for MacosConfig {
+    fn default() -> Self {
+        Self {
+            menu_bar: true,
+            notifications: true,
+            reconnect_on_wake: true,
+        }
+    }
+}
+
+/// User script configuration
+#[cfg(feature = "scripting")]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScriptsConfig {
+    /// Run the scripts
+    pub enabled: bool,
+    /// Rhai script files, relative to the executable directory
+    pub files: Vec<PathBuf>,
+    /// Stop a script that runs more operations than this for a single event
+    pub max_operations: u64,
+}
+
+#[cfg(feature = "scripting")]
+impl Default for ScriptsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            files: Vec::new(),
+            max_operations: 1_000_000,
+        }
+    }
 }
 
 /// Get the current executable path
 pub fn get_exe_path() -> Result<PathBuf> {
+    // The state files are named after the executable, so they end up in the data directory
+    if let Some(dir) = env::var_os(DATA_DIR_ENV) {
+        return Ok(Path::new(&dir).join("remoteplay-inviter"));
+    }
     // If the APPIMAGE environment variable is set, use its path as the current executable path.
     match env::var("APPIMAGE") {
         Ok(appimage_path) => {
@@ -40,6 +1099,14 @@ pub fn get_exe_path() -> Result<PathBuf> {
 
 /// Read the endpoint configuration
 pub fn read_endpoint_config() -> Result<Option<EndpointConfig>> {
+    if container::is_enabled() {
+        return match env::var(ENDPOINT_ENV) {
+            Ok(content) => toml::from_str(&content)
+                .map(Some)
+                .with_context(|| format!("Unable to parse {ENDPOINT_ENV}")),
+            Err(_) => Ok(None),
+        };
+    }
     let exe_path = get_exe_path()?;
     let config_path = exe_path.with_extension("endpoint.toml");
 
@@ -56,20 +1123,191 @@ pub fn read_endpoint_config() -> Result<Option<EndpointConfig>> {
 
 /// Read or generate the UUID configuration
 pub fn read_or_generate_config<F: Fn() -> Config>(generate_config: F) -> Result<Config> {
+    if container::is_enabled() {
+        return read_env_config();
+    }
     let exe_path = get_exe_path()?;
     let config_path = exe_path.with_extension("config.toml");
 
     if config_path.exists() {
         let config_content = fs::read_to_string(&config_path)
             .with_context(|| format!("Unable to read UUID config file: {:?}", &config_path))?;
-        let config: Config =
+        let mut config: Config =
             toml::from_str(&config_content).context("Unable to parse UUID config file")?;
+        config.timeouts.validate(&config.shaping)?;
+        if vault::is_sealed(&config.uuid) {
+            config.uuid = vault::unseal(&config.uuid)?;
+        }
         Ok(config)
     } else {
         let config = generate_config();
         let config_content = toml::to_string(&config).context("Unable to serialize config")?;
-        fs::write(&config_path, config_content)
+        files::write(&config_path, config_content)
             .with_context(|| format!("Unable to write config file: {:?}", &config_path))?;
         Ok(config)
     }
 }
+
+/**
+ * Reads the log file settings without creating a config file (or a token)
+ * @return The settings of the config file (the defaults if there is none)
+ */
+pub fn read_logging_config() -> Result<LoggingConfig> {
+    /// The logging section of the config file (the token is not decrypted)
+    #[derive(Default, Deserialize)]
+    #[serde(default)]
+    struct LoggingOnly {
+        logging: LoggingConfig,
+    }
+
+    if container::is_enabled() {
+        return Ok(read_env_config()?.logging);
+    }
+    let config_path = get_exe_path()?.with_extension("config.toml");
+    if !config_path.exists() {
+        return Ok(LoggingConfig::default());
+    }
+    let config_content = fs::read_to_string(&config_path)
+        .with_context(|| format!("Unable to read UUID config file: {:?}", &config_path))?;
+    let config: LoggingOnly =
+        toml::from_str(&config_content).context("Unable to parse UUID config file")?;
+    Ok(config.logging)
+}
+
+/**
+ * Reads the configuration from the environment (container mode, nothing is written)
+ * @return The settings of REMOTEPLAY_INVITER_CONFIG with the REMOTEPLAY_INVITER__<SECTION>__<KEY> values on top
+ */
+fn read_env_config() -> Result<Config> {
+    let mut document: toml::Table = match env::var(CONFIG_ENV) {
+        Ok(content) => {
+            toml::from_str(&content).with_context(|| format!("Unable to parse {CONFIG_ENV}"))?
+        }
+        Err(_) => toml::Table::new(),
+    };
+    for (name, value) in env::vars() {
+        let Some(path) = name.strip_prefix(VALUE_ENV_PREFIX) else {
+            continue;
+        };
+        let path: Vec<String> = path.split("__").map(str::to_lowercase).collect();
+        let Some((key, sections)) = path.split_last() else {
+            continue;
+        };
+        let mut table = &mut document;
+        for section in sections {
+            table = table
+                .entry(section.as_str())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                .as_table_mut()
+                .with_context(|| format!("{name}: {section} is not a section"))?;
+        }
+        // A TOML value (true, 8080, ["a", "b"]), anything else is text
+        let value = toml::from_str::<toml::Table>(&format!("value = {value}"))
+            .ok()
+            .and_then(|mut parsed| parsed.remove("value"))
+            .unwrap_or(toml::Value::String(value));
+        table.insert(key.clone(), value);
+    }
+    if !document.contains_key("uuid") {
+        anyhow::bail!("Set the token in {VALUE_ENV_PREFIX}UUID (container mode)");
+    }
+
+    let mut config: Config = document
+        .try_into()
+        .map_err(|err| anyhow::anyhow!("Invalid configuration in the environment: {err}"))?;
+    config.timeouts.validate(&config.shaping)?;
+    if vault::is_sealed(&config.uuid) {
+        config.uuid = vault::unseal(&config.uuid)?;
+    }
+    Ok(config)
+}
+
+/// Replace the UUID in the configuration file (keeping the other settings and comments)
+pub fn write_uuid(old: &str, new: &str) -> Result<()> {
+    let exe_path = get_exe_path()?;
+    let config_path = exe_path.with_extension("config.toml");
+
+    let config_content = fs::read_to_string(&config_path)
+        .with_context(|| format!("Unable to read UUID config file: {:?}", &config_path))?;
+    let stored = stored_uuid(&config_content)?;
+    // An encrypted UUID stays encrypted
+    let (old, new) = if vault::is_sealed(&stored) {
+        (stored, vault::seal(new)?)
+    } else {
+        (old.to_owned(), new.to_owned())
+    };
+    replace_uuid(&config_path, &config_content, &old, &new)
+}
+
+/// Encrypt (or decrypt) the UUID in the configuration file
+pub fn write_sealed_uuid(uuid: &str, sealed: bool) -> Result<()> {
+    let exe_path = get_exe_path()?;
+    let config_path = exe_path.with_extension("config.toml");
+
+    let config_content = fs::read_to_string(&config_path)
+        .with_context(|| format!("Unable to read UUID config file: {:?}", &config_path))?;
+    let stored = stored_uuid(&config_content)?;
+    let new = if sealed {
+        vault::seal(uuid)?
+    } else {
+        uuid.to_owned()
+    };
+    replace_uuid(&config_path, &config_content, &stored, &new)
+}
+
+/// Whether the UUID in the configuration file is encrypted
+pub fn is_uuid_sealed() -> Result<bool> {
+    let exe_path = get_exe_path()?;
+    let config_path = exe_path.with_extension("config.toml");
+
+    let config_content = fs::read_to_string(&config_path)
+        .with_context(|| format!("Unable to read UUID config file: {:?}", &config_path))?;
+    Ok(vault::is_sealed(&stored_uuid(&config_content)?))
+}
+
+/// UUID as written in the configuration file (possibly encrypted)
+fn stored_uuid(config_content: &str) -> Result<String> {
+    #[derive(Deserialize)]
+    struct Stored {
+        uuid: String,
+    }
+    let stored: Stored =
+        toml::from_str(config_content).context("Unable to parse UUID config file")?;
+    Ok(stored.uuid)
+}
+
+/// Replace the first occurrence of the stored UUID and write the file
+fn replace_uuid(config_path: &Path, config_content: &str, old: &str, new: &str) -> Result<()> {
+    let quoted = format!("\"{old}\"");
+    if !config_content.contains(&quoted) {
+        anyhow::bail!("UUID not found in config file: {:?}", config_path);
+    }
+    let config_content = config_content.replacen(&quoted, &format!("\"{new}\""), 1);
+    files::write(config_path, config_content)
+        .with_context(|| format!("Unable to write config file: {:?}", config_path))
+}
+
+/// Add the device ID to the configuration file (keeping the other settings and comments)
+pub fn write_device_id(id: &str) -> Result<()> {
+    let exe_path = get_exe_path()?;
+    let config_path = exe_path.with_extension("config.toml");
+
+    let config_content = fs::read_to_string(&config_path)
+        .with_context(|| format!("Unable to read UUID config file: {:?}", &config_path))?;
+    let id_line = format!("id = \"{id}\"");
+    let config_content = if config_content.lines().any(|line| line.trim() == "[device]") {
+        // Put the ID at the top of the existing section
+        let mut lines = Vec::new();
+        for line in config_content.lines() {
+            lines.push(line);
+            if line.trim() == "[device]" {
+                lines.push(&id_line);
+            }
+        }
+        lines.join("\n") + "\n"
+    } else {
+        format!("{}\n\n[device]\n{id_line}\n", config_content.trim_end())
+    };
+    files::write(&config_path, config_content)
+        .with_context(|| format!("Unable to write config file: {:?}", &config_path))
+}
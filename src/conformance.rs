@@ -0,0 +1,168 @@
+use anyhow::Result;
+use schemars::{schema::RootSchema, schema_for};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    console::{self, Glyph},
+    models::{ClientMessage, ServerMessage},
+};
+
+/// Canonical requests of the servers, one JSON message per line
+const SERVER_VECTORS: &str = include_str!("../resources/protocol/server.jsonl");
+/// Canonical answers and notifications of the client, one JSON message per line
+const CLIENT_VECTORS: &str = include_str!("../resources/protocol/client.jsonl");
+
+/**
+ * Round-trips every test vector through the message types
+ * @return Why each failing vector failed (empty if the protocol is unchanged)
+ */
+pub fn verify() -> Vec<String> {
+    let mut failures = Vec::new();
+
+    for (line, text) in vectors(SERVER_VECTORS) {
+        let result = round_trip::<ServerMessage>(text).and_then(|(message, value)| {
+            // Unknown commands from newer servers are ignored, never rejected
+            if message.cmd.name().is_none() {
+                return Ok(());
+            }
+            // Servers may leave out the optional fields, so only the given ones must match
+            compare(&parse(text)?, &value, false)
+        });
+        if let Err(err) = result {
+            failures.push(format!("server.jsonl:{line}: {err}"));
+        }
+    }
+    for (line, text) in vectors(CLIENT_VECTORS) {
+        let result = round_trip::<ClientMessage>(text)
+            .and_then(|(_, value)| compare(&parse(text)?, &value, true));
+        if let Err(err) = result {
+            failures.push(format!("client.jsonl:{line}: {err}"));
+        }
+    }
+
+    // Every command needs a vector, or a change to it would go unnoticed
+    for (file, lines, schema) in [
+        ("server.jsonl", SERVER_VECTORS, schema_for!(ServerMessage)),
+        ("client.jsonl", CLIENT_VECTORS, schema_for!(ClientMessage)),
+    ] {
+        let covered: Vec<String> = vectors(lines)
+            .filter_map(|(_, text)| Some(parse(text).ok()?.get("cmd")?.as_str()?.to_owned()))
+            .collect();
+        for cmd in commands(schema) {
+            if !covered.contains(&cmd) {
+                failures.push(format!("{file}: no vector for the {cmd} command"));
+            }
+        }
+    }
+    failures
+}
+
+/// `verify-protocol` subcommand
+pub fn run() -> Result<()> {
+    let failures = verify();
+    for failure in &failures {
+        console::eprintln!("{} {}", Glyph::Err, failure)?;
+    }
+    if !failures.is_empty() {
+        anyhow::bail!(
+            "Protocol check failed ({}): the messages may no longer be compatible with the deployed servers",
+            failures.len()
+        );
+    }
+    console::println!(
+        "{} {} protocol test vectors passed",
+        Glyph::Ok,
+        vectors(SERVER_VECTORS).count() + vectors(CLIENT_VECTORS).count()
+    )?;
+    Ok(())
+}
+
+/// Non-empty lines of a vector file with their line numbers
+fn vectors(file: &str) -> impl Iterator<Item = (usize, &str)> {
+    file.lines()
+        .enumerate()
+        .map(|(index, text)| (index + 1, text.trim()))
+        .filter(|(_, text)| !text.is_empty())
+}
+
+fn parse(text: &str) -> Result<Map<String, Value>, String> {
+    serde_json::from_str(text).map_err(|err| format!("invalid JSON: {err}"))
+}
+
+/// Reads a message and writes it again
+fn round_trip<T: DeserializeOwned + Serialize>(
+    text: &str,
+) -> Result<(T, Map<String, Value>), String> {
+    let message: T = serde_json::from_str(text).map_err(|err| format!("not accepted: {err}"))?;
+    let value = serde_json::to_value(&message).map_err(|err| format!("not written: {err}"))?;
+    let Value::Object(value) = value else {
+        return Err("not written as an object".to_owned());
+    };
+    Ok((message, value))
+}
+
+/**
+ * Compares a vector with the message written back
+ * @param exact Whether the message may not have fields missing from the vector
+ */
+fn compare(
+    vector: &Map<String, Value>,
+    written: &Map<String, Value>,
+    exact: bool,
+) -> Result<(), String> {
+    for (field, expected) in vector {
+        match written.get(field) {
+            Some(actual) if actual == expected => (),
+            Some(actual) => return Err(format!("{field} is written as {actual}, not {expected}")),
+            None => return Err(format!("{field} is not written")),
+        }
+    }
+    for (field, actual) in written {
+        // Unset optional fields of server messages are written as null
+        if !vector.contains_key(field) && (exact || !actual.is_null()) {
+            return Err(format!("{field} is written but not in the vector"));
+        }
+    }
+    Ok(())
+}
+
+/// Command names of a message schema
+fn commands(schema: RootSchema) -> Vec<String> {
+    let Ok(schema) = serde_json::to_value(schema) else {
+        return Vec::new();
+    };
+    schema["oneOf"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|variant| variant["properties"]["cmd"]["enum"][0].as_str())
+        .map(str::to_owned)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vectors_round_trip() {
+        let failures = verify();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn every_command_has_a_name() {
+        assert!(!commands(schema_for!(ServerMessage)).is_empty());
+        assert!(!commands(schema_for!(ClientMessage)).is_empty());
+    }
+
+    #[test]
+    fn renamed_field_is_detected() {
+        let vector = parse(r#"{"id":"","cmd":"steam","available":true}"#).unwrap();
+        let renamed = parse(r#"{"id":"","cmd":"steam","enabled":true}"#).unwrap();
+        assert!(compare(&vector, &renamed, true).is_err());
+        assert!(compare(&vector, &renamed, false).is_err());
+        assert!(compare(&vector, &vector, true).is_ok());
+    }
+}
@@ -0,0 +1,353 @@
+use std::{sync::OnceLock, time::Duration};
+use tokio::time::Instant;
+
+use crate::{error::ClientError, retry::RetrySec};
+
+/// Set when a process supervisor restarts the client: reconnection attempts before it gives up
+/// (--max-retries, 0 with --exit-on-disconnect, None: keep reconnecting)
+static SUPERVISED: OnceLock<Option<u32>> = OnceLock::new();
+
+/// Hands the restarts over to a process supervisor: stop after this many failed reconnections (None: never)
+pub fn set_supervised(max_retries: Option<u32>) {
+    let _ = SUPERVISED.set(max_retries);
+}
+
+/// Whether a process supervisor restarts the client (the exit code tells it why the client stopped)
+pub fn is_supervised() -> bool {
+    SUPERVISED.get().is_some()
+}
+
+/// Error of a connection that ran out of reconnection attempts
+pub fn gave_up(retries: u32) -> ClientError {
+    ClientError::Network(match retries {
+        0 => anyhow::anyhow!("Disconnected from the server (--exit-on-disconnect)"),
+        retries => {
+            anyhow::anyhow!("Unable to reach the server, giving up (--max-retries {retries})")
+        }
+    })
+}
+
+/// State of the connection to a server
+#[derive(Debug, Clone, PartialEq)]
+pub enum State {
+    /// Opening the connection (again after a backoff when `reconnect` is set)
+    Connecting { reconnect: bool },
+    /// Registered with the server and processing its messages
+    Connected { reconnect: bool },
+    /// Waiting before the next connection attempt
+    Backoff {
+        /// How long to wait
+        wait: Duration,
+        /// Whether the server closed the connection on purpose (restart or shutdown)
+        planned: bool,
+    },
+    /// Stopped for good (exit requested, outdated client, refused by the server)
+    Fatal,
+    /// Stopped after the last allowed reconnection failed (a process supervisor restarts the client)
+    GaveUp { retries: u32 },
+}
+
+/// Something that happened to the connection
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    /// The WebSocket handshake succeeded
+    Opened,
+    /// The server sent a message (the connection is healthy)
+    Received,
+    /// The connection attempt failed or the connection was lost
+    Lost { planned: bool },
+    /// Reconnect soon (the system resumed or the server moved)
+    Reset,
+    /// The server asked to wait this many seconds before reconnecting
+    Hint(u64),
+    /// The backoff is over (or a reconnect was requested)
+    Elapsed,
+    /// The client has to stop
+    Stop,
+}
+
+/// Idle time after which a dropped connection looks like a NAT or proxy timeout
+const MIN_IDLE: Duration = Duration::from_secs(20);
+/// Drops in a row after being idle before the host is told
+const IDLE_DROPS: u32 = 2;
+
+/// Detects connections that are dropped after a while without traffic (aggressive NAT or proxy timeouts)
+pub struct IdleWatch {
+    /// Whether drops are reported
+    enabled: bool,
+    /// How long the server may stay silent before the connection counts as lost
+    timeout: Duration,
+    /// When something was last sent or received
+    last_traffic: Instant,
+    /// Drops in a row after being idle
+    drops: u32,
+    /// Whether the host was told already
+    warned: bool,
+}
+
+impl IdleWatch {
+    pub fn new(enabled: bool, timeout: Duration) -> Self {
+        Self {
+            enabled,
+            timeout,
+            last_traffic: Instant::now(),
+            drops: 0,
+            warned: false,
+        }
+    }
+
+    /// How long the server may stay silent before the connection counts as lost
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Records traffic on the connection
+    pub fn traffic(&mut self) {
+        self.last_traffic = Instant::now();
+    }
+
+    /**
+     * Records an unexpected drop of the connection
+     * @return How long the connection was idle, when it keeps dropping after being idle (reported once)
+     */
+    pub fn dropped(&mut self) -> Option<Duration> {
+        let idle = self.last_traffic.elapsed();
+        if idle < MIN_IDLE {
+            self.drops = 0;
+            return None;
+        }
+        self.drops += 1;
+        if !self.enabled || self.drops < IDLE_DROPS || self.warned {
+            return None;
+        }
+        self.warned = true;
+        Some(idle)
+    }
+}
+
+/// Connection state machine of a server endpoint
+/// Decides when to connect and how long to wait, the I/O is done by the caller
+pub struct ConnectionStateMachine {
+    state: State,
+    retry_sec: RetrySec,
+    /// Failed attempts allowed in a row (None: retry forever)
+    max_retries: Option<u32>,
+    /// Failed attempts since the server last sent something
+    retries: u32,
+}
+
+impl ConnectionStateMachine {
+    /// State machine with the retry limit of the command line
+    pub fn new() -> Self {
+        Self::with_max_retries(SUPERVISED.get().copied().flatten())
+    }
+
+    pub fn with_max_retries(max_retries: Option<u32>) -> Self {
+        Self {
+            state: State::Connecting { reconnect: false },
+            retry_sec: RetrySec::new(),
+            max_retries,
+            retries: 0,
+        }
+    }
+
+    /// Current state
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    /**
+     * Applies an input (inputs that do not fit the state are ignored)
+     * @return The new state
+     */
+    pub fn handle(&mut self, input: Input) -> &State {
+        let next = match (&self.state, input) {
+            (State::Fatal | State::GaveUp { .. }, _) => None,
+            (_, Input::Stop) => Some(State::Fatal),
+            (_, Input::Reset) => {
+                self.retry_sec.reset();
+                None
+            }
+            (_, Input::Hint(sec)) => {
+                self.retry_sec.hint(sec);
+                None
+            }
+            (State::Connecting { reconnect }, Input::Opened) => Some(State::Connected {
+                reconnect: *reconnect,
+            }),
+            (State::Connected { .. }, Input::Received) => {
+                self.retry_sec.reset();
+                self.retries = 0;
+                None
+            }
+            (State::Connecting { .. } | State::Connected { .. }, Input::Lost { .. })
+                if self.max_retries.is_some_and(|max| self.retries >= max) =>
+            {
+                Some(State::GaveUp {
+                    retries: self.retries,
+                })
+            }
+            (State::Connecting { .. } | State::Connected { .. }, Input::Lost { planned }) => {
+                self.retries += 1;
+                Some(State::Backoff {
+                    wait: self.retry_sec.next(),
+                    planned,
+                })
+            }
+            (State::Backoff { .. }, Input::Elapsed) => Some(State::Connecting { reconnect: true }),
+            _ => None,
+        };
+        if let Some(next) = next {
+            self.state = next;
+        }
+        &self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wait of a backoff state
+    fn wait(machine: &ConnectionStateMachine) -> Duration {
+        match machine.state() {
+            State::Backoff { wait, .. } => *wait,
+            state => panic!("not backing off: {state:?}"),
+        }
+    }
+
+    #[test]
+    fn connects_and_reconnects() {
+        let mut machine = ConnectionStateMachine::new();
+        assert_eq!(machine.state(), &State::Connecting { reconnect: false });
+        assert_eq!(
+            machine.handle(Input::Opened),
+            &State::Connected { reconnect: false }
+        );
+        machine.handle(Input::Lost { planned: true });
+        assert!(matches!(
+            machine.state(),
+            State::Backoff { planned: true, .. }
+        ));
+        assert_eq!(
+            machine.handle(Input::Elapsed),
+            &State::Connecting { reconnect: true }
+        );
+        assert_eq!(
+            machine.handle(Input::Opened),
+            &State::Connected { reconnect: true }
+        );
+    }
+
+    #[test]
+    fn failed_attempts_back_off_up_to_the_cap() {
+        let mut machine = ConnectionStateMachine::new();
+        machine.handle(Input::Lost { planned: false });
+        let first = wait(&machine);
+        assert!(first >= Duration::from_secs(1) && first <= Duration::from_secs(2));
+        for _ in 0..10 {
+            machine.handle(Input::Elapsed);
+            machine.handle(Input::Lost { planned: false });
+            assert!(wait(&machine) <= Duration::from_secs(60));
+        }
+        assert!(wait(&machine) >= Duration::from_secs(30));
+    }
+
+    #[test]
+    fn messages_reset_the_backoff() {
+        let mut machine = ConnectionStateMachine::new();
+        for _ in 0..6 {
+            machine.handle(Input::Lost { planned: false });
+            machine.handle(Input::Elapsed);
+        }
+        machine.handle(Input::Opened);
+        machine.handle(Input::Received);
+        machine.handle(Input::Lost { planned: false });
+        assert!(wait(&machine) <= Duration::from_secs(2));
+    }
+
+    #[test]
+    fn reset_shortens_the_next_wait() {
+        let mut machine = ConnectionStateMachine::new();
+        for _ in 0..6 {
+            machine.handle(Input::Lost { planned: false });
+            machine.handle(Input::Elapsed);
+        }
+        machine.handle(Input::Reset);
+        machine.handle(Input::Lost { planned: false });
+        assert!(wait(&machine) <= Duration::from_secs(2));
+    }
+
+    #[test]
+    fn hint_sets_the_minimum_wait() {
+        let mut machine = ConnectionStateMachine::new();
+        machine.handle(Input::Opened);
+        machine.handle(Input::Hint(30));
+        machine.handle(Input::Lost { planned: true });
+        assert!(wait(&machine) >= Duration::from_secs(30));
+    }
+
+    #[test]
+    fn stop_is_final() {
+        for input in [Input::Opened, Input::Lost { planned: false }] {
+            let mut machine = ConnectionStateMachine::new();
+            machine.handle(input);
+            assert_eq!(machine.handle(Input::Stop), &State::Fatal);
+            assert_eq!(machine.handle(Input::Elapsed), &State::Fatal);
+            assert_eq!(machine.handle(Input::Opened), &State::Fatal);
+        }
+    }
+
+    #[test]
+    fn gives_up_after_the_max_retries() {
+        let mut machine = ConnectionStateMachine::with_max_retries(Some(2));
+        for _ in 0..2 {
+            machine.handle(Input::Lost { planned: false });
+            machine.handle(Input::Elapsed);
+        }
+        assert_eq!(
+            machine.handle(Input::Lost { planned: false }),
+            &State::GaveUp { retries: 2 }
+        );
+        assert_eq!(
+            machine.handle(Input::Elapsed),
+            &State::GaveUp { retries: 2 }
+        );
+
+        // A healthy connection starts the count over
+        let mut machine = ConnectionStateMachine::with_max_retries(Some(1));
+        machine.handle(Input::Lost { planned: false });
+        machine.handle(Input::Elapsed);
+        machine.handle(Input::Opened);
+        machine.handle(Input::Received);
+        assert!(matches!(
+            machine.handle(Input::Lost { planned: true }),
+            State::Backoff { .. }
+        ));
+
+        let mut machine = ConnectionStateMachine::with_max_retries(Some(0));
+        machine.handle(Input::Opened);
+        assert_eq!(
+            machine.handle(Input::Lost { planned: true }),
+            &State::GaveUp { retries: 0 }
+        );
+    }
+
+    #[test]
+    fn ignores_inputs_that_do_not_fit() {
+        let mut machine = ConnectionStateMachine::new();
+        assert_eq!(
+            machine.handle(Input::Elapsed),
+            &State::Connecting { reconnect: false }
+        );
+        machine.handle(Input::Opened);
+        assert_eq!(
+            machine.handle(Input::Opened),
+            &State::Connected { reconnect: false }
+        );
+        assert_eq!(
+            machine.handle(Input::Elapsed),
+            &State::Connected { reconnect: false }
+        );
+    }
+}
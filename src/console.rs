@@ -1,17 +1,392 @@
 use anyhow::{Context as _, Result};
-use crossterm::{cursor, terminal, QueueableCommand};
-use std::fmt::Arguments;
+#[cfg(feature = "tui")]
+use clipboard::{ClipboardContext, ClipboardProvider};
+use crossterm::{cursor, style::Stylize as _, terminal, QueueableCommand};
+#[cfg(feature = "tui")]
+use qrcode::{render::unicode::Dense1x2, QrCode};
+use std::env;
+use std::fmt::{self, Arguments, Display};
+use std::future::Future;
 use std::io::{self, Write as _};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use std::sync::{LazyLock, Mutex};
 
+use crate::logger::{self, Level};
+
 /// Last line
 static LAST_LINE: LazyLock<Mutex<String>> = LazyLock::new(|| Mutex::new("".to_string()));
 
+tokio::task_local! {
+    /// Label of the server connection that prints (only set when there are several)
+    static LABEL: String;
+}
+
+/// Runs a task whose output is prefixed with a label
+pub async fn labeled<F: Future>(label: String, future: F) -> F::Output {
+    LABEL.scope(label, future).await
+}
+
+/// Accessible mode (plain sequential output for screen readers)
+static ACCESSIBLE: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables the accessible mode
+pub fn set_accessible(enabled: bool) {
+    ACCESSIBLE.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether the accessible mode is enabled
+pub fn is_accessible() -> bool {
+    ACCESSIBLE.load(Ordering::Relaxed)
+}
+
+/// How much of the output reaches the terminal (the log file always gets everything)
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Verbosity {
+    #[default]
+    Normal,
+    /// Only the warnings and errors (--quiet)
+    Quiet,
+    /// Nothing, only the log file (--silent)
+    Silent,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+/// Sets how much of the output reaches the terminal
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+/// How much of the output reaches the terminal
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Normal,
+        1 => Verbosity::Quiet,
+        _ => Verbosity::Silent,
+    }
+}
+
+/// Whether the regular messages reach the terminal (not in --quiet, --silent or the critical-only Deck mode)
+fn prints_info() -> bool {
+    verbosity() == Verbosity::Normal && !CRITICAL_ONLY.load(Ordering::Relaxed)
+}
+
+/// Machine-readable output on stdout (--json, --quiet and --silent), so the messages go to stderr
+static MACHINE_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables the machine-readable output on stdout
+pub fn set_machine_output(enabled: bool) {
+    MACHINE_OUTPUT.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether stdout only carries the machine-readable output
+pub fn is_machine_output() -> bool {
+    MACHINE_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Stream of the messages for people (stderr while stdout carries the machine-readable output)
+fn messages() -> Box<dyn io::Write> {
+    if is_machine_output() {
+        Box::new(io::stderr())
+    } else {
+        Box::new(io::stdout())
+    }
+}
+
+/// Prints machine-readable output (a JSON document or an invite link) on stdout, skipped in --silent
+pub fn data(text: &str) -> Result<()> {
+    if verbosity() == Verbosity::Silent {
+        return Ok(());
+    }
+    let mut stdout = io::stdout();
+    writeln!(stdout, "{text}")?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Prints a text for people (the printdoc macro)
+pub(crate) fn print_text(text: &str) -> Result<()> {
+    let mut out = messages();
+    out.write_all(text.as_bytes())?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Steam Deck mode (large minimal output)
+static DECK: AtomicBool = AtomicBool::new(false);
+
+/// Only print critical messages (errors, headlines and prompts)
+static CRITICAL_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables the Steam Deck mode
+pub fn set_deck(enabled: bool, critical_only: bool) {
+    DECK.store(enabled, Ordering::Relaxed);
+    CRITICAL_ONLY.store(enabled && critical_only, Ordering::Relaxed);
+}
+
+/// Whether the Steam Deck mode is enabled
+pub fn is_deck() -> bool {
+    DECK.load(Ordering::Relaxed)
+}
+
+/// Prints a short status in block letters (Steam Deck mode only)
+pub fn headline(text: &str) -> Result<()> {
+    if !is_deck() || is_accessible() {
+        return Ok(());
+    }
+    let pixel = if is_ascii() { "##" } else { "██" };
+    let text = crate::deck::render_big(text, pixel);
+    logger::write(Level::Info, format_args!("{}", text));
+    if verbosity() != Verbosity::Normal || hold(&text) {
+        return Ok(());
+    }
+    clear_line()?;
+    let mut out = messages();
+    writeln!(out)?;
+    out.write_all(text.as_bytes())?;
+    update_line()?;
+    Ok(())
+}
+
+/// Copies a text to the clipboard
+#[cfg(feature = "tui")]
+pub fn copy(text: &str) -> Result<()> {
+    ClipboardProvider::new()
+        .and_then(|mut ctx: ClipboardContext| ctx.set_contents(text.to_owned()))
+        .map_err(|err| anyhow::anyhow!("{err}"))
+}
+
+/// Copies a text to the clipboard (needs the tui feature)
+#[cfg(not(feature = "tui"))]
+pub fn copy(_text: &str) -> Result<()> {
+    anyhow::bail!("This build has no clipboard support")
+}
+
+/// Prints a text as a QR code (needs the tui feature)
+#[cfg(not(feature = "tui"))]
+pub fn qr(_text: &str) -> Result<()> {
+    anyhow::bail!("This build cannot draw QR codes")
+}
+
+/// Prints a text as a QR code (skipped in accessible mode and --silent)
+#[cfg(feature = "tui")]
+pub fn qr(text: &str) -> Result<()> {
+    if is_accessible() || verbosity() == Verbosity::Silent {
+        return Ok(());
+    }
+    let code = QrCode::new(text.as_bytes()).context("Unable to create QR code")?;
+    // Light modules are drawn, so it scans on a dark terminal background
+    let image = if is_ascii() {
+        code.render::<char>()
+            .dark_color(' ')
+            .light_color('#')
+            .module_dimensions(2, 1)
+            .build()
+    } else {
+        code.render::<Dense1x2>()
+            .dark_color(Dense1x2::Light)
+            .light_color(Dense1x2::Dark)
+            .build()
+    };
+    clear_line()?;
+    writeln!(messages(), "{image}")?;
+    update_line()?;
+    Ok(())
+}
+
+/// ASCII mode (no Unicode glyphs)
+static ASCII: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables the ASCII mode
+pub fn set_ascii(enabled: bool) {
+    ASCII.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether the ASCII mode is enabled
+pub fn is_ascii() -> bool {
+    ASCII.load(Ordering::Relaxed)
+}
+
+/// Do-not-disturb mode (a fullscreen game has the focus)
+static DO_NOT_DISTURB: AtomicBool = AtomicBool::new(false);
+
+/// Maximum number of held messages (the oldest are dropped)
+const MAX_PENDING: usize = 200;
+
+/// Messages held back while in do-not-disturb mode
+static PENDING: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Whether the do-not-disturb mode is enabled
+pub fn is_do_not_disturb() -> bool {
+    DO_NOT_DISTURB.load(Ordering::Relaxed)
+}
+
+/// Enables or disables the do-not-disturb mode, printing the held messages when it ends
+pub fn set_do_not_disturb(enabled: bool) -> Result<()> {
+    DO_NOT_DISTURB.store(enabled, Ordering::Relaxed);
+    if enabled {
+        return Ok(());
+    }
+
+    let pending = std::mem::take(
+        &mut *PENDING
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Failed to lock pending messages"))?,
+    );
+    if pending.is_empty() {
+        return Ok(());
+    }
+    clear_line()?;
+    let mut out = messages();
+    writeln!(
+        out,
+        "{} {} message(s) while in game:",
+        Glyph::Wait,
+        pending.len()
+    )?;
+    for text in pending {
+        out.write_all(text.as_bytes())?;
+    }
+    update_line()?;
+    Ok(())
+}
+
+/// Holds the text back if the do-not-disturb mode is enabled
+/// @return Whether the text was held back
+pub fn hold(text: &str) -> bool {
+    if !is_do_not_disturb() {
+        return false;
+    }
+    match PENDING.lock() {
+        Ok(mut pending) => {
+            if pending.len() >= MAX_PENDING {
+                pending.remove(0);
+            }
+            pending.push(text.to_owned());
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Rings the terminal bell (suppressed in do-not-disturb mode and --silent)
+pub fn bell() -> Result<()> {
+    if is_do_not_disturb() || verbosity() == Verbosity::Silent {
+        return Ok(());
+    }
+    let mut out = messages();
+    out.write_all(b"\x07")?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Detects whether the terminal is likely to render Unicode glyphs
+pub fn supports_unicode() -> bool {
+    if cfg!(windows) {
+        // Windows Terminal and VS Code render Unicode, the legacy console may not
+        env::var_os("WT_SESSION").is_some()
+            || env::var("TERM_PROGRAM").is_ok_and(|term| term == "vscode")
+    } else {
+        // Check the locale encoding (assume Unicode if the locale is not set)
+        ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .find_map(|key| env::var(key).ok().filter(|value| !value.is_empty()))
+            .map(|locale| {
+                let locale = locale.to_lowercase();
+                locale.contains("utf-8") || locale.contains("utf8")
+            })
+            .unwrap_or(true)
+    }
+}
+
+/// Status glyphs with ASCII fallbacks
+#[derive(Debug, Clone, Copy)]
+pub enum Glyph {
+    /// Success (✓)
+    Ok,
+    /// Error (☓)
+    Err,
+    /// Retry / reconnect (↪)
+    Retry,
+    /// Waiting for user input (□)
+    Wait,
+    /// Player list (★)
+    Players,
+    /// Update available (↑)
+    Update,
+    /// Chat message (✉)
+    Chat,
+    /// Warning (⚠)
+    Warn,
+}
+
+impl Glyph {
+    /// Returns the glyph for the current output mode
+    pub fn as_str(self) -> &'static str {
+        match (self, is_ascii()) {
+            (Glyph::Ok, false) => "✓",
+            (Glyph::Ok, true) => "[OK]",
+            (Glyph::Err, false) => "☓",
+            (Glyph::Err, true) => "[ERR]",
+            (Glyph::Retry, false) => "↪",
+            (Glyph::Retry, true) => "[->]",
+            (Glyph::Wait, false) => "□",
+            (Glyph::Wait, true) => "[..]",
+            (Glyph::Players, false) => "★",
+            (Glyph::Players, true) => "[*]",
+            (Glyph::Update, false) => "↑",
+            (Glyph::Update, true) => "[UP]",
+            (Glyph::Chat, false) => "✉",
+            (Glyph::Chat, true) => "[MSG]",
+            (Glyph::Warn, false) => "⚠",
+            (Glyph::Warn, true) => "[!]",
+        }
+    }
+}
+
+impl Display for Glyph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// In-place line updates (disabled when the terminal rejects cursor control)
+static INLINE_UPDATES: AtomicBool = AtomicBool::new(true);
+
+/// Whether the status line can be rewritten in place
+fn inline_updates() -> bool {
+    // Screen readers re-announce rewritten lines, so never touch the cursor
+    // (and the status line would end up between the machine-readable output)
+    !is_accessible() && !is_deck() && !is_machine_output() && INLINE_UPDATES.load(Ordering::Relaxed)
+}
+
+/// Prepares the terminal for output
+pub fn init_terminal() {
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::{Globalization::CP_UTF8, System::Console::SetConsoleOutputCP};
+
+        // Output UTF-8 regardless of the system code page
+        unsafe { SetConsoleOutputCP(CP_UTF8) };
+
+        // Enable virtual terminal processing (crossterm falls back to WinAPI calls otherwise)
+        let _ = crossterm::ansi_support::supports_ansi();
+    }
+}
+
 /// Clears the current line
 pub fn clear_line() -> Result<()> {
-    io::stdout()
+    if !inline_updates() || verbosity() == Verbosity::Silent {
+        return Ok(());
+    }
+
+    if io::stdout()
         .queue(terminal::Clear(terminal::ClearType::CurrentLine))
-        .context("Failed to update output (clear line)")?;
+        .is_err()
+    {
+        // Legacy consoles may reject the sequence, fall back to plain output
+        INLINE_UPDATES.store(false, Ordering::Relaxed);
+    }
     Ok(())
 }
 
@@ -28,41 +403,134 @@ pub fn save_line(args: std::fmt::Arguments<'_>) -> Result<()> {
 /// Updates the current line
 /// <https://stackoverflow.com/a/59890400>
 pub fn update_line() -> Result<()> {
+    // The status line is printed as a regular line without in-place updates
+    if !inline_updates() || verbosity() != Verbosity::Normal {
+        return Ok(());
+    }
+
     let mut stdout = io::stdout();
     let data = LAST_LINE
         .lock()
         .map_err(|_| anyhow::anyhow!("Failed to lock last line"))?;
-    stdout
+    if stdout
         .queue(terminal::Clear(terminal::ClearType::CurrentLine))
-        .context("Failed to update output (clear line)")?;
+        .is_err()
+    {
+        // Legacy consoles may reject the sequence, fall back to plain output
+        INLINE_UPDATES.store(false, Ordering::Relaxed);
+        return Ok(());
+    }
     stdout
         .write_all(data.as_bytes())
         .context("Failed to update output (write)")?;
-    stdout
-        .queue(cursor::MoveToColumn(0))
-        .context("Failed to update output (left feed)")?;
+    if stdout.queue(cursor::MoveToColumn(0)).is_err() {
+        // Finish the line so the next output does not overwrite it
+        INLINE_UPDATES.store(false, Ordering::Relaxed);
+        stdout
+            .write_all(b"\n")
+            .context("Failed to update output (write)")?;
+    }
     stdout.flush().context("Failed to update output (flush)")?;
     Ok(())
 }
 
-pub(crate) fn fn_println(args: std::fmt::Arguments<'_>) -> Result<()> {
+pub(crate) fn fn_println(args: std::fmt::Arguments<'_>, level: Level, urgent: bool) -> Result<()> {
+    // --quiet still prints the warnings and errors
+    let important = verbosity() == Verbosity::Quiet && level != Level::Info;
+    // Errors are never held back
+    let urgent = urgent || level == Level::Error;
+    LABEL
+        .try_with(|label| println_line(format_args!("[{label}] {args}"), level, important, urgent))
+        .unwrap_or_else(|_| println_line(args, level, important, urgent))
+}
+
+fn println_line(
+    args: std::fmt::Arguments<'_>,
+    level: Level,
+    important: bool,
+    urgent: bool,
+) -> Result<()> {
+    logger::write(level, args);
+    // Only the log file gets the regular messages
+    if !prints_info() && !important {
+        return Ok(());
+    }
+    // Non-urgent messages wait until the game loses the focus
+    if !urgent && hold(&format!("{args}\n")) {
+        return Ok(());
+    }
     clear_line()?;
-    io::stdout().write_fmt(args)?; // Call the original macro
+    let mut out = messages();
+    out.write_fmt(args)?; // Call the original macro
+    out.write_all(b"\n")?;
     update_line()?;
     Ok(())
 }
 
-/// println macro
+/// println macro (`@level` for warnings and errors, which --quiet still prints)
 macro_rules! println {
+    (@level $level:expr, $($arg:tt)*) => {{
+        $crate::console::fn_println(format_args!($($arg)*), $level, false)
+    }};
     ($($arg:tt)*) => {{
-        $crate::console::fn_println(format_args!($($arg)*))
+        $crate::console::fn_println(format_args!($($arg)*), $crate::logger::Level::Info, false)
     }};
 }
 pub(crate) use println;
 
+/// println that is not held back in do-not-disturb mode (prompts and confirmations the host acts on)
+macro_rules! urgent_println {
+    ($($arg:tt)*) => {{
+        $crate::console::fn_println(format_args!($($arg)*), $crate::logger::Level::Info, true)
+    }};
+}
+pub(crate) use urgent_println;
+
+/// Start of chat lines in the log and for screen readers (diagnostics leave them out)
+pub const CHAT_PREFIX: &str = "Chat: ";
+
+/// Prints a chat message in its own color so it stands out from the event log
+pub fn chat(from: &str, to: Option<&str>, text: &str) -> Result<()> {
+    let line = match to {
+        Some(to) => format!("{} {from} -> {to}: {text}", Glyph::Chat),
+        None => format!("{} {from}: {text}", Glyph::Chat),
+    };
+    let line = LABEL
+        .try_with(|label| format!("[{label}] {line}"))
+        .unwrap_or(line);
+    // One log line per message so no part of the text loses the prefix
+    logger::write(
+        Level::Info,
+        format_args!("{CHAT_PREFIX}{}", line.replace(['\r', '\n'], " ")),
+    );
+    if !prints_info() || hold(&format!("{line}\n")) {
+        return Ok(());
+    }
+    clear_line()?;
+    if is_accessible() {
+        // Screen readers announce who is talking instead of a color
+        writeln!(messages(), "{CHAT_PREFIX}{line}")?;
+    } else {
+        writeln!(messages(), "{}", line.cyan())?;
+    }
+    update_line()?;
+    Ok(())
+}
+
 pub(crate) fn fn_eprintln(args: Arguments) -> Result<()> {
+    LABEL
+        .try_with(|label| eprintln_line(format_args!("[{label}] {args}")))
+        .unwrap_or_else(|_| eprintln_line(args))
+}
+
+fn eprintln_line(args: Arguments) -> Result<()> {
+    logger::write(Level::Error, args);
+    if verbosity() == Verbosity::Silent {
+        return Ok(());
+    }
     clear_line()?;
     io::stderr().write_fmt(args)?;
+    io::stderr().write_all(b"\n")?;
     update_line()?;
     Ok(())
 }
@@ -77,13 +545,21 @@ pub(crate) use eprintln;
 
 /// printdoc macro
 macro_rules! printdoc {
-    ($($arg:tt)*) => {{
+    (@urgent $urgent:expr, $($arg:tt)*) => {{
         'aaa: {
             if let Err(e) = $crate::console::clear_line() {
                 break 'aaa Err(e);
             }
 
-            ::indoc::printdoc!($($arg)*);
+            let text = ::indoc::formatdoc!($($arg)*);
+            $crate::logger::write($crate::logger::Level::Info, format_args!("{text}"));
+            if $crate::console::verbosity() == $crate::console::Verbosity::Normal
+                && ($urgent || !$crate::console::hold(&text))
+            {
+                if let Err(e) = $crate::console::print_text(&text) {
+                    break 'aaa Err(e);
+                }
+            }
 
             if let Err(e) = $crate::console::update_line() {
                 break 'aaa Err(e);
@@ -92,10 +568,27 @@ macro_rules! printdoc {
             Ok(())
         }
     }};
+    ($($arg:tt)*) => {
+        $crate::console::printdoc!(@urgent false, $($arg)*)
+    };
 }
 pub(crate) use printdoc;
 
+/// printdoc that is not held back in do-not-disturb mode (errors and codes the host has to act on)
+macro_rules! urgent_printdoc {
+    ($($arg:tt)*) => {
+        $crate::console::printdoc!(@urgent true, $($arg)*)
+    };
+}
+pub(crate) use urgent_printdoc;
+
 pub(crate) fn fn_print_update(args: Arguments) -> Result<()> {
+    // Announce the update as a new line instead of rewriting the status line
+    if !inline_updates() {
+        return fn_println(args, Level::Info, false);
+    }
+
+    logger::write(Level::Info, args);
     save_line(args)?;
     update_line()?;
     Ok(())
@@ -0,0 +1,35 @@
+//! Container mode (--container or REMOTEPLAY_INVITER_CONTAINER=1), for running next to a streaming box
+//!
+//! - The configuration comes from the environment instead of the files next to the executable:
+//!   REMOTEPLAY_INVITER_CONFIG and REMOTEPLAY_INVITER_ENDPOINT hold whole TOML documents, and
+//!   REMOTEPLAY_INVITER__<SECTION>__<KEY> sets a single value (e.g. REMOTEPLAY_INVITER__UUID=...,
+//!   REMOTEPLAY_INVITER__HEALTH__PORT=8080, quote the numbers meant as text)
+//! - The log is written to stdout as JSON lines, nothing else is printed
+//! - No console commands, colors, status line or Unicode glyphs
+//! - The health endpoint is served (GET /healthz on port 8731 by default)
+//! - SIGTERM stops the client cleanly, and it exits instead of waiting for Ctrl+C
+//!
+//! State files (bans, stats, caches) go to REMOTEPLAY_INVITER_DATA_DIR, so mount a volume there.
+
+use std::{
+    env,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the container mode is asked for on the command line or in the environment
+pub fn requested(flag: bool) -> bool {
+    flag || env::var("REMOTEPLAY_INVITER_CONTAINER")
+        .is_ok_and(|value| matches!(value.as_str(), "1" | "true"))
+}
+
+/// Switches to the container mode
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether the client runs in a container
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
@@ -0,0 +1,119 @@
+use std::env;
+
+/// Detects Steam Deck Game Mode (Steam sets these for launched games)
+pub fn is_steam_deck() -> bool {
+    ["SteamDeck", "SteamOS"]
+        .iter()
+        .any(|key| env::var(key).is_ok_and(|value| value == "1"))
+}
+
+/// Block letter (3x5 pixels) of a character, `#` marks a pixel
+fn glyph(c: char) -> [&'static str; 5] {
+    match c {
+        'A' => ["###", "# #", "###", "# #", "# #"],
+        'B' => ["## ", "# #", "## ", "# #", "## "],
+        'C' => ["###", "#  ", "#  ", "#  ", "###"],
+        'D' => ["## ", "# #", "# #", "# #", "## "],
+        'E' => ["###", "#  ", "## ", "#  ", "###"],
+        'F' => ["###", "#  ", "## ", "#  ", "#  "],
+        'G' => ["###", "#  ", "# #", "# #", "###"],
+        'H' => ["# #", "# #", "###", "# #", "# #"],
+        'I' => ["###", " # ", " # ", " # ", "###"],
+        'J' => ["  #", "  #", "  #", "# #", "###"],
+        'K' => ["# #", "# #", "## ", "# #", "# #"],
+        'L' => ["#  ", "#  ", "#  ", "#  ", "###"],
+        'M' => ["# #", "###", "###", "# #", "# #"],
+        'N' => ["###", "# #", "# #", "# #", "# #"],
+        'O' => ["###", "# #", "# #", "# #", "###"],
+        'P' => ["###", "# #", "###", "#  ", "#  "],
+        'Q' => ["###", "# #", "# #", "###", "  #"],
+        'R' => ["###", "# #", "## ", "# #", "# #"],
+        'S' => ["###", "#  ", "###", "  #", "###"],
+        'T' => ["###", " # ", " # ", " # ", " # "],
+        'U' => ["# #", "# #", "# #", "# #", "###"],
+        'V' => ["# #", "# #", "# #", "# #", " # "],
+        'W' => ["# #", "# #", "###", "###", "# #"],
+        'X' => ["# #", "# #", " # ", "# #", "# #"],
+        'Y' => ["# #", "# #", " # ", " # ", " # "],
+        'Z' => ["###", "  #", " # ", "#  ", "###"],
+        '0' => ["###", "# #", "# #", "# #", "###"],
+        '1' => [" # ", "## ", " # ", " # ", "###"],
+        '2' => ["###", "  #", "###", "#  ", "###"],
+        '3' => ["###", "  #", "###", "  #", "###"],
+        '4' => ["# #", "# #", "###", "  #", "  #"],
+        '5' => ["###", "#  ", "###", "  #", "###"],
+        '6' => ["###", "#  ", "###", "# #", "###"],
+        '7' => ["###", "  #", "  #", "  #", "  #"],
+        '8' => ["###", "# #", "###", "# #", "###"],
+        '9' => ["###", "# #", "###", "  #", "###"],
+        ':' => ["   ", " # ", "   ", " # ", "   "],
+        '-' => ["   ", "   ", "###", "   ", "   "],
+        '!' => [" # ", " # ", " # ", "   ", " # "],
+        '?' => ["###", "  #", " ##", "   ", " # "],
+        '.' => ["   ", "   ", "   ", "   ", " # "],
+        _ => ["   ", "   ", "   ", "   ", "   "],
+    }
+}
+
+/// Renders text in block letters (each pixel is two cells wide to look square)
+pub fn render_big(text: &str, pixel: &str) -> String {
+    let glyphs: Vec<_> = text.to_uppercase().chars().map(glyph).collect();
+    let blank = " ".repeat(pixel.chars().count());
+    let mut out = String::new();
+    for row in 0..5 {
+        let line = glyphs
+            .iter()
+            .map(|glyph| {
+                glyph[row]
+                    .chars()
+                    .map(|c| if c == '#' { pixel } else { blank.as_str() })
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join(&blank);
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// Approves (A) or denies (B) the longest waiting guest with a game controller
+#[cfg(feature = "deck")]
+pub fn spawn_controller(handler: std::sync::Arc<crate::handlers::Handler>) {
+    use gilrs::{Button, EventType, Gilrs};
+
+    use crate::{
+        audit::{Origin, Source},
+        console::{self, Glyph},
+    };
+
+    let runtime = tokio::runtime::Handle::current();
+    std::thread::spawn(move || {
+        let mut gilrs = match Gilrs::new() {
+            Ok(gilrs) => gilrs,
+            Err(err) => {
+                let _ = console::eprintln!("{} Controller input disabled: {}", Glyph::Err, err);
+                return;
+            }
+        };
+        loop {
+            // Blocks until the next controller event
+            let Some(event) = gilrs.next_event_blocking(None) else {
+                continue;
+            };
+            let approve = match event.event {
+                EventType::ButtonPressed(Button::South, _) => true,
+                EventType::ButtonPressed(Button::East, _) => false,
+                _ => continue,
+            };
+            runtime.block_on(async {
+                if let Some(guest_id) = handler.first_pending().await {
+                    let origin = Origin::new(Source::Console, None);
+                    if let Err(err) = handler.decide_guest(guest_id, approve, origin).await {
+                        let _ = console::eprintln!("{} {}", Glyph::Err, err);
+                    }
+                }
+            });
+        }
+    });
+}
@@ -0,0 +1,48 @@
+use std::collections::VecDeque;
+
+use crate::models::ClientMessage;
+
+/// How many commands are remembered
+const CAPACITY: usize = 256;
+
+/// Recently processed server commands, by endpoint and idempotency key
+/// A server that retries a command across a reconnect gets the first response again
+#[derive(Default)]
+pub struct RecentCommands {
+    /// Oldest first, with the response that was sent (None for commands without a response)
+    entries: VecDeque<((usize, String), Option<ClientMessage>)>,
+}
+
+impl RecentCommands {
+    /**
+     * Looks up a processed command and marks it as recently used
+     * @return The response that was sent (None if the command is new)
+     */
+    pub fn get(&mut self, key: &(usize, String)) -> Option<Option<ClientMessage>> {
+        let position = self.entries.iter().position(|(k, _)| k == key)?;
+        let entry = self.entries.remove(position)?;
+        let response = entry.1.clone();
+        self.entries.push_back(entry);
+        Some(response)
+    }
+
+    /// Remembers a command before it is processed
+    pub fn insert(&mut self, key: (usize, String)) {
+        if self.entries.len() >= CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((key, None));
+    }
+
+    /// Remembers the response to a command
+    pub fn set_response(&mut self, key: &(usize, String), response: &ClientMessage) {
+        if let Some((_, slot)) = self.entries.iter_mut().rev().find(|(k, _)| k == key) {
+            *slot = Some(response.clone());
+        }
+    }
+
+    /// Forgets a command that failed without a response (a retry runs it again)
+    pub fn remove(&mut self, key: &(usize, String)) {
+        self.entries.retain(|(k, _)| k != key);
+    }
+}
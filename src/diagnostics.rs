@@ -0,0 +1,218 @@
+use anyhow::{Context as _, Result};
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::{Duration, SystemTime},
+};
+use tokio::task;
+
+use crate::{console, logger, models::RequestId};
+
+/// Most log lines sent for one request (the newest are kept)
+const MAX_LINES: usize = 2000;
+/// Console fields that hold personal data
+const PERSONAL_FIELDS: &[&str] = &[
+    "claimer",
+    "name",
+    "from",
+    "steam_id",
+    "invite_url",
+    "url",
+    "host",
+    "device",
+];
+
+/// Log request of a server, waiting for the host's decision
+pub struct Request {
+    /// Index of the server that asked
+    pub endpoint: usize,
+    /// ID of the request (the answer refers to it)
+    pub id: RequestId,
+    /// Minutes of log to send
+    pub minutes: u64,
+}
+
+/**
+ * Reads the recent log lines and hides the personal data in them
+ * @param secrets Texts that never leave the PC (the token)
+ */
+pub async fn collect(minutes: u64, secrets: Vec<String>) -> Result<Vec<String>> {
+    let since = SystemTime::now() - Duration::from_secs(minutes * 60);
+    let lines = task::spawn_blocking(move || {
+        let lines = logger::recent(since)?;
+        let skip = lines.len().saturating_sub(MAX_LINES);
+        Ok::<_, anyhow::Error>(
+            lines[skip..]
+                .iter()
+                .filter(|line| !is_chat(line))
+                .map(|line| redact(line, &secrets))
+                .collect(),
+        )
+    })
+    .await
+    .context("Log reading task failed")??;
+    Ok(lines)
+}
+
+/// Whether a log line holds a chat message (the names and the text are private)
+fn is_chat(line: &str) -> bool {
+    // `<timestamp> [<level>] Chat: ...`
+    line.splitn(3, ' ')
+        .nth(2)
+        .is_some_and(|message| message.starts_with(console::CHAT_PREFIX))
+}
+
+/// Hides the token, names, Steam IDs, links and IP addresses in a log line
+pub fn redact(line: &str, secrets: &[String]) -> String {
+    let mut line = line.to_owned();
+    for secret in secrets.iter().filter(|secret| !secret.is_empty()) {
+        line = line.replace(secret.as_str(), "<token>");
+    }
+    let line = hide_fields(&line);
+
+    let mut words: Vec<String> = Vec::new();
+    // Inside the Steam name that follows a player: `[1]name (Name)`
+    let mut in_persona = false;
+    for word in line.split(' ') {
+        let starts_persona =
+            word.starts_with('(') && words.last().is_some_and(|last| last.ends_with("]<hidden>"));
+        if in_persona || starts_persona {
+            in_persona = !word.contains(')');
+            if starts_persona {
+                words.push("(<hidden>)".to_owned());
+            }
+            // Keep the separator after the name
+            if let Some((_, rest)) = word.rsplit_once(')') {
+                words.last_mut().unwrap().push_str(rest);
+            }
+            continue;
+        }
+
+        let (prefix, word) = match word.strip_prefix('(') {
+            Some(word) => ("(", word),
+            None => ("", word),
+        };
+        let bare = word.trim_end_matches([',', '.', ';', ')']);
+        let suffix = &word[bare.len()..];
+        let redacted = match bare {
+            _ if bare.ends_with("=<hidden>") => bare.to_owned(),
+            _ if bare.contains("://") => "<url>".to_owned(),
+            _ if bare.len() == 17 && bare.bytes().all(|b| b.is_ascii_digit()) => {
+                "<steam_id>".to_owned()
+            }
+            _ if bare.parse::<IpAddr>().is_ok() || bare.parse::<SocketAddr>().is_ok() => {
+                "<ip>".to_owned()
+            }
+            _ if is_mac(bare) => "<mac>".to_owned(),
+            // Player lists: `[guest_id]name`
+            _ if bare.starts_with('[') && !bare.ends_with(']') => match bare.split_once(']') {
+                Some((guest, _)) => format!("{guest}]<hidden>"),
+                None => bare.to_owned(),
+            },
+            _ => bare.to_owned(),
+        };
+        words.push(format!("{prefix}{redacted}{suffix}"));
+    }
+    words.join(" ")
+}
+
+/// Hides the values of the personal fields, which may contain spaces (`claimer=Alice Smith`)
+/// A value ends at the next `, field=` separator or at the end of the line
+fn hide_fields(line: &str) -> String {
+    let mut hidden = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some((start, field)) = PERSONAL_FIELDS
+        .iter()
+        .filter_map(|field| find_field(rest, field).map(|start| (start, field)))
+        .min()
+    {
+        let value_start = start + field.len() + 1;
+        hidden.push_str(&rest[..value_start]);
+        hidden.push_str("<hidden>");
+        let value = &rest[value_start..];
+        rest = &value[value_end(value)..];
+    }
+    hidden.push_str(rest);
+    hidden
+}
+
+/// Position of `field=` at the start of a word
+fn find_field(text: &str, field: &str) -> Option<usize> {
+    text.match_indices(&format!("{field}="))
+        .map(|(start, _)| start)
+        .find(|&start| start == 0 || text[..start].ends_with([' ', '(']))
+}
+
+/// Length of a field value: up to the next `, field=` separator
+fn value_end(value: &str) -> usize {
+    value
+        .match_indices(", ")
+        .map(|(end, _)| end)
+        .find(|&end| {
+            value[end + 2..].split_once('=').is_some_and(|(field, _)| {
+                !field.is_empty() && field.bytes().all(|b| b.is_ascii_lowercase() || b == b'_')
+            })
+        })
+        .unwrap_or(value.len())
+}
+
+/// Whether the text is a MAC address (`01:23:45:67:89:ab`)
+fn is_mac(text: &str) -> bool {
+    let parts: Vec<&str> = text.split([':', '-']).collect();
+    parts.len() == 6
+        && parts
+            .iter()
+            .all(|part| part.len() == 2 && part.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_with_spaces_are_hidden_up_to_the_next_field() {
+        assert_eq!(
+            redact(
+                "-> Create Invite Link : claimer=Alice Smith, guest_id=3, game_id=480, invite_url=https://s.team/p/abc",
+                &[]
+            ),
+            "-> Create Invite Link : claimer=<hidden>, guest_id=3, game_id=480, invite_url=<hidden>"
+        );
+        assert_eq!(
+            redact(
+                "Approve Player? : claimer=Bob, guest_id=7, steam_id=76561198000000000 (Bob the Builder) (y/n)",
+                &[]
+            ),
+            "Approve Player? : claimer=<hidden>, guest_id=7, steam_id=<hidden>"
+        );
+    }
+
+    #[test]
+    fn tokens_ids_and_addresses_are_hidden() {
+        assert_eq!(
+            redact(
+                "Connected with secret-token to 192.168.1.20:8080 as 76561198000000000",
+                &["secret-token".to_owned()]
+            ),
+            "Connected with <token> to <ip> as <steam_id>"
+        );
+        assert_eq!(
+            redact("Players: [1]alice (Alice Smith), [2]bob", &[]),
+            "Players: [1]<hidden> (<hidden>), [2]<hidden>"
+        );
+        assert_eq!(
+            redact("Joined guest_id=3, game_id=480", &[]),
+            "Joined guest_id=3, game_id=480"
+        );
+    }
+
+    #[test]
+    fn chat_lines_are_left_out() {
+        assert!(is_chat(
+            "2024-07-01T12:00:00Z [INFO] Chat: [main] ✉ alice -> bob: hi"
+        ));
+        assert!(is_chat(
+            "2024-07-01T12:00:00Z [INFO] Chat: [MSG] You: see you"
+        ));
+        assert!(!is_chat("2024-07-01T12:00:00Z [INFO] ✓ Connected"));
+    }
+}
@@ -0,0 +1,89 @@
+//! Discord rich presence: shows the connection state and guest count on the host's Discord profile
+
+use chrono::Utc;
+use discord_rich_presence::{
+    activity::{Activity, Timestamps},
+    DiscordIpc, DiscordIpcClient,
+};
+use std::{sync::mpsc, thread};
+use tokio::sync::watch;
+
+use crate::{
+    config::DiscordRpcConfig,
+    console::{self, Glyph},
+    handlers::ConnectionState,
+    status::HostStatus,
+};
+
+/// Keeps the Discord activity in sync with the host status
+pub fn spawn(status: &watch::Receiver<HostStatus>, config: DiscordRpcConfig) {
+    if !config.enabled {
+        return;
+    }
+    let Some(client_id) = config.client_id else {
+        let _ = console::eprintln!(
+            "{} Discord rich presence needs discord_rpc.client_id (the ID of a Discord application)",
+            Glyph::Warn
+        );
+        return;
+    };
+
+    // The Discord IPC client blocks, so it gets its own thread
+    let (status_tx, status_rx) = mpsc::channel::<Option<String>>();
+    thread::spawn(move || publish(&client_id, status_rx));
+
+    let mut status = status.clone();
+    tokio::spawn(async move {
+        while status.changed().await.is_ok() {
+            if status_tx
+                .send(status_text(*status.borrow_and_update()))
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+}
+
+/// State line of the activity (None: no activity while not connected to the server)
+fn status_text(status: HostStatus) -> Option<String> {
+    match status.state {
+        ConnectionState::Connected if status.paused => Some("Invites paused".to_owned()),
+        ConnectionState::Connected if status.guests == 0 => Some("Waiting for guests".to_owned()),
+        ConnectionState::Connected if status.guests == 1 => Some("1 guest".to_owned()),
+        ConnectionState::Connected => Some(format!("{} guests", status.guests)),
+        _ => None,
+    }
+}
+
+/// Sets the activity for each status (Discord is connected on demand, it may start later)
+fn publish(client_id: &str, status_rx: mpsc::Receiver<Option<String>>) {
+    let started = Utc::now().timestamp();
+    let mut client = DiscordIpcClient::new(client_id);
+    let mut connected = false;
+    for status in status_rx {
+        // Once more after a reconnect (Discord was restarted since the last status)
+        for _ in 0..2 {
+            if !connected {
+                connected = client.connect().is_ok();
+                if !connected {
+                    break;
+                }
+            }
+            let result = match &status {
+                Some(status) => client.set_activity(
+                    Activity::new()
+                        .details("Hosting Remote Play Together")
+                        .state(status)
+                        .timestamps(Timestamps::new().start(started)),
+                ),
+                None => client.clear_activity(),
+            };
+            if result.is_ok() {
+                break;
+            }
+            let _ = client.close();
+            connected = false;
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use anyhow::{Context as _, Result};
+use serde::Deserialize;
+use std::{net::IpAddr, time::Duration};
+use tokio::task;
+
+use crate::config::DnsConfig;
+
+/// Cloudflare's resolver (by address, so resolving it needs no DNS)
+const CLOUDFLARE: &str = "https://1.1.1.1/dns-query";
+/// Google's resolver (by address, so resolving it needs no DNS)
+const GOOGLE: &str = "https://8.8.8.8/resolve";
+/// DNS record types
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+
+/// Response of a DNS JSON API
+#[derive(Deserialize)]
+struct DnsResponse {
+    /// DNS response code (0: no error)
+    #[serde(rename = "Status")]
+    status: u16,
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DnsAnswer>,
+}
+
+#[derive(Deserialize)]
+struct DnsAnswer {
+    #[serde(rename = "type")]
+    record_type: u16,
+    data: String,
+}
+
+/// Resolves the server hosts with DNS-over-HTTPS (for ISPs that hijack DNS)
+pub struct Resolver {
+    /// JSON API of the provider
+    url: String,
+    /// How long the provider may take to answer
+    timeout: Duration,
+    /// Whether the system resolver is used when the provider fails
+    system_fallback: bool,
+}
+
+impl Resolver {
+    /// Resolver of the configured provider (None: the system resolver is used)
+    pub fn new(config: &DnsConfig, timeout: Duration) -> Option<Self> {
+        let url = match config.doh.as_deref()? {
+            "cloudflare" => CLOUDFLARE,
+            "google" => GOOGLE,
+            url => url,
+        };
+        Some(Self {
+            url: url.to_owned(),
+            timeout,
+            system_fallback: config.system_fallback,
+        })
+    }
+
+    /// Whether the system resolver is used when the provider fails
+    pub fn system_fallback(&self) -> bool {
+        self.system_fallback
+    }
+
+    /**
+     * Looks up the IPv4 and IPv6 addresses of a host
+     * @return The addresses (IPv4 first)
+     */
+    pub async fn resolve(&self, host: &str) -> Result<Vec<IpAddr>> {
+        let mut addrs = Vec::new();
+        for record_type in [TYPE_A, TYPE_AAAA] {
+            let request = ureq::get(&self.url)
+                .query("name", host)
+                .query("type", &record_type.to_string())
+                .set("Accept", "application/dns-json")
+                .timeout(self.timeout);
+            let response: DnsResponse = task::spawn_blocking(move || -> Result<DnsResponse> {
+                request
+                    .call()
+                    .context("DNS-over-HTTPS request failed")?
+                    .into_json()
+                    .context("Invalid DNS-over-HTTPS response")
+            })
+            .await??;
+            if response.status != 0 {
+                anyhow::bail!(
+                    "DNS-over-HTTPS lookup of {host} failed (response code {})",
+                    response.status
+                );
+            }
+            // CNAME records are followed by the provider
+            addrs.extend(
+                response
+                    .answer
+                    .iter()
+                    .filter(|answer| answer.record_type == record_type)
+                    .filter_map(|answer| answer.data.parse::<IpAddr>().ok()),
+            );
+        }
+        if addrs.is_empty() {
+            anyhow::bail!("DNS-over-HTTPS found no address for {host}");
+        }
+        Ok(addrs)
+    }
+}
@@ -0,0 +1,475 @@
+use anyhow::{bail, Context as _, Result};
+use std::sync::{
+    self,
+    atomic::{AtomicBool, AtomicU64, Ordering},
+};
+use std::{collections::BTreeMap, time::Duration};
+use tokio::sync::{Mutex, Notify};
+use tokio_tungstenite::tungstenite::http::{
+    header::{HeaderName, USER_AGENT},
+    uri::Builder,
+    HeaderMap, HeaderValue, Uri,
+};
+
+use crate::{
+    handlers::ConnectionState,
+    models::{ClientMessage, Protocol},
+    shaping::NoticeQueue,
+    transport::{self, IpFamily},
+    VERSION,
+};
+
+/// Failed connections to a server the client was moved to before it goes back to the configured one
+const MAX_MOVED_FAILURES: u32 = 3;
+
+/// WebSocket URL of a server, which may move the client to another host
+pub struct ServerUrl {
+    /// URL to connect to
+    url: String,
+    /// Configured URL (the client goes back to its server when the moved one stays unreachable)
+    home: String,
+    /// Failed connections since the last successful one (None: not moved)
+    moved_failures: Option<u32>,
+}
+
+impl ServerUrl {
+    pub fn new(url: String) -> Self {
+        Self {
+            home: url.clone(),
+            url,
+            moved_failures: None,
+        }
+    }
+
+    /// URL to connect to
+    pub fn as_str(&self) -> &str {
+        &self.url
+    }
+
+    /**
+     * Moves to the server of another URL, keeping the path and query (version, token, session)
+     * @return Scheme and host of the new server
+     */
+    pub fn redirect(&mut self, target: &str) -> Result<String> {
+        let target: Uri = target.parse().context("Invalid URL to move to")?;
+        let current: Uri = self.url.parse().context("Invalid server URL")?;
+        match (current.scheme_str(), target.scheme_str()) {
+            (Some("ws" | "wss"), Some("wss")) | (Some("ws"), Some("ws")) => {}
+            (Some("wss"), Some("ws")) => {
+                bail!("Refused to move to {} without TLS", origin(&target))
+            }
+            _ => bail!("Refused to move to {}", origin(&target)),
+        }
+        self.url = with_origin(&current, &target)?;
+        self.moved_failures = Some(0);
+        Ok(origin(&target))
+    }
+
+    /**
+     * Counts a failed connection
+     * @return Scheme and host of the configured server if the client went back to it
+     */
+    pub fn connect_failed(&mut self) -> Option<String> {
+        let failures = self.moved_failures.as_mut()?;
+        *failures += 1;
+        if *failures < MAX_MOVED_FAILURES {
+            return None;
+        }
+        self.moved_failures = None;
+        let home: Uri = self.home.parse().ok()?;
+        let current: Uri = self.url.parse().ok()?;
+        self.url = with_origin(&current, &home).ok()?;
+        Some(origin(&home))
+    }
+
+    /// Counts a successful connection (the client stays on a server it was moved to)
+    pub fn connected(&mut self) {
+        if let Some(failures) = &mut self.moved_failures {
+            *failures = 0;
+        }
+    }
+}
+
+/// Scheme and host of a URL (without the token in the query)
+fn origin(uri: &Uri) -> String {
+    match (uri.scheme_str(), uri.authority()) {
+        (Some(scheme), Some(authority)) => format!("{scheme}://{authority}"),
+        _ => "(unknown server)".to_owned(),
+    }
+}
+
+/// URL with the scheme and host of another one
+fn with_origin(url: &Uri, origin: &Uri) -> Result<String> {
+    let (Some(scheme), Some(authority)) = (origin.scheme(), origin.authority()) else {
+        bail!("The URL to move to has no host");
+    };
+    let uri = Builder::new()
+        .scheme(scheme.clone())
+        .authority(authority.clone())
+        .path_and_query(url.path_and_query().map_or("/", |path| path.as_str()))
+        .build()
+        .context("Failed to build URL")?;
+    Ok(uri.to_string())
+}
+
+/// A server the client registers with
+/// Each one has its own connection loop, retry state and queue of messages to send
+pub struct Endpoint {
+    /// Position in the endpoint list (routes messages back to the server that asked)
+    pub index: usize,
+    /// Host of the server
+    pub host: String,
+    /// Name given by the user
+    pub label: Option<String>,
+    /// HTTP headers of the connection request (User-Agent and the configured ones)
+    pub headers: HeaderMap,
+    /// WebSocket URL to connect to (changed when the server redirects)
+    url: Mutex<ServerUrl>,
+    /// Connection state
+    state: Mutex<ConnectionState>,
+    /// Messages to send without a request (highest priority first)
+    notices: sync::Mutex<NoticeQueue>,
+    notice_ready: Notify,
+    /// Drop the connection or skip the backoff (kept until the connection loop waits again)
+    reconnect: AtomicBool,
+    reconnect_ready: Notify,
+    /// Send the session state to the server after the next connect (set on resume)
+    resync: AtomicBool,
+    /// Seconds the server asked to wait before reconnecting (0: none)
+    backoff: AtomicU64,
+    /// Device that gets the invites of this server instead (same token on another PC)
+    active_device: sync::Mutex<Option<String>>,
+    /// IP version of the last connection (None: not connected yet or a Unix domain socket)
+    family: sync::Mutex<Option<IpFamily>>,
+    /// Protocol version of the last connection
+    protocol: sync::Mutex<Protocol>,
+    /// Sum and number of the heartbeat round trips
+    rtt: sync::Mutex<(Duration, u32)>,
+}
+
+impl Endpoint {
+    pub fn new(index: usize, url: String, label: Option<String>, headers: HeaderMap) -> Self {
+        let host = match transport::unix_socket(&url) {
+            Some((socket, _)) => socket.to_owned(),
+            None => url
+                .parse::<Uri>()
+                .ok()
+                .and_then(|uri| uri.host().map(str::to_owned))
+                .unwrap_or_default(),
+        };
+        Self {
+            index,
+            host,
+            label,
+            headers,
+            url: Mutex::new(ServerUrl::new(url)),
+            state: Mutex::new(ConnectionState::Connecting),
+            notices: sync::Mutex::new(NoticeQueue::default()),
+            notice_ready: Notify::new(),
+            reconnect: AtomicBool::new(false),
+            reconnect_ready: Notify::new(),
+            resync: AtomicBool::new(false),
+            backoff: AtomicU64::new(0),
+            active_device: sync::Mutex::new(None),
+            family: sync::Mutex::new(None),
+            protocol: sync::Mutex::new(Protocol::default()),
+            rtt: sync::Mutex::new((Duration::ZERO, 0)),
+        }
+    }
+
+    /// Name of the server (the label, or the host if there is none)
+    pub fn name(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.host)
+    }
+
+    /// WebSocket URL to connect to
+    pub async fn url(&self) -> String {
+        self.url.lock().await.url.clone()
+    }
+
+    /// WebSocket URL to connect to (None while it is being changed)
+    pub fn try_url(&self) -> Option<String> {
+        self.url.try_lock().ok().map(|url| url.url.clone())
+    }
+
+    /**
+     * Connects to the server of another URL from now on (the endpoint keeps its name)
+     * @return Scheme and host of the new server
+     */
+    pub async fn redirect(&self, target: &str) -> Result<String> {
+        self.url.lock().await.redirect(target)
+    }
+
+    /**
+     * Counts a failed connection
+     * @return Scheme and host of the configured server if the client went back to it
+     */
+    pub async fn connect_failed(&self) -> Option<String> {
+        self.url.lock().await.connect_failed()
+    }
+
+    /// Counts a successful connection
+    pub async fn connected(&self) {
+        self.url.lock().await.connected();
+    }
+
+    /**
+     * Asks for a pairing code on the next connections (to link the token with Discord again)
+     * @return Whether the pairing was not asked for already
+     */
+    pub async fn request_pairing(&self) -> bool {
+        let url = &mut self.url.lock().await.url;
+        if url.contains("&pairing=1") {
+            return false;
+        }
+        url.push_str("&pairing=1");
+        true
+    }
+
+    /// Connection state
+    pub async fn state(&self) -> ConnectionState {
+        *self.state.lock().await
+    }
+
+    /// Updates the connection state
+    pub async fn set_state(&self, state: ConnectionState) {
+        *self.state.lock().await = state;
+    }
+
+    /// Queues a message for the server that is not a response to a request
+    pub fn notify(&self, msg: ClientMessage) {
+        if self.notices.lock().unwrap().push(msg) {
+            self.notice_ready.notify_one();
+        }
+    }
+
+    /// IP version of the last connection
+    pub fn family(&self) -> Option<IpFamily> {
+        *self.family.lock().unwrap()
+    }
+
+    pub fn set_family(&self, family: Option<IpFamily>) {
+        *self.family.lock().unwrap() = family;
+    }
+
+    /// Protocol version of the last connection
+    pub fn protocol(&self) -> Protocol {
+        *self.protocol.lock().unwrap()
+    }
+
+    pub fn set_protocol(&self, protocol: Protocol) {
+        *self.protocol.lock().unwrap() = protocol;
+    }
+
+    /// Adds the round trip time of a heartbeat
+    pub fn record_rtt(&self, sample: Duration) {
+        let mut rtt = self.rtt.lock().unwrap();
+        rtt.0 += sample;
+        rtt.1 += 1;
+    }
+
+    /// Sum and number of the heartbeat round trips since the start
+    pub fn rtt_total(&self) -> (Duration, u32) {
+        *self.rtt.lock().unwrap()
+    }
+
+    /// Number of messages waiting to be sent
+    pub fn queued(&self) -> usize {
+        self.notices.lock().unwrap().count()
+    }
+
+    /// Waits for the most urgent message to send to the server without a request
+    pub async fn next_notice(&self) -> ClientMessage {
+        loop {
+            if let Some(msg) = self.notices.lock().unwrap().pop() {
+                return msg;
+            }
+            self.notice_ready.notified().await;
+        }
+    }
+
+    /// Asks the connection loop to drop the connection, or to skip the backoff
+    /// A request while the loop handles a message or connects is kept for its next wait
+    pub fn request_reconnect(&self) {
+        self.reconnect.store(true, Ordering::Relaxed);
+        self.reconnect_ready.notify_waiters();
+    }
+
+    /// Waits until a reconnect is requested (returns at once for a pending request)
+    pub async fn reconnect_requested(&self) {
+        loop {
+            // Registered before the check so a request in between is not missed
+            let notified = self.reconnect_ready.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.reconnect.swap(false, Ordering::Relaxed) {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Drops a pending reconnect request (a new connection is about to be opened)
+    pub fn clear_reconnect(&self) {
+        self.reconnect.store(false, Ordering::Relaxed);
+    }
+
+    /**
+     * Marks the connection as stale after a resume
+     * @return Whether it was not marked yet
+     */
+    pub fn mark_resync(&self) -> bool {
+        !self.resync.swap(true, Ordering::Relaxed)
+    }
+
+    /// Whether the connection is being re-established after a resume
+    pub fn is_resuming(&self) -> bool {
+        self.resync.load(Ordering::Relaxed)
+    }
+
+    /**
+     * Clears the resume mark
+     * @return Whether the session state has to be sent
+     */
+    pub fn take_resync(&self) -> bool {
+        self.resync.swap(false, Ordering::Relaxed)
+    }
+
+    /// Records the wait the server asked for before the next reconnect
+    pub fn set_backoff(&self, sec: u64) {
+        self.backoff.store(sec, Ordering::Relaxed);
+    }
+
+    /// Takes the wait the server asked for
+    pub fn take_backoff(&self) -> Option<u64> {
+        Some(self.backoff.swap(0, Ordering::Relaxed)).filter(|&sec| sec > 0)
+    }
+
+    /// Device that gets the invites instead (None while this PC is the active one)
+    pub fn active_device(&self) -> Option<String> {
+        self.active_device.lock().unwrap().clone()
+    }
+
+    /**
+     * Records which device gets the invites
+     * @return The device recorded before
+     */
+    pub fn set_active_device(&self, device: Option<String>) -> Option<String> {
+        std::mem::replace(&mut *self.active_device.lock().unwrap(), device)
+    }
+}
+
+/**
+ * Builds the HTTP headers of the connection request
+ * @param user_agent User-Agent (the client name and version if not set)
+ * @param headers Extra headers from the endpoint config
+ */
+pub fn request_headers(
+    user_agent: Option<&str>,
+    headers: &BTreeMap<String, String>,
+) -> Result<HeaderMap> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        // The values may be credentials, so only the name is shown
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .with_context(|| format!("Invalid header name in the endpoint config: {name}"))?;
+        let value = HeaderValue::from_str(value)
+            .with_context(|| format!("Invalid value of header {name} in the endpoint config"))?;
+        map.append(name, value);
+    }
+    // A User-Agent in the headers counts as well
+    let user_agent = match user_agent {
+        Some(user_agent) => user_agent.to_owned(),
+        None if map.contains_key(USER_AGENT) => return Ok(map),
+        None => format!("remoteplay-inviter/{VERSION}"),
+    };
+    map.insert(
+        USER_AGENT,
+        HeaderValue::from_str(&user_agent).context("Invalid User-Agent in the endpoint config")?,
+    );
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time;
+
+    const URL: &str = "wss://home.example.com/ws?v=1&token=secret&session=abc";
+
+    #[test]
+    fn moves_keep_the_path_and_query() {
+        let mut url = ServerUrl::new(URL.to_owned());
+        assert_eq!(
+            url.redirect("wss://other.example.com:8443/elsewhere")
+                .unwrap(),
+            "wss://other.example.com:8443"
+        );
+        assert_eq!(
+            url.as_str(),
+            "wss://other.example.com:8443/ws?v=1&token=secret&session=abc"
+        );
+    }
+
+    #[test]
+    fn moves_never_drop_tls() {
+        let mut url = ServerUrl::new(URL.to_owned());
+        assert!(url.redirect("ws://other.example.com/ws").is_err());
+        assert_eq!(url.as_str(), URL);
+
+        // Moving up to TLS is fine
+        let mut url = ServerUrl::new("ws://127.0.0.1:8080/ws?token=secret".to_owned());
+        url.redirect("wss://other.example.com").unwrap();
+        assert_eq!(url.as_str(), "wss://other.example.com/ws?token=secret");
+    }
+
+    #[test]
+    fn unreachable_moved_servers_send_the_client_back() {
+        let mut url = ServerUrl::new(URL.to_owned());
+        // Failures of the configured server are not counted
+        for _ in 0..MAX_MOVED_FAILURES {
+            assert_eq!(url.connect_failed(), None);
+        }
+
+        url.redirect("wss://other.example.com").unwrap();
+        for _ in 1..MAX_MOVED_FAILURES {
+            assert_eq!(url.connect_failed(), None);
+        }
+        // A successful connection starts the count again
+        url.connected();
+        for _ in 1..MAX_MOVED_FAILURES {
+            assert_eq!(url.connect_failed(), None);
+        }
+        assert_eq!(
+            url.connect_failed().as_deref(),
+            Some("wss://home.example.com")
+        );
+        assert_eq!(url.as_str(), URL);
+        assert_eq!(url.connect_failed(), None);
+    }
+
+    #[tokio::test]
+    async fn reconnect_requests_wait_for_the_loop() {
+        let endpoint = Endpoint::new(0, URL.to_owned(), None, HeaderMap::new());
+        // Requested while the loop was busy
+        endpoint.request_reconnect();
+        time::timeout(Duration::from_secs(1), endpoint.reconnect_requested())
+            .await
+            .unwrap();
+        // Taken by the wait
+        assert!(
+            time::timeout(Duration::from_millis(10), endpoint.reconnect_requested())
+                .await
+                .is_err()
+        );
+
+        // Dropped when a new connection is opened anyway
+        endpoint.request_reconnect();
+        endpoint.clear_reconnect();
+        assert!(
+            time::timeout(Duration::from_millis(10), endpoint.reconnect_requested())
+                .await
+                .is_err()
+        );
+    }
+}
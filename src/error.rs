@@ -0,0 +1,90 @@
+use thiserror::Error;
+
+/// Errors of the connection loop and the server message handler
+/// The kind decides whether the client reconnects, stops or waits for the user
+#[derive(Debug, Error)]
+pub enum ClientError {
+    /// The configuration is invalid or was rejected by the server (e.g. a revoked token)
+    #[error(transparent)]
+    Config(anyhow::Error),
+    /// The connection to the server failed or was lost
+    #[error(transparent)]
+    Network(anyhow::Error),
+    /// The server sent a message the client does not understand
+    #[error(transparent)]
+    Protocol(anyhow::Error),
+    /// The console can no longer be written
+    #[error(transparent)]
+    Console(anyhow::Error),
+    /// Steam is not running or stopped answering (the user has to start it)
+    #[error(transparent)]
+    Steam(anyhow::Error),
+}
+
+/// What to do after an error (ordered from the mildest)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Recovery {
+    /// Reconnect after a wait
+    Retry,
+    /// Stop until the user fixes the cause
+    UserAction,
+    /// Stop for good
+    Fatal,
+}
+
+impl ClientError {
+    /// What to do after the error
+    pub fn recovery(&self) -> Recovery {
+        match self {
+            ClientError::Network(_) | ClientError::Protocol(_) => Recovery::Retry,
+            ClientError::Config(_) | ClientError::Steam(_) => Recovery::UserAction,
+            ClientError::Console(_) => Recovery::Fatal,
+        }
+    }
+}
+
+impl Recovery {
+    /// Exit code telling a process supervisor whether a restart can help (sysexits.h)
+    pub fn exit_code(self) -> i32 {
+        match self {
+            // EX_TEMPFAIL
+            Recovery::Retry => 75,
+            // EX_CONFIG
+            Recovery::UserAction => 78,
+            Recovery::Fatal => 1,
+        }
+    }
+}
+
+pub type ClientResult<T> = Result<T, ClientError>;
+
+/// Tags an error with its kind
+pub trait ErrorKind<T> {
+    fn config(self) -> ClientResult<T>;
+    fn network(self) -> ClientResult<T>;
+    fn protocol(self) -> ClientResult<T>;
+    fn console(self) -> ClientResult<T>;
+    fn steam(self) -> ClientResult<T>;
+}
+
+impl<T, E: Into<anyhow::Error>> ErrorKind<T> for Result<T, E> {
+    fn config(self) -> ClientResult<T> {
+        self.map_err(|err| ClientError::Config(err.into()))
+    }
+
+    fn network(self) -> ClientResult<T> {
+        self.map_err(|err| ClientError::Network(err.into()))
+    }
+
+    fn protocol(self) -> ClientResult<T> {
+        self.map_err(|err| ClientError::Protocol(err.into()))
+    }
+
+    fn console(self) -> ClientResult<T> {
+        self.map_err(|err| ClientError::Console(err.into()))
+    }
+
+    fn steam(self) -> ClientResult<T> {
+        self.map_err(|err| ClientError::Steam(err.into()))
+    }
+}
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{handlers::ConnectionState, models::RevokeReason};
+
+/// Events published by the handler (local API subscribers and integrations)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// A guest joined the Remote Play session
+    GuestJoined {
+        /// Guest ID
+        guest_id: u64,
+        /// Steam ID of the guest
+        steam_id: u64,
+        /// Discord user associated with the guest
+        name: Option<String>,
+        /// Steam name of the guest
+        #[serde(default)]
+        persona: Option<String>,
+        /// Label of the server that requested the invite
+        #[serde(default)]
+        endpoint: Option<String>,
+    },
+    /// A guest is waiting for the host's approval
+    GuestPending {
+        /// Guest ID
+        guest_id: u64,
+        /// Steam ID of the guest
+        steam_id: u64,
+        /// Discord user associated with the guest
+        name: Option<String>,
+        /// Steam name of the guest
+        #[serde(default)]
+        persona: Option<String>,
+        /// Label of the server that requested the invite
+        #[serde(default)]
+        endpoint: Option<String>,
+    },
+    /// A guest was denied (or the approval timed out) and disconnected
+    GuestDenied {
+        /// Guest ID
+        guest_id: u64,
+        /// Steam ID of the guest
+        steam_id: u64,
+        /// Discord user associated with the guest
+        name: Option<String>,
+    },
+    /// A guest left the Remote Play session
+    GuestLeft {
+        /// Guest ID
+        guest_id: u64,
+        /// Steam ID of the guest
+        steam_id: u64,
+        /// Discord user associated with the guest
+        name: Option<String>,
+    },
+    /// An invite link was created
+    InviteCreated {
+        /// Guest ID
+        guest_id: u64,
+        /// Game ID
+        game: u32,
+        /// Invite URL
+        url: String,
+        /// Label of the server that requested the invite
+        #[serde(default)]
+        endpoint: Option<String>,
+    },
+    /// An invite link was revoked
+    InviteRevoked {
+        /// Guest ID
+        guest_id: u64,
+        /// Why the link was revoked
+        reason: RevokeReason,
+    },
+    /// A chat message arrived from a Discord user
+    ChatReceived {
+        /// Discord user name
+        name: Option<String>,
+        /// Message text
+        text: String,
+        /// Label of the server the message came through
+        #[serde(default)]
+        endpoint: Option<String>,
+    },
+    /// The connection to the server changed
+    ConnectionChanged {
+        /// New connection state
+        state: ConnectionState,
+    },
+    /// The connection to one of the servers was established or lost
+    EndpointChanged {
+        /// Label of the server (the host if there is none)
+        endpoint: String,
+        /// New connection state of the server
+        state: ConnectionState,
+    },
+    /// Invite creation was paused or resumed
+    PauseChanged {
+        /// Whether new invites are refused
+        paused: bool,
+    },
+}
@@ -0,0 +1,293 @@
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::config;
+
+/// Files next to the executable that hold the token or data about the guests
+const PRIVATE_FILES: &[&str] = &[
+    "config.toml",
+    "endpoint.toml",
+    "bans.toml",
+    "relink.toml",
+    "audit.jsonl",
+    "stats.jsonl",
+    "history",
+    "logs",
+    "avatars",
+];
+
+/// Directories among the private files whose entries are checked as well
+const PRIVATE_DIRS: &[&str] = &["logs", "avatars"];
+
+/// How to fix the access to an exposed file
+#[cfg(unix)]
+pub const EXPOSED_FIX: &str = "chmod go-rwx to fix";
+/// How to fix the access to an exposed file
+#[cfg(windows)]
+pub const EXPOSED_FIX: &str = "remove the other users in Properties > Security to fix";
+
+/// Writes a file only the current user can read (the permissions of an existing file are kept)
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    #[cfg(not(windows))]
+    let mut file = {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options.open(path)?
+    };
+    #[cfg(windows)]
+    let mut file = windows::create_file(path, false)?;
+    io::Write::write_all(&mut file, contents.as_ref())
+}
+
+/// Opens a file for appending, creating it so only the current user can read it
+pub fn append(path: &Path) -> io::Result<File> {
+    #[cfg(not(windows))]
+    {
+        let mut options = fs::OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options.open(path)
+    }
+    #[cfg(windows)]
+    windows::create_file(path, true)
+}
+
+/// Creates a directory (and its parents) only the current user can open
+pub fn create_dir_all(path: &Path) -> io::Result<()> {
+    if path.is_dir() {
+        return Ok(());
+    }
+    #[cfg(not(windows))]
+    {
+        let mut builder = fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(path)
+    }
+    #[cfg(windows)]
+    {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        windows::create_dir(path)
+    }
+}
+
+/**
+ * Finds the private files that other users of this PC can read
+ * @return Paths of the exposed files
+ */
+pub fn exposed_files() -> Vec<PathBuf> {
+    let Ok(exe_path) = config::get_exe_path() else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = PRIVATE_FILES
+        .iter()
+        .map(|extension| exe_path.with_extension(extension))
+        .collect();
+    // Each file in the directories as well as the directories
+    for extension in PRIVATE_DIRS {
+        if let Ok(entries) = fs::read_dir(exe_path.with_extension(extension)) {
+            paths.extend(entries.flatten().map(|entry| entry.path()));
+        }
+    }
+    paths.into_iter().filter(|path| is_exposed(path)).collect()
+}
+
+/// Whether users other than the owner can read a file
+#[cfg(unix)]
+fn is_exposed(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    fs::metadata(path).is_ok_and(|metadata| metadata.permissions().mode() & 0o004 != 0)
+}
+
+/// Whether users other than the owner can read a file
+#[cfg(windows)]
+fn is_exposed(path: &Path) -> bool {
+    path.exists() && windows::grants_read_to_others(path)
+}
+
+/// Whether users other than the owner can read a file (no permissions to check)
+#[cfg(not(any(unix, windows)))]
+fn is_exposed(_path: &Path) -> bool {
+    false
+}
+
+/// Owner-only security descriptors and ACL checks (Windows)
+#[cfg(windows)]
+mod windows {
+    use std::{
+        ffi::c_void,
+        fs::File,
+        io, mem,
+        os::windows::{ffi::OsStrExt as _, io::FromRawHandle as _},
+        path::Path,
+        ptr,
+    };
+    use windows_sys::Win32::{
+        Foundation::{
+            LocalFree, ERROR_SUCCESS, GENERIC_ALL, GENERIC_READ, GENERIC_WRITE,
+            INVALID_HANDLE_VALUE,
+        },
+        Security::{
+            Authorization::{
+                ConvertStringSecurityDescriptorToSecurityDescriptorW, GetNamedSecurityInfoW,
+                SDDL_REVISION_1, SE_FILE_OBJECT,
+            },
+            GetAce, IsWellKnownSid, WinAuthenticatedUserSid, WinBuiltinUsersSid, WinInteractiveSid,
+            WinWorldSid, ACCESS_ALLOWED_ACE, ACL, DACL_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR,
+            SECURITY_ATTRIBUTES,
+        },
+        Storage::FileSystem::{
+            CreateDirectoryW, CreateFileW, CREATE_ALWAYS, FILE_APPEND_DATA, FILE_ATTRIBUTE_NORMAL,
+            FILE_GENERIC_WRITE, FILE_READ_DATA, FILE_SHARE_DELETE, FILE_SHARE_READ,
+            FILE_SHARE_WRITE, FILE_WRITE_DATA, OPEN_ALWAYS,
+        },
+        System::SystemServices::ACCESS_ALLOWED_ACE_TYPE,
+    };
+
+    /// Full access for the owner and SYSTEM only, inherited by the files of a directory
+    const OWNER_ONLY: &str = "D:P(A;OICI;FA;;;OW)(A;OICI;FA;;;SY)";
+
+    /// Security descriptor allocated by Windows (freed when dropped)
+    struct Descriptor(PSECURITY_DESCRIPTOR);
+
+    impl Descriptor {
+        /// Parses a descriptor in the SDDL format
+        fn parse(sddl: &str) -> io::Result<Self> {
+            let sddl = wide(sddl.as_ref());
+            let mut descriptor: PSECURITY_DESCRIPTOR = ptr::null_mut();
+            if unsafe {
+                ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                    sddl.as_ptr(),
+                    SDDL_REVISION_1,
+                    &mut descriptor,
+                    ptr::null_mut(),
+                )
+            } == 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self(descriptor))
+        }
+
+        /// Attributes that create an object with this descriptor
+        fn attributes(&self) -> SECURITY_ATTRIBUTES {
+            SECURITY_ATTRIBUTES {
+                nLength: mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+                lpSecurityDescriptor: self.0,
+                bInheritHandle: 0,
+            }
+        }
+    }
+
+    impl Drop for Descriptor {
+        fn drop(&mut self) {
+            unsafe { LocalFree(self.0 as _) };
+        }
+    }
+
+    /// Null terminated UTF-16 string for the Windows API
+    fn wide(text: &std::ffi::OsStr) -> Vec<u16> {
+        text.encode_wide().chain([0]).collect()
+    }
+
+    /**
+     * Opens a file for writing, creating it with the owner-only ACL
+     * @param append Append to the file instead of replacing its contents
+     */
+    pub fn create_file(path: &Path, append: bool) -> io::Result<File> {
+        let descriptor = Descriptor::parse(OWNER_ONLY)?;
+        let attributes = descriptor.attributes();
+        let (access, disposition) = if append {
+            (
+                (FILE_GENERIC_WRITE & !FILE_WRITE_DATA) | FILE_APPEND_DATA,
+                OPEN_ALWAYS,
+            )
+        } else {
+            (GENERIC_WRITE, CREATE_ALWAYS)
+        };
+        let handle = unsafe {
+            CreateFileW(
+                wide(path.as_os_str()).as_ptr(),
+                access,
+                FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                &attributes,
+                disposition,
+                FILE_ATTRIBUTE_NORMAL,
+                0,
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(unsafe { File::from_raw_handle(handle as _) })
+    }
+
+    /// Creates a directory with the owner-only ACL
+    pub fn create_dir(path: &Path) -> io::Result<()> {
+        let descriptor = Descriptor::parse(OWNER_ONLY)?;
+        let attributes = descriptor.attributes();
+        if unsafe { CreateDirectoryW(wide(path.as_os_str()).as_ptr(), &attributes) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Whether the ACL of a file lets everyone or all the users of this PC read it
+    pub fn grants_read_to_others(path: &Path) -> bool {
+        let mut acl: *mut ACL = ptr::null_mut();
+        let mut descriptor: PSECURITY_DESCRIPTOR = ptr::null_mut();
+        if unsafe {
+            GetNamedSecurityInfoW(
+                wide(path.as_os_str()).as_ptr(),
+                SE_FILE_OBJECT,
+                DACL_SECURITY_INFORMATION,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                &mut acl,
+                ptr::null_mut(),
+                &mut descriptor,
+            )
+        } != ERROR_SUCCESS
+        {
+            return false;
+        }
+        // Freed once the ACL in it has been checked
+        let _descriptor = Descriptor(descriptor);
+
+        // No ACL at all gives everyone full access
+        if acl.is_null() {
+            return true;
+        }
+        let read = FILE_READ_DATA | GENERIC_READ | GENERIC_ALL;
+        (0..unsafe { (*acl).AceCount }).any(|index| {
+            let mut ace: *mut c_void = ptr::null_mut();
+            if unsafe { GetAce(acl, index.into(), &mut ace) } == 0 {
+                return false;
+            }
+            let ace = ace as *const ACCESS_ALLOWED_ACE;
+            let (header, mask) = unsafe { ((*ace).Header, (*ace).Mask) };
+            if u32::from(header.AceType) != ACCESS_ALLOWED_ACE_TYPE || mask & read == 0 {
+                return false;
+            }
+            let sid = unsafe { ptr::addr_of!((*ace).SidStart) } as *mut c_void;
+            [
+                WinWorldSid,
+                WinAuthenticatedUserSid,
+                WinBuiltinUsersSid,
+                WinInteractiveSid,
+            ]
+            .into_iter()
+            .any(|kind| unsafe { IsWellKnownSid(sid, kind) } != 0)
+        })
+    }
+}
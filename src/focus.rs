@@ -0,0 +1,77 @@
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::{config::DoNotDisturbConfig, console};
+
+/// Starts watching the foreground window and toggles do-not-disturb while a game is fullscreen
+pub fn spawn_watcher(config: DoNotDisturbConfig) -> Option<JoinHandle<()>> {
+    if !config.enabled {
+        return None;
+    }
+
+    Some(tokio::spawn(async move {
+        let interval = Duration::from_secs(config.poll_secs.max(1));
+        loop {
+            let in_game = tokio::task::spawn_blocking(fullscreen_app_focused)
+                .await
+                .unwrap_or(false);
+            if in_game != console::is_do_not_disturb() {
+                let _ = console::set_do_not_disturb(in_game);
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }))
+}
+
+/// Whether a fullscreen application (usually the game) has the focus
+#[cfg(windows)]
+fn fullscreen_app_focused() -> bool {
+    use windows_sys::Win32::UI::Shell::{
+        SHQueryUserNotificationState, QUNS_BUSY, QUNS_PRESENTATION_MODE,
+        QUNS_RUNNING_D3D_FULL_SCREEN,
+    };
+
+    // The same state Windows uses to hold back its own notifications
+    let mut state = 0;
+    if unsafe { SHQueryUserNotificationState(&mut state) } != 0 {
+        return false;
+    }
+    matches!(
+        state,
+        QUNS_BUSY | QUNS_RUNNING_D3D_FULL_SCREEN | QUNS_PRESENTATION_MODE
+    )
+}
+
+/// Whether a fullscreen application (usually the game) has the focus
+#[cfg(target_os = "linux")]
+fn fullscreen_app_focused() -> bool {
+    use std::process::Command;
+
+    // Ask the X11 window manager through xprop (not detected if it is unavailable)
+    let xprop = |args: &[&str]| {
+        Command::new("xprop")
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+    // "_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3c00007"
+    let Some(window) = xprop(&["-root", "_NET_ACTIVE_WINDOW"])
+        .and_then(|active| active.split_whitespace().last().map(str::to_owned))
+        .filter(|window| window.starts_with("0x") && window != "0x0")
+    else {
+        return false;
+    };
+    // "_NET_WM_STATE(ATOM) = _NET_WM_STATE_FULLSCREEN"
+    xprop(&["-id", &window, "_NET_WM_STATE"])
+        .map(|state| state.contains("_NET_WM_STATE_FULLSCREEN"))
+        .unwrap_or(false)
+}
+
+/// Whether a fullscreen application (usually the game) has the focus
+#[cfg(not(any(windows, target_os = "linux")))]
+fn fullscreen_app_focused() -> bool {
+    // Not detected on this platform
+    false
+}
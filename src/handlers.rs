@@ -1,274 +1,2976 @@
 use anyhow::{Context, Result};
-use clipboard::{ClipboardContext, ClipboardProvider};
+use chrono::{DateTime, Local, Utc};
 use futures::SinkExt;
+use rand::Rng as _;
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::{BTreeSet, HashMap},
-    sync::Arc,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     time::Duration,
 };
-use steam_stuff::{GameID, GameUID};
+use steam_stuff::{GameID, GameUID, Steam};
 use tokio::{
     sync::{
+        broadcast,
         mpsc::{channel, Receiver, Sender},
-        Mutex,
+        oneshot, Mutex,
     },
     task,
-    time::interval,
+    time::{interval, timeout, Instant},
 };
 use tokio_tungstenite::tungstenite::{protocol::Message, Error as WsError};
 
-use crate::SteamStuff;
 use crate::{
-    console,
-    models::{ClientCmd, ClientMessage, ErrorStatus, ServerCmd, ServerMessage},
+    audit::{self, Action, Entry, Origin, Source},
+    avatars::Avatars,
+    bans::{Ban, BanDuration, BanList},
+    capacity::Capacity,
+    commands::{CommandFn, CommandFuture, CommandMetrics, Outcome, Registry},
+    compat::{CompatEntry, CompatList},
+    config::{ApprovalConfig, Config, InstructionsConfig},
+    console::{self, Glyph},
+    diagnostics,
+    endpoint::Endpoint,
+    error::{ClientError, ClientResult, ErrorKind as _},
+    events::Event,
+    instructions,
+    ipc::{EndpointStatus, GuestStatus, InviteStatus, StatusReport},
+    launch,
+    logger::Level,
+    middleware::{self, Middleware, Verdict},
+    models::{
+        ApprovalState, ClientCmd, ClientMessage, ErrorStatus, HostLoad, InviteKind, JoinFailure,
+        NatMapping, Protocol, RequestId, RevokeReason, ServerCmd, ServerMessage, SessionRecap,
+        StoreInfo, User,
+    },
+    personas::{self, Personas},
+    quiet::QuietHours,
+    replay::{self, Callback},
+    shortener::Shortener,
+    steam_health::{SteamHealth, SteamOp},
+    store::Store,
+    token::PendingRelink,
+    watchdog::Usage,
+    VERSION,
 };
 
+/// An invite link created by this client
+pub struct Invite {
+    /// Game ID
+    pub game: u32,
+    /// Invite URL shared with the guests (short link if shortened)
+    pub url: String,
+    /// Invite URL created by Steam
+    pub steam_url: String,
+    /// Number of guests who joined with the link
+    pub uses: u32,
+    /// Revoke the link after this many joins
+    pub max_uses: Option<u32>,
+    /// Kind of invite
+    pub kind: InviteKind,
+    /// Index of the server that requested the link (None: created locally)
+    pub endpoint: Option<usize>,
+    /// When the link is revoked (None: when the game exits)
+    pub expires: Option<DateTime<Utc>>,
+}
+
+impl Invite {
+    /**
+     * Counts a guest who joined with the link
+     * @return Whether the link reached its maximum number of uses
+     */
+    fn record_use(&mut self) -> bool {
+        self.uses += 1;
+        self.max_uses.is_some_and(|max_uses| self.uses >= max_uses)
+    }
+}
+
+/// No invite link with this guest ID (already revoked, expired or never created)
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown invite: guest_id={0}")]
+pub struct UnknownInvite(pub u64);
+
+/// Game, kind, maximum number of uses and lifetime of a `link` request
+type LinkKey = (u32, InviteKind, Option<u32>, Option<u64>);
+
+/// Guest waiting for the host's approval
+struct PendingGuest {
+    /// Steam ID of the guest
+    steam_id: u64,
+    /// When the guest started waiting (the longest waiting guest is admitted first)
+    since: Instant,
+    /// Sends the host's decision (true: approved)
+    decision: oneshot::Sender<bool>,
+}
+
 pub struct GuestData {
     pub guest_map: HashMap<u64, String>,
     pub user_set: BTreeSet<u64>,
+    pub invites: BTreeMap<u64, Invite>,
+    /// Steam ID of each guest in the session
+    pub player_map: HashMap<u64, u64>,
+    /// Links that reached their maximum number of uses
+    pub exhausted: BTreeSet<u64>,
+    /// Links created for the server's waitlist (each holds a slot until it is used)
+    pub admitted: BTreeSet<u64>,
+}
+
+impl GuestData {
+    /// Displays the user list
+    fn print_players(&self, personas: &Personas) -> Result<()> {
+        let users_text = self
+            .user_set
+            .iter()
+            .map(|id| {
+                let persona = self
+                    .player_map
+                    .get(id)
+                    .and_then(|steam_id| personas.cached(*steam_id));
+                personas::guest_label(
+                    *id,
+                    self.guest_map.get(id).map(String::as_str),
+                    persona.as_deref(),
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(", ");
+        console::print_update!(
+            "{} Players({}): {users_text}",
+            Glyph::Players,
+            self.user_set.len()
+        )?;
+        console::headline(&format!("{} Players", self.user_set.len()))
+    }
+}
+
+/// Connection state of the link to the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    /// Connecting for the first time
+    Connecting,
+    /// Connected to the server
+    Connected,
+    /// Connection lost, waiting to reconnect
+    Reconnecting,
+    /// Connected, waiting for the host to enter the pairing code in Discord
+    Pairing,
 }
 
 pub struct Handler {
-    steam: Arc<Mutex<SteamStuff>>,
-    invite_tx: Sender<(u64, String)>,
-    invite_rx: Receiver<(u64, String)>,
+    steam: Arc<Mutex<dyn Steam>>,
+    /// Invite links created by Steam (or why Steam could not create one)
+    invite_tx: Sender<Result<(u64, String), JoinFailure>>,
+    invite_rx: Mutex<Receiver<Result<(u64, String), JoinFailure>>>,
     guest_data: Arc<Mutex<GuestData>>,
+    connection: Mutex<ConnectionState>,
+    paused: AtomicBool,
+    /// Daily quiet hours (None if disabled)
+    quiet_hours: Option<QuietHours>,
+    /// The quiet hours are in effect (invites paused, notifications held back)
+    quiet: AtomicBool,
+    /// The host lifted the current quiet hours
+    quiet_lifted: AtomicBool,
+    events: broadcast::Sender<Event>,
+    approval: ApprovalConfig,
+    /// Steam IDs and Discord users who skip the approval, the waitlist and the playtime cap
+    priority: Vec<String>,
+    /// Stages every server message passes before its command runs
+    middleware: Vec<Box<dyn Middleware>>,
+    /// Server commands by name
+    commands: Registry,
+    /// Most minutes of log a server may get (0: log requests are refused)
+    share_max_minutes: u64,
+    /// Token of the client (hidden in the shared log)
+    token: String,
+    /// Log request of a server, waiting for the host's decision
+    diagnostics: std::sync::Mutex<Option<diagnostics::Request>>,
+    /// Default maximum number of uses of an invite link
+    max_uses: Option<u32>,
+    /// How guests are told to join
+    instructions: InstructionsConfig,
+    /// Guest slots the server's waitlist can fill (0: no waitlist)
+    slots: u32,
+    /// Guest limit that follows the host load
+    capacity: std::sync::Mutex<Capacity>,
+    /// Free slots and limit last reported to the servers
+    reported_slots: std::sync::Mutex<Option<(u32, u32)>>,
+    /// Game handed over to another client, waiting for the server
+    handoff: std::sync::Mutex<Option<u32>>,
+    /// Game this client was asked to host and the server that asked
+    host_request: std::sync::Mutex<Option<(usize, u32)>>,
+    /// Banned guests
+    bans: Mutex<BanList>,
+    /// Games known to work poorly with Remote Play Together
+    compat: Mutex<CompatList>,
+    /// Move of the Discord link to a new token, waiting for the server
+    relink: Mutex<Option<PendingRelink>>,
+    /// Shortens the invite URLs
+    shortener: Shortener,
+    /// NAT of the host found by the STUN probe
+    network: std::sync::Mutex<Option<NatMapping>>,
+    /// Latest load sample of the host (None while not streaming)
+    load: std::sync::Mutex<Option<HostLoad>>,
+    /// Consecutive failures of the Steam operations
+    steam_health: SteamHealth,
+    /// Steam names of the guests
+    personas: Personas,
+    /// Avatars of the guests (None without a Steam Web API key)
+    avatars: Option<Avatars>,
+    /// Remote Play Together and controller support from the Steam store
+    store: Store,
+    /// How long Steam may take to create an invite link
+    invite_timeout: Duration,
+    /// Repeated `link` requests within this time get the same link (zero: always a new one)
+    invite_cooldown: Duration,
+    /// Last link created for each game, kind, maximum number of uses and lifetime requested by a server
+    recent_links: std::sync::Mutex<HashMap<LinkKey, (Instant, u64)>>,
+    /// Default ban for kicked guests
+    ban_on_kick: Option<BanDuration>,
+    /// Guests waiting for approval
+    pending: Mutex<BTreeMap<u64, PendingGuest>>,
+    /// Guest IDs and Steam IDs whose stream started (a session that stops before is a failed join)
+    connected: std::sync::Mutex<BTreeSet<(u64, u64)>>,
+    /// Servers the client registers with
+    endpoints: Vec<Arc<Endpoint>>,
+    /// Sender of the last chat message (the target of replies) and the server it came from
+    last_chat: Mutex<Option<(usize, User)>>,
 }
 
 impl Handler {
-    pub fn new(steam: Arc<Mutex<SteamStuff>>) -> Self {
-        let (invite_tx, invite_rx) = channel::<(u64, String)>(32);
+    pub fn new(
+        steam: Arc<Mutex<dyn Steam>>,
+        config: &Config,
+        bans: BanList,
+        compat: CompatList,
+        relink: Option<PendingRelink>,
+        endpoints: Vec<Arc<Endpoint>>,
+    ) -> Self {
+        let (invite_tx, invite_rx) = channel::<Result<(u64, String), JoinFailure>>(32);
+        // The shortener lives on the first server (a direct invite has none: read the configured one)
+        let endpoint_url = match endpoints.first() {
+            Some(endpoint) => endpoint.try_url(),
+            None => crate::config::read_endpoint_config()
+                .ok()
+                .flatten()
+                .and_then(|endpoint| endpoint.url),
+        };
         Self {
             steam,
             invite_tx,
-            invite_rx,
+            invite_rx: Mutex::new(invite_rx),
             guest_data: Arc::new(Mutex::new(GuestData {
                 guest_map: HashMap::<u64, String>::new(),
                 user_set: BTreeSet::<u64>::new(),
+                invites: BTreeMap::<u64, Invite>::new(),
+                player_map: HashMap::<u64, u64>::new(),
+                exhausted: BTreeSet::<u64>::new(),
+                admitted: BTreeSet::<u64>::new(),
             })),
+            connection: Mutex::new(ConnectionState::Connecting),
+            paused: AtomicBool::new(false),
+            quiet_hours: QuietHours::new(&config.quiet_hours).unwrap_or_else(|err| {
+                let _ = console::eprintln!("{} {:#}", Glyph::Err, err);
+                None
+            }),
+            quiet: AtomicBool::new(false),
+            quiet_lifted: AtomicBool::new(false),
+            events: broadcast::channel(64).0,
+            approval: config.approval.clone(),
+            priority: config.priority.guests.clone(),
+            middleware: middleware::chain(&config.permissions),
+            commands: builtin_commands(),
+            share_max_minutes: if config.logging.enabled {
+                config.logging.share_max_minutes
+            } else {
+                0
+            },
+            token: config.uuid.clone(),
+            diagnostics: std::sync::Mutex::new(None),
+            max_uses: config.invites.max_uses,
+            instructions: config.instructions.clone(),
+            slots: config.waitlist.slots,
+            capacity: std::sync::Mutex::new(Capacity::new(&config.waitlist)),
+            reported_slots: std::sync::Mutex::new(None),
+            handoff: std::sync::Mutex::new(None),
+            host_request: std::sync::Mutex::new(None),
+            bans: Mutex::new(bans),
+            compat: Mutex::new(compat),
+            relink: Mutex::new(relink),
+            shortener: Shortener::new(
+                &config.shortener,
+                endpoint_url.as_deref(),
+                config.timeouts.http(),
+            ),
+            network: std::sync::Mutex::new(None),
+            load: std::sync::Mutex::new(None),
+            steam_health: SteamHealth::default(),
+            personas: Personas::new(config.personas.clone(), config.timeouts.persona()),
+            avatars: Avatars::new(&config.avatars, config.timeouts.http()),
+            store: Store::new(&config.store, config.timeouts.http()),
+            invite_timeout: config.timeouts.invite(),
+            invite_cooldown: Duration::from_secs(config.invites.cooldown_secs),
+            recent_links: std::sync::Mutex::new(HashMap::new()),
+            ban_on_kick: config.bans.on_kick,
+            pending: Mutex::new(BTreeMap::new()),
+            connected: std::sync::Mutex::new(BTreeSet::new()),
+            endpoints,
+            last_chat: Mutex::new(None),
         }
     }
 
+    /// Servers the client registers with
+    pub fn endpoints(&self) -> &[Arc<Endpoint>] {
+        &self.endpoints
+    }
+
     /**
-     * Handles server messages
-     * @return Whether to exit (true: exit)
+     * Label of a server for the console, notifications and status
+     * @return The label (None with a single server the user did not name)
      */
-    pub async fn handle_server_message(
-        &mut self,
-        msg: ServerMessage,
-        write: &mut (impl SinkExt<Message, Error = WsError> + Unpin),
-    ) -> Result<bool> {
-        // Branch based on command type
-        let res = match msg.cmd {
-            ServerCmd::Message { text: data, copy } => {
-                // Indent the message
-                let message = data
-                    .lines()
-                    .map(|line| format!("  {}", line))
-                    .collect::<Vec<String>>()
-                    .join("\n");
-
-                // Display the welcome message
-                console::printdoc! {"
-
-                {message}
-
-                "}?;
-
-                // If there is a copy, copy it
-                if let Some(copy) = copy {
-                    // Copy to clipboard
-                    if let Err(_err) = ClipboardProvider::new()
-                        .map(|mut ctx: ClipboardContext| ctx.set_contents(copy.clone()))
-                    {
-                        console::eprintln!("☓ Failed to copy to clipboard: {}", copy)?;
-                    }
-                }
+    pub fn label_of(&self, endpoint: &Endpoint) -> Option<String> {
+        (self.endpoints.len() > 1 || endpoint.label.is_some()).then(|| endpoint.name().to_owned())
+    }
 
-                return Ok(false);
+    /// Label of the server with the index
+    fn endpoint_label(&self, index: Option<usize>) -> Option<String> {
+        self.label_of(self.endpoints.get(index?)?)
+    }
+
+    /// Asks every connection loop to reconnect (a loop that is busy does it at its next wait)
+    fn request_reconnect(&self) {
+        for endpoint in &self.endpoints {
+            endpoint.request_reconnect();
+        }
+    }
+
+    /// Recovers from a system suspend: drops the stale connections and catches up with Steam
+    pub async fn on_resume(self: &Arc<Self>) {
+        let mut marked = false;
+        for endpoint in &self.endpoints {
+            marked |= endpoint.mark_resync();
+        }
+        // Already recovering (detected by more than one watcher)
+        if !marked {
+            return;
+        }
+        let _ = console::println!("{} Resumed from sleep, reconnecting...", Glyph::Retry);
+
+        // Register the callbacks again and handle what Steam queued while suspended
+        self.setup_steam_callbacks().await;
+        self.steam.lock().await.run_callbacks();
+
+        self.request_reconnect();
+    }
+
+    /**
+     * Called when the connection to a server is established
+     * @param reconnect Whether the server was connected before
+     * @return Session state to send to the server (it may have missed changes while disconnected)
+     */
+    pub async fn on_connected(
+        &self,
+        endpoint: &Endpoint,
+        reconnect: bool,
+    ) -> Option<ClientMessage> {
+        self.set_connection_state(endpoint, ConnectionState::Connected)
+            .await;
+        // The server tells again if another device is still active
+        endpoint.set_active_device(None);
+
+        // Ask again on every connection until the server confirms the move
+        if let Some(relink) = self.relink.lock().await.clone() {
+            let _ = console::urgent_println!(
+                "{} Confirm the new token in Discord with the pairing code: {}",
+                Glyph::Wait,
+                relink.code
+            );
+            self.notify(
+                Some(endpoint.index),
+                ClientCmd::Relink {
+                    previous: relink.previous,
+                    code: relink.code,
+                },
+            );
+        }
+
+        // Invites stay unavailable until Steam is restarted
+        if self.steam_health.is_degraded() {
+            self.notify(Some(endpoint.index), ClientCmd::Steam { available: false });
+        }
+
+        // Tell the server if guests will likely need relays
+        if let Some(mapping) = self.network() {
+            self.notify(
+                Some(endpoint.index),
+                ClientCmd::Network {
+                    mapping,
+                    relay_likely: mapping.relay_likely(),
+                },
+            );
+        }
+
+        // Tell the server's waitlist how many guests it can admit
+        if let Some(free) = self.free_slots().await {
+            self.notify(Some(endpoint.index), self.slots_cmd(free));
+        }
+
+        // A fresh registration has nothing to catch up with
+        if !endpoint.take_resync() && !reconnect {
+            return None;
+        }
+        let guest_data = self.guest_data.lock().await;
+        Some(ClientMessage::notice(ClientCmd::Sync {
+            paused: self.is_paused(),
+            invites: guest_data.invites.keys().copied().collect(),
+            guests: guest_data.user_set.iter().copied().collect(),
+        }))
+    }
+
+    /// Called when the connection to a server was lost (or the server stopped the client)
+    pub async fn on_disconnected(&self, endpoint: &Endpoint) {
+        self.set_connection_state(endpoint, ConnectionState::Reconnecting)
+            .await;
+    }
+
+    /**
+     * Queues a message for the servers that is not a response to a request
+     * @param endpoint Index of the server to tell (None: all of them)
+     */
+    fn notify(&self, endpoint: Option<usize>, cmd: ClientCmd) {
+        let msg = ClientMessage::notice(cmd);
+        for target in &self.endpoints {
+            if endpoint.is_none() || endpoint == Some(target.index) {
+                target.notify(msg.clone());
             }
-            ServerCmd::GameId => 'cmd: {
-                let game_id = self.steam.lock().await.get_running_game_id();
-
-                if !game_id.is_valid_app() {
-                    // If the game is not running
-                    // Create the response data
-                    break 'cmd ClientMessage {
-                        id: msg.id,
-                        cmd: ClientCmd::Error {
-                            code: ErrorStatus::InvalidApp,
-                        },
-                    };
-                }
+        }
+    }
 
-                let app_id = game_id.app_id;
-                let game_uid: GameUID = game_id.into();
-
-                if !self.steam.lock().await.can_remote_play_together(game_uid) {
-                    // If the game is not supported for Remote Play Together
-                    // Create the response data
-                    break 'cmd ClientMessage {
-                        id: msg.id,
-                        cmd: ClientCmd::Error {
-                            code: ErrorStatus::UnsupportedApp,
-                        },
-                    };
-                }
+    /// Avatar of a Steam user, downloaded if needed (None without a Steam Web API key)
+    pub async fn avatar(&self, steam_id: u64) -> Option<PathBuf> {
+        self.avatars.as_ref()?.fetch(steam_id).await
+    }
 
-                // Log the output
-                let claimer = msg.user.as_ref().map_or_else(|| "?", |s| &s.name);
-                console::println!(
-                    "-> Create Panel       : claimer={claimer}, game_id={0}",
-                    app_id
-                )?;
-
-                // Create the response data
-                ClientMessage {
-                    id: msg.id,
-                    cmd: ClientCmd::GameId { game: app_id },
+    /// Free guest slots for the server's waitlist (None without a waitlist)
+    pub async fn free_slots(&self) -> Option<u32> {
+        if self.slots == 0 {
+            return None;
+        }
+        let mut guest_data = self.guest_data.lock().await;
+        let pending = self.pending.lock().await;
+        // Admission links stop holding a slot once they are used or revoked
+        let GuestData {
+            invites, admitted, ..
+        } = &mut *guest_data;
+        admitted.retain(|guest_id| invites.get(guest_id).is_some_and(|invite| invite.uses == 0));
+        let waiting = admitted
+            .iter()
+            .filter(|guest_id| !pending.contains_key(guest_id))
+            .count();
+        let taken = guest_data.user_set.len() + pending.len() + waiting;
+        let limit = self.capacity.lock().unwrap().limit();
+        Some(limit.saturating_sub(taken as u32))
+    }
+
+    fn slots_cmd(&self, free: u32) -> ClientCmd {
+        ClientCmd::Slots {
+            free,
+            total: self.slots,
+            limit: self.capacity.lock().unwrap().limit(),
+        }
+    }
+
+    /// Tells the servers when the number of free slots or the limit changed
+    pub async fn report_slots(&self) {
+        let Some(free) = self.free_slots().await else {
+            return;
+        };
+        let limit = self.capacity.lock().unwrap().limit();
+        let changed =
+            self.reported_slots.lock().unwrap().replace((free, limit)) != Some((free, limit));
+        if changed {
+            self.notify(None, self.slots_cmd(free));
+        }
+    }
+
+    /// Tells the server that requested the invite about the approval state of a guest
+    async fn notify_approval(
+        &self,
+        guest_id: u64,
+        steam_id: u64,
+        persona: Option<String>,
+        state: ApprovalState,
+    ) {
+        let endpoint = self
+            .guest_data
+            .lock()
+            .await
+            .invites
+            .get(&guest_id)
+            .and_then(|invite| invite.endpoint);
+        self.notify(
+            endpoint,
+            ClientCmd::Approval {
+                guest_id,
+                steam_id,
+                persona,
+                state,
+            },
+        );
+    }
+
+    /// Guest ID of the longest waiting guest
+    pub async fn first_pending(&self) -> Option<u64> {
+        self.pending
+            .lock()
+            .await
+            .iter()
+            .min_by_key(|(_, guest)| guest.since)
+            .map(|(guest_id, _)| *guest_id)
+    }
+
+    /// Approves or denies a guest waiting for approval
+    pub async fn decide_guest(&self, guest_id: u64, approve: bool, origin: Origin) -> Result<()> {
+        let PendingGuest {
+            steam_id, decision, ..
+        } = self
+            .pending
+            .lock()
+            .await
+            .remove(&guest_id)
+            .with_context(|| format!("Guest is not waiting for approval: guest_id={guest_id}"))?;
+        let name = self
+            .guest_data
+            .lock()
+            .await
+            .guest_map
+            .get(&guest_id)
+            .cloned();
+        audit::record(
+            Entry::new(
+                if approve {
+                    Action::Approve
+                } else {
+                    Action::Deny
+                },
+                &origin,
+            )
+            .guest(Some(guest_id), Some(steam_id), name),
+        );
+        let _ = decision.send(approve);
+        Ok(())
+    }
+
+    /// Subscribes to the handler events
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// Publishes an event to the subscribers
+    fn emit(&self, event: Event) {
+        // Sending only fails when nobody is subscribed
+        let _ = self.events.send(event);
+    }
+
+    /// Updates the connection state of a server (reported by the status command)
+    async fn set_connection_state(&self, endpoint: &Endpoint, state: ConnectionState) {
+        if endpoint.state().await != state {
+            endpoint.set_state(state).await;
+            self.emit(Event::EndpointChanged {
+                endpoint: endpoint.name().to_owned(),
+                state,
+            });
+        }
+
+        // Connected while any server is connected
+        let mut state = ConnectionState::Connecting;
+        for endpoint in &self.endpoints {
+            match endpoint.state().await {
+                ConnectionState::Connected => {
+                    state = ConnectionState::Connected;
+                    break;
                 }
-            }
-            ServerCmd::Link { game } => {
-                // Get the game ID
-                let game_uid: GameUID = GameID::new(game, 0, 0).into();
-
-                // Create an invite link
-                let recv = self.invite_rx.recv();
-                self.steam.lock().await.send_invite(0, game_uid);
-                let (guest_id, connect_url) = recv.await.unwrap();
-
-                // Associate the Discord user with guest_id
-                if let Some(user) = &msg.user {
-                    self.guest_data
-                        .lock()
-                        .await
-                        .guest_map
-                        .insert(guest_id, user.name.clone());
+                ConnectionState::Pairing => state = ConnectionState::Pairing,
+                ConnectionState::Reconnecting if state == ConnectionState::Connecting => {
+                    state = ConnectionState::Reconnecting
                 }
+                _ => (),
+            }
+        }
 
-                // Log the output
-                let claimer = msg.user.as_ref().map_or_else(|| "?", |s| &s.name);
-                console::println!(
-                    "-> Create Invite Link : claimer={claimer}, guest_id={guest_id}, game_id={game}, invite_url={connect_url}",
-                )?;
+        let mut connection = self.connection.lock().await;
+        if *connection != state {
+            *connection = state;
+            self.emit(Event::ConnectionChanged { state });
+        }
+    }
 
-                // Create the response data
-                ClientMessage {
-                    id: msg.id,
-                    cmd: ClientCmd::Link { url: connect_url },
-                }
+    /// Asks the servers that send the invites to another device to send them to this PC again
+    pub fn take_over(&self) -> Result<()> {
+        let mut taken = false;
+        for endpoint in &self.endpoints {
+            let Some(device) = endpoint.active_device() else {
+                continue;
+            };
+            console::println!("{} Taking over from {device}...", Glyph::Retry)?;
+            self.notify(Some(endpoint.index), ClientCmd::Takeover);
+            taken = true;
+        }
+        if !taken {
+            anyhow::bail!("This PC is already the active device");
+        }
+        Ok(())
+    }
+
+    /// Relays a chat message from the host to the server (a reply to the last sender)
+    pub async fn send_chat(&self, text: &str) -> Result<()> {
+        let text = text.trim();
+        if text.is_empty() {
+            anyhow::bail!("The message is empty");
+        }
+        let to = self.last_chat.lock().await.clone();
+        console::chat("You", to.as_ref().map(|(_, user)| user.name.as_str()), text)?;
+        self.notify(
+            to.as_ref().map(|(endpoint, _)| *endpoint),
+            ClientCmd::Chat {
+                text: text.to_owned(),
+                to: to.map(|(_, user)| user.id),
+            },
+        );
+        Ok(())
+    }
+
+    /// Asks the servers for the latest game compatibility list
+    pub async fn request_compat(&self) {
+        let updated = self.compat.lock().await.updated;
+        self.notify(None, ClientCmd::CompatQuery { updated });
+    }
+
+    /// Replaces the game compatibility list with the one from the server
+    async fn update_compat(&self, games: Vec<CompatEntry>) -> Result<()> {
+        let count = games.len();
+        self.compat.lock().await.replace(games)?;
+        console::println!(
+            "{} Updated the game compatibility list ({count} games)",
+            Glyph::Ok
+        )
+    }
+
+    /**
+     * Warns the host about the known problems of the game
+     * @return The problems to show along with the invite
+     */
+    async fn check_compat(&self, app_id: u32) -> Result<Vec<String>> {
+        let compat = self.compat.lock().await;
+        let Some(entry) = compat.find(app_id) else {
+            return Ok(Vec::new());
+        };
+        let name = entry
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("game_id={app_id}"));
+        console::println!(
+            @level Level::Warn,
+            "{} {name} is known to work poorly with Remote Play Together",
+            Glyph::Warn
+        )?;
+        for caveat in &entry.caveats {
+            console::println!("  - {caveat}")?;
+        }
+        Ok(entry.caveats.clone())
+    }
+
+    /**
+     * Looks the game up on the Steam store and warns the host if it does not list Remote Play Together
+     * @return Store metadata to send to the servers (UnsupportedApp if such games are refused)
+     */
+    async fn check_store(&self, app_id: u32) -> Result<Option<StoreInfo>, ErrorStatus> {
+        let info = self.store.lookup(app_id).await;
+        if info.is_some_and(|info| !info.remote_play_together) {
+            let refused = self.store.refuses_unsupported();
+            let _ = console::println!(
+                @level Level::Warn,
+                "{} The Steam store does not list Remote Play Together for game_id={app_id}{}",
+                Glyph::Warn,
+                if refused { ", refusing the invite" } else { "" }
+            );
+            if refused {
+                return Err(ErrorStatus::UnsupportedApp);
+            }
+        }
+        Ok(info)
+    }
+
+    /// Join instructions to send with an invite for the game (None if disabled)
+    async fn join_instructions(&self, app_id: u32) -> Option<String> {
+        let store = self.store.lookup(app_id).await;
+        let compat = self.compat.lock().await;
+        let name = compat.find(app_id).and_then(|entry| entry.name.as_deref());
+        instructions::build(&self.instructions, app_id, name, store)
+    }
+
+    /// Whether a Remote Play session is active
+    pub async fn is_streaming(&self) -> bool {
+        !self.guest_data.lock().await.user_set.is_empty()
+    }
+
+    /// Whether invite creation is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Pauses or resumes invite creation
+    pub fn set_paused(&self, paused: bool, origin: Origin) -> Result<()> {
+        if self.paused.swap(paused, Ordering::Relaxed) != paused {
+            if paused {
+                console::println!("-> Invites Paused")?;
+            } else {
+                console::println!("-> Invites Resumed")?;
             }
-            ServerCmd::Exit => {
-                // Exit the application
-                return Ok(true);
+            audit::record(Entry::new(
+                if paused {
+                    Action::Pause
+                } else {
+                    Action::Resume
+                },
+                &origin,
+            ));
+            self.emit(Event::PauseChanged { paused });
+        }
+        Ok(())
+    }
+
+    /// Daily quiet hours (None if disabled)
+    pub fn quiet_hours(&self) -> Option<QuietHours> {
+        self.quiet_hours
+    }
+
+    /// Whether the quiet hours are in effect (notifications are held back)
+    // Only the notifiers read it, and they are optional features
+    #[cfg_attr(not(feature = "sounds"), allow(dead_code))]
+    pub fn is_quiet(&self) -> bool {
+        self.quiet.load(Ordering::Relaxed)
+    }
+
+    /**
+     * Enters or leaves the quiet hours by the clock, pausing or resuming the invites
+     * @param origin Who changed the quiet hours (None: the clock)
+     */
+    pub fn update_quiet(&self, origin: Option<Origin>) -> Result<()> {
+        let Some(hours) = self.quiet_hours else {
+            return Ok(());
+        };
+        let inside = hours.contains(Local::now().time());
+        // An exception lasts until the quiet hours end
+        if !inside {
+            self.quiet_lifted.store(false, Ordering::Relaxed);
+        }
+        let quiet = inside && !self.quiet_lifted.load(Ordering::Relaxed);
+        if self.quiet.swap(quiet, Ordering::Relaxed) == quiet {
+            return Ok(());
+        }
+        if quiet {
+            console::println!(
+                "-> Quiet Hours Started (until {})",
+                hours.end.format("%H:%M")
+            )?;
+        } else if inside {
+            console::println!(
+                "-> Quiet Hours Lifted (until {})",
+                hours.end.format("%H:%M")
+            )?;
+        } else {
+            console::println!("-> Quiet Hours Ended")?;
+        }
+        self.set_paused(quiet, origin.unwrap_or_else(|| Origin::auto("quiet hours")))
+    }
+
+    /// Lifts the current quiet hours until they end (false: keeps them again)
+    pub fn lift_quiet(&self, lifted: bool, origin: Origin) -> Result<()> {
+        let Some(hours) = self.quiet_hours else {
+            anyhow::bail!("Quiet hours are not enabled");
+        };
+        if !hours.contains(Local::now().time()) {
+            anyhow::bail!(
+                "Not in the quiet hours ({} to {})",
+                hours.start.format("%H:%M"),
+                hours.end.format("%H:%M")
+            );
+        }
+        self.quiet_lifted.store(lifted, Ordering::Relaxed);
+        self.update_quiet(Some(origin))
+    }
+
+    /// Returns the app ID of the running game if it can be shared with Remote Play Together
+    pub async fn running_game(&self) -> Result<u32, ErrorStatus> {
+        let steam = self.steam.lock().await;
+        let game_id = steam.get_running_game_id();
+
+        // If the game is not running
+        if !game_id.is_valid_app() {
+            return Err(ErrorStatus::InvalidApp);
+        }
+
+        let app_id = game_id.app_id;
+        let game_uid: GameUID = game_id.into();
+
+        // If the game is not supported for Remote Play Together
+        if !steam.can_remote_play_together(game_uid) {
+            return Err(ErrorStatus::UnsupportedApp);
+        }
+
+        Ok(app_id)
+    }
+
+    /**
+     * Creates a Remote Play invite link for the game
+     * @param max_uses Revoke the link after this many joins (defaults to the config)
+     * @param kind Kind of invite (only players are supported by Steam for now)
+     * @param endpoint Index of the server that requested the link (None: created locally)
+     * @return Guest ID and invite URL
+     */
+    pub async fn create_invite(
+        &self,
+        game: u32,
+        claimer: Option<&str>,
+        max_uses: Option<u32>,
+        kind: InviteKind,
+        endpoint: Option<usize>,
+    ) -> Result<(u64, String)> {
+        if !kind.is_supported() {
+            anyhow::bail!("Steam cannot create {kind:?} invites");
+        }
+        if self.steam_health.is_degraded() {
+            return Err(ClientError::Steam(anyhow::anyhow!(
+                "Steam is not responding, reconnecting to it"
+            ))
+            .into());
+        }
+
+        // Get the game ID
+        let game_uid: GameUID = GameID::new(game, 0, 0).into();
+
+        // Create an invite link (hold the receiver so concurrent requests get their own link)
+        let mut invite_rx = self.invite_rx.lock().await;
+        // Links of requests that timed out are no longer wanted
+        while invite_rx.try_recv().is_ok() {}
+        if self.steam.lock().await.send_invite(0, game_uid) == 0 {
+            anyhow::bail!("Steam cannot create invites for this game");
+        }
+        let received = timeout(self.invite_timeout, invite_rx.recv()).await;
+        drop(invite_rx);
+        let (guest_id, steam_url) = match received {
+            Ok(Some(Ok(invite))) => {
+                self.steam_health.success(SteamOp::Invite);
+                invite
             }
-            ServerCmd::Invalid => {
-                // Create the response data
-                ClientMessage {
-                    id: msg.id,
-                    cmd: ClientCmd::Error {
-                        code: ErrorStatus::InvalidCmd,
-                    },
+            Ok(Some(Err(reason))) => {
+                // Failures on the host side (the others depend on the guest's client)
+                if matches!(reason, JoinFailure::LaunchFailed | JoinFailure::Unknown) {
+                    self.steam_failed(SteamOp::Invite);
                 }
+                anyhow::bail!("Steam could not create the invite link: {}", reason.hint());
+            }
+            Ok(None) => {
+                return Err(ClientError::Steam(anyhow::anyhow!(
+                    "Steam invite callback is no longer available"
+                ))
+                .into())
+            }
+            Err(_) => {
+                self.steam_failed(SteamOp::Invite);
+                return Err(ClientError::Steam(anyhow::anyhow!(
+                    "Steam did not create the invite link in time"
+                ))
+                .into());
             }
         };
+        let connect_url = self.shortener.shorten(&steam_url).await;
 
-        // Convert the response data to JSON
-        let res_str = serde_json::to_string(&res)
-            .context("Failed to serialize JSON message for the server")?;
-        // Send the response data
-        write
-            .send(Message::Text(res_str))
+        let mut guest_data = self.guest_data.lock().await;
+        guest_data.invites.insert(
+            guest_id,
+            Invite {
+                game,
+                url: connect_url.clone(),
+                steam_url,
+                uses: 0,
+                max_uses: max_uses.or(self.max_uses),
+                kind,
+                endpoint,
+                expires: None,
+            },
+        );
+        // Associate the Discord user with guest_id
+        if let Some(claimer) = claimer {
+            guest_data.guest_map.insert(guest_id, claimer.to_owned());
+        }
+
+        self.emit(Event::InviteCreated {
+            guest_id,
+            game,
+            url: connect_url.clone(),
+            endpoint: self.endpoint_label(endpoint),
+        });
+        Ok((guest_id, connect_url))
+    }
+
+    /**
+     * Creates an invite link requested on this machine (console, control API, scripts, invite page)
+     * @param claimer_tag Where the request came from, for the log (`local`, `script`, `lan <ip>`)
+     * @param game Game of the link (None: the running game)
+     * @param ttl Revoke the link after this time
+     * @return Guest ID, game and invite URL
+     */
+    pub async fn create_local_invite(
+        &self,
+        claimer_tag: &str,
+        game: Option<u32>,
+        max_uses: Option<u32>,
+        kind: InviteKind,
+        ttl: Option<Duration>,
+    ) -> Result<(u64, u32, String)> {
+        if self.is_paused() {
+            anyhow::bail!("Invites are paused");
+        }
+        let game = match game {
+            Some(game) => game,
+            None => self.running_game().await.map_err(|code| {
+                anyhow::anyhow!("No game available for Remote Play Together ({code:?})")
+            })?,
+        };
+        let (guest_id, url) = self.create_invite(game, None, max_uses, kind, None).await?;
+        if let Some(ttl) = ttl {
+            self.set_expiry(guest_id, ttl).await;
+        }
+
+        // Log the output
+        let ttl = match ttl.map(|ttl| ttl.as_secs()) {
+            Some(secs) if secs % 60 == 0 => format!(", ttl={}m", secs / 60),
+            Some(secs) => format!(", ttl={secs}s"),
+            None => String::new(),
+        };
+        console::println!(
+            "-> Create Invite Link : claimer=({claimer_tag}), guest_id={guest_id}, \
+             game_id={game}{ttl}, invite_url={url}",
+        )?;
+        Ok((guest_id, game, url))
+    }
+
+    /**
+     * Link created for the same `link` request within the cooldown, if it still works
+     * @return Guest ID and invite URL
+     */
+    async fn recent_link(&self, key: LinkKey) -> Option<(u64, String)> {
+        let (created, guest_id) = *self.recent_links.lock().unwrap().get(&key)?;
+        if created.elapsed() >= self.invite_cooldown {
+            return None;
+        }
+        let guest_data = self.guest_data.lock().await;
+        if guest_data.exhausted.contains(&guest_id) {
+            return None;
+        }
+        guest_data
+            .invites
+            .get(&guest_id)
+            .map(|invite| (guest_id, invite.url.clone()))
+    }
+
+    /// Creates an invite for a game that was just started and posts it to the servers
+    pub async fn auto_invite(&self, game: u32) -> Result<()> {
+        if self.is_paused() {
+            return Ok(());
+        }
+        let Ok(store) = self.check_store(game).await else {
+            return Ok(());
+        };
+        let (guest_id, url) = self
+            .create_invite(game, None, None, InviteKind::Player, None)
+            .await?;
+        console::println!(
+            "-> Auto Invite Link   : guest_id={guest_id}, game_id={game}, invite_url={url}",
+        )?;
+        let caveats = self.check_compat(game).await?;
+        let instructions = self.join_instructions(game).await;
+        self.notify(
+            None,
+            ClientCmd::Invite {
+                guest_id,
+                game,
+                url,
+                caveats,
+                instructions,
+                store,
+            },
+        );
+        Ok(())
+    }
+
+    /// Revokes an invite link (also ends the session of the guest who used it)
+    pub async fn revoke_invite(&self, guest_id: u64, origin: Origin) -> Result<()> {
+        let invite = self
+            .guest_data
+            .lock()
             .await
-            .context("Failed to send message to the server")?;
+            .invites
+            .remove(&guest_id)
+            .ok_or(UnknownInvite(guest_id))?;
+        self.steam.lock().await.cancel_invite(0, guest_id);
+        self.shortener.revoke(&invite.steam_url).await;
 
-        Ok(false)
+        // Log the output
+        console::println!(
+            "-> Revoke Invite Link : guest_id={guest_id}, game_id={0}, invite_url={1}",
+            invite.game,
+            invite.url
+        )?;
+        let name = self
+            .guest_data
+            .lock()
+            .await
+            .guest_map
+            .get(&guest_id)
+            .cloned();
+        audit::record(
+            Entry::new(Action::Revoke, &origin)
+                .guest(Some(guest_id), None, name)
+                .detail(format!("invite_url={}", invite.url)),
+        );
+
+        // The server already knows about the revocations it requested
+        let reason = match origin.source {
+            Source::Server => RevokeReason::Server,
+            _ => {
+                self.notify(
+                    invite.endpoint,
+                    ClientCmd::Revoked {
+                        guest_id,
+                        reason: RevokeReason::Host,
+                    },
+                );
+                RevokeReason::Host
+            }
+        };
+        self.emit(Event::InviteRevoked { guest_id, reason });
+        Ok(())
     }
 
-    // Set up SteamStuff callbacks
-    pub async fn setup_steam_callbacks(&self) {
-        // Register callbacks
-        let steam = self.steam.lock().await;
-        let guest_data = self.guest_data.clone();
-        steam.set_on_remote_started(move |invitee, guest_id| {
-            let guest_data = guest_data.clone();
-            tokio::spawn(async move {
-                let mut guest_data = guest_data.lock().await;
-                guest_data.user_set.insert(guest_id);
-                let user_name = guest_data.guest_map.get(&guest_id).map_or_else(|| "?", |s| s);
-                let _: Result<()> = 'tryblock: {
-                    // Log the output
-                    if let Err(err) = console::println!(
-                        "-> Player Joined        : claimer={user_name}, guest_id={guest_id}, steam_id={invitee}",
-                    ) {
-                        break 'tryblock Err(err);
-                    }
-
-                    // Display the user list
-                    let users_text = guest_data
-                        .user_set
-                        .iter()
-                        .map(|id| format!("[{}]{}", id, guest_data.guest_map.get(id).map_or_else(|| "?", |s| s)))
-                        .collect::<Vec<String>>()
-                        .join(", ");
-                    if let Err(err) = console::print_update!("★ Players({}): {users_text}", guest_data.user_set.len()) {
-                        break 'tryblock Err(err);
-                    }
-
-                    Ok(())
-                };
-            });
-        });
-        let guest_data = self.guest_data.clone();
-        steam.set_on_remote_stopped(move |invitee, guest_id| {
-            let guest_data = guest_data.clone();
-            tokio::spawn(async move {
-                let mut guest_data = guest_data.lock().await;
-                guest_data.user_set.remove(&guest_id);
-                let user_name = guest_data.guest_map.get(&guest_id).map_or_else(|| "?", |s| s);
-                let _: Result<()> = 'tryblock: {
-                    // Log the output
-                    if let Err(err) = console::println!(
-                        "-> Player Left          : claimer={user_name}, guest_id={guest_id}, steam_id={invitee}",
-                    ) {
-                        break 'tryblock Err(err);
-                    }
-
-                    // Display the user list
-                    let users_text = guest_data
-                        .user_set
-                        .iter()
-                        .map(|id| format!("[{}]{}", id, guest_data.guest_map.get(id).map_or_else(|| "?", |s| s)))
-                        .collect::<Vec<String>>()
-                        .join(", ");
-                    if let Err(err) = console::print_update!("★ Players({}): {users_text}", guest_data.user_set.len()) {
-                        break 'tryblock Err(err);
-                    }
-
-                    Ok(())
-                };
+    /// Revokes an invite link after a while (a time-boxed invite)
+    pub async fn set_expiry(&self, guest_id: u64, ttl: Duration) {
+        let expires = chrono::Duration::from_std(ttl)
+            .ok()
+            .and_then(|ttl| Utc::now().checked_add_signed(ttl));
+        if let Some(invite) = self.guest_data.lock().await.invites.get_mut(&guest_id) {
+            invite.expires = expires;
+        }
+    }
+
+    /// Revokes the time-boxed invites that expired and tells the servers
+    pub async fn revoke_expired(&self) {
+        let now = Utc::now();
+        let mut guest_data = self.guest_data.lock().await;
+        let guest_ids: Vec<u64> = guest_data
+            .invites
+            .iter()
+            .filter(|(_, invite)| invite.expires.is_some_and(|expires| expires <= now))
+            .map(|(&guest_id, _)| guest_id)
+            .collect();
+        for guest_id in guest_ids {
+            let Some(invite) = guest_data.invites.remove(&guest_id) else {
+                continue;
+            };
+            self.steam.lock().await.cancel_invite(0, guest_id);
+            self.shortener.revoke(&invite.steam_url).await;
+            let _ = console::println!(
+                "-> Invite Expired     : guest_id={guest_id}, game_id={0}, invite_url={1}",
+                invite.game,
+                invite.url
+            );
+            audit::record(
+                Entry::new(Action::Revoke, &Origin::auto("invite link expired"))
+                    .guest(
+                        Some(guest_id),
+                        None,
+                        guest_data.guest_map.get(&guest_id).cloned(),
+                    )
+                    .detail(format!("invite_url={}", invite.url)),
+            );
+            self.notify(
+                invite.endpoint,
+                ClientCmd::Revoked {
+                    guest_id,
+                    reason: RevokeReason::Expired,
+                },
+            );
+            self.emit(Event::InviteRevoked {
+                guest_id,
+                reason: RevokeReason::Expired,
             });
-        });
-        let invite_tx = self.invite_tx.clone();
-        steam.set_on_remote_invited(move |_invitee, guest_id, connect_url| {
-            // Send the invite link
-            let invite_tx = invite_tx.clone();
-            let connect_url = String::from(connect_url);
-            tokio::spawn(async move {
-                invite_tx.send((guest_id, connect_url)).await.unwrap();
+        }
+    }
+
+    /// Revokes the invites of a game that was closed so the servers can disable the links
+    pub async fn on_game_exited(&self, game: u32) {
+        let mut guest_data = self.guest_data.lock().await;
+        let guest_ids: Vec<u64> = guest_data
+            .invites
+            .iter()
+            .filter(|(_, invite)| invite.game == game)
+            .map(|(&guest_id, _)| guest_id)
+            .collect();
+        for guest_id in guest_ids {
+            let Some(invite) = guest_data.invites.remove(&guest_id) else {
+                continue;
+            };
+            self.steam.lock().await.cancel_invite(0, guest_id);
+            self.shortener.revoke(&invite.steam_url).await;
+            let _ = console::println!(
+                "-> Invite Game Exited : guest_id={guest_id}, game_id={game}, invite_url={0}",
+                invite.url
+            );
+            audit::record(
+                Entry::new(Action::Revoke, &Origin::auto("game exited"))
+                    .guest(
+                        Some(guest_id),
+                        None,
+                        guest_data.guest_map.get(&guest_id).cloned(),
+                    )
+                    .detail(format!("invite_url={}", invite.url)),
+            );
+            self.notify(
+                invite.endpoint,
+                ClientCmd::Revoked {
+                    guest_id,
+                    reason: RevokeReason::GameExited,
+                },
+            );
+            self.emit(Event::InviteRevoked {
+                guest_id,
+                reason: RevokeReason::GameExited,
             });
-        });
+        }
     }
 
-    // Start a task to periodically call SteamStuff_RunCallbacks
-    pub fn run_steam_callbacks(&self) {
-        let steam_clone = self.steam.clone();
-        task::spawn(async move {
-            let mut interval = interval(Duration::from_millis(200));
-            loop {
-                interval.tick().await;
-                steam_clone.lock().await.run_callbacks();
+    /// Asks the servers to move the session to another client (the host wants to stop)
+    pub async fn request_handoff(&self, origin: Origin) -> Result<()> {
+        if self.endpoints.is_empty() {
+            anyhow::bail!("A host handoff needs a server");
+        }
+        let game = self
+            .running_game()
+            .await
+            .map_err(|_| anyhow::anyhow!("No game is running to hand over"))?;
+        if self.handoff.lock().unwrap().replace(game).is_some() {
+            anyhow::bail!("A host handoff is already in progress");
+        }
+        // No new guests while another client takes over
+        self.set_paused(true, origin)?;
+        let guests = self.guest_data.lock().await.user_set.len();
+        console::println!("-> Request Handoff      : game_id={game}, guests={guests}")?;
+        self.notify(None, ClientCmd::Handoff { game, guests });
+        Ok(())
+    }
+
+    /**
+     * Ends the session after another client took it over
+     * @return Whether a handoff was in progress (the client exits)
+     */
+    async fn finish_handoff(&self, host: Option<&str>) -> bool {
+        let Some(game) = self.handoff.lock().unwrap().take() else {
+            return false;
+        };
+        let _ = console::println!(
+            "-> Handoff Complete     : game_id={game}, new_host={0}",
+            host.unwrap_or("?")
+        );
+        let origin = Origin::auto("host handoff");
+        let (guest_ids, player_ids) = {
+            let guest_data = self.guest_data.lock().await;
+            let guest_ids: Vec<u64> = guest_data.invites.keys().copied().collect();
+            let player_ids: Vec<u64> = guest_data.user_set.iter().copied().collect();
+            (guest_ids, player_ids)
+        };
+        for guest_id in guest_ids {
+            let _ = self.revoke_invite(guest_id, origin.clone()).await;
+        }
+        for guest_id in player_ids {
+            let _ = self.disconnect_guest(guest_id, None, origin.clone()).await;
+        }
+        true
+    }
+
+    /// Resumes hosting when no other client took the session over
+    fn cancel_handoff(&self) -> Result<()> {
+        if self.handoff.lock().unwrap().take().is_none() {
+            return Ok(());
+        }
+        console::println!(
+            @level Level::Warn,
+            "{} No other client took the session over, invites resumed",
+            Glyph::Warn
+        )?;
+        self.set_paused(false, Origin::auto("host handoff failed"))
+    }
+
+    /**
+     * Asks the host whether to send the recent log to the server
+     * @return Whether the host is asked (false: refused right away)
+     */
+    fn request_diagnostics(
+        &self,
+        endpoint: &Endpoint,
+        id: RequestId,
+        minutes: u64,
+        reason: Option<&str>,
+    ) -> Result<bool> {
+        if self.share_max_minutes == 0 {
+            return Ok(false);
+        }
+        let minutes = minutes.clamp(1, self.share_max_minutes);
+        let server = endpoint.label.as_ref().unwrap_or(&endpoint.host);
+        console::urgent_printdoc! {"
+
+            {warn} {server} asks for the log of the last {minutes} minutes{reason}.
+              Names, Steam IDs, links, IP addresses and the token are hidden.
+              Type share to send it or decline to refuse.
+
+            ",
+            warn = Glyph::Warn,
+            reason = reason.map(|reason| format!(" ({reason})")).unwrap_or_default()
+        }?;
+        let request = diagnostics::Request {
+            endpoint: endpoint.index,
+            id,
+            minutes,
+        };
+        // Only the latest request waits for a decision
+        if let Some(old) = self.diagnostics.lock().unwrap().replace(request) {
+            self.answer_diagnostics(
+                old,
+                ClientCmd::Error {
+                    code: ErrorStatus::DiagnosticsDeclined,
+                },
+            );
+        }
+        Ok(true)
+    }
+
+    fn answer_diagnostics(&self, request: diagnostics::Request, cmd: ClientCmd) {
+        if let Some(endpoint) = self.endpoints.get(request.endpoint) {
+            endpoint.notify(ClientMessage::reply(request.id, cmd));
+        }
+    }
+
+    /// Sends (or refuses) the log the server asked for
+    pub async fn decide_diagnostics(&self, share: bool, origin: Origin) -> Result<()> {
+        let request = self
+            .diagnostics
+            .lock()
+            .unwrap()
+            .take()
+            .context("No server asked for the log")?;
+        if !share {
+            console::println!("-> Decline Log Request")?;
+            self.answer_diagnostics(
+                request,
+                ClientCmd::Error {
+                    code: ErrorStatus::DiagnosticsDeclined,
+                },
+            );
+            return Ok(());
+        }
+
+        let minutes = request.minutes;
+        let lines = match diagnostics::collect(minutes, vec![self.token.clone()]).await {
+            Ok(lines) => lines,
+            Err(err) => {
+                self.answer_diagnostics(
+                    request,
+                    ClientCmd::Error {
+                        code: ErrorStatus::DiagnosticsDeclined,
+                    },
+                );
+                return Err(err);
             }
-        });
+        };
+        console::println!(
+            "-> Share Log            : minutes={minutes}, lines={0}",
+            lines.len()
+        )?;
+        audit::record(
+            Entry::new(Action::ShareLogs, &origin)
+                .detail(format!("minutes={minutes}, lines={}", lines.len())),
+        );
+        self.answer_diagnostics(request, ClientCmd::Diagnostics { minutes, lines });
+        Ok(())
+    }
+
+    /// Starts the game of a host request (the server is told once it runs)
+    async fn take_over_hosting(
+        &self,
+        endpoint: usize,
+        game: u32,
+        from: Option<&str>,
+    ) -> Result<()> {
+        console::println!(
+            "-> Take Over Hosting    : from={0}, game_id={game}",
+            from.unwrap_or("?")
+        )?;
+        *self.host_request.lock().unwrap() = Some((endpoint, game));
+        if self.running_game().await.ok() == Some(game) {
+            self.on_game_started(game).await;
+            return Ok(());
+        }
+        launch::start_game(game)
+    }
+
+    /// Tells the server that asked this client to host the game that it is running
+    pub async fn on_game_started(&self, game: u32) {
+        let mut host_request = self.host_request.lock().unwrap();
+        let Some((endpoint, _)) = host_request.take_if(|(_, requested)| *requested == game) else {
+            return;
+        };
+        drop(host_request);
+        let _ = console::println!("-> Hosting Ready        : game_id={game}");
+        self.notify(Some(endpoint), ClientCmd::HostReady { game });
+    }
+
+    /// Replaces the invites Steam silently dropped (the Remote Play session is gone) and posts the new links
+    pub async fn check_invites(&self) -> Result<()> {
+        // The invites of a closed game are revoked by on_game_exited
+        let Ok(game) = self.running_game().await else {
+            return Ok(());
+        };
+        let broken: Vec<(u64, Invite, Option<String>)> = {
+            let mut guest_data = self.guest_data.lock().await;
+            if guest_data
+                .invites
+                .values()
+                .all(|invite| invite.game != game)
+                || self.steam.lock().await.is_session_active()
+            {
+                return Ok(());
+            }
+            let guest_ids: Vec<u64> = guest_data
+                .invites
+                .iter()
+                .filter(|(_, invite)| invite.game == game)
+                .map(|(&guest_id, _)| guest_id)
+                .collect();
+            guest_ids
+                .into_iter()
+                .filter_map(|guest_id| {
+                    let invite = guest_data.invites.remove(&guest_id)?;
+                    Some((
+                        guest_id,
+                        invite,
+                        guest_data.guest_map.get(&guest_id).cloned(),
+                    ))
+                })
+                .collect()
+        };
+
+        for (guest_id, invite, name) in broken {
+            self.shortener.revoke(&invite.steam_url).await;
+            console::println!(
+                "-> Invite Link Broken : guest_id={guest_id}, game_id={game}, invite_url={0}",
+                invite.url
+            )?;
+            audit::record(
+                Entry::new(
+                    Action::Revoke,
+                    &Origin::auto("invite link no longer accepted"),
+                )
+                .guest(Some(guest_id), None, name.clone())
+                .detail(format!("invite_url={}", invite.url)),
+            );
+            self.notify(
+                invite.endpoint,
+                ClientCmd::Revoked {
+                    guest_id,
+                    reason: RevokeReason::Invalid,
+                },
+            );
+            self.emit(Event::InviteRevoked {
+                guest_id,
+                reason: RevokeReason::Invalid,
+            });
+            if self.is_paused() {
+                continue;
+            }
+
+            // Post a new link with the uses left
+            let max_uses = invite
+                .max_uses
+                .map(|max_uses| max_uses.saturating_sub(invite.uses).max(1));
+            let (guest_id, url) = self
+                .create_invite(
+                    game,
+                    name.as_deref(),
+                    max_uses,
+                    invite.kind,
+                    invite.endpoint,
+                )
+                .await?;
+            console::println!(
+                "-> Renew Invite Link  : guest_id={guest_id}, game_id={game}, invite_url={url}",
+            )?;
+            let caveats = self.check_compat(game).await?;
+            let instructions = self.join_instructions(game).await;
+            let store = self.store.lookup(game).await;
+            self.notify(
+                invite.endpoint,
+                ClientCmd::Invite {
+                    guest_id,
+                    game,
+                    url,
+                    caveats,
+                    instructions,
+                    store,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// Consecutive failures of the Steam operations
+    pub fn steam_health(&self) -> &SteamHealth {
+        &self.steam_health
+    }
+
+    /// Records a failed Steam operation and tells the servers when Steam stops responding
+    fn steam_failed(&self, op: SteamOp) {
+        if !self.steam_health.failure(op) {
+            return;
+        }
+        let _ = console::eprintln!(
+            "{} Steam keeps failing ({op:?}), invites are unavailable until the connection to Steam is restarted",
+            Glyph::Warn
+        );
+        self.notify(None, ClientCmd::Steam { available: false });
+    }
+
+    /// Restarts the connection to Steam (after repeated failures)
+    pub async fn restart_steam(self: &Arc<Self>) -> Result<()> {
+        self.steam.lock().await.reinit()?;
+        self.setup_steam_callbacks().await;
+        Ok(())
+    }
+
+    /// Tells the servers that invites are available again
+    pub fn on_steam_recovered(&self) {
+        let _ = console::println!("{} Reconnected to Steam", Glyph::Ok);
+        self.notify(None, ClientCmd::Steam { available: true });
+    }
+
+    /// Latest load sample of the host (None while not streaming)
+    pub fn load(&self) -> Option<HostLoad> {
+        *self.load.lock().unwrap()
+    }
+
+    /// Records a load sample of the host and tells the connected servers (None: the session ended)
+    #[cfg(feature = "metrics")]
+    pub async fn set_load(&self, load: Option<HostLoad>) {
+        *self.load.lock().unwrap() = load;
+        self.adapt_slots(load).await;
+        let Some(load) = load else {
+            return;
+        };
+        // Old samples are useless, so they are not queued for the disconnected servers
+        for endpoint in &self.endpoints {
+            if endpoint.state().await == ConnectionState::Connected {
+                self.notify(Some(endpoint.index), ClientCmd::Load { load });
+            }
+        }
+    }
+
+    /// Tells the servers about a Remote Play session that ended
+    pub fn report_recap(&self, recap: SessionRecap) {
+        self.notify(None, ClientCmd::Recap { recap });
+    }
+
+    /// Lowers or raises the waitlist's guest limit with the host load (None: the session ended)
+    #[cfg(feature = "metrics")]
+    async fn adapt_slots(&self, load: Option<HostLoad>) {
+        let changed = {
+            let mut capacity = self.capacity.lock().unwrap();
+            let old = capacity.limit();
+            match load {
+                Some(load) => capacity.update(load),
+                None => capacity.reset(),
+            }
+            .map(|limit| (old, limit))
+        };
+        let Some((old, limit)) = changed else {
+            return;
+        };
+        let _ = if limit < old {
+            console::println!(
+                @level Level::Warn,
+                "{} Host is overloaded ({}), guest limit lowered to {limit}/{}",
+                Glyph::Warn,
+                load.map(crate::load::describe).unwrap_or_default(),
+                self.slots
+            )
+        } else {
+            console::println!("{} Guest limit raised to {limit}/{}", Glyph::Ok, self.slots)
+        };
+        self.report_slots().await;
+    }
+
+    /// NAT of the host found by the STUN probe
+    pub fn network(&self) -> Option<NatMapping> {
+        *self.network.lock().unwrap()
+    }
+
+    /// Records the result of the STUN probe and tells the connected servers (the others learn it on connection)
+    pub async fn set_network(&self, mapping: NatMapping) {
+        *self.network.lock().unwrap() = Some(mapping);
+        for endpoint in &self.endpoints {
+            if endpoint.state().await == ConnectionState::Connected {
+                self.notify(
+                    Some(endpoint.index),
+                    ClientCmd::Network {
+                        mapping,
+                        relay_likely: mapping.relay_likely(),
+                    },
+                );
+            }
+        }
+    }
+
+    /**
+     * Disconnects a guest from the Remote Play session
+     * @param ban Ban the guest for this long (defaults to the config)
+     */
+    pub async fn kick_guest(
+        &self,
+        guest_id: u64,
+        ban: Option<BanDuration>,
+        origin: Origin,
+    ) -> Result<()> {
+        self.disconnect_guest(guest_id, ban.or(self.ban_on_kick), origin)
+            .await
+    }
+
+    /// Disconnects a guest, banning the guest if a duration is given
+    async fn disconnect_guest(
+        &self,
+        guest_id: u64,
+        ban: Option<BanDuration>,
+        origin: Origin,
+    ) -> Result<()> {
+        let guest_data = self.guest_data.lock().await;
+        if !guest_data.user_set.contains(&guest_id) {
+            anyhow::bail!("Guest is not in the session: guest_id={guest_id}");
+        }
+        let steam_id = guest_data.player_map.get(&guest_id).copied().unwrap_or(0);
+        let name = guest_data.guest_map.get(&guest_id).cloned();
+        drop(guest_data);
+        let user_name = name.as_deref().unwrap_or("?");
+        let steam_user = personas::steam_user(steam_id, &self.personas.cached(steam_id));
+
+        // Log the output
+        console::urgent_println!(
+            "-> Kick Player          : claimer={user_name}, guest_id={guest_id}, steam_id={steam_user}",
+        )?;
+
+        self.steam.lock().await.cancel_invite(steam_id, guest_id);
+        audit::record(Entry::new(Action::Kick, &origin).guest(
+            Some(guest_id),
+            Some(steam_id),
+            name.clone(),
+        ));
+
+        // Keep the guest from joining again
+        if let Some(duration) = ban {
+            let ban = self
+                .bans
+                .lock()
+                .await
+                .ban(steam_id, name.clone(), duration)?;
+            let until = ban
+                .until
+                .map(|until| until.to_rfc3339())
+                .unwrap_or_else(|| "permanent".to_owned());
+            console::urgent_println!(
+                "-> Ban Player           : claimer={user_name}, steam_id={steam_id}, until={until}",
+            )?;
+            audit::record(
+                Entry::new(Action::Ban, &origin)
+                    .guest(Some(guest_id), Some(steam_id), name)
+                    .detail(format!("until={until}")),
+            );
+        }
+        Ok(())
+    }
+
+    /// Warns a guest (through the server of the invite) that the playtime cap is near
+    pub async fn warn_playtime(&self, guest_id: u64, steam_id: u64, left: Duration) {
+        let guest_data = self.guest_data.lock().await;
+        let user_name = guest_data
+            .guest_map
+            .get(&guest_id)
+            .map_or_else(|| "?", |s| s);
+        let endpoint = guest_data
+            .invites
+            .get(&guest_id)
+            .and_then(|invite| invite.endpoint);
+        let minutes_left = left.as_secs() / 60;
+        let _ = console::println!(
+            "-> Playtime Warning     : claimer={user_name}, guest_id={guest_id}, minutes_left={minutes_left}",
+        );
+        drop(guest_data);
+        self.notify(
+            endpoint,
+            ClientCmd::PlaytimeWarning {
+                guest_id,
+                steam_id,
+                minutes_left,
+            },
+        );
+    }
+
+    /// Disconnects a guest who reached the playtime cap so the server can invite the next guest
+    pub async fn end_playtime(&self, guest_id: u64) -> Result<()> {
+        let guest_data = self.guest_data.lock().await;
+        // The guest already left
+        let Some(&steam_id) = guest_data.player_map.get(&guest_id) else {
+            return Ok(());
+        };
+        let endpoint = guest_data
+            .invites
+            .get(&guest_id)
+            .and_then(|invite| invite.endpoint);
+        drop(guest_data);
+
+        self.disconnect_guest(guest_id, None, Origin::auto("playtime cap reached"))
+            .await?;
+        self.notify(endpoint, ClientCmd::PlaytimeOver { guest_id, steam_id });
+        Ok(())
+    }
+
+    /// Active bans
+    pub async fn bans(&self) -> Vec<Ban> {
+        let mut bans = self.bans.lock().await;
+        bans.prune();
+        bans.bans.clone()
+    }
+
+    /// Lifts the ban of a guest
+    pub async fn unban(&self, steam_id: u64, origin: Origin) -> Result<()> {
+        let ban = self.bans.lock().await.unban(steam_id)?;
+
+        // Log the output
+        console::println!(
+            "-> Unban Player         : claimer={0}, steam_id={steam_id}",
+            ban.name.as_deref().unwrap_or("?")
+        )?;
+        audit::record(Entry::new(Action::Unban, &origin).guest(None, Some(steam_id), ban.name));
+        Ok(())
+    }
+
+    /// Counts of the state that must stay bounded (checked by the watchdog)
+    pub async fn usage(&self) -> Usage {
+        let guest_data = self.guest_data.lock().await;
+        Usage {
+            tasks: tokio::runtime::Handle::current()
+                .metrics()
+                .num_alive_tasks(),
+            subscribers: self.events.receiver_count(),
+            invites: guest_data.invites.len(),
+            guests: guest_data.guest_map.len(),
+            players: guest_data.player_map.len(),
+            exhausted: guest_data.exhausted.len(),
+            personas: self.personas.count(),
+            approvals: self.pending.lock().await.len(),
+            notices: self
+                .endpoints
+                .iter()
+                .map(|endpoint| endpoint.queued())
+                .sum(),
+        }
+    }
+
+    /// Builds a status report for the local control API
+    pub async fn status(&self) -> StatusReport {
+        let connection = *self.connection.lock().await;
+        let mut endpoints = Vec::new();
+        for endpoint in &self.endpoints {
+            endpoints.push(EndpointStatus {
+                host: endpoint.host.clone(),
+                label: endpoint.label.clone(),
+                connection: endpoint.state().await,
+                invites: 0,
+                active_device: endpoint.active_device(),
+                family: endpoint.family(),
+            });
+        }
+        let guest_data = self.guest_data.lock().await;
+        for invite in guest_data.invites.values() {
+            if let Some(endpoint) = invite.endpoint.and_then(|index| endpoints.get_mut(index)) {
+                endpoint.invites += 1;
+            }
+        }
+        let name_of = |guest_id: &u64| guest_data.guest_map.get(guest_id).cloned();
+        let persona_of =
+            |steam_id: Option<&u64>| steam_id.and_then(|steam_id| self.personas.cached(*steam_id));
+        let avatar_of = |steam_id: Option<&u64>| {
+            let avatars = self.avatars.as_ref()?;
+            avatars.cached(*steam_id?)
+        };
+        StatusReport {
+            version: VERSION.to_owned(),
+            connection,
+            endpoint: self
+                .endpoints
+                .iter()
+                .map(|endpoint| endpoint.host.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            endpoints,
+            paused: self.is_paused(),
+            pending: self
+                .pending
+                .lock()
+                .await
+                .iter()
+                .map(|(guest_id, PendingGuest { steam_id, .. })| GuestStatus {
+                    guest_id: *guest_id,
+                    name: name_of(guest_id),
+                    persona: persona_of(Some(steam_id)),
+                    avatar: avatar_of(Some(steam_id)),
+                })
+                .collect(),
+            invites: guest_data
+                .invites
+                .iter()
+                .map(|(guest_id, invite)| InviteStatus {
+                    guest_id: *guest_id,
+                    game: invite.game,
+                    url: invite.url.clone(),
+                    claimer: name_of(guest_id),
+                    uses: invite.uses,
+                    max_uses: invite.max_uses,
+                    kind: invite.kind,
+                    endpoint: self.endpoint_label(invite.endpoint),
+                    expires: invite.expires,
+                })
+                .collect(),
+            guests: guest_data
+                .user_set
+                .iter()
+                .map(|guest_id| GuestStatus {
+                    guest_id: *guest_id,
+                    name: name_of(guest_id),
+                    persona: persona_of(guest_data.player_map.get(guest_id)),
+                    avatar: avatar_of(guest_data.player_map.get(guest_id)),
+                })
+                .collect(),
+            network: self.network(),
+            load: self.load(),
+            commands: self.command_metrics(),
+        }
+    }
+
+    /**
+     * Handles server messages
+     * @return Whether to exit (true: exit)
+     */
+    pub async fn handle_server_message(
+        &self,
+        msg: ServerMessage,
+        endpoint: &Endpoint,
+        write: &mut (impl SinkExt<Message, Error = WsError> + Unpin),
+    ) -> ClientResult<bool> {
+        // Permissions, rate limit, retries and the log file
+        match middleware::screen(&self.middleware, &msg, endpoint).console()? {
+            Verdict::Continue => (),
+            Verdict::Reply(res) => {
+                send_message(&res, endpoint.protocol(), write).await?;
+                return Ok(false);
+            }
+            Verdict::Drop => return Ok(false),
+        }
+        // Commands borrow the message, so it is not copied for the stages
+        let outcome = match self.run_command(&msg, endpoint).await {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                for stage in &self.middleware {
+                    stage.failed(&msg, endpoint);
+                }
+                return Err(err);
+            }
+        };
+        let res = match outcome {
+            Outcome::Reply(cmd) => ClientMessage::reply(msg.request_id(), cmd),
+            Outcome::Done => return Ok(false),
+            Outcome::Exit => return Ok(true),
+        };
+
+        // Send the response data
+        for stage in &self.middleware {
+            stage.after(&msg, endpoint, &res);
+        }
+        send_message(&res, endpoint.protocol(), write).await?;
+
+        Ok(false)
+    }
+
+    /// Runs the command of a server message that passed the middleware
+    pub async fn run_command(
+        &self,
+        msg: &ServerMessage,
+        endpoint: &Endpoint,
+    ) -> ClientResult<Outcome> {
+        self.commands.run(self, msg, endpoint).await
+    }
+
+    /// Metrics of the server commands that ran
+    pub fn command_metrics(&self) -> Vec<CommandMetrics> {
+        self.commands.metrics()
+    }
+
+    /// Why a new invite is refused (None: it can be created)
+    fn invite_refusal(&self, kind: InviteKind) -> Option<ErrorStatus> {
+        if self.is_paused() {
+            Some(ErrorStatus::Paused)
+        } else if self.steam_health.is_degraded() {
+            Some(ErrorStatus::SteamUnavailable)
+        } else if !kind.is_supported() {
+            // Answer with a typed error instead of dropping the connection
+            Some(ErrorStatus::UnsupportedInvite)
+        } else {
+            None
+        }
+    }
+
+    /// `message` command: shows an announcement
+    fn message_command<'a>(&'a self, msg: &'a ServerMessage, _: &'a Endpoint) -> CommandFuture<'a> {
+        Box::pin(async move {
+            let ServerCmd::Message { text: data, copy } = &msg.cmd else {
+                return Ok(Outcome::Done);
+            };
+            // Indent the message
+            let message = data
+                .lines()
+                .map(|line| format!("  {}", line))
+                .collect::<Vec<String>>()
+                .join("\n");
+
+            // Display the welcome message
+            console::printdoc! {"
+
+            {message}
+
+            "}
+            .console()?;
+
+            // If there is a copy, copy it
+            if let Some(copy) = copy {
+                // Copy to clipboard
+                if console::copy(copy).is_err() {
+                    console::eprintln!("{} Failed to copy to clipboard: {}", Glyph::Err, copy)
+                        .console()?;
+                }
+            }
+            Ok(Outcome::Done)
+        })
+    }
+
+    /// `game` command: answers with the running game
+    fn game_command<'a>(&'a self, msg: &'a ServerMessage, _: &'a Endpoint) -> CommandFuture<'a> {
+        Box::pin(async move {
+            // Refuse new invites while paused
+            if let Some(code) = self.invite_refusal(InviteKind::Player) {
+                return Ok(Outcome::Reply(ClientCmd::Error { code }));
+            }
+            let app_id = match self.running_game().await {
+                Ok(app_id) => app_id,
+                // If the game is not running or not supported for Remote Play Together
+                Err(code) => return Ok(Outcome::Reply(ClientCmd::Error { code })),
+            };
+
+            // Log the output
+            let claimer = msg.user.as_ref().map_or_else(|| "?", |s| &s.name);
+            console::println!(
+                "-> Create Panel       : claimer={claimer}, game_id={0}",
+                app_id
+            )
+            .console()?;
+            let store = match self.check_store(app_id).await {
+                Ok(store) => store,
+                Err(code) => return Ok(Outcome::Reply(ClientCmd::Error { code })),
+            };
+            let caveats = self.check_compat(app_id).await.console()?;
+
+            // Create the response data
+            Ok(Outcome::Reply(ClientCmd::GameId {
+                game: app_id,
+                caveats,
+                store,
+            }))
+        })
+    }
+
+    /// `admit_next` command: invites the next guest of the server's waitlist
+    fn admit_next_command<'a>(
+        &'a self,
+        msg: &'a ServerMessage,
+        endpoint: &'a Endpoint,
+    ) -> CommandFuture<'a> {
+        Box::pin(async move {
+            let &ServerCmd::AdmitNext { game, kind } = &msg.cmd else {
+                return Ok(Outcome::Done);
+            };
+            if let Some(code) = self.invite_refusal(kind) {
+                return Ok(Outcome::Reply(ClientCmd::Error { code }));
+            }
+            if let Err(code) = self.check_store(game).await {
+                return Ok(Outcome::Reply(ClientCmd::Error { code }));
+            }
+            // Without a waitlist the slots are not counted, priority guests do not wait for one
+            let priority = msg
+                .user
+                .as_ref()
+                .is_some_and(|user| self.is_priority(None, &[&user.name, &user.id]));
+            if !priority && self.free_slots().await == Some(0) {
+                return Ok(Outcome::Reply(ClientCmd::Error {
+                    code: ErrorStatus::NoFreeSlot,
+                }));
+            }
+
+            // One join per admission, so the slot is not shared
+            let (guest_id, connect_url) = match self
+                .create_invite(
+                    game,
+                    msg.user.as_ref().map(|user| user.name.as_str()),
+                    Some(1),
+                    kind,
+                    Some(endpoint.index),
+                )
+                .await
+            {
+                Ok(invite) => invite,
+                Err(err) => {
+                    // Answer the server instead of dropping the connection
+                    console::eprintln!("{} {}", Glyph::Err, err).console()?;
+                    return Ok(Outcome::Reply(ClientCmd::Error {
+                        code: ErrorStatus::SteamUnavailable,
+                    }));
+                }
+            };
+            self.guest_data.lock().await.admitted.insert(guest_id);
+            self.report_slots().await;
+
+            // Log the output
+            let claimer = msg.user.as_ref().map_or_else(|| "?", |s| &s.name);
+            console::println!(
+                "-> Admit Next Guest   : claimer={claimer}, guest_id={guest_id}, game_id={game}, invite_url={connect_url}",
+            ).console()?;
+            let caveats = self.check_compat(game).await.console()?;
+            let instructions = self.join_instructions(game).await;
+
+            // Create the response data
+            Ok(Outcome::Reply(ClientCmd::Link {
+                url: connect_url,
+                caveats,
+                instructions,
+            }))
+        })
+    }
+
+    /// `link` command: creates an invite link
+    fn link_command<'a>(
+        &'a self,
+        msg: &'a ServerMessage,
+        endpoint: &'a Endpoint,
+    ) -> CommandFuture<'a> {
+        Box::pin(async move {
+            let &ServerCmd::Link {
+                game,
+                max_uses,
+                kind,
+                ttl_secs,
+            } = &msg.cmd
+            else {
+                return Ok(Outcome::Done);
+            };
+            if let Some(code) = self.invite_refusal(kind) {
+                return Ok(Outcome::Reply(ClientCmd::Error { code }));
+            }
+            if let Err(code) = self.check_store(game).await {
+                return Ok(Outcome::Reply(ClientCmd::Error { code }));
+            }
+            let claimer = msg.user.as_ref().map_or_else(|| "?", |s| &s.name);
+            let key = (game, kind, max_uses, ttl_secs);
+            if let Some((guest_id, connect_url)) = self.recent_link(key).await {
+                // Repeated requests get the same link until the cooldown ends
+                console::println!(
+                    "-> Reuse Invite Link  : claimer={claimer}, guest_id={guest_id}, game_id={game}, invite_url={connect_url}",
+                ).console()?;
+                let caveats = self.check_compat(game).await.console()?;
+                let instructions = self.join_instructions(game).await;
+                return Ok(Outcome::Reply(ClientCmd::Link {
+                    url: connect_url,
+                    caveats,
+                    instructions,
+                }));
+            }
+
+            // Create an invite link
+            let (guest_id, connect_url) = match self
+                .create_invite(
+                    game,
+                    msg.user.as_ref().map(|user| user.name.as_str()),
+                    max_uses,
+                    kind,
+                    Some(endpoint.index),
+                )
+                .await
+            {
+                Ok(invite) => invite,
+                Err(err) => {
+                    // Answer the server instead of dropping the connection
+                    console::eprintln!("{} {}", Glyph::Err, err).console()?;
+                    return Ok(Outcome::Reply(ClientCmd::Error {
+                        code: ErrorStatus::SteamUnavailable,
+                    }));
+                }
+            };
+            self.recent_links
+                .lock()
+                .unwrap()
+                .insert(key, (Instant::now(), guest_id));
+            if let Some(ttl_secs) = ttl_secs {
+                self.set_expiry(guest_id, Duration::from_secs(ttl_secs))
+                    .await;
+            }
+
+            // Log the output
+            let ttl = ttl_secs.map_or_else(String::new, |secs| format!(", ttl={secs}s"));
+            console::println!(
+                "-> Create Invite Link : claimer={claimer}, guest_id={guest_id}, game_id={game}{ttl}, invite_url={connect_url}",
+            ).console()?;
+            let caveats = self.check_compat(game).await.console()?;
+            let instructions = self.join_instructions(game).await;
+
+            // Create the response data
+            Ok(Outcome::Reply(ClientCmd::Link {
+                url: connect_url,
+                caveats,
+                instructions,
+            }))
+        })
+    }
+
+    /// `revoke` command: revokes an invite link
+    fn revoke_command<'a>(&'a self, msg: &'a ServerMessage, _: &'a Endpoint) -> CommandFuture<'a> {
+        Box::pin(async move {
+            let ServerCmd::Revoke { guest_id, reason } = &msg.cmd else {
+                return Ok(Outcome::Done);
+            };
+            let guest_id = *guest_id;
+            Ok(Outcome::Reply(
+                match self
+                    .revoke_invite(guest_id, Origin::new(Source::Server, reason.clone()))
+                    .await
+                {
+                    Ok(()) => ClientCmd::Revoked {
+                        guest_id,
+                        reason: RevokeReason::Server,
+                    },
+                    Err(err) if err.is::<UnknownInvite>() => ClientCmd::Error {
+                        code: ErrorStatus::UnknownInvite,
+                    },
+                    // Revoked, but the console could not show it
+                    Err(err) => return Err(err).console(),
+                },
+            ))
+        })
+    }
+
+    /// `compat` command: stores the latest game compatibility list
+    fn compat_command<'a>(&'a self, msg: &'a ServerMessage, _: &'a Endpoint) -> CommandFuture<'a> {
+        Box::pin(async move {
+            let ServerCmd::Compat { games } = &msg.cmd else {
+                return Ok(Outcome::Done);
+            };
+            // A stale list only means missing warnings
+            if let Err(err) = self.update_compat(games.clone()).await {
+                console::eprintln!("{} {}", Glyph::Err, err).console()?;
+            }
+            Ok(Outcome::Done)
+        })
+    }
+
+    /// `chat` command: shows a chat message from Discord
+    fn chat_command<'a>(
+        &'a self,
+        msg: &'a ServerMessage,
+        endpoint: &'a Endpoint,
+    ) -> CommandFuture<'a> {
+        Box::pin(async move {
+            let ServerCmd::Chat { from, text } = &msg.cmd else {
+                return Ok(Outcome::Done);
+            };
+            let name = from
+                .clone()
+                .or_else(|| msg.user.as_ref().map(|user| user.name.clone()));
+            console::chat(name.as_deref().unwrap_or("?"), None, text).console()?;
+            if let Some(user) = &msg.user {
+                *self.last_chat.lock().await = Some((endpoint.index, user.clone()));
+            }
+            self.emit(Event::ChatReceived {
+                name,
+                text: text.clone(),
+                endpoint: self.label_of(endpoint),
+            });
+            Ok(Outcome::Done)
+        })
+    }
+
+    /// `backoff` command: waits before the next reconnect
+    fn backoff_command<'a>(
+        &'a self,
+        msg: &'a ServerMessage,
+        endpoint: &'a Endpoint,
+    ) -> CommandFuture<'a> {
+        Box::pin(async move {
+            if let &ServerCmd::Backoff { secs } = &msg.cmd {
+                // Applied when the server closes the connection
+                endpoint.set_backoff(secs);
+            }
+            Ok(Outcome::Done)
+        })
+    }
+
+    /// `pair` command: shows a pairing code for Discord
+    fn pair_command<'a>(
+        &'a self,
+        _: &'a ServerMessage,
+        endpoint: &'a Endpoint,
+    ) -> CommandFuture<'a> {
+        Box::pin(async move {
+            // A new code every time (the server asks again when a code expires)
+            let code = format!("{:06}", rand::thread_rng().gen_range(0..1_000_000));
+            let shown = format!("{} {}", &code[..3], &code[3..]);
+            console::urgent_printdoc! {"
+
+                {wait} Pairing code: {shown}
+                  Enter this code in Discord to link this PC with your account.
+
+                ",
+                wait = Glyph::Wait
+            }
+            .console()?;
+            console::headline(&shown).console()?;
+            self.set_connection_state(endpoint, ConnectionState::Pairing)
+                .await;
+            Ok(Outcome::Reply(ClientCmd::Pair { code }))
+        })
+    }
+
+    /// `paired` command: the pairing code was entered in Discord
+    fn paired_command<'a>(
+        &'a self,
+        msg: &'a ServerMessage,
+        endpoint: &'a Endpoint,
+    ) -> CommandFuture<'a> {
+        Box::pin(async move {
+            let ServerCmd::Paired { name } = &msg.cmd else {
+                return Ok(Outcome::Done);
+            };
+            match name {
+                Some(name) => console::println!("{} Paired with {}", Glyph::Ok, name),
+                None => console::println!("{} Paired with Discord", Glyph::Ok),
+            }
+            .console()?;
+            self.set_connection_state(endpoint, ConnectionState::Connected)
+                .await;
+            Ok(Outcome::Done)
+        })
+    }
+
+    /// `conflict` command: another device gets the invites now
+    fn conflict_command<'a>(
+        &'a self,
+        msg: &'a ServerMessage,
+        endpoint: &'a Endpoint,
+    ) -> CommandFuture<'a> {
+        Box::pin(async move {
+            let ServerCmd::Conflict { device } = &msg.cmd else {
+                return Ok(Outcome::Done);
+            };
+            console::urgent_printdoc! {"
+
+                {warn} This UUID is also in use on {device}, which gets the invites now.
+                  Type takeover to use this PC instead.
+
+                ",
+                warn = Glyph::Warn
+            }
+            .console()?;
+            console::headline("Used elsewhere").console()?;
+            endpoint.set_active_device(Some(device.clone()));
+            Ok(Outcome::Done)
+        })
+    }
+
+    /// `active` command: this device gets the invites again
+    fn active_command<'a>(
+        &'a self,
+        _: &'a ServerMessage,
+        endpoint: &'a Endpoint,
+    ) -> CommandFuture<'a> {
+        Box::pin(async move {
+            if endpoint.set_active_device(None).is_some() {
+                console::println!("{} This PC gets the invites again", Glyph::Ok).console()?;
+            }
+            Ok(Outcome::Done)
+        })
+    }
+
+    /// `relinked` command: the Discord link was moved to the new token
+    fn relinked_command<'a>(&'a self, _: &'a ServerMessage, _: &'a Endpoint) -> CommandFuture<'a> {
+        Box::pin(async move {
+            if self.relink.lock().await.take().is_some() {
+                console::println!("{} The Discord link was moved to the new token", Glyph::Ok)
+                    .console()?;
+                if let Err(err) = PendingRelink::clear() {
+                    console::eprintln!("{} {}", Glyph::Err, err).console()?;
+                }
+            }
+            Ok(Outcome::Done)
+        })
+    }
+
+    /// `request_diagnostics` command: asks the host to share the recent log
+    fn request_diagnostics_command<'a>(
+        &'a self,
+        msg: &'a ServerMessage,
+        endpoint: &'a Endpoint,
+    ) -> CommandFuture<'a> {
+        Box::pin(async move {
+            let &ServerCmd::RequestDiagnostics {
+                minutes,
+                ref reason,
+            } = &msg.cmd
+            else {
+                return Ok(Outcome::Done);
+            };
+            // Answered once the host decided
+            if self
+                .request_diagnostics(endpoint, msg.request_id(), minutes, reason.as_deref())
+                .console()?
+            {
+                return Ok(Outcome::Done);
+            }
+            Ok(Outcome::Reply(ClientCmd::Error {
+                code: ErrorStatus::DiagnosticsDeclined,
+            }))
+        })
+    }
+
+    /// `host_request` command: starts the game to take a session over
+    fn host_request_command<'a>(
+        &'a self,
+        msg: &'a ServerMessage,
+        endpoint: &'a Endpoint,
+    ) -> CommandFuture<'a> {
+        Box::pin(async move {
+            let &ServerCmd::HostRequest { game, ref from } = &msg.cmd else {
+                return Ok(Outcome::Done);
+            };
+            match self
+                .take_over_hosting(endpoint.index, game, from.as_deref())
+                .await
+            {
+                // The server hears back when the game is running
+                Ok(()) => Ok(Outcome::Done),
+                Err(err) => {
+                    console::eprintln!("{} {}", Glyph::Err, err).console()?;
+                    Ok(Outcome::Reply(ClientCmd::Error {
+                        code: ErrorStatus::InvalidApp,
+                    }))
+                }
+            }
+        })
+    }
+
+    /// `handoff_ready` command: another client took the session over
+    fn handoff_ready_command<'a>(
+        &'a self,
+        msg: &'a ServerMessage,
+        _: &'a Endpoint,
+    ) -> CommandFuture<'a> {
+        Box::pin(async move {
+            let ServerCmd::HandoffReady { host } = &msg.cmd else {
+                return Ok(Outcome::Done);
+            };
+            // Exit the application once the session is torn down
+            Ok(if self.finish_handoff(host.as_deref()).await {
+                Outcome::Exit
+            } else {
+                Outcome::Done
+            })
+        })
+    }
+
+    /// `handoff_failed` command: no other client took the session over
+    fn handoff_failed_command<'a>(
+        &'a self,
+        _: &'a ServerMessage,
+        _: &'a Endpoint,
+    ) -> CommandFuture<'a> {
+        Box::pin(async move {
+            self.cancel_handoff().console()?;
+            Ok(Outcome::Done)
+        })
+    }
+
+    /// `wake_host` command: this PC is running already, no agent has to wake it
+    fn wake_host_command<'a>(&'a self, _: &'a ServerMessage, _: &'a Endpoint) -> CommandFuture<'a> {
+        Box::pin(async { Ok(Outcome::Reply(ClientCmd::Awake)) })
+    }
+
+    /// `exit` command: exits the application
+    fn exit_command<'a>(&'a self, _: &'a ServerMessage, _: &'a Endpoint) -> CommandFuture<'a> {
+        Box::pin(async { Ok(Outcome::Exit) })
+    }
+
+    // Set up SteamStuff callbacks
+    pub async fn setup_steam_callbacks(self: &Arc<Self>) {
+        // The recording stands in for Steam
+        if replay::is_replaying() {
+            return;
+        }
+        // Register callbacks
+        let steam = self.steam.lock().await;
+        let handler = self.clone();
+        steam.set_on_remote_started(Box::new(move |invitee, guest_id| {
+            handler.on_steam_callback(Callback::Started { invitee, guest_id });
+        }));
+        let handler = self.clone();
+        steam.set_on_remote_stopped(Box::new(move |invitee, guest_id| {
+            handler.on_steam_callback(Callback::Stopped { invitee, guest_id });
+        }));
+        let handler = self.clone();
+        steam.set_on_remote_failed(Box::new(move |invitee, guest_id, result| {
+            handler.on_steam_callback(Callback::Failed {
+                invitee,
+                guest_id,
+                result,
+            });
+        }));
+        let handler = self.clone();
+        steam.set_on_remote_invited(Box::new(move |invitee, guest_id, connect_url: &str| {
+            handler.on_steam_callback(Callback::Invited {
+                invitee,
+                guest_id,
+                url: String::from(connect_url),
+            });
+        }));
+    }
+
+    /// Handles a callback of Steam (or of a recording with --replay-steam)
+    pub fn on_steam_callback(self: &Arc<Self>, callback: Callback) {
+        replay::record(&callback);
+        let handler = self.clone();
+        match callback {
+            Callback::Started { invitee, guest_id } => {
+                // Recorded before the spawn so a quick stop still finds it
+                self.connected.lock().unwrap().insert((guest_id, invitee));
+                tokio::spawn(async move {
+                    handler.on_remote_started(invitee, guest_id).await;
+                })
+            }
+            Callback::Stopped { invitee, guest_id } => tokio::spawn(async move {
+                handler.on_remote_stopped(invitee, guest_id).await;
+            }),
+            // Tell the waiting request why there is no invite link
+            Callback::Failed { result, .. } => tokio::spawn(async move {
+                let reason = JoinFailure::from_result(result);
+                handler.invite_tx.send(Err(reason)).await.unwrap();
+            }),
+            // Send the invite link
+            Callback::Invited { guest_id, url, .. } => tokio::spawn(async move {
+                handler.invite_tx.send(Ok((guest_id, url))).await.unwrap();
+            }),
+        };
+    }
+
+    /**
+     * Whether a guest is on the priority access list
+     * @param discord Discord user names or IDs known for the guest
+     */
+    pub fn is_priority(&self, steam_id: Option<u64>, discord: &[&str]) -> bool {
+        let steam_id = steam_id.map(|steam_id| steam_id.to_string());
+        self.priority.iter().any(|entry| {
+            steam_id.as_deref() == Some(entry.as_str()) || discord.contains(&entry.as_str())
+        })
+    }
+
+    /// Whether the guest has to wait for the host's approval
+    async fn needs_approval(&self, invitee: u64, guest_id: u64) -> bool {
+        if !self.approval.enabled {
+            return false;
+        }
+        let guest_data = self.guest_data.lock().await;
+        let name = guest_data.guest_map.get(&guest_id).map(String::as_str);
+        // Priority guests are let in without asking
+        if self.is_priority(Some(invitee), name.as_slice()) {
+            return false;
+        }
+        !self
+            .approval
+            .auto_approve
+            .iter()
+            .any(|entry| *entry == invitee.to_string() || Some(entry.as_str()) == name)
+    }
+
+    /**
+     * Holds the guest until the host approves, disconnecting the guest if denied
+     * @return Whether the guest was approved
+     */
+    async fn wait_for_approval(
+        &self,
+        invitee: u64,
+        guest_id: u64,
+        persona: Option<String>,
+    ) -> bool {
+        let (decision_tx, decision_rx) = oneshot::channel();
+        self.pending.lock().await.insert(
+            guest_id,
+            PendingGuest {
+                steam_id: invitee,
+                since: Instant::now(),
+                decision: decision_tx,
+            },
+        );
+        let guest_data = self.guest_data.lock().await;
+        let name = guest_data.guest_map.get(&guest_id).cloned();
+        let endpoint =
+            self.endpoint_label(guest_data.invites.get(&guest_id).and_then(|i| i.endpoint));
+        drop(guest_data);
+        let user_name = name.as_deref().unwrap_or("?");
+        let via = endpoint_field(&endpoint);
+        let steam_user = personas::steam_user(invitee, &persona);
+
+        let _ = console::urgent_println!(
+            "{} Approve Player?      : claimer={user_name}, guest_id={guest_id}, steam_id={steam_user}{via} (y/n)",
+            Glyph::Wait
+        );
+        let _ = console::headline("Guest waiting");
+        let _ = console::headline("A:Yes B:No");
+        self.notify_approval(guest_id, invitee, persona.clone(), ApprovalState::Pending)
+            .await;
+        self.emit(Event::GuestPending {
+            guest_id,
+            steam_id: invitee,
+            name: name.clone(),
+            persona: persona.clone(),
+            endpoint,
+        });
+
+        let approved =
+            match timeout(Duration::from_secs(self.approval.timeout_secs), decision_rx).await {
+                Ok(Ok(approved)) => approved,
+                // The guest left before the host answered
+                Ok(Err(_)) => return false,
+                // Nobody answered in time
+                Err(_) => {
+                    self.pending.lock().await.remove(&guest_id);
+                    audit::record(
+                        Entry::new(Action::Deny, &Origin::auto("approval timed out")).guest(
+                            Some(guest_id),
+                            Some(invitee),
+                            name.clone(),
+                        ),
+                    );
+                    false
+                }
+            };
+
+        if approved {
+            let _ = console::println!(
+                "-> Approve Player       : claimer={user_name}, guest_id={guest_id}, steam_id={steam_user}",
+            );
+            self.notify_approval(guest_id, invitee, persona, ApprovalState::Approved)
+                .await;
+        } else {
+            let _ = console::println!(
+                "-> Deny Player          : claimer={user_name}, guest_id={guest_id}, steam_id={steam_user}",
+            );
+            self.steam.lock().await.cancel_invite(invitee, guest_id);
+            self.notify_approval(guest_id, invitee, persona, ApprovalState::Denied)
+                .await;
+            self.emit(Event::GuestDenied {
+                guest_id,
+                steam_id: invitee,
+                name,
+            });
+        }
+        approved
+    }
+
+    /// Called when a guest joins the Remote Play session
+    async fn on_remote_started(&self, invitee: u64, guest_id: u64) {
+        self.steam_health.success(SteamOp::Launch);
+        // Links that reached their maximum number of uses no longer let anyone in
+        if self.guest_data.lock().await.exhausted.contains(&guest_id) {
+            let _ = console::println!(
+                "-> Reject Player        : guest_id={guest_id}, steam_id={invitee} (invite link exhausted)",
+            );
+            self.steam.lock().await.cancel_invite(invitee, guest_id);
+            audit::record(
+                Entry::new(Action::Reject, &Origin::auto("invite link exhausted")).guest(
+                    Some(guest_id),
+                    Some(invitee),
+                    None,
+                ),
+            );
+            return;
+        }
+
+        // Banned guests are disconnected right away
+        if let Some(ban) = self.bans.lock().await.find(invitee) {
+            let until = ban
+                .until
+                .map(|until| until.to_rfc3339())
+                .unwrap_or_else(|| "permanent".to_owned());
+            let _ = console::println!(
+                "-> Reject Player        : guest_id={guest_id}, steam_id={invitee} (banned until {until})",
+            );
+            audit::record(
+                Entry::new(Action::Reject, &Origin::auto("banned"))
+                    .guest(Some(guest_id), Some(invitee), ban.name.clone())
+                    .detail(format!("until={until}")),
+            );
+            self.steam.lock().await.cancel_invite(invitee, guest_id);
+            return;
+        }
+
+        // Look the name and avatar up after the checks above, so rejected guests are not kept waiting
+        let (persona, _) = tokio::join!(
+            self.personas.resolve(&self.steam, invitee),
+            self.avatar(invitee)
+        );
+
+        // Hold the guest until the host approves
+        if self.needs_approval(invitee, guest_id).await
+            && !self
+                .wait_for_approval(invitee, guest_id, persona.clone())
+                .await
+        {
+            return;
+        }
+
+        let mut guest_data = self.guest_data.lock().await;
+        guest_data.user_set.insert(guest_id);
+        guest_data.player_map.insert(guest_id, invitee);
+        let user_name = guest_data
+            .guest_map
+            .get(&guest_id)
+            .map_or_else(|| "?", |s| s);
+        let endpoint =
+            self.endpoint_label(guest_data.invites.get(&guest_id).and_then(|i| i.endpoint));
+        let via = endpoint_field(&endpoint);
+        let steam_user = personas::steam_user(invitee, &persona);
+        let _: Result<()> = 'tryblock: {
+            // Log the output
+            if let Err(err) = console::println!(
+                "-> Player Joined        : claimer={user_name}, guest_id={guest_id}, steam_id={steam_user}{via}",
+            ) {
+                break 'tryblock Err(err);
+            }
+
+            // Display the user list
+            if let Err(err) = guest_data.print_players(&self.personas) {
+                break 'tryblock Err(err);
+            }
+
+            Ok(())
+        };
+
+        self.emit(Event::GuestJoined {
+            guest_id,
+            steam_id: invitee,
+            name: guest_data.guest_map.get(&guest_id).cloned(),
+            persona,
+            endpoint,
+        });
+
+        // Count the use of the invite link
+        let exhausted = guest_data
+            .invites
+            .get_mut(&guest_id)
+            .is_some_and(Invite::record_use);
+        if exhausted {
+            let invite = guest_data.invites.remove(&guest_id);
+            let endpoint = match invite {
+                Some(invite) => {
+                    let _ = console::println!(
+                        "-> Invite Exhausted     : guest_id={guest_id}, uses={0}, invite_url={1}",
+                        invite.uses,
+                        invite.url
+                    );
+                    self.shortener.revoke(&invite.steam_url).await;
+                    invite.endpoint
+                }
+                None => None,
+            };
+            guest_data.exhausted.insert(guest_id);
+            audit::record(
+                Entry::new(Action::Revoke, &Origin::auto("invite link exhausted")).guest(
+                    Some(guest_id),
+                    None,
+                    guest_data.guest_map.get(&guest_id).cloned(),
+                ),
+            );
+            self.notify(
+                endpoint,
+                ClientCmd::Revoked {
+                    guest_id,
+                    reason: RevokeReason::Exhausted,
+                },
+            );
+            self.emit(Event::InviteRevoked {
+                guest_id,
+                reason: RevokeReason::Exhausted,
+            });
+        }
+    }
+
+    /// Called when a guest leaves the Remote Play session
+    async fn on_remote_stopped(&self, invitee: u64, guest_id: u64) {
+        // The stream of the guest never started
+        if !self.connected.lock().unwrap().remove(&(guest_id, invitee)) {
+            self.on_join_failed(invitee, guest_id, JoinFailure::Unreachable)
+                .await;
+            return;
+        }
+
+        // A pending guest left before the host answered
+        if self.pending.lock().await.remove(&guest_id).is_some() {
+            return;
+        }
+
+        let mut guest_data = self.guest_data.lock().await;
+        // Denied guests never joined
+        if !guest_data.user_set.remove(&guest_id) {
+            return;
+        }
+        guest_data.player_map.remove(&guest_id);
+        let user_name = guest_data
+            .guest_map
+            .get(&guest_id)
+            .map_or_else(|| "?", |s| s);
+        let steam_user = personas::steam_user(invitee, &self.personas.cached(invitee));
+        let _: Result<()> = 'tryblock: {
+            // Log the output
+            if let Err(err) = console::println!(
+                "-> Player Left          : claimer={user_name}, guest_id={guest_id}, steam_id={steam_user}",
+            ) {
+                break 'tryblock Err(err);
+            }
+
+            // Display the user list
+            if let Err(err) = guest_data.print_players(&self.personas) {
+                break 'tryblock Err(err);
+            }
+
+            Ok(())
+        };
+
+        self.emit(Event::GuestLeft {
+            guest_id,
+            steam_id: invitee,
+            name: guest_data.guest_map.get(&guest_id).cloned(),
+        });
+    }
+
+    /// Shows how to fix a failed join and relays the hint to the server that requested the invite
+    async fn on_join_failed(&self, invitee: u64, guest_id: u64, reason: JoinFailure) {
+        // Cancelled invites and revoked links are not failed joins
+        if invitee == 0 || !self.guest_data.lock().await.invites.contains_key(&guest_id) {
+            return;
+        }
+        let persona = self.personas.resolve(&self.steam, invitee).await;
+        let steam_user = personas::steam_user(invitee, &persona);
+        let guest_data = self.guest_data.lock().await;
+        let user_name = guest_data
+            .guest_map
+            .get(&guest_id)
+            .map_or_else(|| "?", |s| s);
+        let endpoint = guest_data
+            .invites
+            .get(&guest_id)
+            .and_then(|invite| invite.endpoint);
+        let _ = console::println!(
+            "-> Join Failed          : claimer={user_name}, guest_id={guest_id}, steam_id={steam_user}, reason={reason:?}",
+        );
+        let _ = console::println!(@level Level::Warn, "{} {}", Glyph::Warn, reason.hint());
+        drop(guest_data);
+
+        // Only the server that shared the link knows the guest (local links stay local)
+        if let Some(endpoint) = endpoint {
+            self.notify(
+                Some(endpoint),
+                ClientCmd::join_failed(guest_id, invitee, persona, reason),
+            );
+        }
+    }
+
+    // Start a task to periodically call SteamStuff_RunCallbacks
+    pub fn run_steam_callbacks(&self) -> task::JoinHandle<()> {
+        let steam_clone = self.steam.clone();
+        task::spawn(async move {
+            let mut interval = interval(Duration::from_millis(200));
+            loop {
+                interval.tick().await;
+                steam_clone.lock().await.run_callbacks();
+            }
+        })
+    }
+}
+
+/// Server commands of the client
+fn builtin_commands() -> Registry {
+    let commands: [(&'static str, CommandFn); 19] = [
+        ("message", Handler::message_command),
+        ("game", Handler::game_command),
+        ("link", Handler::link_command),
+        ("admit_next", Handler::admit_next_command),
+        ("revoke", Handler::revoke_command),
+        ("chat", Handler::chat_command),
+        ("compat", Handler::compat_command),
+        ("host_request", Handler::host_request_command),
+        ("handoff_ready", Handler::handoff_ready_command),
+        ("handoff_failed", Handler::handoff_failed_command),
+        ("request_diagnostics", Handler::request_diagnostics_command),
+        ("wake_host", Handler::wake_host_command),
+        ("backoff", Handler::backoff_command),
+        ("pair", Handler::pair_command),
+        ("paired", Handler::paired_command),
+        ("conflict", Handler::conflict_command),
+        ("active", Handler::active_command),
+        ("relinked", Handler::relinked_command),
+        ("exit", Handler::exit_command),
+    ];
+    let mut registry = Registry::default();
+    for (name, command) in commands {
+        registry.register(name, command);
+    }
+    registry
+}
+
+/// Server label as a field of a console line (empty without a label)
+fn endpoint_field(endpoint: &Option<String>) -> String {
+    match endpoint {
+        Some(endpoint) => format!(", endpoint={endpoint}"),
+        None => String::new(),
+    }
+}
+
+/**
+ * Sends a message to the server
+ * @param protocol Protocol version of the connection (messages it has no command for are skipped)
+ */
+pub async fn send_message(
+    msg: &ClientMessage,
+    protocol: Protocol,
+    write: &mut (impl SinkExt<Message, Error = WsError> + Unpin),
+) -> ClientResult<()> {
+    // Convert the message to JSON
+    let Some(text) = protocol
+        .encode(msg)
+        .context("Failed to serialize JSON message for the server")
+        .protocol()?
+    else {
+        return Ok(());
+    };
+    write
+        .send(Message::Text(text))
+        .await
+        .context("Failed to send message to the server")
+        .network()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use steam_stuff::{OnFailed, OnInvited, OnSession};
+
+    /// Steam without a running game (the tests only look at the handler state)
+    struct IdleSteam;
+
+    impl Steam for IdleSteam {
+        fn reinit(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn run_callbacks(&self) {}
+
+        fn get_running_game_id(&self) -> GameID {
+            GameID::new(0, 0, 0)
+        }
+
+        fn can_remote_play_together(&self, _game_id: u64) -> bool {
+            false
+        }
+
+        fn persona_name(&self, _steam_id: u64) -> Option<String> {
+            None
+        }
+
+        fn send_invite(&self, _invitee: u64, _game_id: u64) -> u64 {
+            0
+        }
+
+        fn cancel_invite(&self, _invitee: u64, _guest_id: u64) {}
+
+        fn is_session_active(&self) -> bool {
+            false
+        }
+
+        fn set_on_remote_invited(&self, _callback: OnInvited) {}
+
+        fn set_on_remote_started(&self, _callback: OnSession) {}
+
+        fn set_on_remote_stopped(&self, _callback: OnSession) {}
+
+        fn set_on_remote_failed(&self, _callback: OnFailed) {}
+    }
+
+    fn handler(config: &Config) -> Handler {
+        Handler::new(
+            Arc::new(Mutex::new(IdleSteam)),
+            config,
+            BanList::default(),
+            CompatList::default(),
+            None,
+            Vec::new(),
+        )
+    }
+
+    fn invite(max_uses: Option<u32>) -> Invite {
+        Invite {
+            game: 480,
+            url: "https://s.example.com/abc".to_owned(),
+            steam_url: "https://s.team/p/abc".to_owned(),
+            uses: 0,
+            max_uses,
+            kind: InviteKind::Player,
+            endpoint: None,
+            expires: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_invites_are_told_apart_from_other_errors() {
+        let handler = handler(&Config::default());
+        let origin = Origin::new(Source::Server, None);
+        let err = handler.revoke_invite(42, origin).await.unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(UnknownInvite(42))));
+    }
+
+    #[test]
+    fn links_are_exhausted_at_max_uses() {
+        let mut limited = invite(Some(2));
+        assert!(!limited.record_use());
+        assert!(limited.record_use());
+        assert_eq!(limited.uses, 2);
+
+        let mut unlimited = invite(None);
+        for _ in 0..100 {
+            assert!(!unlimited.record_use());
+        }
+    }
+
+    #[tokio::test]
+    async fn repeated_link_requests_share_a_link_within_the_cooldown() {
+        let handler = handler(&Config::default());
+        handler
+            .guest_data
+            .lock()
+            .await
+            .invites
+            .insert(1, invite(None));
+        let key = (480, InviteKind::Player, None, None);
+        handler
+            .recent_links
+            .lock()
+            .unwrap()
+            .insert(key, (Instant::now(), 1));
+
+        assert_eq!(
+            handler.recent_link(key).await,
+            Some((1, "https://s.example.com/abc".to_owned()))
+        );
+        // Another game, kind, maximum number of uses or lifetime gets its own link
+        for other in [
+            (440, InviteKind::Player, None, None),
+            (480, InviteKind::Spectator, None, None),
+            (480, InviteKind::Player, Some(1), None),
+            (480, InviteKind::Player, None, Some(600)),
+        ] {
+            assert_eq!(handler.recent_link(other).await, None);
+        }
+
+        // Used up links are not handed out again
+        handler.guest_data.lock().await.exhausted.insert(1);
+        assert_eq!(handler.recent_link(key).await, None);
+    }
+
+    #[tokio::test]
+    async fn no_cooldown_always_creates_a_new_link() {
+        let mut config = Config::default();
+        config.invites.cooldown_secs = 0;
+        let handler = handler(&config);
+        handler
+            .guest_data
+            .lock()
+            .await
+            .invites
+            .insert(1, invite(None));
+        let key = (480, InviteKind::Player, None, None);
+        handler
+            .recent_links
+            .lock()
+            .unwrap()
+            .insert(key, (Instant::now(), 1));
+
+        assert_eq!(handler.recent_link(key).await, None);
+    }
+
+    #[tokio::test]
+    async fn resume_reconnects_loops_that_are_busy() {
+        let endpoint = Arc::new(Endpoint::new(
+            0,
+            "ws://localhost/ws".to_owned(),
+            None,
+            Default::default(),
+        ));
+        let handler = Arc::new(Handler::new(
+            Arc::new(Mutex::new(IdleSteam)),
+            &Config::default(),
+            BanList::default(),
+            CompatList::default(),
+            None,
+            vec![endpoint.clone()],
+        ));
+
+        // No connection loop waits while the resume is handled
+        handler.on_resume().await;
+        assert!(endpoint.is_resuming());
+        timeout(Duration::from_secs(1), endpoint.reconnect_requested())
+            .await
+            .unwrap();
     }
 }
@@ -0,0 +1,43 @@
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    task::JoinHandle,
+    time::{interval, MissedTickBehavior},
+};
+
+use crate::{
+    console::{self, Glyph},
+    handlers::Handler,
+};
+
+/// How often the time-boxed invites are checked
+const EXPIRY_TICK: Duration = Duration::from_secs(1);
+
+/// Revokes the time-boxed invites when they expire
+pub fn spawn_expiry(handler: Arc<Handler>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = interval(EXPIRY_TICK);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            handler.revoke_expired().await;
+        }
+    })
+}
+
+/// Checks that Steam still accepts the invite links
+/// Links stop working without notice when the Remote Play session ends, so they are replaced and posted again
+pub fn spawn_invite_checker(handler: Arc<Handler>, secs: u64) -> Option<JoinHandle<()>> {
+    if secs == 0 {
+        return None;
+    }
+    Some(tokio::spawn(async move {
+        let mut ticker = interval(Duration::from_secs(secs));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            if let Err(err) = handler.check_invites().await {
+                let _ = console::eprintln!("{} {}", Glyph::Err, err);
+            }
+        }
+    }))
+}
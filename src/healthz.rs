@@ -0,0 +1,123 @@
+use anyhow::{Context as _, Result};
+use serde_json::json;
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+    time::timeout,
+};
+
+use crate::{
+    config::HealthConfig,
+    handlers::{ConnectionState, Handler},
+    ipc, logger,
+};
+
+/// Largest request accepted from a probe
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+/// How long the handler may take to answer before the client counts as wedged
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/**
+ * Serves the health endpoint for container orchestrators
+ * GET /livez: the handler responds (for restarting a wedged client)
+ * GET /healthz: a server is connected (for the readiness checks)
+ * @return None if disabled (enabled by default in container mode)
+ */
+pub async fn serve(
+    handler: Arc<Handler>,
+    config: &HealthConfig,
+    container: bool,
+    request_timeout: Duration,
+) -> Result<Option<JoinHandle<()>>> {
+    if !config.enabled.unwrap_or(container) {
+        return Ok(None);
+    }
+    let listener = TcpListener::bind(("0.0.0.0", config.port))
+        .await
+        .with_context(|| {
+            format!(
+                "Unable to serve the health endpoint on port {}",
+                config.port
+            )
+        })?;
+    logger::write(
+        logger::Level::Info,
+        format_args!("Health endpoint: http://0.0.0.0:{}/healthz", config.port),
+    );
+    Ok(Some(tokio::spawn(async move {
+        let mut wait = Duration::ZERO;
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    ipc::accept_failed("health endpoint", err, &mut wait).await;
+                    continue;
+                }
+            };
+            wait = Duration::ZERO;
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let _ = handle_request(stream, &handler, request_timeout).await;
+            });
+        }
+    })))
+}
+
+/// Answers a single probe
+async fn handle_request(
+    mut stream: TcpStream,
+    handler: &Handler,
+    request_timeout: Duration,
+) -> Result<()> {
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let n = timeout(request_timeout, stream.read(&mut buf)).await??;
+        if n == 0 || request.len() + n > MAX_REQUEST_SIZE {
+            return Ok(());
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut parts = request.split_whitespace();
+    let (method, path) = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or("/"),
+    );
+
+    let (status, body) = match (method, path) {
+        ("GET", "/livez" | "/healthz") => match timeout(PROBE_TIMEOUT, handler.status()).await {
+            Err(_) => (
+                "503 Service Unavailable",
+                json!({ "status": "wedged" }).to_string(),
+            ),
+            Ok(report) => {
+                let connected = matches!(
+                    report.connection,
+                    ConnectionState::Connected | ConnectionState::Pairing
+                );
+                let body = json!({
+                    "status": if connected { "ok" } else { "disconnected" },
+                    "connection": report.connection,
+                    "paused": report.paused,
+                    "players": report.guests.len(),
+                })
+                .to_string();
+                if connected || path == "/livez" {
+                    ("200 OK", body)
+                } else {
+                    ("503 Service Unavailable", body)
+                }
+            }
+        },
+        _ => ("404 Not Found", json!({ "error": "Not found" }).to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    Ok(())
+}
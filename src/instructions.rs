@@ -0,0 +1,87 @@
+use crate::{
+    config::{ControllerNeed, InstructionsConfig, Language},
+    models::{ControllerSupport, StoreInfo},
+};
+
+/// Built-in sentences of a language
+struct Phrases {
+    /// How to join ({game} is replaced)
+    join: &'static str,
+    required: &'static str,
+    recommended: &'static str,
+    optional: &'static str,
+    /// Name of a game without a known name
+    unknown_game: &'static str,
+    /// Between the sentences
+    separator: &'static str,
+}
+
+const EN: Phrases = Phrases {
+    join: "Open the link with the Steam client or the free Steam Link app (PC, Mac, Android, iOS or smart TV). You do not need to own {game}.",
+    required: "A controller is required for {game}.",
+    recommended: "A controller is recommended for {game}, but keyboard and mouse work too.",
+    optional: "Keyboard and mouse or a controller both work for {game}.",
+    unknown_game: "the game",
+    separator: " ",
+};
+
+const JA: Phrases = Phrases {
+    join: "招待リンクを Steam クライアントか無料の Steam Link アプリ（PC・Mac・Android・iOS・スマートテレビ）で開いてください。{game} を持っていなくても参加できます。",
+    required: "{game} はコントローラーが必要です。",
+    recommended: "{game} はコントローラー推奨です（キーボードとマウスでも遊べます）。",
+    optional: "{game} はキーボードとマウスでもコントローラーでも遊べます。",
+    unknown_game: "このゲーム",
+    separator: "",
+};
+
+/**
+ * Writes the join instructions for the guests of a game
+ * @param compat_name Name of the game in the compatibility list
+ * @param store Steam store metadata (for the games without a controller setting)
+ * @return The instructions (None if disabled)
+ */
+pub fn build(
+    config: &InstructionsConfig,
+    app_id: u32,
+    compat_name: Option<&str>,
+    store: Option<StoreInfo>,
+) -> Option<String> {
+    if !config.enabled {
+        return None;
+    }
+    let phrases = match config.language {
+        Language::En => &EN,
+        Language::Ja => &JA,
+    };
+    let game = config.games.get(&app_id.to_string());
+    let name = game
+        .and_then(|game| game.name.as_deref())
+        .or(compat_name)
+        .unwrap_or(phrases.unknown_game);
+    let detected = store.and_then(|store| match store.controller {
+        ControllerSupport::Full => Some(ControllerNeed::Recommended),
+        ControllerSupport::Partial => Some(ControllerNeed::Optional),
+        ControllerSupport::None => None,
+    });
+    let controller = match game.and_then(|game| game.controller).or(detected) {
+        Some(ControllerNeed::Required) => phrases.required,
+        Some(ControllerNeed::Recommended) => phrases.recommended,
+        Some(ControllerNeed::Optional) => phrases.optional,
+        None => "",
+    };
+    let note = game.and_then(|game| game.note.as_deref()).unwrap_or("");
+
+    let text = match &config.template {
+        Some(template) => template
+            .replace("{join}", phrases.join)
+            .replace("{controller}", controller)
+            .replace("{note}", note),
+        None => [phrases.join, controller, note]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(phrases.separator),
+    };
+    // The game name last, so a name with braces is kept as is
+    Some(text.replace("{game}", name).trim().to_owned())
+}
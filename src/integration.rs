@@ -0,0 +1,211 @@
+//! Stream Deck / OBS integration (`integration` feature)
+//!
+//! # OBS
+//!
+//! Connects to the [obs-websocket](https://github.com/obsproject/obs-websocket) v5 server
+//! built into OBS 28+ and switches scenes when guests join or leave:
+//!
+//! ```toml
+//! [obs]
+//! enabled = true
+//! url = "ws://127.0.0.1:4455"
+//! password = "secret"          # omit if authentication is disabled
+//! join_scene = "Playing with guests"
+//! leave_scene = "Solo"         # switched to when the last guest leaves
+//! ```
+//!
+//! # Stream Deck
+//!
+//! Every action talks to the running instance through the local control API, so
+//! buttons are plain "System > Open" actions running the executable:
+//!
+//! | Button        | Command                                  |
+//! |---------------|------------------------------------------|
+//! | New invite    | `remoteplay-inviter invite new`          |
+//! | Pause invites | `remoteplay-inviter pause`               |
+//! | Resume        | `remoteplay-inviter resume`              |
+//! | Show status   | `remoteplay-inviter status`              |
+//!
+//! Plugins able to open a Unix socket can also send JSON-RPC requests directly
+//! (see [`crate::ipc`]), e.g. `{"jsonrpc": "2.0", "id": 1, "method": "guest.kick",
+//! "params": {"guest_id": 1}}`.
+
+use anyhow::{bail, Context as _, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use futures::SinkExt;
+use futures_util::stream::StreamExt;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::{collections::HashSet, sync::Arc};
+use tokio::{
+    sync::broadcast::{error::RecvError, Receiver},
+    task::JoinHandle,
+    time::{self, Duration},
+};
+use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+
+use crate::{
+    config::ObsConfig,
+    console::{self, Glyph},
+    events::Event,
+    handlers::Handler,
+};
+
+/// obs-websocket RPC version
+const RPC_VERSION: u64 = 1;
+
+/// obs-websocket opcodes
+mod op {
+    pub const HELLO: u64 = 0;
+    pub const IDENTIFY: u64 = 1;
+    pub const IDENTIFIED: u64 = 2;
+    pub const REQUEST: u64 = 6;
+}
+
+/// Starts the OBS scene switcher
+pub fn spawn_obs(handler: &Arc<Handler>, config: ObsConfig) -> Option<JoinHandle<()>> {
+    if !config.enabled {
+        return None;
+    }
+
+    let mut events = handler.subscribe();
+    Some(tokio::spawn(async move {
+        // Guests currently playing
+        let mut guests = HashSet::new();
+        loop {
+            match run_obs(&config, &mut events, &mut guests).await {
+                Ok(()) => break,
+                Err(err) => {
+                    let _ = console::eprintln!("{} OBS: {:#}", Glyph::Err, err);
+                }
+            }
+            // Retry later (OBS may not be running yet)
+            time::sleep(Duration::from_secs(30)).await;
+        }
+    }))
+}
+
+/// Connects to OBS and switches scenes until the connection is lost
+/// @return Ok(()) when the event bus is closed
+async fn run_obs(
+    config: &ObsConfig,
+    events: &mut Receiver<Event>,
+    guests: &mut HashSet<u64>,
+) -> Result<()> {
+    let (ws_stream, _) = connect_async(&config.url)
+        .await
+        .with_context(|| format!("Failed to connect to {}", config.url))?;
+    let (mut write, mut read) = ws_stream.split();
+
+    // Hello -> Identify -> Identified
+    let hello = read_op(&mut read, op::HELLO).await?;
+    let authentication = match hello.get("authentication") {
+        Some(auth) => {
+            let Some(password) = &config.password else {
+                bail!("OBS requires a password (set obs.password)");
+            };
+            let challenge = auth["challenge"].as_str().unwrap_or_default();
+            let salt = auth["salt"].as_str().unwrap_or_default();
+            Some(auth_response(password, salt, challenge))
+        }
+        None => None,
+    };
+    let identify = json!({
+        "op": op::IDENTIFY,
+        "d": {
+            "rpcVersion": RPC_VERSION,
+            "authentication": authentication,
+            // Scene switching only needs requests, no OBS events
+            "eventSubscriptions": 0,
+        },
+    });
+    write
+        .send(Message::Text(identify.to_string()))
+        .await
+        .context("Failed to identify")?;
+    read_op(&mut read, op::IDENTIFIED)
+        .await
+        .context("Authentication failed")?;
+    console::println!("{} OBS: Connected to {}", Glyph::Ok, config.url)?;
+
+    let mut request_id = 0u64;
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return Ok(()),
+                };
+                let scene = match event {
+                    Event::GuestJoined { guest_id, .. } => {
+                        guests.insert(guest_id);
+                        config.join_scene.as_ref()
+                    }
+                    Event::GuestLeft { guest_id, .. } => {
+                        guests.remove(&guest_id);
+                        config.leave_scene.as_ref().filter(|_| guests.is_empty())
+                    }
+                    _ => None,
+                };
+                let Some(scene) = scene else {
+                    continue;
+                };
+
+                request_id += 1;
+                let request = json!({
+                    "op": op::REQUEST,
+                    "d": {
+                        "requestType": "SetCurrentProgramScene",
+                        "requestId": request_id.to_string(),
+                        "requestData": { "sceneName": scene },
+                    },
+                });
+                write
+                    .send(Message::Text(request.to_string()))
+                    .await
+                    .context("Failed to switch scene")?;
+                console::println!("-> OBS Scene           : {scene}")?;
+            }
+            message = read.next() => {
+                // Responses are ignored, only watch for the connection closing
+                match message {
+                    Some(Ok(Message::Close(_))) | None => bail!("Connection closed"),
+                    Some(Err(err)) => return Err(err).context("Connection lost"),
+                    Some(Ok(_)) => (),
+                }
+            }
+        }
+    }
+}
+
+/// Reads messages until one with the given opcode arrives
+/// @return Data of the message
+async fn read_op(
+    read: &mut (impl StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin),
+    expected: u64,
+) -> Result<Value> {
+    while let Some(message) = read.next().await {
+        match message.context("Connection lost")? {
+            Message::Text(text) => {
+                let mut msg: Value = serde_json::from_str(&text).context("Invalid message")?;
+                if msg["op"].as_u64() == Some(expected) {
+                    return Ok(msg["d"].take());
+                }
+            }
+            Message::Close(frame) => bail!(
+                "Connection closed{}",
+                frame.map(|f| format!(": {}", f.reason)).unwrap_or_default()
+            ),
+            _ => (),
+        }
+    }
+    bail!("Connection closed")
+}
+
+/// Computes the obs-websocket authentication string
+/// base64(sha256(base64(sha256(password + salt)) + challenge))
+fn auth_response(password: &str, salt: &str, challenge: &str) -> String {
+    let secret = STANDARD.encode(Sha256::digest(format!("{password}{salt}")));
+    STANDARD.encode(Sha256::digest(format!("{secret}{challenge}")))
+}
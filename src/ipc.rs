@@ -0,0 +1,1158 @@
+//! Local control API
+//!
+//! A running instance listens on a local socket (a named pipe on Windows, see [`socket_path`]) and speaks
+//! [JSON-RPC 2.0](https://www.jsonrpc.org/specification), one JSON object per line.
+//!
+//! Methods:
+//! - `status` → [`StatusReport`]
+//! - `invite.create` `{"game"?: u32, "max_uses"?: u32, "ttl_secs"?: u64}` → `{"guest_id", "game", "url"}`
+//!   (defaults to the running game and the configured maximum number of uses, `ttl_secs` revokes the link after a while)
+//! - `invite.revoke` `{"guest_id": u64}` → `true`
+//! - `guest.kick` `{"guest_id": u64, "ban"?: "30m" | "12h" | "7d" | "permanent"}` → `true`
+//! - `guest.bans` → list of `{"steam_id", "name", "since", "until"}`
+//! - `guest.unban` `{"steam_id": u64}` → `true`
+//! - `guest.approve` / `guest.deny` `{"guest_id": u64}` → `true` (guests waiting for approval)
+//! - `pause` `{"paused": bool}` → `{"paused"}` (paused hosts refuse new invites)
+//! - `session.handoff` → `true` (asks the server to move the session to another client)
+//! - `chat.send` `{"text": string}` → `true` (a reply to the last chat message)
+//! - `events.subscribe` → `true`, then every [`Event`] is pushed as a
+//!   `{"jsonrpc": "2.0", "method": "event", "params": Event}` notification
+//!
+//! Methods that change something accept an optional `"reason"` recorded in the audit log.
+//!
+//! Errors use the standard codes (-32700 parse error, -32600 invalid request,
+//! -32601 method not found, -32602 invalid params) and -32000 for failures
+//! reported by the client itself.
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{path::PathBuf, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncBufReadExt as _, AsyncRead, AsyncWrite, AsyncWriteExt as _, BufReader},
+    sync::mpsc,
+    task::{JoinHandle, JoinSet},
+};
+
+use crate::{
+    audit::{Origin, Source},
+    bans::{Ban, BanDuration},
+    commands::CommandMetrics,
+    console::{self, Glyph},
+    events::Event,
+    handlers::{ConnectionState, Handler},
+    load,
+    logger::{self, Level},
+    models::{HostLoad, InviteKind, NatMapping},
+    nat, personas,
+    transport::IpFamily,
+};
+
+/// JSON-RPC version
+const JSONRPC: &str = "2.0";
+
+/// First wait after a failed accept
+const ACCEPT_RETRY: Duration = Duration::from_millis(100);
+/// Longest wait between two failed accepts
+const MAX_ACCEPT_RETRY: Duration = Duration::from_secs(10);
+
+/// Invalid JSON was received
+const PARSE_ERROR: i64 = -32700;
+/// The JSON is not a valid request object
+const INVALID_REQUEST: i64 = -32600;
+/// The method does not exist
+const METHOD_NOT_FOUND: i64 = -32601;
+/// Invalid method parameters
+const INVALID_PARAMS: i64 = -32602;
+/// The request failed in the client
+const SERVER_ERROR: i64 = -32000;
+
+/// JSON-RPC request (a notification when the ID is missing)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcRequest {
+    /// Protocol version ("2.0")
+    pub jsonrpc: String,
+    /// Request ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    /// Method name
+    pub method: String,
+    /// Method parameters
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub params: Value,
+}
+
+/// JSON-RPC response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcResponse {
+    /// Protocol version ("2.0")
+    pub jsonrpc: String,
+    /// ID of the request
+    pub id: Value,
+    /// Result on success
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    /// Error on failure
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+/// JSON-RPC error object
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcError {
+    /// Error code
+    pub code: i64,
+    /// Error message
+    pub message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Status of the running instance
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatusReport {
+    /// Client version
+    pub version: String,
+    /// Connection state
+    pub connection: ConnectionState,
+    /// Host of the server endpoint (comma separated when there are several)
+    pub endpoint: String,
+    /// Connection of each server endpoint
+    #[serde(default)]
+    pub endpoints: Vec<EndpointStatus>,
+    /// Whether new invites are refused
+    pub paused: bool,
+    /// Guests waiting for approval
+    #[serde(default)]
+    pub pending: Vec<GuestStatus>,
+    /// Invite links created in this session
+    pub invites: Vec<InviteStatus>,
+    /// Guests currently playing
+    pub guests: Vec<GuestStatus>,
+    /// NAT of the host found by the STUN probe
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<NatMapping>,
+    /// CPU, GPU and video encoder load while streaming
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load: Option<HostLoad>,
+    /// Server commands that ran in this session
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commands: Vec<CommandMetrics>,
+}
+
+/// Server endpoint entry of the status report
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EndpointStatus {
+    /// Host of the server
+    pub host: String,
+    /// Name given by the user
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Connection state
+    pub connection: ConnectionState,
+    /// Number of active invite links the server requested
+    #[serde(default)]
+    pub invites: usize,
+    /// Device that gets the invites instead (the token is in use on another PC)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_device: Option<String>,
+    /// IP version of the connection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family: Option<IpFamily>,
+}
+
+/// Invite link entry of the status report
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InviteStatus {
+    /// Guest ID
+    pub guest_id: u64,
+    /// Game ID
+    pub game: u32,
+    /// Invite URL
+    pub url: String,
+    /// Discord user who requested the link
+    pub claimer: Option<String>,
+    /// Number of guests who joined with the link
+    #[serde(default)]
+    pub uses: u32,
+    /// Revoke the link after this many joins
+    #[serde(default)]
+    pub max_uses: Option<u32>,
+    /// Kind of invite
+    #[serde(default)]
+    pub kind: InviteKind,
+    /// Label of the server that requested the link
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    /// When the time-boxed link is revoked
+    #[serde(default)]
+    pub expires: Option<DateTime<Utc>>,
+}
+
+/// Guest entry of the status report
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GuestStatus {
+    /// Guest ID
+    pub guest_id: u64,
+    /// Discord user associated with the guest
+    pub name: Option<String>,
+    /// Steam name of the guest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persona: Option<String>,
+    /// Cached avatar image of the guest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<PathBuf>,
+}
+
+/// Created invite link
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatedInvite {
+    /// Guest ID
+    pub guest_id: u64,
+    /// Game ID
+    pub game: u32,
+    /// Invite URL
+    pub url: String,
+}
+
+/// Parameters of `invite.create`
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct CreateInviteParams {
+    /// Game ID (defaults to the running game)
+    game: Option<u32>,
+    /// Revoke the link after this many joins
+    max_uses: Option<u32>,
+    /// Kind of invite
+    #[serde(default)]
+    kind: InviteKind,
+    /// Revoke the link after this many seconds
+    ttl_secs: Option<u64>,
+}
+
+/// Parameters of `invite.revoke` and `guest.kick`
+#[derive(Deserialize)]
+struct GuestParams {
+    /// Guest ID
+    guest_id: u64,
+    /// Reason recorded in the audit log
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Parameters of `guest.kick`
+#[derive(Deserialize)]
+struct KickParams {
+    /// Guest ID
+    guest_id: u64,
+    /// Ban the guest for this long (defaults to the config)
+    #[serde(default)]
+    ban: Option<BanDuration>,
+    /// Reason recorded in the audit log
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Parameters of `guest.unban`
+#[derive(Deserialize)]
+struct UnbanParams {
+    /// Steam ID of the guest
+    steam_id: u64,
+    /// Reason recorded in the audit log
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Parameters of `pause`
+#[derive(Deserialize)]
+struct PauseParams {
+    /// Whether to refuse new invites
+    paused: bool,
+    /// Reason recorded in the audit log
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Parameters of `session.handoff`
+#[derive(Deserialize)]
+struct HandoffParams {
+    /// Reason recorded in the audit log
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Parameters of `chat.send`
+#[derive(Deserialize)]
+struct ChatParams {
+    /// Message text
+    text: String,
+}
+
+/// Path of the control socket
+#[cfg(not(windows))]
+pub fn socket_path() -> PathBuf {
+    // Prefer the per-user runtime directory, fall back to the temp directory
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("remoteplay-inviter.sock"),
+        None => {
+            let user = std::env::var("USER").unwrap_or_default();
+            std::env::temp_dir().join(format!("remoteplay-inviter-{user}.sock"))
+        }
+    }
+}
+
+/// Name of the control pipe (one per user)
+#[cfg(windows)]
+pub fn socket_path() -> PathBuf {
+    let user = std::env::var("USERNAME").unwrap_or_default();
+    PathBuf::from(format!(r"\\.\pipe\remoteplay-inviter-{user}"))
+}
+
+/// Starts serving the local control API
+#[cfg(unix)]
+pub fn serve(handler: Arc<Handler>) -> Result<Option<JoinHandle<()>>> {
+    use std::os::unix::net::UnixStream;
+    use tokio::net::UnixListener;
+
+    let path = socket_path();
+    if path.exists() {
+        // Another instance is already listening
+        if UnixStream::connect(&path).is_ok() {
+            bail!(
+                "Another instance is already running (control socket {:?})",
+                path
+            );
+        }
+        // Remove the stale socket left by a crashed instance
+        std::fs::remove_file(&path)
+            .with_context(|| format!("Unable to remove stale control socket: {:?}", path))?;
+    }
+    let listener = UnixListener::bind(&path)
+        .with_context(|| format!("Unable to create control socket: {:?}", path))?;
+
+    Ok(Some(tokio::spawn(async move {
+        let mut wait = Duration::ZERO;
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    accept_failed("control socket", err, &mut wait).await;
+                    continue;
+                }
+            };
+            wait = Duration::ZERO;
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let _ = handle_client(stream, handler).await;
+            });
+        }
+    })))
+}
+
+/**
+ * Logs a failed accept and waits longer each time (wait is reset to zero after an accepted connection),
+ * so a lasting error such as running out of file descriptors does not spin
+ */
+pub async fn accept_failed(listener: &str, err: std::io::Error, wait: &mut Duration) {
+    *wait = (*wait * 2).clamp(ACCEPT_RETRY, MAX_ACCEPT_RETRY);
+    logger::write(
+        logger::Level::Warn,
+        format_args!(
+            "Unable to accept a connection on the {listener}: {err} (retrying in {:.1} seconds)",
+            wait.as_secs_f32()
+        ),
+    );
+    tokio::time::sleep(*wait).await;
+}
+
+/// Starts serving the local control API
+#[cfg(windows)]
+pub fn serve(handler: Arc<Handler>) -> Result<Option<JoinHandle<()>>> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let path = socket_path();
+    let sddl = pipe::owner_only_sddl().context("Unable to look up the current user")?;
+    let mut options = ServerOptions::new();
+    options
+        .reject_remote_clients(true)
+        .first_pipe_instance(true);
+    // The pipe is gone with the instance that created it, so there is nothing stale to remove
+    let server = pipe::create(&options, &path, &sddl).map_err(|err| {
+        match err.raw_os_error().map(|code| code as u32) {
+            Some(windows_sys::Win32::Foundation::ERROR_ACCESS_DENIED) => {
+                anyhow!(
+                    "Another instance is already running (control pipe {:?})",
+                    path
+                )
+            }
+            _ => anyhow!(err).context(format!("Unable to create control pipe: {:?}", path)),
+        }
+    })?;
+    options.first_pipe_instance(false);
+
+    Ok(Some(tokio::spawn(async move {
+        let mut wait = Duration::ZERO;
+        let mut server = Some(server);
+        loop {
+            // Each client connects to a new instance of the pipe (retried while it cannot be created)
+            let stream = match server.take() {
+                Some(stream) => stream,
+                None => match pipe::create(&options, &path, &sddl) {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        accept_failed("control pipe", err, &mut wait).await;
+                        continue;
+                    }
+                },
+            };
+            if let Err(err) = stream.connect().await {
+                accept_failed("control pipe", err, &mut wait).await;
+                continue;
+            }
+            wait = Duration::ZERO;
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let _ = handle_client(stream, handler).await;
+            });
+        }
+    })))
+}
+
+/// Starts serving the local control API
+#[cfg(not(any(unix, windows)))]
+pub fn serve(_handler: Arc<Handler>) -> Result<Option<JoinHandle<()>>> {
+    // The control API is not available on this platform yet
+    Ok(None)
+}
+
+/// Removes the control socket on exit (the next start would remove it as stale otherwise)
+pub fn remove_socket() {
+    #[cfg(not(windows))]
+    let _ = std::fs::remove_file(socket_path());
+}
+
+/// Named pipes only the current user can open
+#[cfg(windows)]
+mod pipe {
+    use std::{io, path::Path, ptr};
+    use tokio::net::windows::named_pipe::{NamedPipeServer, ServerOptions};
+    use windows_sys::Win32::{
+        Foundation::{CloseHandle, LocalFree, HANDLE},
+        Security::{
+            Authorization::{
+                ConvertSidToStringSidW, ConvertStringSecurityDescriptorToSecurityDescriptorW,
+                SDDL_REVISION_1,
+            },
+            GetTokenInformation, TokenUser, PSECURITY_DESCRIPTOR, SECURITY_ATTRIBUTES, TOKEN_QUERY,
+            TOKEN_USER,
+        },
+        System::Threading::{GetCurrentProcess, OpenProcessToken},
+    };
+
+    /**
+     * Security descriptor allowing only the user running this process (the default one lets everyone read)
+     * @return SDDL text as a null-terminated UTF-16 string
+     */
+    pub fn owner_only_sddl() -> io::Result<Vec<u16>> {
+        let sid = current_user_sid()?;
+        Ok(format!("D:P(A;;GA;;;{sid})")
+            .encode_utf16()
+            .chain([0])
+            .collect())
+    }
+
+    /// SID of the user running this process (S-1-5-21-...)
+    fn current_user_sid() -> io::Result<String> {
+        let mut token: HANDLE = 0;
+        if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut size = 0;
+        unsafe { GetTokenInformation(token, TokenUser, ptr::null_mut(), 0, &mut size) };
+        // Aligned for the TOKEN_USER at its start
+        let mut buffer = vec![0u64; (size as usize).div_ceil(8)];
+        let result = if unsafe {
+            GetTokenInformation(
+                token,
+                TokenUser,
+                buffer.as_mut_ptr().cast(),
+                size,
+                &mut size,
+            )
+        } == 0
+        {
+            Err(io::Error::last_os_error())
+        } else {
+            let user = unsafe { &*buffer.as_ptr().cast::<TOKEN_USER>() };
+            let mut text = ptr::null_mut();
+            if unsafe { ConvertSidToStringSidW(user.User.Sid, &mut text) } == 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                let len = (0..).take_while(|&i| unsafe { *text.add(i) } != 0).count();
+                let sid =
+                    String::from_utf16_lossy(unsafe { std::slice::from_raw_parts(text, len) });
+                unsafe { LocalFree(text as _) };
+                Ok(sid)
+            }
+        };
+        unsafe { CloseHandle(token) };
+        result
+    }
+
+    /// Creates an instance of the pipe with the security descriptor
+    pub fn create(
+        options: &ServerOptions,
+        path: &Path,
+        sddl: &[u16],
+    ) -> io::Result<NamedPipeServer> {
+        let mut descriptor: PSECURITY_DESCRIPTOR = ptr::null_mut();
+        if unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                sddl.as_ptr(),
+                SDDL_REVISION_1,
+                &mut descriptor,
+                ptr::null_mut(),
+            )
+        } == 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        let mut attributes = SECURITY_ATTRIBUTES {
+            nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: descriptor,
+            bInheritHandle: 0,
+        };
+        let result = unsafe {
+            options.create_with_security_attributes_raw(
+                path,
+                (&mut attributes as *mut SECURITY_ATTRIBUTES).cast(),
+            )
+        };
+        unsafe { LocalFree(descriptor as _) };
+        result
+    }
+}
+
+/// Answers the requests of a connected client (one JSON object per line)
+async fn handle_client(
+    stream: impl AsyncRead + AsyncWrite + Send + 'static,
+    handler: Arc<Handler>,
+) -> Result<()> {
+    let (read, mut write) = tokio::io::split(stream);
+    // Aborted when dropped, so the writer and the subscriptions stop however this returns
+    let mut tasks = JoinSet::new();
+
+    // Responses and event notifications share the writer
+    let (out_tx, mut out_rx) = mpsc::channel::<String>(64);
+    tasks.spawn(async move {
+        while let Some(mut text) = out_rx.recv().await {
+            text.push('\n');
+            if write.write_all(text.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut lines = BufReader::new(read).lines();
+    while let Some(line) = lines.next_line().await? {
+        let response = match serde_json::from_str::<Value>(&line) {
+            Err(err) => Some(error_response(
+                Value::Null,
+                RpcError::new(PARSE_ERROR, format!("Parse error: {err}")),
+            )),
+            Ok(value) => match serde_json::from_value::<RpcRequest>(value) {
+                Err(err) => Some(error_response(
+                    Value::Null,
+                    RpcError::new(INVALID_REQUEST, format!("Invalid request: {err}")),
+                )),
+                Ok(request) if request.jsonrpc != JSONRPC => Some(error_response(
+                    request.id.unwrap_or(Value::Null),
+                    RpcError::new(INVALID_REQUEST, "Invalid request: jsonrpc must be \"2.0\""),
+                )),
+                Ok(request) => {
+                    let result = dispatch(&request, &handler, &out_tx, &mut tasks).await;
+                    // Notifications are not answered
+                    request.id.map(|id| match result {
+                        Ok(result) => RpcResponse {
+                            jsonrpc: JSONRPC.to_owned(),
+                            id,
+                            result: Some(result),
+                            error: None,
+                        },
+                        Err(error) => error_response(id, error),
+                    })
+                }
+            },
+        };
+        if let Some(response) = response {
+            if out_tx
+                .send(serde_json::to_string(&response)?)
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Creates an error response
+fn error_response(id: Value, error: RpcError) -> RpcResponse {
+    RpcResponse {
+        jsonrpc: JSONRPC.to_owned(),
+        id,
+        result: None,
+        error: Some(error),
+    }
+}
+
+/// Parses the method parameters
+fn params<T: for<'de> Deserialize<'de>>(params: &Value) -> Result<T, RpcError> {
+    // Omitted parameters are treated as an empty object
+    let params = if params.is_null() {
+        json!({})
+    } else {
+        params.clone()
+    };
+    serde_json::from_value(params)
+        .map_err(|err| RpcError::new(INVALID_PARAMS, format!("Invalid params: {err}")))
+}
+
+/// Executes a method on the running instance
+async fn dispatch(
+    request: &RpcRequest,
+    handler: &Arc<Handler>,
+    out_tx: &mpsc::Sender<String>,
+    tasks: &mut JoinSet<()>,
+) -> Result<Value, RpcError> {
+    let failed = |err: anyhow::Error| RpcError::new(SERVER_ERROR, err.to_string());
+    match request.method.as_str() {
+        "status" => Ok(json!(handler.status().await)),
+        "invite.create" => {
+            let CreateInviteParams {
+                game,
+                max_uses,
+                kind,
+                ttl_secs,
+            } = params(&request.params)?;
+            let ttl = ttl_secs.map(Duration::from_secs);
+            let (guest_id, game, url) = handler
+                .create_local_invite("local", game, max_uses, kind, ttl)
+                .await
+                .map_err(failed)?;
+            Ok(json!(CreatedInvite {
+                guest_id,
+                game,
+                url
+            }))
+        }
+        "invite.revoke" => {
+            let GuestParams { guest_id, reason } = params(&request.params)?;
+            handler
+                .revoke_invite(guest_id, Origin::new(Source::Api, reason))
+                .await
+                .map_err(failed)?;
+            Ok(json!(true))
+        }
+        "guest.kick" => {
+            let KickParams {
+                guest_id,
+                ban,
+                reason,
+            } = params(&request.params)?;
+            handler
+                .kick_guest(guest_id, ban, Origin::new(Source::Api, reason))
+                .await
+                .map_err(failed)?;
+            Ok(json!(true))
+        }
+        "guest.bans" => Ok(json!(handler.bans().await)),
+        "guest.unban" => {
+            let UnbanParams { steam_id, reason } = params(&request.params)?;
+            handler
+                .unban(steam_id, Origin::new(Source::Api, reason))
+                .await
+                .map_err(failed)?;
+            Ok(json!(true))
+        }
+        "guest.approve" | "guest.deny" => {
+            let GuestParams { guest_id, reason } = params(&request.params)?;
+            handler
+                .decide_guest(
+                    guest_id,
+                    request.method == "guest.approve",
+                    Origin::new(Source::Api, reason),
+                )
+                .await
+                .map_err(failed)?;
+            Ok(json!(true))
+        }
+        "pause" => {
+            let PauseParams { paused, reason } = params(&request.params)?;
+            handler
+                .set_paused(paused, Origin::new(Source::Api, reason))
+                .map_err(failed)?;
+            Ok(json!({ "paused": paused }))
+        }
+        "session.handoff" => {
+            let HandoffParams { reason } = params(&request.params)?;
+            handler
+                .request_handoff(Origin::new(Source::Api, reason))
+                .await
+                .map_err(failed)?;
+            Ok(json!(true))
+        }
+        "chat.send" => {
+            let ChatParams { text } = params(&request.params)?;
+            handler.send_chat(&text).await.map_err(failed)?;
+            Ok(json!(true))
+        }
+        "events.subscribe" => {
+            // Forward the events as notifications until the client disconnects
+            let mut events = handler.subscribe();
+            let out_tx = out_tx.clone();
+            tasks.spawn(async move {
+                loop {
+                    let event: Event = match events.recv().await {
+                        Ok(event) => event,
+                        // Skip the events a slow client missed
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(_) => break,
+                    };
+                    let notification = RpcRequest {
+                        jsonrpc: JSONRPC.to_owned(),
+                        id: None,
+                        method: "event".to_owned(),
+                        params: json!(event),
+                    };
+                    let Ok(text) = serde_json::to_string(&notification) else {
+                        continue;
+                    };
+                    if out_tx.send(text).await.is_err() {
+                        break;
+                    }
+                }
+            });
+            Ok(json!(true))
+        }
+        method => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Method not found: {method}"),
+        )),
+    }
+}
+
+/// Whether an instance is running (and listening on the control socket)
+#[cfg(unix)]
+pub fn is_running() -> bool {
+    std::os::unix::net::UnixStream::connect(socket_path()).is_ok()
+}
+
+/// Whether an instance is running (and listening on the control pipe)
+#[cfg(windows)]
+pub fn is_running() -> bool {
+    match std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(socket_path())
+    {
+        Ok(_) => true,
+        // Every instance of the pipe is talking to another client
+        Err(err) => {
+            err.raw_os_error() == Some(windows_sys::Win32::Foundation::ERROR_PIPE_BUSY as i32)
+        }
+    }
+}
+
+/// Whether an instance is running (and listening on the control socket)
+#[cfg(not(any(unix, windows)))]
+pub fn is_running() -> bool {
+    false
+}
+
+/// Calls a method on the running instance
+#[cfg(unix)]
+pub async fn call(method: &str, params: Value) -> Result<Value> {
+    use tokio::net::UnixStream;
+
+    let path = socket_path();
+    let stream = UnixStream::connect(&path)
+        .await
+        .with_context(|| format!("No running instance found (control socket {:?})", path))?;
+    exchange(stream, method, params).await
+}
+
+/// Calls a method on the running instance
+#[cfg(windows)]
+pub async fn call(method: &str, params: Value) -> Result<Value> {
+    use tokio::net::windows::named_pipe::ClientOptions;
+    use windows_sys::Win32::Foundation::ERROR_PIPE_BUSY;
+
+    let path = socket_path();
+    let mut attempts = 0;
+    let stream = loop {
+        match ClientOptions::new().open(&path) {
+            Ok(stream) => break stream,
+            // The server is between two instances of the pipe
+            Err(err) if err.raw_os_error() == Some(ERROR_PIPE_BUSY as i32) && attempts < 20 => {
+                attempts += 1;
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            Err(err) => {
+                return Err(anyhow!(err).context(format!(
+                    "No running instance found (control pipe {:?})",
+                    path
+                )))
+            }
+        }
+    };
+    exchange(stream, method, params).await
+}
+
+/// Calls a method on the running instance
+#[cfg(not(any(unix, windows)))]
+pub async fn call(_method: &str, _params: Value) -> Result<Value> {
+    bail!("Talking to a running instance is not supported on this platform yet")
+}
+
+/// Writes a request and reads its response
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+async fn exchange(
+    stream: impl AsyncRead + AsyncWrite,
+    method: &str,
+    params: Value,
+) -> Result<Value> {
+    let (read, mut write) = tokio::io::split(stream);
+    let request = RpcRequest {
+        jsonrpc: JSONRPC.to_owned(),
+        id: Some(json!(1)),
+        method: method.to_owned(),
+        params,
+    };
+    let mut text = serde_json::to_string(&request)?;
+    text.push('\n');
+    write
+        .write_all(text.as_bytes())
+        .await
+        .context("Failed to send request to the running instance")?;
+    let line = BufReader::new(read)
+        .lines()
+        .next_line()
+        .await
+        .context("Failed to read response from the running instance")?
+        .context("The running instance closed the connection")?;
+    let response: RpcResponse =
+        serde_json::from_str(&line).context("Invalid response from the running instance")?;
+    match (response.result, response.error) {
+        (_, Some(error)) => Err(anyhow!(error.message)),
+        (Some(result), None) => Ok(result),
+        (None, None) => Ok(Value::Null),
+    }
+}
+
+/// `status` subcommand (the report as JSON with --json)
+pub async fn print_status(json: bool) -> Result<()> {
+    let report: StatusReport = serde_json::from_value(call("status", Value::Null).await?)
+        .context("Invalid status from the running instance")?;
+    if json {
+        return console::data(
+            &serde_json::to_string(&report).context("Unable to serialize the status")?,
+        );
+    }
+    print_report(&report)
+}
+
+/// Name of a connection state
+fn connection_name(state: ConnectionState) -> &'static str {
+    match state {
+        ConnectionState::Connecting => "connecting",
+        ConnectionState::Connected => "connected",
+        ConnectionState::Reconnecting => "reconnecting",
+        ConnectionState::Pairing => "pairing",
+    }
+}
+
+/// Prints a status report
+pub fn print_report(report: &StatusReport) -> Result<()> {
+    let connection = connection_name(report.connection);
+    let family_of = |endpoint: &EndpointStatus| {
+        endpoint
+            .family
+            .map(|family| format!(", {family}"))
+            .unwrap_or_default()
+    };
+    // The IP version of a single server is shown with its host
+    let family = match report.endpoints.as_slice() {
+        [endpoint] => family_of(endpoint),
+        _ => String::new(),
+    };
+    console::println!(
+        "{} Version: {}, Connection: {connection} ({}{family}){}",
+        Glyph::Ok,
+        report.version,
+        report.endpoint,
+        if report.paused {
+            ", Invites paused"
+        } else {
+            ""
+        }
+    )?;
+    for endpoint in &report.endpoints {
+        if let Some(device) = &endpoint.active_device {
+            console::println!(
+                @level Level::Warn,
+                "{} {} sends the invites to {device} (type takeover to use this PC)",
+                Glyph::Warn,
+                endpoint.label.as_ref().unwrap_or(&endpoint.host)
+            )?;
+        }
+    }
+    if let Some(mapping) = report.network {
+        let (glyph, level) = if mapping.relay_likely() {
+            (Glyph::Warn, Level::Warn)
+        } else {
+            (Glyph::Ok, Level::Info)
+        };
+        console::println!(@level level, "{} NAT: {}", glyph, nat::describe(mapping))?;
+    }
+    if let Some(load) = report.load {
+        console::println!("{} Load: {}", Glyph::Ok, load::describe(load))?;
+    }
+    if !report.commands.is_empty() {
+        let commands = report
+            .commands
+            .iter()
+            .map(|metrics| {
+                let failed = match metrics.errors {
+                    0 => String::new(),
+                    errors => format!("{errors} failed, "),
+                };
+                format!(
+                    "{} {} ({failed}avg {} ms)",
+                    metrics.command,
+                    metrics.calls,
+                    metrics.total_ms / metrics.calls.max(1)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(", ");
+        console::println!("{} Commands: {commands}", Glyph::Ok)?;
+    }
+    // Registered with several servers (or named by the user)
+    if report.endpoints.len() > 1 || report.endpoints.iter().any(|e| e.label.is_some()) {
+        for endpoint in &report.endpoints {
+            let name = match &endpoint.label {
+                Some(label) => format!("{label} ({})", endpoint.host),
+                None => endpoint.host.clone(),
+            };
+            console::println!(
+                "  {name}: {}{}, invites={}",
+                connection_name(endpoint.connection),
+                family_of(endpoint),
+                endpoint.invites
+            )?;
+        }
+    }
+
+    let guests = report
+        .guests
+        .iter()
+        .map(|guest| {
+            personas::guest_label(
+                guest.guest_id,
+                guest.name.as_deref(),
+                guest.persona.as_deref(),
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(", ");
+    console::println!(
+        "{} Players({}): {guests}",
+        Glyph::Players,
+        report.guests.len()
+    )?;
+
+    if !report.pending.is_empty() {
+        let pending = report
+            .pending
+            .iter()
+            .map(|guest| {
+                personas::guest_label(
+                    guest.guest_id,
+                    guest.name.as_deref(),
+                    guest.persona.as_deref(),
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(", ");
+        console::println!(
+            "{} Waiting for approval({}): {pending}",
+            Glyph::Wait,
+            report.pending.len()
+        )?;
+    }
+
+    console::println!("Invites({}):", report.invites.len())?;
+    for invite in &report.invites {
+        let uses = match invite.max_uses {
+            Some(max_uses) => format!("{}/{max_uses}", invite.uses),
+            None => invite.uses.to_string(),
+        };
+        let endpoint = match &invite.endpoint {
+            Some(endpoint) => format!(", endpoint={endpoint}"),
+            None => String::new(),
+        };
+        let kind = match invite.kind {
+            InviteKind::Player => "",
+            InviteKind::Spectator => ", kind=spectator",
+        };
+        let expires = match invite.expires {
+            Some(expires) => format!(
+                ", expires={}",
+                expires.with_timezone(&Local).format("%H:%M:%S")
+            ),
+            None => String::new(),
+        };
+        console::println!(
+            "  claimer={}, guest_id={}, game_id={}, uses={uses}{kind}{endpoint}{expires}, invite_url={}",
+            invite.claimer.as_deref().unwrap_or("?"),
+            invite.guest_id,
+            invite.game,
+            invite.url
+        )?;
+    }
+    Ok(())
+}
+
+/// Creates an invite link in the running instance (revoked after `ttl` if given)
+pub async fn create_invite(game: Option<u32>, ttl: Option<Duration>) -> Result<CreatedInvite> {
+    let ttl_secs = ttl.map(|ttl| ttl.as_secs());
+    serde_json::from_value(
+        call(
+            "invite.create",
+            json!({ "game": game, "ttl_secs": ttl_secs }),
+        )
+        .await?,
+    )
+    .context("Invalid invite from the running instance")
+}
+
+/// `invite` subcommand output (only the link on stdout with --quiet, the invite as JSON with --json)
+pub fn print_invite(invite: &CreatedInvite, copy: bool, qr: bool, json: bool) -> Result<()> {
+    if json {
+        console::data(&serde_json::to_string(invite).context("Unable to serialize the invite")?)?;
+    } else if console::is_machine_output() {
+        console::data(&invite.url)?;
+    } else {
+        console::println!(
+            "{} Invite link for game {}: {}",
+            Glyph::Ok,
+            invite.game,
+            invite.url
+        )?;
+    }
+    if copy {
+        // The link is printed anyway, so a missing clipboard is not fatal
+        match console::copy(&invite.url) {
+            Ok(()) => console::println!("{} Copied to clipboard", Glyph::Ok)?,
+            Err(err) => console::eprintln!("{} Failed to copy to clipboard: {}", Glyph::Err, err)?,
+        }
+    }
+    if qr {
+        console::qr(&invite.url)?;
+    }
+    Ok(())
+}
+
+/// `pause` / `resume` subcommands
+pub async fn print_pause(paused: bool) -> Result<()> {
+    call("pause", json!({ "paused": paused })).await?;
+    console::println!(
+        "{} {}",
+        Glyph::Ok,
+        if paused {
+            "Invites paused"
+        } else {
+            "Invites resumed"
+        }
+    )?;
+    Ok(())
+}
+
+/// `chat` subcommand
+pub async fn print_chat(text: &str) -> Result<()> {
+    call("chat.send", json!({ "text": text })).await?;
+    console::println!("{} Message sent", Glyph::Ok)?;
+    Ok(())
+}
+
+/// `guest approve` / `guest deny` subcommands
+pub async fn print_decide_guest(guest_id: u64, approve: bool) -> Result<()> {
+    let method = if approve {
+        "guest.approve"
+    } else {
+        "guest.deny"
+    };
+    call(method, json!({ "guest_id": guest_id })).await?;
+    console::println!(
+        "{} Guest {guest_id} {}",
+        Glyph::Ok,
+        if approve { "approved" } else { "denied" }
+    )?;
+    Ok(())
+}
+
+/// `guest kick` subcommand
+pub async fn print_kick(
+    guest_id: u64,
+    ban: Option<BanDuration>,
+    reason: Option<String>,
+) -> Result<()> {
+    call(
+        "guest.kick",
+        json!({ "guest_id": guest_id, "ban": ban, "reason": reason }),
+    )
+    .await?;
+    console::println!("{} Guest {guest_id} kicked", Glyph::Ok)?;
+    Ok(())
+}
+
+/// `guest bans` subcommand
+pub async fn print_bans() -> Result<()> {
+    let bans: Vec<Ban> = serde_json::from_value(call("guest.bans", Value::Null).await?)
+        .context("Invalid ban list from the running instance")?;
+    print_ban_list(&bans)
+}
+
+/// Prints the banned guests
+pub fn print_ban_list(bans: &[Ban]) -> Result<()> {
+    console::println!("Bans({}):", bans.len())?;
+    for ban in bans {
+        console::println!(
+            "  claimer={}, steam_id={}, since={}, until={}",
+            ban.name.as_deref().unwrap_or("?"),
+            ban.steam_id,
+            ban.since.to_rfc3339(),
+            ban.until
+                .map(|until| until.to_rfc3339())
+                .unwrap_or_else(|| "permanent".to_owned())
+        )?;
+    }
+    Ok(())
+}
+
+/// `guest unban` subcommand
+pub async fn print_unban(steam_id: u64) -> Result<()> {
+    call("guest.unban", json!({ "steam_id": steam_id })).await?;
+    console::println!("{} Steam user {steam_id} unbanned", Glyph::Ok)?;
+    Ok(())
+}
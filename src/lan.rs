@@ -0,0 +1,277 @@
+use anyhow::{Context as _, Result};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr, UdpSocket},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    net::{TcpListener, TcpStream},
+    sync::{broadcast::error::RecvError, Mutex},
+    task::JoinHandle,
+    time::timeout,
+};
+
+use crate::{
+    config::LanConfig,
+    console::{self, Glyph},
+    events::Event,
+    handlers::Handler,
+    ipc,
+    models::InviteKind,
+};
+
+/// Largest request accepted from a browser
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+/// How often a device on the network may ask for a link
+const INVITE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Page shown to the friends on the local network
+const PAGE: &str = include_str!("lan.html");
+
+/// State shared by the requests
+struct Lan {
+    handler: Arc<Handler>,
+    /// Guests that joined since the start
+    joins: AtomicUsize,
+    /// When each device last got a link
+    last_invite: Mutex<HashMap<IpAddr, Instant>>,
+    /// How long a browser may take to send its request
+    request_timeout: Duration,
+}
+
+/// Serves a page on the local network where friends get an invite link (no Discord bot needed)
+pub async fn serve(
+    handler: Arc<Handler>,
+    config: LanConfig,
+    request_timeout: Duration,
+) -> Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(("0.0.0.0", config.port))
+        .await
+        .with_context(|| format!("Unable to serve the LAN page on port {}", config.port))?;
+    let host = local_ip().map_or_else(|| "localhost".to_owned(), |ip| ip.to_string());
+    console::println!(
+        "{} LAN page for your friends: http://{host}:{}/",
+        Glyph::Ok,
+        config.port
+    )?;
+
+    let lan = Arc::new(Lan {
+        handler: handler.clone(),
+        joins: AtomicUsize::new(0),
+        last_invite: Mutex::new(HashMap::new()),
+        request_timeout,
+    });
+
+    // Count the joins for the page
+    let mut events = handler.subscribe();
+    let counter = lan.clone();
+    let count_joins = async move {
+        loop {
+            match events.recv().await {
+                Ok(Event::GuestJoined { .. }) => {
+                    counter.joins.fetch_add(1, Ordering::Relaxed);
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => (),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    };
+
+    let accept = async move {
+        let mut wait = Duration::ZERO;
+        loop {
+            let (stream, addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    ipc::accept_failed("invite page", err, &mut wait).await;
+                    continue;
+                }
+            };
+            wait = Duration::ZERO;
+            let lan = lan.clone();
+            tokio::spawn(async move {
+                let _ = handle_request(stream, addr, &lan).await;
+            });
+        }
+    };
+    Ok(tokio::spawn(async move {
+        tokio::join!(count_joins, accept);
+    }))
+}
+
+/// Address of this PC on the local network
+pub fn local_ip() -> Option<IpAddr> {
+    // Nothing is sent, connecting only picks the outgoing interface
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("192.168.0.1:9").ok()?;
+    socket.local_addr().ok().map(|addr| addr.ip())
+}
+
+/// Answers a single HTTP request
+async fn handle_request(mut stream: TcpStream, addr: SocketAddr, lan: &Lan) -> Result<()> {
+    // The port is open on every interface, but the page is only for the local network
+    if !is_local_peer(addr.ip()) {
+        return respond(&mut stream, "403 Forbidden", "text/plain", "Forbidden").await;
+    }
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let n = timeout(lan.request_timeout, stream.read(&mut buf)).await??;
+        if n == 0 || request.len() + n > MAX_REQUEST_SIZE {
+            return Ok(());
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut parts = request.split_whitespace();
+    let (method, path) = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or("/"),
+    );
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/") => ("200 OK", "text/html; charset=utf-8", PAGE.to_owned()),
+        ("GET", "/status") => ("200 OK", "application/json", status(lan).await),
+        // Other sites open in the browser of a friend must not get links
+        ("POST", "/invite") if !is_same_origin(&request) => (
+            "403 Forbidden",
+            "application/json",
+            json!({ "error": "Cross-site request" }).to_string(),
+        ),
+        ("POST", "/invite") => match invite(lan, addr.ip()).await {
+            Ok(url) => (
+                "200 OK",
+                "application/json",
+                json!({ "url": url }).to_string(),
+            ),
+            Err(err) => (
+                "409 Conflict",
+                "application/json",
+                json!({ "error": err.to_string() }).to_string(),
+            ),
+        },
+        _ => ("404 Not Found", "text/plain", "Not found".to_owned()),
+    };
+    respond(&mut stream, status, content_type, &body).await
+}
+
+/// Sends the response to a request
+async fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> Result<()> {
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    Ok(())
+}
+
+/// Whether a peer is on the local network (private, link-local or this PC)
+fn is_local_peer(ip: IpAddr) -> bool {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => ip.is_private() || ip.is_link_local() || ip.is_loopback(),
+        IpAddr::V6(ip) => {
+            let segment = ip.segments()[0];
+            // Unique local (fc00::/7) and link-local (fe80::/10) addresses
+            (segment & 0xfe00) == 0xfc00 || (segment & 0xffc0) == 0xfe80 || ip.is_loopback()
+        }
+    }
+}
+
+/// Whether a request was sent by the page itself (its Origin is the host it was sent to)
+fn is_same_origin(request: &str) -> bool {
+    match (header(request, "Origin"), header(request, "Host")) {
+        (Some(origin), Some(host)) => origin.strip_prefix("http://") == Some(host),
+        _ => false,
+    }
+}
+
+/// Value of a request header
+fn header<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+    request.lines().skip(1).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then_some(value.trim())
+    })
+}
+
+/// Counters shown on the page
+async fn status(lan: &Lan) -> String {
+    let report = lan.handler.status().await;
+    json!({
+        "game": lan.handler.running_game().await.ok(),
+        "players": report.guests.len(),
+        "joins": lan.joins.load(Ordering::Relaxed),
+        "paused": report.paused,
+    })
+    .to_string()
+}
+
+/**
+ * Creates an invite link for a device on the local network
+ * @return The invite URL
+ */
+async fn invite(lan: &Lan, ip: IpAddr) -> Result<String> {
+    {
+        let mut last_invite = lan.last_invite.lock().await;
+        last_invite.retain(|_, last| last.elapsed() < INVITE_INTERVAL);
+        if last_invite
+            .get(&ip)
+            .is_some_and(|last| last.elapsed() < INVITE_INTERVAL)
+        {
+            anyhow::bail!("Please wait a moment before asking again");
+        }
+        last_invite.insert(ip, Instant::now());
+    }
+    let (_, _, url) = lan
+        .handler
+        .create_local_invite(&format!("lan {ip}"), None, None, InviteKind::Player, None)
+        .await?;
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_local_peers_are_served() {
+        for ip in [
+            "192.168.1.20",
+            "10.0.0.5",
+            "169.254.3.4",
+            "127.0.0.1",
+            "fe80::1",
+            "fd12::1",
+        ] {
+            assert!(is_local_peer(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["8.8.8.8", "100.64.0.1", "2001:db8::1", "::ffff:1.1.1.1"] {
+            assert!(!is_local_peer(ip.parse().unwrap()), "{ip}");
+        }
+        assert!(is_local_peer("::ffff:192.168.1.20".parse().unwrap()));
+    }
+
+    #[test]
+    fn invites_need_the_page_origin() {
+        let request = |headers: &str| format!("POST /invite HTTP/1.1\r\n{headers}\r\n\r\n");
+        assert!(is_same_origin(&request(
+            "Host: 192.168.1.2:8730\r\norigin: http://192.168.1.2:8730"
+        )));
+        assert!(!is_same_origin(&request(
+            "Host: 192.168.1.2:8730\r\nOrigin: http://evil.example"
+        )));
+        assert!(!is_same_origin(&request("Host: 192.168.1.2:8730")));
+    }
+}
@@ -0,0 +1,65 @@
+use anyhow::{Context as _, Result};
+use std::{process::Command, sync::Arc, time::Duration};
+use tokio::{
+    task::JoinHandle,
+    time::{interval, MissedTickBehavior},
+};
+
+use crate::{
+    console::{self, Glyph},
+    handlers::Handler,
+};
+
+/// How often the running game is checked
+const TICK: Duration = Duration::from_secs(5);
+
+/// Watches the running game
+/// Revokes the invites of a game when it is closed, and creates one when a supported game is started
+pub fn spawn_game_watcher(handler: Arc<Handler>, auto_invite: bool) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = interval(TICK);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // A game that is already running at startup does not count as started
+        let mut last = handler.running_game().await.ok();
+        loop {
+            ticker.tick().await;
+            let game = handler.running_game().await.ok();
+            if game == last {
+                continue;
+            }
+            if let Some(exited) = last {
+                handler.on_game_exited(exited).await;
+            }
+            last = game;
+            if let Some(game) = game {
+                handler.on_game_started(game).await;
+            }
+            let Some(game) = game.filter(|_| auto_invite) else {
+                continue;
+            };
+            if let Err(err) = handler.auto_invite(game).await {
+                let _ = console::eprintln!("{} {}", Glyph::Err, err);
+            }
+        }
+    })
+}
+
+/// Asks Steam to start a game
+pub fn start_game(game: u32) -> Result<()> {
+    let url = format!("steam://rungameid/{game}");
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    };
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let mut command = Command::new("xdg-open");
+    command
+        .arg(&url)
+        .spawn()
+        .with_context(|| format!("Unable to start the game: {url}"))?;
+    Ok(())
+}
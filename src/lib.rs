@@ -0,0 +1,1160 @@
+use anyhow::{anyhow, Context as _, Result};
+use dotenvy_macro::dotenv;
+use futures::SinkExt;
+use futures_util::stream::StreamExt;
+use socket2::{SockRef, TcpKeepalive};
+use std::{borrow::Cow, sync::Arc};
+use steam_stuff::Backend;
+use tokio::{
+    sync::Mutex,
+    time::{self, timeout, Duration},
+};
+use tokio_tungstenite::{
+    client_async_tls_with_config, client_async_with_config,
+    tungstenite::{
+        client::IntoClientRequest,
+        handshake::client::Response,
+        http::{uri::Builder, HeaderMap, HeaderValue, Uri},
+        protocol::{Message, WebSocketConfig},
+        Error as WsError,
+    },
+    Connector, MaybeTlsStream, WebSocketStream,
+};
+use uuid::Uuid;
+
+mod agent;
+mod audit;
+mod avatars;
+mod bans;
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench;
+mod capacity;
+mod cli;
+mod commands;
+mod compat;
+mod config;
+mod conformance;
+mod connection;
+mod console;
+mod container;
+mod deck;
+mod dedup;
+mod diagnostics;
+#[cfg(feature = "discord-rpc")]
+mod discord_rpc;
+mod doh;
+mod endpoint;
+mod error;
+mod events;
+mod files;
+mod focus;
+mod handlers;
+mod health;
+mod healthz;
+mod instructions;
+#[cfg(feature = "integration")]
+mod integration;
+mod ipc;
+mod lan;
+mod launch;
+mod load;
+mod logger;
+#[cfg(all(target_os = "macos", feature = "macos"))]
+mod macos;
+mod middleware;
+mod models;
+mod nat;
+mod personas;
+mod platform;
+mod playtime;
+mod power;
+mod prompt;
+mod quiet;
+mod recap;
+mod replay;
+mod retry;
+mod schema;
+#[cfg(feature = "scripting")]
+mod scripting;
+mod shaping;
+mod shortener;
+#[cfg(feature = "simulate")]
+mod simulate;
+#[cfg(feature = "sounds")]
+mod sound;
+mod stats;
+#[cfg(any(all(windows, feature = "tray"), feature = "discord-rpc"))]
+mod status;
+mod steam_health;
+mod store;
+mod supervisor;
+mod systemd;
+#[cfg(all(windows, feature = "notifications"))]
+mod toast;
+mod token;
+mod transport;
+#[cfg(all(windows, feature = "tray"))]
+mod tray;
+mod vault;
+mod waitlist;
+mod watchdog;
+mod ws_error_handler;
+
+use cli::{Args, Command, GuestCommand, InviteCommand, LogsCommand, StatsCommand, TokenCommand};
+use config::{read_or_generate_config, Config, DeviceConfig};
+use config::{DnsConfig, KeepaliveConfig, ShapingConfig, TimeoutsConfig};
+use connection::{ConnectionStateMachine, IdleWatch, Input};
+use console::{Glyph, Verbosity};
+use endpoint::Endpoint;
+use error::{ClientError, ClientResult, ErrorKind as _, Recovery};
+use handlers::Handler;
+use logger::Level;
+use models::*;
+use platform::{encode_query, Platform};
+use shaping::Shaper;
+use supervisor::Supervisor;
+use transport::{Transport, UNIX_SCHEME};
+use ws_error_handler::{close_action, handle_ws_error, retry_after, CloseAction, RefusedAction};
+
+// Version
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// Endpoint URL
+const DEFAULT_URL: &str = dotenv!("ENDPOINT_URL");
+
+#[cfg(all(target_os = "macos", feature = "macos"))]
+pub use macos::run_app;
+
+/// Optional features compiled into this build (cargo features)
+fn compiled_features() -> Vec<&'static str> {
+    [
+        ("tui", cfg!(feature = "tui")),
+        ("tray", cfg!(feature = "tray")),
+        ("notifications", cfg!(feature = "notifications")),
+        ("metrics", cfg!(feature = "metrics")),
+        ("discord-rpc", cfg!(feature = "discord-rpc")),
+        ("sounds", cfg!(feature = "sounds")),
+        ("deck", cfg!(feature = "deck")),
+        ("integration", cfg!(feature = "integration")),
+        ("scripting", cfg!(feature = "scripting")),
+        ("native-tls", cfg!(feature = "native-tls")),
+        ("nvidia", cfg!(feature = "nvidia")),
+        ("keychain", cfg!(feature = "keychain")),
+        ("macos", cfg!(feature = "macos")),
+        ("simulate", cfg!(feature = "simulate")),
+        ("no-steam", cfg!(feature = "no-steam")),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect()
+}
+
+/// Runs the inviter (or a subcommand)
+pub async fn run() -> Result<()> {
+    // Event loop (stopped early: what would fix the failure)
+    let stopped = 'main: {
+        // Set up the terminal (UTF-8 and ANSI sequences on Windows)
+        console::init_terminal();
+
+        // Parse the command line arguments
+        let args = match Args::parse() {
+            Ok(args) => args,
+            Err(err) => {
+                console::eprintln!("{} {} (see --help)", Glyph::Err, err)?;
+                return Ok(());
+            }
+        };
+
+        // Container mode (configuration from the environment, JSON log on stdout, no terminal)
+        let in_container = container::requested(args.container);
+        if in_container {
+            container::enable();
+        }
+        // Everything goes to the JSON log in a container
+        let verbosity = if in_container {
+            Verbosity::Silent
+        } else {
+            args.verbosity
+        };
+
+        // Accessible mode (for screen readers)
+        console::set_accessible(args.accessible);
+        // ASCII mode (for terminals without Unicode support)
+        console::set_ascii(args.ascii || in_container || !console::supports_unicode());
+        // Steam Deck mode (large minimal output)
+        console::set_deck(args.deck || deck::is_steam_deck(), false);
+        // Keep stdout for the JSON and the invite links, so the output can be piped
+        console::set_machine_output(args.json || verbosity != Verbosity::Normal);
+        // Limit the connections to an IP version (for debugging)
+        if let Some(family) = args.only_family {
+            transport::set_only_family(family);
+        }
+        // Simulate the server commands that change something (for testing bots)
+        middleware::set_dry_run(args.dry_run);
+        // Leave the restarts to a process supervisor (systemd, pm2, the container runtime)
+        if args.exit_on_disconnect {
+            connection::set_supervised(Some(0));
+        } else if args.max_retries.is_some() || in_container {
+            connection::set_supervised(args.max_retries);
+        }
+
+        // Run the subcommand instead of the inviter
+        if let Some(command) = args.command {
+            if let Err(err) = run_command(command, args.json).await {
+                console::eprintln!("{} {}", Glyph::Err, err)?;
+                // A supervised agent stops with the same exit codes as the inviter
+                if connection::is_supervised() {
+                    let recovery = err
+                        .downcast_ref::<ClientError>()
+                        .map_or(Recovery::Fatal, ClientError::recovery);
+                    std::process::exit(recovery.exit_code());
+                }
+            }
+            return Ok(());
+        }
+
+        // No banner for the scripts (--quiet and --silent)
+        if verbosity == Verbosity::Normal {
+            if console::is_accessible() || console::is_ascii() {
+                console::printdoc! {"
+                    Remote Play Inviter, Version {VERSION} by Kamesuta
+                    Invite your friends via Discord and play Steam games together for free!
+
+                "}?;
+            } else {
+                console::printdoc! {"
+                    ------------------------------------------------------------------------------
+                                ╦═╗┌─┐┌┬┐┌─┐┌┬┐┌─┐┌─┐┬  ┌─┐┬ ┬  ╦┌┐┌┬  ┬┬┌┬┐┌─┐┬─┐
+                                ╠╦╝├┤ ││││ │ │ ├┤ ├─┘│  ├─┤└┬┘  ║│││└┐┌┘│ │ ├┤ ├┬┘
+                                ╩╚═└─┘┴ ┴└─┘ ┴ └─┘┴  ┴─┘┴ ┴ ┴   ╩┘└┘ └┘ ┴ ┴ └─┘┴└─
+                                   Version: {VERSION}                   by Kamesuta
+
+                        Invite your friends via Discord and play Steam games together for free!
+                    ------------------------------------------------------------------------------
+
+                "}?;
+            }
+        }
+
+        // Version command
+        if args.version {
+            console::println!("{} Version: {}", Glyph::Ok, VERSION)?;
+            let features = compiled_features();
+            console::println!(
+                "  Features: {}",
+                if features.is_empty() {
+                    "none".to_owned()
+                } else {
+                    features.join(", ")
+                }
+            )?;
+            return Ok(());
+        }
+
+        // Help command
+        if args.help {
+            let program = std::env::current_exe()
+                .ok()
+                .and_then(|f| f.file_name().map(|f| f.to_string_lossy().into_owned()))
+                .unwrap_or_else(|| "remoteplay-inviter".to_owned());
+            console::printdoc! {"{}", cli::help_text(&program)}?;
+            return Ok(());
+        }
+
+        // Quiet or silent terminal (after --version and --help, which print what was asked for)
+        console::set_verbosity(verbosity);
+        if in_container {
+            logger::init_json_stdout();
+        }
+
+        // Read or generate the configuration file (if it doesn't exist)
+        let config = match load_config() {
+            Ok(config) => config,
+            Err(err) => {
+                console::eprintln!("{} {}", Glyph::Err, err)?;
+                break 'main Recovery::UserAction;
+            }
+        };
+
+        // Tell this PC apart from the other devices sharing the UUID
+        let device_id = device_id(&config);
+        // Where the game is hosted from (OS, architecture, host name, Steam version)
+        let platform = Platform::detect(&config.device);
+
+        // Write the console output to the log file
+        if let Err(err) = logger::init(&config.logging) {
+            console::eprintln!("{} {}", Glyph::Err, err)?;
+        }
+
+        // The token and the guests' data must not be readable by the other users of this PC
+        for path in files::exposed_files() {
+            console::eprintln!(
+                "{} {:?} can be read by other users of this PC ({})",
+                Glyph::Warn,
+                path,
+                files::EXPOSED_FIX
+            )?;
+        }
+
+        // Apply the Steam Deck settings (the command line flag wins)
+        console::set_deck(
+            args.deck || config.deck.enabled.unwrap_or_else(deck::is_steam_deck),
+            config.deck.critical_only,
+        );
+
+        // Initialize SteamStuff
+        let steam = match Backend::new()
+            .context("Failed to connect to Steam Client. Please make sure Steam is running.")
+            .steam()
+        {
+            Ok(steam) => Arc::new(Mutex::new(steam)),
+            Err(err) => {
+                console::eprintln!("{} {}", Glyph::Err, err)?;
+                break 'main err.recovery();
+            }
+        };
+        if cfg!(feature = "no-steam") {
+            console::eprintln!(
+                "{} Built without Steam: relaying the server only, every invite is refused",
+                Glyph::Warn
+            )?;
+        }
+
+        // Read the ban list
+        let bans = match bans::BanList::load() {
+            Ok(bans) => bans,
+            Err(err) => {
+                console::eprintln!("{} {}", Glyph::Err, err)?;
+                break 'main Recovery::UserAction;
+            }
+        };
+
+        // Read the cached game compatibility list (only used for warnings)
+        let compat = compat::CompatList::load().unwrap_or_else(|err| {
+            let _ = console::eprintln!("{} {}", Glyph::Err, err);
+            compat::CompatList::default()
+        });
+
+        // Read the pending move of the Discord link to a new token
+        let relink = token::PendingRelink::load().unwrap_or_else(|err| {
+            let _ = console::eprintln!("{} {}", Glyph::Err, err);
+            None
+        });
+
+        // URLs of the servers to register with
+        let result: Result<Vec<(String, Option<String>, HeaderMap)>> = 'tryblock: {
+            // Only the LAN page is served in offline mode
+            if config.lan.enabled && config.lan.offline {
+                break 'tryblock Ok(Vec::new());
+            }
+
+            // Read the endpoint configuration file
+            let endpoint_config = match config::read_endpoint_config() {
+                Ok(config) => config,
+                Err(err) => {
+                    break 'tryblock Err(err);
+                }
+            };
+
+            // Session ID
+            let session_id: u32 = rand::random();
+
+            // Endpoint URLs, labels and request headers
+            let mut endpoint_urls: Vec<(Cow<'_, str>, Option<String>, HeaderMap)> = Vec::new();
+            match endpoint_config {
+                Some(e) => {
+                    let headers =
+                        match endpoint::request_headers(e.user_agent.as_deref(), &e.headers) {
+                            Ok(headers) => headers,
+                            Err(err) => {
+                                break 'tryblock Err(err);
+                            }
+                        };
+                    match e.url {
+                        Some(url) => {
+                            if let Err(err) = console::println!(
+                                "{} Using custom endpoint URL: {}",
+                                Glyph::Ok,
+                                url
+                            ) {
+                                break 'tryblock Err(err);
+                            }
+                            endpoint_urls.push((url.into(), e.label, headers));
+                        }
+                        None => endpoint_urls.push((DEFAULT_URL.into(), e.label, headers)),
+                    }
+                    for extra in e.extra {
+                        if let Err(err) =
+                            console::println!("{} Also registering with: {}", Glyph::Ok, extra.url)
+                        {
+                            break 'tryblock Err(err);
+                        }
+                        let headers = match endpoint::request_headers(
+                            extra.user_agent.as_deref(),
+                            &extra.headers,
+                        ) {
+                            Ok(headers) => headers,
+                            Err(err) => {
+                                break 'tryblock Err(err);
+                            }
+                        };
+                        endpoint_urls.push((extra.url.into(), extra.label, headers));
+                    }
+                }
+                None => match endpoint::request_headers(None, &Default::default()) {
+                    Ok(headers) => endpoint_urls.push((DEFAULT_URL.into(), None, headers)),
+                    Err(err) => {
+                        break 'tryblock Err(err);
+                    }
+                },
+            }
+
+            let mut urls = Vec::new();
+            for (endpoint_url, label, headers) in endpoint_urls {
+                let path_and_query = format!(
+                    "/ws?v={VERSION}&token={0}&session={session_id}&device={1}{2}{3}",
+                    config.uuid,
+                    encode_query(&device_id),
+                    platform.query(),
+                    // Ask for a pairing code instead of a link message when not linked yet
+                    if config.pairing.enabled {
+                        "&pairing=1"
+                    } else {
+                        ""
+                    }
+                );
+
+                // A server on the same host listening on a Unix domain socket
+                if let Some((socket, _)) = transport::unix_socket(&endpoint_url) {
+                    urls.push((
+                        format!("{UNIX_SCHEME}{socket}:{path_and_query}"),
+                        label,
+                        headers,
+                    ));
+                    continue;
+                }
+
+                // Create the URL
+                let uri: Uri = match endpoint_url.parse().context("Failed to parse URL") {
+                    Ok(uri) => uri,
+                    Err(err) => {
+                        break 'tryblock Err(err);
+                    }
+                };
+                let uri = match Builder::from(uri)
+                    .path_and_query(path_and_query)
+                    .build()
+                    .context("Failed to build URL")
+                {
+                    Ok(uri) => uri,
+                    Err(err) => {
+                        break 'tryblock Err(err);
+                    }
+                };
+                urls.push((uri.to_string(), label, headers));
+            }
+            Ok(urls)
+        };
+        let urls = match result {
+            Ok(urls) => urls,
+            Err(err) => {
+                console::eprintln!("{} {}", Glyph::Err, err)?;
+                break 'main Recovery::UserAction;
+            }
+        };
+        let endpoints = urls
+            .into_iter()
+            .enumerate()
+            .map(|(index, (url, label, headers))| {
+                Arc::new(Endpoint::new(index, url, label, headers))
+            })
+            .collect();
+
+        // Record or replay the Steam callbacks (for reproducing bugs)
+        let steam_replay = match (&args.record_steam, &args.replay_steam) {
+            (Some(path), _) => replay::start_recording(path),
+            (_, Some(path)) => replay::start_replay(path),
+            _ => Ok(()),
+        };
+        if let Err(err) = steam_replay {
+            console::eprintln!("{} {:#}", Glyph::Err, err)?;
+            break 'main Recovery::UserAction;
+        }
+        // Fake guest traffic (for load tests)
+        #[cfg(feature = "simulate")]
+        if let Some(per_minute) = args.simulate_guests {
+            simulate::start(per_minute);
+        }
+        if middleware::is_dry_run() {
+            console::println!(
+                @level Level::Warn,
+                "{} Dry run: links, revocations, host requests and exits of the servers are only simulated",
+                Glyph::Warn
+            )?;
+        }
+
+        // Create a Handler
+        let handler = Arc::new(Handler::new(
+            steam.clone(),
+            &config,
+            bans,
+            compat,
+            relink,
+            endpoints,
+        ));
+
+        // Set up Steam callbacks
+        handler.setup_steam_callbacks().await;
+
+        // Start the background services
+        let mut supervisor = Supervisor::start(&handler, &config).await;
+
+        // Register with each server on its own connection
+        let connector = match transport::connector(config.tls.backend) {
+            Ok(connector) => connector,
+            Err(err) => {
+                console::eprintln!("{} {}", Glyph::Err, err)?;
+                supervisor.shutdown().await;
+                break 'main Recovery::UserAction;
+            }
+        };
+        for endpoint in handler.endpoints() {
+            let connection = run_endpoint(
+                handler.clone(),
+                endpoint.clone(),
+                config.shaping.clone(),
+                config.keepalive.clone(),
+                config.timeouts.clone(),
+                config.dns.clone(),
+                connector.clone(),
+            );
+            supervisor.add_connection(match handler.label_of(endpoint) {
+                Some(label) => tokio::spawn(console::labeled(label, connection)),
+                None => tokio::spawn(connection),
+            });
+        }
+
+        // Runs until every connection stopped (exit requested, an outdated client or out of retries), Ctrl+C or SIGTERM
+        match supervisor.wait().await {
+            // A process supervisor decides from the exit code whether to restart the client
+            Some(code) if connection::is_supervised() => {
+                supervisor.shutdown().await;
+                std::process::exit(code);
+            }
+            Some(_) => {
+                // The services keep running until then (e.g. the LAN page in offline mode)
+                console::println!("{} Press Ctrl+C to exit...", Glyph::Wait)?;
+                supervisor::stop_requested().await;
+            }
+            None => {}
+        }
+        supervisor.shutdown().await;
+        return Ok(());
+    };
+    if connection::is_supervised() {
+        std::process::exit(stopped.exit_code());
+    }
+
+    // Wait for input before exiting
+    console::println!("{} Press Ctrl+C to exit...", Glyph::Wait)?;
+    supervisor::stop_requested().await;
+
+    Ok(())
+}
+
+/// Largest message accepted from the server
+const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+/// Largest frame accepted from the server
+const MAX_FRAME_SIZE: usize = 256 * 1024;
+
+/// WebSocket connection to a server
+type WsStream = WebSocketStream<MaybeTlsStream<Transport>>;
+
+/**
+ * Keeps the registration with a server alive, reconnecting whenever the connection is lost
+ * @return When the server asked to exit (or the client is outdated), or an error that another try cannot fix
+ */
+async fn run_endpoint(
+    handler: Arc<Handler>,
+    endpoint: Arc<Endpoint>,
+    shaping: ShapingConfig,
+    keepalive: KeepaliveConfig,
+    timeouts: TimeoutsConfig,
+    dns: DnsConfig,
+    connector: Connector,
+) -> ClientResult<()> {
+    let mut machine = ConnectionStateMachine::new();
+    // Resolves the server with DNS-over-HTTPS if configured
+    let resolver = doh::Resolver::new(&dns, timeouts.connect());
+    // Holds back background messages while streaming saturates the upstream
+    let mut shaper = Shaper::new(shaping);
+    // Notices connections that a NAT or proxy drops while idle
+    let mut idle = IdleWatch::new(keepalive.detect_idle_drops, timeouts.read());
+
+    loop {
+        let result = match machine.state().clone() {
+            connection::State::Connecting { reconnect } => {
+                // The new connection is what a pending request asked for
+                endpoint.clear_reconnect();
+                let connected = connect(
+                    &endpoint,
+                    &mut machine,
+                    &keepalive,
+                    &timeouts,
+                    resolver.as_ref(),
+                    &connector,
+                    reconnect,
+                )
+                .await;
+                if let Ok(Ok(_)) = connected {
+                    endpoint.connected().await;
+                } else if let Some(server) = endpoint.connect_failed().await {
+                    // The server the client was moved to stays unreachable
+                    console::println!("{} Moving back to {server}", Glyph::Retry).console()?;
+                }
+                match connected {
+                    Ok(Ok(ws_stream)) => {
+                        // Process messages until the connection is lost
+                        machine.handle(Input::Opened);
+                        let result = run_session(
+                            &handler,
+                            &endpoint,
+                            &mut machine,
+                            &mut shaper,
+                            &mut idle,
+                            ws_stream,
+                            reconnect,
+                        )
+                        .await;
+                        handler.on_disconnected(&endpoint).await;
+                        result
+                    }
+                    // The client is outdated, was refused or has to pair again
+                    Ok(Err(input)) => Ok(input),
+                    Err(err) => Err(err),
+                }
+            }
+            // Only reached through a session, which runs right after connecting
+            connection::State::Connected { .. } => Ok(Input::Lost { planned: false }),
+            connection::State::Backoff { wait, planned } => {
+                // Also reached when the first connection attempt failed
+                handler.on_disconnected(&endpoint).await;
+                back_off(&endpoint, wait, planned).await
+            }
+            connection::State::Fatal => return Ok(()),
+            connection::State::GaveUp { retries } => return Err(connection::gave_up(retries)),
+        };
+
+        let input = match result {
+            Ok(input) => input,
+            // Only connection problems are worth another try
+            Err(err) if err.recovery() == Recovery::Retry => {
+                console::eprintln!("{} {}", Glyph::Err, err).console()?;
+                Input::Lost { planned: false }
+            }
+            Err(err) => {
+                machine.handle(Input::Stop);
+                return Err(err);
+            }
+        };
+        if let Input::Lost { .. } = input {
+            // Reconnect soon after a resume (the network may take a moment to come back)
+            if endpoint.is_resuming() {
+                machine.handle(Input::Reset);
+            }
+            if let Some(sec) = endpoint.take_backoff() {
+                machine.handle(Input::Hint(sec));
+            }
+        }
+        machine.handle(input);
+    }
+}
+
+/**
+ * Opens the connection to the server
+ * @return The connection, or what to do instead (stop, or reconnect to pair the token again)
+ */
+async fn connect(
+    endpoint: &Endpoint,
+    machine: &mut ConnectionStateMachine,
+    keepalive: &KeepaliveConfig,
+    timeouts: &TimeoutsConfig,
+    resolver: Option<&doh::Resolver>,
+    connector: &Connector,
+    reconnect: bool,
+) -> ClientResult<Result<WsStream, Input>> {
+    // Display the reconnection message
+    if reconnect {
+        console::println!("{} Reconnecting to the server...", Glyph::Retry).console()?;
+    }
+
+    // Create a WebSocket client
+    // Keep an absurd payload from exhausting the memory
+    let ws_config = WebSocketConfig {
+        max_message_size: Some(MAX_MESSAGE_SIZE),
+        max_frame_size: Some(MAX_FRAME_SIZE),
+        ..Default::default()
+    };
+    let connect_result = timeout(
+        timeouts.connect(),
+        open(
+            endpoint.url().await,
+            &endpoint.headers,
+            ws_config,
+            keepalive,
+            resolver,
+            connector.clone(),
+        ),
+    )
+    .await
+    .context("Connection timed out to the server")
+    .network()?;
+    match connect_result {
+        Ok((ws_stream, response)) => {
+            endpoint.set_family(transport::family_of(ws_stream.get_ref()));
+            // Servers from before the negotiation do not send the header: they only know the first
+            // version (newer error statuses such as paused would not parse there)
+            let protocol = response
+                .headers()
+                .get(Protocol::HEADER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(Protocol::parse)
+                .unwrap_or(Protocol::V1);
+            if protocol != Protocol::default() && protocol != endpoint.protocol() {
+                console::println!(
+                    @level Level::Warn,
+                    "{} {} speaks an older protocol ({protocol}): only invites and messages work",
+                    Glyph::Warn,
+                    endpoint.name()
+                )
+                .console()?;
+            }
+            endpoint.set_protocol(protocol);
+            Ok(Ok(ws_stream))
+        }
+        Err(err) => {
+            // The server is overloaded or restarting
+            if let Some(sec) = retry_after(&err) {
+                machine.handle(Input::Hint(sec));
+            }
+            match handle_ws_error(err)? {
+                RefusedAction::Stop => Ok(Err(Input::Stop)),
+                RefusedAction::Relink => {
+                    // A server that cannot pair refuses the pairing too
+                    if !endpoint.request_pairing().await {
+                        console::eprintln!(
+                            "{} The server does not pair this client, link it again with the Discord bot",
+                            Glyph::Err
+                        )
+                        .console()?;
+                        return Ok(Err(Input::Stop));
+                    }
+                    // Linked again once the pairing code is entered, the session goes on from there
+                    machine.handle(Input::Reset);
+                    Ok(Err(Input::Lost { planned: false }))
+                }
+            }
+        }
+    }
+}
+
+/// Opens a TCP connection with keepalive probes and performs the WebSocket handshake
+async fn open(
+    url: String,
+    headers: &HeaderMap,
+    ws_config: WebSocketConfig,
+    keepalive: &KeepaliveConfig,
+    resolver: Option<&doh::Resolver>,
+    connector: Connector,
+) -> Result<(WsStream, Response), WsError> {
+    // A server on the same host (no TCP or TLS)
+    if let Some((socket, path)) = transport::unix_socket(&url) {
+        let mut request = format!("ws://localhost{path}").into_client_request()?;
+        request.headers_mut().insert(
+            Protocol::HEADER,
+            HeaderValue::from_static(Protocol::SUPPORTED),
+        );
+        request.headers_mut().extend(headers.clone());
+        let stream = transport::connect_unix(socket).await?;
+        return client_async_with_config(request, MaybeTlsStream::Plain(stream), Some(ws_config))
+            .await;
+    }
+
+    let mut request = url.into_client_request()?;
+    request.headers_mut().insert(
+        Protocol::HEADER,
+        HeaderValue::from_static(Protocol::SUPPORTED),
+    );
+    // Configured headers replace the defaults of the same name
+    request.headers_mut().extend(headers.clone());
+    let host = request.uri().host().unwrap_or_default().to_owned();
+    let port = request
+        .uri()
+        .port_u16()
+        .unwrap_or(if request.uri().scheme_str() == Some("wss") {
+            443
+        } else {
+            80
+        });
+    let stream = transport::connect_tcp(&host, port, resolver).await?;
+    if keepalive.tcp_secs > 0 {
+        // Keeps NAT mappings alive while no messages are exchanged
+        let time = Duration::from_secs(keepalive.tcp_secs);
+        let params = TcpKeepalive::new().with_time(time).with_interval(time);
+        let _ = SockRef::from(&stream).set_tcp_keepalive(&params);
+    }
+    client_async_tls_with_config(
+        request,
+        Transport::Tcp(stream),
+        Some(ws_config),
+        Some(connector),
+    )
+    .await
+}
+
+/// Tells the host how to keep a connection alive that a NAT or proxy drops while idle
+fn report_idle_drop(idle: &mut IdleWatch) -> ClientResult<()> {
+    let Some(idle) = idle.dropped() else {
+        return Ok(());
+    };
+    console::printdoc! {"
+
+        {warn} The connection keeps dropping after {secs} seconds without traffic.
+          A router or proxy may be closing idle connections.
+          Try lowering heartbeat_secs and throttled_heartbeat_secs in [shaping]
+          and tcp_secs in [keepalive] below {secs} seconds.
+
+        ",
+        warn = Glyph::Warn,
+        secs = idle.as_secs()
+    }
+    .console()
+}
+
+/**
+ * Processes the messages of the server until the connection ends
+ * @return How the connection ended
+ */
+async fn run_session(
+    handler: &Handler,
+    endpoint: &Endpoint,
+    machine: &mut ConnectionStateMachine,
+    shaper: &mut Shaper,
+    idle: &mut IdleWatch,
+    ws_stream: WsStream,
+    reconnect: bool,
+) -> ClientResult<Input> {
+    // Stream and sink for communicating with the server
+    let (mut write, mut read) = ws_stream.split();
+    shaper.reset_link();
+    idle.traffic();
+
+    // Display the reconnection message
+    if reconnect {
+        console::println!("{} Reconnected!", Glyph::Ok)
+    } else {
+        console::println!("{} Connected to the server!", Glyph::Ok)
+    }
+    .console()?;
+    console::headline("Connected").console()?;
+
+    // Tell the server what changed while it was disconnected
+    if let Some(sync) = handler.on_connected(endpoint, reconnect).await {
+        handlers::send_message(&sync, endpoint.protocol(), &mut write).await?;
+    }
+
+    // Loop to process messages received from the server
+    'recv: while let Some(message) = {
+        shaper.set_streaming(handler.is_streaming().await);
+        tokio::select! {
+            message = timeout(idle.timeout(), read.next()) => {
+                if message.is_err() {
+                    report_idle_drop(idle)?;
+                }
+                message.context("Connection timed out").network()?
+            }
+            // Send messages that are not responses (guest approvals)
+            notice = endpoint.next_notice() => {
+                for notice in shaper.submit(notice) {
+                    handlers::send_message(&notice, endpoint.protocol(), &mut write).await?;
+                    idle.traffic();
+                }
+                continue 'recv;
+            }
+            // Send the held back messages
+            _ = shaping::wait(shaper.deadline()) => {
+                for notice in shaper.flush() {
+                    handlers::send_message(&notice, endpoint.protocol(), &mut write).await?;
+                    idle.traffic();
+                }
+                continue 'recv;
+            }
+            // Heartbeat to measure the round trip time
+            _ = time::sleep_until(shaper.next_ping()) => {
+                write
+                    .send(Message::Ping(Vec::new()))
+                    .await
+                    .context("Failed to send ping message to the server")
+                    .network()?;
+                shaper.ping_sent();
+                idle.traffic();
+                continue 'recv;
+            }
+            // The connection is stale (the system resumed from sleep)
+            _ = endpoint.reconnect_requested() => {
+                return Ok(Input::Lost { planned: false });
+            }
+        }
+    } {
+        let message = match message {
+            Ok(message) => {
+                idle.traffic();
+                message
+            }
+            // Oversized or malformed frames
+            Err(err @ (WsError::Capacity(_) | WsError::Protocol(_) | WsError::Utf8)) => {
+                return Err(ClientError::Protocol(anyhow!(
+                    "Invalid message from the server: {err}"
+                )));
+            }
+            Err(err) => {
+                report_idle_drop(idle)?;
+                Err(err)
+                    .context("Failed to receive message from the server")
+                    .network()?
+            }
+        };
+
+        // Process each message
+        match message {
+            Message::Close(frame) => match close_action(frame.as_ref()) {
+                CloseAction::Reconnect { planned } => return Ok(Input::Lost { planned }),
+                CloseAction::Stop(reason) => {
+                    console::eprintln!(
+                        "{} Disconnected by the server: {}",
+                        Glyph::Err,
+                        if reason.is_empty() {
+                            "(no reason given)"
+                        } else {
+                            &reason
+                        }
+                    )
+                    .console()?;
+                    return Ok(Input::Stop);
+                }
+                CloseAction::Redirect(url) => {
+                    match endpoint.redirect(&url).await {
+                        Ok(server) => {
+                            console::println!("{} Moving to {server}", Glyph::Retry).console()?;
+                            machine.handle(Input::Reset);
+                        }
+                        Err(err) => console::eprintln!("{} {err}", Glyph::Warn).console()?,
+                    }
+                    return Ok(Input::Lost { planned: true });
+                }
+            },
+            Message::Ping(ping) => {
+                // Send a Pong message
+                write
+                    .send(Message::Pong(ping))
+                    .await
+                    .context("Failed to send pong message to the server")
+                    .network()?;
+                machine.handle(Input::Received);
+            }
+            Message::Text(text) => {
+                // Parse the JSON data
+                let msg = endpoint
+                    .protocol()
+                    .decode(&text)
+                    .map_err(|err| ClientError::Protocol(err.into()))?;
+                msg.check()
+                    .context("Invalid message from the server")
+                    .protocol()?;
+
+                // Process the message (true: exit requested)
+                if handler
+                    .handle_server_message(msg, endpoint, &mut write)
+                    .await?
+                {
+                    return Ok(Input::Stop);
+                }
+                machine.handle(Input::Received);
+            }
+            Message::Pong(_) => {
+                if let Some(sample) = shaper.pong_received() {
+                    endpoint.record_rtt(sample);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // The stream ended without a close frame
+    report_idle_drop(idle)?;
+    Ok(Input::Lost { planned: false })
+}
+
+/**
+ * Waits before reconnecting
+ * @param planned Whether the server closed the connection on purpose
+ */
+async fn back_off(endpoint: &Endpoint, wait: Duration, planned: bool) -> ClientResult<Input> {
+    console::headline("Reconnecting").console()?;
+    if planned {
+        console::println!(
+            "{} The server closed the connection. Reconnecting in {:.1} seconds...",
+            Glyph::Retry,
+            wait.as_secs_f32()
+        )
+        .console()?;
+    } else {
+        console::println!(
+            "{} Connection lost. Reconnecting in {:.1} seconds...",
+            Glyph::Retry,
+            wait.as_secs_f32()
+        )
+        .console()?;
+    }
+    // Skip the wait when a reconnect is requested
+    tokio::select! {
+        _ = time::sleep(wait) => (),
+        _ = endpoint.reconnect_requested() => (),
+    }
+    Ok(Input::Elapsed)
+}
+
+/// ID of this device, generated on the first run
+fn device_id(config: &Config) -> String {
+    match config.device.id.clone() {
+        Some(id) => id,
+        None => {
+            let id = Uuid::new_v4().to_string();
+            // Nothing is written in a container, so the ID only lasts until the restart
+            if container::is_enabled() {
+                logger::write(
+                    logger::Level::Warn,
+                    format_args!("No device ID, set REMOTEPLAY_INVITER__DEVICE__ID to keep {id} across restarts"),
+                );
+                return id;
+            }
+            if let Err(err) = config::write_device_id(&id) {
+                let _ = console::eprintln!("{} {}", Glyph::Err, err);
+            }
+            id
+        }
+    }
+}
+
+/// Reads or generates the configuration file
+fn load_config() -> Result<Config> {
+    read_or_generate_config(|| Config {
+        uuid: Uuid::new_v4().to_string(),
+        device: DeviceConfig {
+            id: Some(Uuid::new_v4().to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+}
+
+/// Creates an invite link with Steam when no instance is running
+async fn create_invite_directly(game: Option<u32>) -> Result<ipc::CreatedInvite> {
+    let config = load_config()?;
+    let steam = Backend::new()
+        .context("Failed to connect to Steam Client. Please make sure Steam is running.")
+        .steam()?;
+    let handler = Arc::new(Handler::new(
+        Arc::new(Mutex::new(steam)),
+        &config,
+        bans::BanList::load()?,
+        compat::CompatList::default(),
+        None,
+        Vec::new(),
+    ));
+    handler.setup_steam_callbacks().await;
+    handler.run_steam_callbacks();
+
+    let game = match game {
+        Some(game) => game,
+        None => handler
+            .running_game()
+            .await
+            .map_err(|code| anyhow!("No game available for Remote Play Together ({code:?})"))?,
+    };
+    let (guest_id, url) = timeout(
+        config.timeouts.invite(),
+        handler.create_invite(game, None, None, InviteKind::Player, None),
+    )
+    .await
+    .context("Steam did not create an invite link")??;
+    Ok(ipc::CreatedInvite {
+        guest_id,
+        game,
+        url,
+    })
+}
+
+/// Runs a subcommand
+async fn run_command(command: Command, json: bool) -> Result<()> {
+    match command {
+        Command::Logs(LogsCommand::Clean { all }) => {
+            logger::clean(&config::read_logging_config()?, all)
+        }
+        Command::Logs(LogsCommand::Tail { level }) => logger::tail(level).await,
+        Command::Status => ipc::print_status(json).await,
+        Command::Invite(InviteCommand::New {
+            game,
+            copy,
+            qr,
+            ttl,
+        }) => {
+            let invite = if ipc::is_running() {
+                ipc::create_invite(game, ttl).await?
+            } else if ttl.is_some() {
+                anyhow::bail!("A time-boxed invite needs the running instance to revoke it");
+            } else {
+                create_invite_directly(game).await?
+            };
+            ipc::print_invite(&invite, copy, qr, json)
+        }
+        Command::Guest(GuestCommand::Approve { guest_id }) => {
+            ipc::print_decide_guest(guest_id, true).await
+        }
+        Command::Guest(GuestCommand::Deny { guest_id }) => {
+            ipc::print_decide_guest(guest_id, false).await
+        }
+        Command::Guest(GuestCommand::Kick {
+            guest_id,
+            ban,
+            reason,
+        }) => ipc::print_kick(guest_id, ban, reason).await,
+        Command::Guest(GuestCommand::Bans) => ipc::print_bans().await,
+        Command::Guest(GuestCommand::Unban { steam_id }) => ipc::print_unban(steam_id).await,
+        Command::Audit { limit } => audit::show(limit),
+        Command::Pause(paused) => ipc::print_pause(paused).await,
+        Command::Chat(text) => ipc::print_chat(&text).await,
+        Command::Token(TokenCommand::Show) => token::show(&load_config()?),
+        Command::Token(TokenCommand::Export { file }) => token::export(&load_config()?, file),
+        Command::Token(TokenCommand::Import { source }) => token::import(&load_config()?, &source),
+        Command::Token(TokenCommand::Rotate { relink }) => token::rotate(&load_config()?, relink),
+        Command::Token(TokenCommand::Encrypt) => token::encrypt(&load_config()?),
+        Command::Token(TokenCommand::Decrypt) => token::decrypt(&load_config()?),
+        Command::Schema(direction) => schema::print(direction),
+        Command::VerifyProtocol => conformance::run(),
+        Command::Stats(StatsCommand::Show { period }) => stats::show(period, json),
+        Command::Stats(StatsCommand::Export { format, since }) => stats::export(format, since),
+        Command::Diagnose => {
+            let config = load_config()?;
+            nat::diagnose(&config.nat, config.timeouts.gateway()).await
+        }
+        Command::Agent => {
+            let config = load_config()?;
+            if let Err(err) = logger::init(&config.logging) {
+                console::eprintln!("{} {}", Glyph::Err, err)?;
+            }
+            agent::run(&config, &device_id(&config)).await
+        }
+    }
+}
@@ -0,0 +1,152 @@
+#[cfg(feature = "nvidia")]
+use nvml_wrapper::Nvml;
+use std::sync::Arc;
+#[cfg(feature = "metrics")]
+use std::time::Duration;
+#[cfg(feature = "metrics")]
+use sysinfo::System;
+use tokio::task::JoinHandle;
+#[cfg(feature = "metrics")]
+use tokio::time::{interval, MissedTickBehavior};
+
+#[cfg(feature = "metrics")]
+use crate::console::{self, Glyph};
+#[cfg(feature = "nvidia")]
+use crate::logger::{self, Level};
+use crate::{config::LoadConfig, handlers::Handler, models::HostLoad};
+
+/// Reads the utilization of the host
+#[cfg(feature = "metrics")]
+struct Sampler {
+    system: System,
+    /// NVIDIA management library (None without an NVIDIA driver)
+    #[cfg(feature = "nvidia")]
+    nvml: Option<Nvml>,
+}
+
+#[cfg(feature = "metrics")]
+impl Sampler {
+    fn new() -> Self {
+        Self {
+            system: System::new(),
+            #[cfg(feature = "nvidia")]
+            nvml: match Nvml::init() {
+                Ok(nvml) => Some(nvml),
+                Err(err) => {
+                    logger::write(Level::Info, format_args!("GPU load is unavailable: {err}"));
+                    None
+                }
+            },
+        }
+    }
+
+    /// Starts a measurement (the CPU usage is the average since the last refresh)
+    fn reset(&mut self) {
+        self.system.refresh_cpu_usage();
+    }
+
+    fn sample(&mut self) -> HostLoad {
+        self.system.refresh_cpu_usage();
+        let (gpu, encoder) = self.gpu();
+        HostLoad {
+            cpu: self.system.global_cpu_info().cpu_usage(),
+            gpu,
+            encoder,
+        }
+    }
+
+    /// Utilization of the busiest GPU and video encoder
+    #[cfg(feature = "nvidia")]
+    fn gpu(&self) -> (Option<f32>, Option<f32>) {
+        let busiest = |max: Option<f32>, value: u32| Some(max.unwrap_or(0.0).max(value as f32));
+        let Some(nvml) = &self.nvml else {
+            return (None, None);
+        };
+        let (mut gpu, mut encoder) = (None, None);
+        for index in 0..nvml.device_count().unwrap_or(0) {
+            let Ok(device) = nvml.device_by_index(index) else {
+                continue;
+            };
+            if let Ok(rates) = device.utilization_rates() {
+                gpu = busiest(gpu, rates.gpu);
+            }
+            if let Ok(info) = device.encoder_utilization() {
+                encoder = busiest(encoder, info.utilization);
+            }
+        }
+        (gpu, encoder)
+    }
+
+    /// Utilization of the busiest GPU and video encoder (needs the nvidia feature)
+    #[cfg(not(feature = "nvidia"))]
+    fn gpu(&self) -> (Option<f32>, Option<f32>) {
+        (None, None)
+    }
+}
+
+/// Load as a status line: `CPU 35%, GPU 60%, encoder 95%`
+pub fn describe(load: HostLoad) -> String {
+    let mut parts = vec![format!("CPU {:.0}%", load.cpu)];
+    if let Some(gpu) = load.gpu {
+        parts.push(format!("GPU {gpu:.0}%"));
+    }
+    if let Some(encoder) = load.encoder {
+        parts.push(format!("encoder {encoder:.0}%"));
+    }
+    parts.join(", ")
+}
+
+/// Samples the host load while guests are playing (needs the metrics feature)
+#[cfg(not(feature = "metrics"))]
+pub fn spawn(_handler: Arc<Handler>, _config: LoadConfig) -> Option<JoinHandle<()>> {
+    None
+}
+
+/// Samples the host load while guests are playing, reports it to the servers
+/// and warns when the video encoder is saturated (the guests' stream stutters)
+#[cfg(feature = "metrics")]
+pub fn spawn(handler: Arc<Handler>, config: LoadConfig) -> Option<JoinHandle<()>> {
+    if config.interval_secs == 0 || !sysinfo::IS_SUPPORTED_SYSTEM {
+        return None;
+    }
+    Some(tokio::spawn(async move {
+        let mut sampler = Sampler::new();
+        let mut streaming = false;
+        // Samples in a row above the encoder limit
+        let mut saturated = 0;
+        let mut ticker = interval(Duration::from_secs(config.interval_secs));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            if !handler.is_streaming().await {
+                if streaming {
+                    streaming = false;
+                    handler.set_load(None).await;
+                }
+                continue;
+            }
+            // The first sample of a session would include the idle time before it
+            if !streaming {
+                streaming = true;
+                saturated = 0;
+                sampler.reset();
+                continue;
+            }
+
+            let load = sampler.sample();
+            handler.set_load(Some(load)).await;
+            match load.encoder {
+                Some(encoder) if encoder >= config.encoder_warn_percent => {
+                    saturated += 1;
+                    if saturated == config.warn_after.max(1) {
+                        let _ = console::eprintln!(
+                            "{} The video encoder is saturated ({encoder:.0}%), the guests' stream may stutter. Lower the resolution or frame rate in the Remote Play settings",
+                            Glyph::Warn
+                        );
+                    }
+                }
+                _ => saturated = 0,
+            }
+        }
+    }))
+}
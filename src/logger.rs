@@ -0,0 +1,392 @@
+use anyhow::{anyhow, bail, Context as _, Result};
+use chrono::{DateTime, Local, Utc};
+use std::{
+    cmp::Reverse,
+    fmt::{self, Arguments, Display, Write as _},
+    fs::{self, File},
+    io::{Read as _, Seek as _, SeekFrom, Write as _},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    config::{self, LoggingConfig},
+    console, container, files,
+};
+
+/// Active log file name
+const ACTIVE_LOG: &str = "remoteplay-inviter.log";
+/// Prefix of rotated log files
+const ROTATED_PREFIX: &str = "remoteplay-inviter.";
+/// Extension of log files
+const LOG_EXTENSION: &str = ".log";
+
+/// Log levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    /// Label written in the log file
+    fn as_str(self) -> &'static str {
+        match self {
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        }
+    }
+
+    /// Name in the JSON log
+    fn name(self) -> &'static str {
+        match self {
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        }
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Level {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "info" => Ok(Level::Info),
+            "warn" | "warning" => Ok(Level::Warn),
+            "error" => Ok(Level::Error),
+            _ => Err(anyhow!(
+                "Unknown log level: {s} (expected info, warn or error)"
+            )),
+        }
+    }
+}
+
+/// Active log file
+struct LogFile {
+    /// Directory containing the log files
+    dir: PathBuf,
+    /// Open handle of the active log
+    file: File,
+    /// Current size of the active log
+    size: u64,
+    /// Rotation and retention settings
+    config: LoggingConfig,
+    /// Reused for every message, so frequent commands do not allocate
+    message: String,
+    entry: String,
+}
+
+/// Log file shared by the console macros
+static LOG_FILE: Mutex<Option<LogFile>> = Mutex::new(None);
+
+/// Log written to stdout as JSON lines instead of the file (container mode)
+static JSON_STDOUT: AtomicBool = AtomicBool::new(false);
+
+impl LogFile {
+    /// Opens (or creates) the active log in the given directory
+    fn open(dir: PathBuf, config: LoggingConfig) -> Result<Self> {
+        let path = dir.join(ACTIVE_LOG);
+        let file =
+            files::append(&path).with_context(|| format!("Unable to open log file: {:?}", path))?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(Self {
+            dir,
+            file,
+            size,
+            config,
+            message: String::new(),
+            entry: String::new(),
+        })
+    }
+
+    /// Appends a message, rotating the file first if it grew too large
+    fn write(&mut self, level: Level, args: Arguments<'_>) -> Result<()> {
+        // RFC 3339 in UTC with seconds (e.g. 2024-07-01T12:00:00Z)
+        let timestamp = Utc::now().format("%Y-%m-%dT%H:%M:%SZ");
+        self.message.clear();
+        self.entry.clear();
+        let _ = self.message.write_fmt(args);
+        for line in self.message.lines().filter(|line| !line.trim().is_empty()) {
+            let _ = writeln!(self.entry, "{timestamp} [{level}] {line}");
+        }
+
+        let len = self.entry.len() as u64;
+        if self.size > 0 && self.size + len > self.config.max_size_kb * 1024 {
+            self.rotate()?;
+        }
+
+        self.file
+            .write_all(self.entry.as_bytes())
+            .context("Unable to write log file")?;
+        self.size += len;
+        Ok(())
+    }
+
+    /// Renames the active log with a timestamp and starts a new one
+    fn rotate(&mut self) -> Result<()> {
+        let active = self.dir.join(ACTIVE_LOG);
+        let rotated = rotated_path(&self.dir)?;
+        fs::rename(&active, &rotated)
+            .with_context(|| format!("Unable to rotate log file: {:?}", active))?;
+        // Keep the buffers, they hold the entry being written
+        let fresh = Self::open(self.dir.clone(), self.config.clone())?;
+        self.file = fresh.file;
+        self.size = fresh.size;
+        apply_retention(&self.dir, &self.config)?;
+        Ok(())
+    }
+}
+
+/**
+ * Name for the next rotated log, with a counter when another one was rotated in the same second
+ * @return A path no file exists at (an archive is never overwritten)
+ */
+fn rotated_path(dir: &Path) -> Result<PathBuf> {
+    let stamp = Local::now().format("%Y%m%d-%H%M%S").to_string();
+    for counter in 0..1000 {
+        let name = match counter {
+            0 => format!("{ROTATED_PREFIX}{stamp}{LOG_EXTENSION}"),
+            _ => format!("{ROTATED_PREFIX}{stamp}-{counter}{LOG_EXTENSION}"),
+        };
+        let path = dir.join(name);
+        if !path.exists() {
+            return Ok(path);
+        }
+    }
+    bail!(
+        "Too many log rotations in {:?}, not overwriting an archive",
+        dir
+    )
+}
+
+/// Directory containing the log files
+pub fn log_dir() -> Result<PathBuf> {
+    Ok(config::get_exe_path()?.with_extension("logs"))
+}
+
+/// Starts writing console output to the log file (to stdout as JSON in container mode)
+pub fn init(config: &LoggingConfig) -> Result<()> {
+    if container::is_enabled() {
+        init_json_stdout();
+        return Ok(());
+    }
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let dir = log_dir()?;
+    files::create_dir_all(&dir)
+        .with_context(|| format!("Unable to create log directory: {:?}", dir))?;
+    apply_retention(&dir, config)?;
+
+    let log = LogFile::open(dir, config.clone())?;
+    *LOG_FILE
+        .lock()
+        .map_err(|_| anyhow!("Failed to lock log file"))? = Some(log);
+    Ok(())
+}
+
+/// Writes the log to stdout as JSON lines from now on (for the log collectors of containers)
+pub fn init_json_stdout() {
+    JSON_STDOUT.store(true, Ordering::Relaxed);
+}
+
+/// Writes a message as a JSON line on stdout
+fn write_json(level: Level, args: Arguments<'_>) {
+    let message = args.to_string();
+    let entry = serde_json::json!({
+        "time": Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        "level": level.name(),
+        "message": message.trim(),
+    });
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{entry}");
+    let _ = stdout.flush();
+}
+
+/// Appends a message to the log file (does nothing if file logging is disabled)
+pub fn write(level: Level, args: Arguments<'_>) {
+    if JSON_STDOUT.load(Ordering::Relaxed) {
+        return write_json(level, args);
+    }
+    let Ok(mut log) = LOG_FILE.lock() else {
+        return;
+    };
+    if let Some(log) = log.as_mut() {
+        // A failing log file must never break the console output
+        let _ = log.write(level, args);
+    }
+}
+
+/// Rotated log files in the directory, newest first
+fn rotated_logs(dir: &Path) -> Result<Vec<(PathBuf, SystemTime)>> {
+    let mut logs = Vec::new();
+    if !dir.exists() {
+        return Ok(logs);
+    }
+    for entry in
+        fs::read_dir(dir).with_context(|| format!("Unable to read log directory: {:?}", dir))?
+    {
+        let entry = entry.context("Unable to read log directory entry")?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name == ACTIVE_LOG || !name.starts_with(ROTATED_PREFIX) || !name.ends_with(LOG_EXTENSION)
+        {
+            continue;
+        }
+        let modified = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        logs.push((entry.path(), modified));
+    }
+    logs.sort_by_key(|(_, modified)| Reverse(*modified));
+    Ok(logs)
+}
+
+/// Deletes rotated logs that exceed the retention policy
+/// @return Number of deleted files
+fn apply_retention(dir: &Path, config: &LoggingConfig) -> Result<usize> {
+    let max_age = Duration::from_secs(config.max_age_days * 24 * 60 * 60);
+    let now = SystemTime::now();
+    let mut deleted = 0;
+    for (index, (path, modified)) in rotated_logs(dir)?.into_iter().enumerate() {
+        let expired = now.duration_since(modified).unwrap_or_default() > max_age;
+        if index >= config.max_files || expired {
+            fs::remove_file(&path)
+                .with_context(|| format!("Unable to delete log file: {:?}", path))?;
+            deleted += 1;
+        }
+    }
+    Ok(deleted)
+}
+
+/**
+ * Reads the log lines written since the given time
+ * @return The lines, oldest first (empty if file logging is disabled)
+ */
+pub fn recent(since: SystemTime) -> Result<Vec<String>> {
+    let dir = log_dir()?;
+    // Rotated logs written to before the start cannot contain newer lines
+    let mut paths: Vec<PathBuf> = rotated_logs(&dir)?
+        .into_iter()
+        .take_while(|(_, modified)| *modified >= since)
+        .map(|(path, _)| path)
+        .collect();
+    paths.reverse();
+    paths.push(dir.join(ACTIVE_LOG));
+
+    let since = DateTime::<Utc>::from(since);
+    let mut lines = Vec::new();
+    for path in paths {
+        let Ok(content) = fs::read(&path) else {
+            continue;
+        };
+        lines.extend(
+            String::from_utf8_lossy(&content)
+                .lines()
+                .filter(|line| line_time(line).is_some_and(|time| time >= since))
+                .map(str::to_owned),
+        );
+    }
+    Ok(lines)
+}
+
+/// Time a log line was written
+fn line_time(line: &str) -> Option<DateTime<Utc>> {
+    let (timestamp, _) = line.split_once(' ')?;
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// `logs clean` subcommand
+pub fn clean(config: &LoggingConfig, all: bool) -> Result<()> {
+    let dir = log_dir()?;
+    let deleted = if all {
+        let logs = rotated_logs(&dir)?;
+        for (path, _) in &logs {
+            fs::remove_file(path)
+                .with_context(|| format!("Unable to delete log file: {:?}", path))?;
+        }
+        logs.len()
+    } else {
+        apply_retention(&dir, config)?
+    };
+    console::println!(
+        "{} Deleted {deleted} rotated log file(s) in {:?}",
+        console::Glyph::Ok,
+        dir
+    )?;
+    Ok(())
+}
+
+/// Level of a log line (lines without a level are treated as info)
+fn line_level(line: &str) -> Level {
+    line.split_once(" [")
+        .and_then(|(_, rest)| rest.split_once(']'))
+        .and_then(|(level, _)| level.parse().ok())
+        .unwrap_or(Level::Info)
+}
+
+/// `logs tail` subcommand: prints the recent lines and follows the active log
+pub async fn tail(level: Level) -> Result<()> {
+    let path = log_dir()?.join(ACTIVE_LOG);
+    let mut position = 0;
+    let mut pending = String::new();
+    let mut first = true;
+
+    loop {
+        if let Ok(mut file) = File::open(&path) {
+            let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+            // The log was rotated, start over from the new file
+            if len < position {
+                position = 0;
+            }
+            if len > position {
+                file.seek(SeekFrom::Start(position))
+                    .context("Unable to read log file")?;
+                let mut buf = Vec::new();
+                file.read_to_end(&mut buf)
+                    .context("Unable to read log file")?;
+                position += buf.len() as u64;
+                pending.push_str(&String::from_utf8_lossy(&buf));
+
+                // Only print complete lines
+                let complete = pending.rfind('\n').map_or(0, |i| i + 1);
+                let lines: Vec<&str> = pending[..complete]
+                    .lines()
+                    .filter(|line| line_level(line) >= level)
+                    .collect();
+                // Show only the last few lines of the existing content
+                let skip = if first {
+                    lines.len().saturating_sub(10)
+                } else {
+                    0
+                };
+                for line in &lines[skip..] {
+                    console::println!("{line}")?;
+                }
+                pending.drain(..complete);
+            }
+        } else if first {
+            console::println!("{} Waiting for log file: {:?}", console::Glyph::Wait, path)?;
+        }
+        first = false;
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
@@ -0,0 +1,243 @@
+//! macOS integration
+//!
+//! - Shows the connection state and guest count in the menu bar
+//! - Posts guest join / disconnect notifications (needs the app bundle for permission)
+//! - Opts out of App Nap so the connection is not throttled in the background
+//! - Reconnects right away when the Mac wakes from sleep
+
+use anyhow::{Context as _, Result};
+use block2::RcBlock;
+use dispatch::Queue;
+use objc2::{rc::Retained, runtime::Bool};
+use objc2_app_kit::{
+    NSApplication, NSApplicationActivationPolicy, NSStatusBar, NSStatusItem,
+    NSVariableStatusItemLength, NSWorkspace, NSWorkspaceDidWakeNotification,
+};
+use objc2_foundation::{
+    MainThreadMarker, NSActivityOptions, NSBundle, NSError, NSNotification, NSProcessInfo, NSString,
+};
+use objc2_user_notifications::{
+    UNAuthorizationOptions, UNMutableNotificationContent, UNNotificationRequest,
+    UNUserNotificationCenter,
+};
+use std::{
+    cell::RefCell, collections::BTreeSet, future::Future, mem, process, ptr::NonNull, sync::Arc,
+    thread,
+};
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+use crate::{
+    config::MacosConfig,
+    console::{self, Glyph},
+    events::Event,
+    handlers::{ConnectionState, Handler},
+};
+
+thread_local! {
+    /// Menu bar item (only touched on the main thread)
+    static STATUS_ITEM: RefCell<Option<Retained<NSStatusItem>>> = const { RefCell::new(None) };
+}
+
+/// Runs the inviter on a worker thread while the main thread runs the AppKit event loop
+pub fn run_app<F>(inviter: fn() -> F) -> Result<()>
+where
+    F: Future<Output = Result<()>> + 'static,
+{
+    let mtm = MainThreadMarker::new().context("AppKit must run on the main thread")?;
+
+    thread::spawn(move || {
+        let result = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .context("Failed to start the async runtime")
+            .and_then(|runtime| runtime.block_on(inviter()));
+        if let Err(err) = &result {
+            eprintln!("Error: {err:?}");
+        }
+        // The AppKit event loop never returns, so the process ends here
+        process::exit(if result.is_ok() { 0 } else { 1 });
+    });
+
+    let app = NSApplication::sharedApplication(mtm);
+    // Menu bar item only, the console stays in the terminal
+    app.setActivationPolicy(NSApplicationActivationPolicy::Accessory);
+    unsafe { app.run() };
+    Ok(())
+}
+
+/// Hooks the handler up to the menu bar, notifications and power events
+pub fn attach(handler: &Arc<Handler>, config: MacosConfig) {
+    // Keep the connection responsive while the terminal is in the background
+    let activity = unsafe {
+        NSProcessInfo::processInfo().beginActivityWithOptions_reason(
+            NSActivityOptions::NSActivityUserInitiatedAllowingIdleSystemSleep,
+            &NSString::from_str("Relaying Remote Play invites"),
+        )
+    };
+    // The activity lasts until it is ended, which is never
+    mem::forget(activity);
+
+    if config.reconnect_on_wake {
+        let handler = handler.clone();
+        let runtime = tokio::runtime::Handle::current();
+        let block = RcBlock::new(move |_: NonNull<NSNotification>| {
+            // Faster than waiting for the clock based detection
+            let handler = handler.clone();
+            runtime.spawn(async move { handler.on_resume().await });
+        });
+        let observer = unsafe {
+            NSWorkspace::sharedWorkspace()
+                .notificationCenter()
+                .addObserverForName_object_queue_usingBlock(
+                    Some(NSWorkspaceDidWakeNotification),
+                    None,
+                    None,
+                    &block,
+                )
+        };
+        // Observe for the lifetime of the process
+        mem::forget(observer);
+    }
+
+    let notifications = config.notifications && request_notifications();
+    if !config.menu_bar && !notifications {
+        return;
+    }
+
+    let mut events = handler.subscribe();
+    let handler = handler.clone();
+    tokio::spawn(async move {
+        let mut state = ConnectionState::Connecting;
+        let mut paused = false;
+        let mut guests = BTreeSet::<u64>::new();
+        if config.menu_bar {
+            set_status(status_title(state, paused, guests.len()));
+        }
+
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            match event {
+                Event::GuestJoined {
+                    guest_id,
+                    name,
+                    persona,
+                    endpoint,
+                    ..
+                } => {
+                    guests.insert(guest_id);
+                    if notifications && !handler.is_quiet() {
+                        let name = name.or(persona);
+                        let name = name.as_deref().unwrap_or("A guest");
+                        let body = match endpoint {
+                            Some(endpoint) => format!("{name} joined the game (via {endpoint})"),
+                            None => format!("{name} joined the game"),
+                        };
+                        post_notification("Guest joined", &body);
+                    }
+                }
+                Event::GuestLeft { guest_id, .. } => {
+                    guests.remove(&guest_id);
+                }
+                Event::ConnectionChanged { state: new_state } => {
+                    // Only notify when an established connection is lost
+                    if notifications
+                        && !handler.is_quiet()
+                        && state == ConnectionState::Connected
+                        && new_state != ConnectionState::Connected
+                    {
+                        post_notification("Disconnected", "Connection to the server was lost");
+                    }
+                    state = new_state;
+                }
+                Event::PauseChanged { paused: new_paused } => paused = new_paused,
+                _ => continue,
+            }
+            if config.menu_bar {
+                set_status(status_title(state, paused, guests.len()));
+            }
+        }
+    });
+}
+
+/// Text of the menu bar item
+fn status_title(state: ConnectionState, paused: bool, guests: usize) -> String {
+    match state {
+        ConnectionState::Connecting => "🎮 Connecting".to_owned(),
+        ConnectionState::Reconnecting => "🎮 Offline".to_owned(),
+        ConnectionState::Pairing => "🎮 Pairing".to_owned(),
+        ConnectionState::Connected if paused => "🎮 Paused".to_owned(),
+        ConnectionState::Connected if guests == 1 => "🎮 1 guest".to_owned(),
+        ConnectionState::Connected => format!("🎮 {guests} guests"),
+    }
+}
+
+/// Updates the menu bar item (created on first use)
+fn set_status(title: String) {
+    // AppKit objects may only be used on the main thread
+    Queue::main().exec_async(move || {
+        let Some(mtm) = MainThreadMarker::new() else {
+            return;
+        };
+        STATUS_ITEM.with(|item| {
+            let mut item = item.borrow_mut();
+            let item = item.get_or_insert_with(|| unsafe {
+                NSStatusBar::systemStatusBar().statusItemWithLength(NSVariableStatusItemLength)
+            });
+            if let Some(button) = unsafe { item.button(mtm) } {
+                unsafe { button.setTitle(&NSString::from_str(&title)) };
+            }
+        });
+    });
+}
+
+/// Asks for permission to post notifications
+/// @return Whether notifications can be requested (the answer arrives asynchronously)
+fn request_notifications() -> bool {
+    // Notification Center refuses processes that are not inside an app bundle
+    if unsafe { NSBundle::mainBundle().bundleIdentifier() }.is_none() {
+        let _ = console::eprintln!(
+            "{} Notifications need the app bundle (Remote Play Inviter.app)",
+            Glyph::Err
+        );
+        return false;
+    }
+
+    let block = RcBlock::new(|granted: Bool, _: *mut NSError| {
+        if !granted.as_bool() {
+            let _ = console::eprintln!(
+                "{} Notifications are not allowed. Enable them in System Settings > Notifications",
+                Glyph::Err
+            );
+        }
+    });
+    unsafe {
+        UNUserNotificationCenter::currentNotificationCenter()
+            .requestAuthorizationWithOptions_completionHandler(
+                UNAuthorizationOptions::UNAuthorizationOptionAlert
+                    | UNAuthorizationOptions::UNAuthorizationOptionSound,
+                &block,
+            );
+    }
+    true
+}
+
+/// Posts a notification (dropped by the system if permission was not granted)
+fn post_notification(title: &str, body: &str) {
+    unsafe {
+        let content = UNMutableNotificationContent::new();
+        content.setTitle(&NSString::from_str(title));
+        content.setBody(&NSString::from_str(body));
+        let request = UNNotificationRequest::requestWithIdentifier_content_trigger(
+            &NSString::from_str(&Uuid::new_v4().to_string()),
+            &content,
+            None,
+        );
+        UNUserNotificationCenter::currentNotificationCenter()
+            .addNotificationRequest_withCompletionHandler(&request, None);
+    }
+}
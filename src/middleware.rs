@@ -0,0 +1,336 @@
+use anyhow::Result;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+use tokio::time::Instant;
+
+use crate::{
+    config::PermissionsConfig,
+    console,
+    dedup::RecentCommands,
+    endpoint::Endpoint,
+    logger::{self, Level},
+    models::{ClientCmd, ClientMessage, ErrorStatus, RevokeReason, ServerCmd, ServerMessage},
+};
+
+/// Invite link returned for the simulated `link` and `admit_next` commands
+const DRY_RUN_URL: &str = "https://s.team/p/DRY-RUN/00000000";
+
+/// Dry run mode (--dry-run, for testing bots against a real client)
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Answers the server commands that change something with simulated results from now on
+pub fn set_dry_run(enabled: bool) {
+    DRY_RUN.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether the server commands that change something are simulated
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// What a stage decided about a server message
+pub enum Verdict {
+    /// Pass the message to the next stage
+    Continue,
+    /// Answer without running the command
+    Reply(ClientMessage),
+    /// Drop the message without an answer
+    Drop,
+}
+
+/// Stage that every server message passes before its command runs
+/// Cross-cutting checks live here instead of in each command
+pub trait Middleware: Send + Sync {
+    /// Inspects a message before the command runs
+    fn before(&self, msg: &ServerMessage, endpoint: &Endpoint) -> Result<Verdict>;
+
+    /// Sees the answer to a message that passed this stage
+    fn after(&self, _msg: &ServerMessage, _endpoint: &Endpoint, _response: &ClientMessage) {}
+
+    /// Sees that the command of a message that passed this stage failed without an answer
+    fn failed(&self, _msg: &ServerMessage, _endpoint: &Endpoint) {}
+}
+
+/// Stages in the order the messages pass them
+pub fn chain(permissions: &PermissionsConfig) -> Vec<Box<dyn Middleware>> {
+    let mut stages: Vec<Box<dyn Middleware>> = vec![
+        Box::new(Permissions(permissions.clone())),
+        Box::new(RateLimit::new(permissions)),
+        Box::new(Dedup::default()),
+        Box::new(Log),
+    ];
+    // Last, so the simulated commands are still checked and logged
+    if is_dry_run() {
+        stages.push(Box::new(DryRun));
+    }
+    stages
+}
+
+/**
+ * Passes a message through the stages before its command runs
+ * @return Continue if every stage let it through (the stages it passed already saw a Reply)
+ */
+pub fn screen(
+    stages: &[Box<dyn Middleware>],
+    msg: &ServerMessage,
+    endpoint: &Endpoint,
+) -> Result<Verdict> {
+    for (passed, stage) in stages.iter().enumerate() {
+        match stage.before(msg, endpoint)? {
+            Verdict::Continue => (),
+            Verdict::Reply(res) => {
+                for stage in &stages[..passed] {
+                    stage.after(msg, endpoint, &res);
+                }
+                return Ok(Verdict::Reply(res));
+            }
+            Verdict::Drop => return Ok(Verdict::Drop),
+        }
+    }
+    Ok(Verdict::Continue)
+}
+
+/// Error answer to a request
+fn error(msg: &ServerMessage, code: ErrorStatus) -> Verdict {
+    Verdict::Reply(ClientMessage::error(msg.request_id(), code))
+}
+
+/// Name of the user who sent the command
+fn claimer(msg: &ServerMessage) -> &str {
+    msg.user.as_ref().map_or("?", |user| &user.name)
+}
+
+/// Refuses the commands the host did not allow
+struct Permissions(PermissionsConfig);
+
+impl Middleware for Permissions {
+    fn before(&self, msg: &ServerMessage, _endpoint: &Endpoint) -> Result<Verdict> {
+        let Some(command) = msg.cmd.name().filter(|command| !self.0.permits(command)) else {
+            return Ok(Verdict::Continue);
+        };
+        console::println!(
+            "-> Deny Command       : claimer={0}, cmd={command} (not permitted)",
+            claimer(msg)
+        )?;
+        Ok(error(msg, ErrorStatus::PermissionDenied))
+    }
+}
+
+/// Refuses requests beyond the allowed rate of each server (token bucket)
+struct RateLimit {
+    /// Requests per minute (0: unlimited)
+    per_minute: u32,
+    /// Requests allowed at once after an idle time
+    burst: u32,
+    /// Tokens left and when they were counted, by server
+    buckets: Mutex<HashMap<usize, (f64, Instant)>>,
+}
+
+impl RateLimit {
+    fn new(permissions: &PermissionsConfig) -> Self {
+        Self {
+            per_minute: permissions.max_per_minute,
+            burst: permissions.burst.max(1),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Middleware for RateLimit {
+    fn before(&self, msg: &ServerMessage, endpoint: &Endpoint) -> Result<Verdict> {
+        // Notifications (no answer expected) and connection control are never refused
+        if self.per_minute == 0
+            || msg.id.is_empty()
+            || matches!(msg.cmd, ServerCmd::Exit | ServerCmd::Backoff { .. })
+        {
+            return Ok(Verdict::Continue);
+        }
+        let now = Instant::now();
+        let limited = {
+            let mut buckets = self.buckets.lock().unwrap_or_else(|err| err.into_inner());
+            let (tokens, counted) = buckets
+                .entry(endpoint.index)
+                .or_insert((self.burst as f64, now));
+            let refill = now.duration_since(*counted).as_secs_f64() * self.per_minute as f64 / 60.0;
+            *tokens = (*tokens + refill).min(self.burst as f64);
+            *counted = now;
+            if *tokens >= 1.0 {
+                *tokens -= 1.0;
+                false
+            } else {
+                true
+            }
+        };
+        if !limited {
+            return Ok(Verdict::Continue);
+        }
+        console::println!(
+            "-> Deny Command       : claimer={0}, cmd={1} (more than {2} per minute)",
+            claimer(msg),
+            msg.cmd.name().unwrap_or("?"),
+            self.per_minute
+        )?;
+        Ok(error(msg, ErrorStatus::RateLimited))
+    }
+}
+
+/// Answers a retried command with the first response instead of running it twice
+#[derive(Default)]
+struct Dedup(Mutex<RecentCommands>);
+
+impl Dedup {
+    fn recent(&self) -> std::sync::MutexGuard<'_, RecentCommands> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl Middleware for Dedup {
+    fn before(&self, msg: &ServerMessage, endpoint: &Endpoint) -> Result<Verdict> {
+        let Some(key) = &msg.key else {
+            return Ok(Verdict::Continue);
+        };
+        let key = (endpoint.index, key.clone());
+        let response = {
+            let mut recent = self.recent();
+            let response = recent.get(&key);
+            if response.is_none() {
+                recent.insert(key.clone());
+            }
+            response
+        };
+        let Some(response) = response else {
+            return Ok(Verdict::Continue);
+        };
+        console::println!(
+            "-> Duplicate Command  : cmd={0}, key={1}",
+            msg.cmd.name().unwrap_or("?"),
+            key.1
+        )?;
+        Ok(match response {
+            Some(res) => Verdict::Reply(ClientMessage::reply(msg.request_id(), res.cmd)),
+            None => Verdict::Drop,
+        })
+    }
+
+    fn after(&self, msg: &ServerMessage, endpoint: &Endpoint, response: &ClientMessage) {
+        if let Some(key) = &msg.key {
+            self.recent()
+                .set_response(&(endpoint.index, key.clone()), response);
+        }
+    }
+
+    fn failed(&self, msg: &ServerMessage, endpoint: &Endpoint) {
+        // The retry after the reconnect runs the command again instead of getting no answer
+        if let Some(key) = &msg.key {
+            self.recent().remove(&(endpoint.index, key.clone()));
+        }
+    }
+}
+
+/// Writes every command to the log file (the console only shows the ones with an effect)
+struct Log;
+
+impl Middleware for Log {
+    fn before(&self, msg: &ServerMessage, endpoint: &Endpoint) -> Result<Verdict> {
+        logger::write(
+            Level::Info,
+            format_args!(
+                "Server command: host={0}, cmd={1}, id={2}, claimer={3}",
+                endpoint.host,
+                msg.cmd.name().unwrap_or("?"),
+                msg.id,
+                claimer(msg)
+            ),
+        );
+        Ok(Verdict::Continue)
+    }
+}
+
+/// Answers the commands that would call Steam or stop the client with a simulated result
+struct DryRun;
+
+impl Middleware for DryRun {
+    fn before(&self, msg: &ServerMessage, _endpoint: &Endpoint) -> Result<Verdict> {
+        let simulated = match &msg.cmd {
+            ServerCmd::Link { .. } | ServerCmd::AdmitNext { .. } => Some(ClientCmd::Link {
+                url: DRY_RUN_URL.to_owned(),
+                caveats: Vec::new(),
+                instructions: None,
+            }),
+            &ServerCmd::Revoke { guest_id, .. } => Some(ClientCmd::Revoked {
+                guest_id,
+                reason: RevokeReason::Server,
+            }),
+            &ServerCmd::HostRequest { game, .. } => Some(ClientCmd::HostReady { game }),
+            // Nothing to answer, but the client keeps running
+            ServerCmd::HandoffReady { .. } | ServerCmd::Exit => None,
+            _ => return Ok(Verdict::Continue),
+        };
+        console::println!(
+            "-> Dry Run            : claimer={0}, cmd={1} (simulated)",
+            claimer(msg),
+            msg.cmd.name().unwrap_or("?")
+        )?;
+        Ok(match simulated {
+            Some(cmd) => Verdict::Reply(ClientMessage::reply(msg.request_id(), cmd)),
+            None => Verdict::Drop,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_tungstenite::tungstenite::http::HeaderMap;
+
+    fn game_request(id: &str) -> ServerMessage {
+        serde_json::from_str(&format!(
+            r#"{{"id":"{id}","user":null,"key":"k1","cmd":"game"}}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn failed_commands_run_again_on_retry() {
+        let dedup = Dedup::default();
+        let endpoint = Endpoint::new(0, "ws://localhost".to_owned(), None, HeaderMap::new());
+
+        let msg = game_request("1");
+        assert!(matches!(
+            dedup.before(&msg, &endpoint).unwrap(),
+            Verdict::Continue
+        ));
+        dedup.failed(&msg, &endpoint);
+
+        // The retry after the reconnect runs and its answer is remembered
+        let retry = game_request("2");
+        assert!(matches!(
+            dedup.before(&retry, &endpoint).unwrap(),
+            Verdict::Continue
+        ));
+        dedup.after(
+            &retry,
+            &endpoint,
+            &ClientMessage::reply(
+                retry.request_id(),
+                ClientCmd::GameId {
+                    game: 480,
+                    caveats: Vec::new(),
+                    store: None,
+                },
+            ),
+        );
+        assert!(matches!(
+            dedup.before(&game_request("3"), &endpoint).unwrap(),
+            Verdict::Reply(ClientMessage {
+                id,
+                cmd: ClientCmd::GameId { game: 480, .. },
+            }) if id == "3"
+        ));
+    }
+}
@@ -1,7 +1,15 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+
+use crate::compat::CompatEntry;
+
+pub mod v1;
 
 /// Connection error message
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ConnectionErrorMessage {
     /// Error message
     pub message: Option<String>,
@@ -10,7 +18,7 @@ pub struct ConnectionErrorMessage {
 }
 
 /// Error types for the daemon server
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "error")]
 pub enum ConnectionErrorType {
     /// Outdated daemon
@@ -21,24 +29,106 @@ pub enum ConnectionErrorType {
         /// Download URL
         download: String,
     },
+    /// The token is no longer linked with a Discord account (unlinked in Discord)
+    #[serde(rename = "unlinked")]
+    Unlinked {
+        /// Page of the bot to link the client again
+        #[serde(default)]
+        link: Option<String>,
+    },
     #[serde(other)]
+    #[schemars(skip)]
     Other,
 }
 
+/// Longest request ID or idempotency key
+const MAX_ID_LEN: usize = 256;
+/// Longest text shown in the console (announcements, chat messages, names)
+const MAX_TEXT_LEN: usize = 8 * 1024;
+/// Most games in a compatibility list
+const MAX_COMPAT_GAMES: usize = 10_000;
+/// Longest wait a server can ask for (a day)
+const MAX_BACKOFF_SECS: u64 = 24 * 60 * 60;
+
+/// Protocol version spoken with a server, chosen in the WebSocket handshake
+/// The types of this module are the latest version, older ones are converted from and to them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Protocol {
+    /// Invites and announcements only (`v1` module)
+    V1,
+    /// Current protocol
+    #[default]
+    V2,
+}
+
+impl Protocol {
+    /// Header of the connection request (the versions the client speaks) and the response (the one the server chose)
+    pub const HEADER: &'static str = "x-protocol-version";
+    /// Versions the client speaks
+    pub const SUPPORTED: &'static str = "1, 2";
+
+    /**
+     * Reads the version the server chose
+     * @return The version (None if the client does not speak it)
+     */
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "1" => Some(Self::V1),
+            "2" => Some(Self::V2),
+            _ => None,
+        }
+    }
+
+    /// Reads a request of the server
+    /// Text frames are valid UTF-8 already, so parsing the str skips checking it again (from_slice is slower)
+    pub fn decode(self, text: &str) -> serde_json::Result<ServerMessage> {
+        match self {
+            Self::V1 => serde_json::from_str::<v1::ServerMessage>(text).map(Into::into),
+            Self::V2 => serde_json::from_str(text),
+        }
+    }
+
+    /**
+     * Writes a message for the server
+     * @return The JSON text (None if this version has no such message)
+     */
+    pub fn encode(self, msg: &ClientMessage) -> serde_json::Result<Option<String>> {
+        match self {
+            Self::V1 => match v1::ClientMessage::view(msg) {
+                Some(msg) => serde_json::to_string(&msg).map(Some),
+                None => Ok(None),
+            },
+            Self::V2 => serde_json::to_string(msg).map(Some),
+        }
+    }
+}
+
+impl Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::V1 => "v1",
+            Self::V2 => "v2",
+        })
+    }
+}
+
 /// A data structure to represent a request to the daemon
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ServerMessage {
     /// Request ID
     pub id: String,
     /// Request user
     pub user: Option<User>,
+    /// Idempotency key (the same for every retry of a command)
+    #[serde(default)]
+    pub key: Option<String>,
     /// Request type
     #[serde(flatten)]
     pub cmd: ServerCmd,
 }
 
 /// Request Type
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "cmd")]
 pub enum ServerCmd {
     /// Announce message
@@ -57,16 +147,220 @@ pub enum ServerCmd {
     Link {
         /// Game ID
         game: u32,
+        /// Revoke the link after this many joins (overrides the client setting)
+        #[serde(default)]
+        max_uses: Option<u32>,
+        /// Kind of invite
+        #[serde(default)]
+        kind: InviteKind,
+        /// Revoke the link after this many seconds (a time-boxed invite)
+        #[serde(default)]
+        ttl_secs: Option<u64>,
+    },
+    /// Invite the next guest of the server's waitlist (refused when no slot is free)
+    #[serde(rename = "admit_next")]
+    AdmitNext {
+        /// Game ID
+        game: u32,
+        /// Kind of invite
+        #[serde(default)]
+        kind: InviteKind,
+    },
+    /// Revoke an invite link
+    #[serde(rename = "revoke")]
+    Revoke {
+        /// Guest ID of the link
+        guest_id: u64,
+        /// Why the link is revoked (recorded in the audit log)
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    /// Chat message from a Discord user to the host
+    #[serde(rename = "chat")]
+    Chat {
+        /// Display name of the sender (the request user if not set)
+        #[serde(default)]
+        from: Option<String>,
+        /// Message text
+        text: String,
+    },
+    /// Latest game compatibility list
+    #[serde(rename = "compat")]
+    Compat {
+        /// Games known to work poorly with Remote Play Together
+        games: Vec<CompatEntry>,
+    },
+    /// Another client wants to stop hosting: start the game to take the session over
+    #[serde(rename = "host_request")]
+    HostRequest {
+        /// Game ID
+        game: u32,
+        /// Name of the device that is handing the session over
+        #[serde(default)]
+        from: Option<String>,
+    },
+    /// Another client took the session over (answer to a handoff)
+    #[serde(rename = "handoff_ready")]
+    HandoffReady {
+        /// Name of the device hosting from now on
+        #[serde(default)]
+        host: Option<String>,
+    },
+    /// No other client took the session over (answer to a handoff)
+    #[serde(rename = "handoff_failed")]
+    HandoffFailed,
+    /// Ask the host for the recent log to debug a reported failure (sent only after the host agrees)
+    #[serde(rename = "request_diagnostics")]
+    RequestDiagnostics {
+        /// Minutes of log (capped by the host)
+        minutes: u64,
+        /// Why the log is needed (shown to the host)
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    /// Wake the gaming PC for a session (answered by a Wake-on-LAN agent)
+    #[serde(rename = "wake_host")]
+    WakeHost {
+        /// MAC address of the gaming PC (the agent's configured one if not set)
+        #[serde(default)]
+        mac: Option<String>,
+    },
+    /// Wait before reconnecting (sent before the server restarts)
+    #[serde(rename = "backoff")]
+    Backoff {
+        /// Seconds to wait
+        secs: u64,
     },
+    /// The token is not linked with a Discord account yet (asks for a pairing code)
+    #[serde(rename = "pair")]
+    Pair,
+    /// The pairing code was entered in Discord
+    #[serde(rename = "paired")]
+    Paired {
+        /// Name of the Discord account the client is linked with
+        #[serde(default)]
+        name: Option<String>,
+    },
+    /// Another device connected with the same token and gets the invites from now on
+    #[serde(rename = "conflict")]
+    Conflict {
+        /// Name of the active device (reported at its handshake)
+        device: String,
+    },
+    /// This device gets the invites again (after a takeover or when the other device left)
+    #[serde(rename = "active")]
+    Active,
+    /// The Discord link was moved to the new token (answer to a relink)
+    #[serde(rename = "relinked")]
+    Relinked,
     /// Exit request
     #[serde(rename = "exit")]
     Exit,
     #[serde(other)]
+    #[schemars(skip)]
     Invalid,
 }
 
+impl ServerMessage {
+    /// Rejects messages with absurd sizes (from a buggy or compromised server)
+    pub fn check(&self) -> Result<()> {
+        let too_long = |text: Option<&str>, max: usize| text.is_some_and(|text| text.len() > max);
+        if too_long(Some(&self.id), MAX_ID_LEN) || too_long(self.key.as_deref(), MAX_ID_LEN) {
+            anyhow::bail!("Request ID too long");
+        }
+        if let Some(user) = &self.user {
+            if too_long(Some(&user.id), MAX_ID_LEN) || too_long(Some(&user.name), MAX_TEXT_LEN) {
+                anyhow::bail!("User name too long");
+            }
+        }
+        match &self.cmd {
+            ServerCmd::Message { text, copy }
+                if too_long(Some(text), MAX_TEXT_LEN)
+                    || too_long(copy.as_deref(), MAX_TEXT_LEN) =>
+            {
+                anyhow::bail!("Message too long")
+            }
+            ServerCmd::Revoke { reason, .. } if too_long(reason.as_deref(), MAX_TEXT_LEN) => {
+                anyhow::bail!("Revoke reason too long")
+            }
+            ServerCmd::Chat { from, text }
+                if too_long(Some(text), MAX_TEXT_LEN)
+                    || too_long(from.as_deref(), MAX_TEXT_LEN) =>
+            {
+                anyhow::bail!("Chat message too long")
+            }
+            ServerCmd::Compat { games } if games.len() > MAX_COMPAT_GAMES => {
+                anyhow::bail!("Too many games in the compatibility list: {}", games.len())
+            }
+            ServerCmd::Paired { name } if too_long(name.as_deref(), MAX_TEXT_LEN) => {
+                anyhow::bail!("Account name too long")
+            }
+            ServerCmd::RequestDiagnostics { reason, .. }
+                if too_long(reason.as_deref(), MAX_TEXT_LEN) =>
+            {
+                anyhow::bail!("Diagnostics reason too long")
+            }
+            ServerCmd::WakeHost { mac } if too_long(mac.as_deref(), MAX_TEXT_LEN) => {
+                anyhow::bail!("MAC address too long")
+            }
+            ServerCmd::Conflict { device }
+            | ServerCmd::HostRequest {
+                from: Some(device), ..
+            }
+            | ServerCmd::HandoffReady { host: Some(device) }
+                if too_long(Some(device), MAX_TEXT_LEN) =>
+            {
+                anyhow::bail!("Device name too long")
+            }
+            ServerCmd::Backoff { secs } if *secs > MAX_BACKOFF_SECS => {
+                anyhow::bail!("Backoff too long: {secs} seconds")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ServerMessage {
+    /// ID to answer this request with
+    pub fn request_id(&self) -> RequestId {
+        RequestId(self.id.clone())
+    }
+}
+
+impl ServerCmd {
+    /// Command name on the wire (None for an unknown command)
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            ServerCmd::Message { .. } => Some("message"),
+            ServerCmd::GameId => Some("game"),
+            ServerCmd::Link { .. } => Some("link"),
+            ServerCmd::AdmitNext { .. } => Some("admit_next"),
+            ServerCmd::Revoke { .. } => Some("revoke"),
+            ServerCmd::Chat { .. } => Some("chat"),
+            ServerCmd::Compat { .. } => Some("compat"),
+            ServerCmd::HostRequest { .. } => Some("host_request"),
+            ServerCmd::HandoffReady { .. } => Some("handoff_ready"),
+            ServerCmd::HandoffFailed => Some("handoff_failed"),
+            ServerCmd::RequestDiagnostics { .. } => Some("request_diagnostics"),
+            ServerCmd::WakeHost { .. } => Some("wake_host"),
+            ServerCmd::Backoff { .. } => Some("backoff"),
+            ServerCmd::Pair => Some("pair"),
+            ServerCmd::Paired { .. } => Some("paired"),
+            ServerCmd::Conflict { .. } => Some("conflict"),
+            ServerCmd::Active => Some("active"),
+            ServerCmd::Relinked => Some("relinked"),
+            ServerCmd::Exit => Some("exit"),
+            ServerCmd::Invalid => None,
+        }
+    }
+}
+
+/// ID of a server request, only taken from the request itself so an answer cannot refer to the wrong one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(String);
+
 /// A data structure to represent a response from the daemon
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ClientMessage {
     /// Request ID
     pub id: String,
@@ -75,8 +369,28 @@ pub struct ClientMessage {
     pub cmd: ClientCmd,
 }
 
+impl ClientMessage {
+    /// Answer to a server request
+    pub fn reply(request: RequestId, cmd: ClientCmd) -> Self {
+        Self { id: request.0, cmd }
+    }
+
+    /// Error answer to a server request
+    pub fn error(request: RequestId, code: ErrorStatus) -> Self {
+        Self::reply(request, ClientCmd::Error { code })
+    }
+
+    /// Message that is not an answer to a request (there is no request ID)
+    pub fn notice(cmd: ClientCmd) -> Self {
+        Self {
+            id: String::new(),
+            cmd,
+        }
+    }
+}
+
 /// Request Type
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "cmd")]
 pub enum ClientCmd {
     /// Generate a game id
@@ -84,12 +398,207 @@ pub enum ClientCmd {
     GameId {
         /// Game ID
         game: u32,
+        /// Known problems of the game with Remote Play Together
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        caveats: Vec<String>,
+        /// Steam store metadata of the game (None if unknown)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        store: Option<StoreInfo>,
     },
     /// Generate a link request
     #[serde(rename = "link")]
     Link {
         /// Invite URL
         url: String,
+        /// Known problems of the game with Remote Play Together
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        caveats: Vec<String>,
+        /// How the guest joins (app to install, controller)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        instructions: Option<String>,
+    },
+    /// Invite link created when a game was started (sent without a request)
+    #[serde(rename = "invite")]
+    Invite {
+        /// Guest ID
+        guest_id: u64,
+        /// Game ID
+        game: u32,
+        /// Invite URL
+        url: String,
+        /// Known problems of the game with Remote Play Together
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        caveats: Vec<String>,
+        /// How the guest joins (app to install, controller)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        instructions: Option<String>,
+        /// Steam store metadata of the game (None if unknown)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        store: Option<StoreInfo>,
+    },
+    /// Guest approval state (sent without a request)
+    #[serde(rename = "approval")]
+    Approval {
+        /// Guest ID
+        guest_id: u64,
+        /// Steam ID of the guest
+        steam_id: u64,
+        /// Steam name of the guest
+        #[serde(skip_serializing_if = "Option::is_none")]
+        persona: Option<String>,
+        /// Approval state
+        state: ApprovalState,
+    },
+    /// Invite link revocation (sent without a request)
+    #[serde(rename = "revoked")]
+    Revoked {
+        /// Guest ID
+        guest_id: u64,
+        /// Why the link was revoked
+        reason: RevokeReason,
+    },
+    /// A guest could not join (sent without a request, the server can DM the guest the hint)
+    #[serde(rename = "join_failed")]
+    JoinFailed {
+        /// Guest ID
+        guest_id: u64,
+        /// Steam ID of the guest
+        steam_id: u64,
+        /// Steam name of the guest
+        #[serde(skip_serializing_if = "Option::is_none")]
+        persona: Option<String>,
+        /// Why the guest could not join
+        reason: JoinFailure,
+        /// How to fix it
+        hint: String,
+    },
+    /// A guest will be disconnected soon for reaching the playtime cap (sent without a request)
+    #[serde(rename = "playtime_warning")]
+    PlaytimeWarning {
+        /// Guest ID
+        guest_id: u64,
+        /// Steam ID of the guest
+        steam_id: u64,
+        /// Minutes until the guest is disconnected
+        minutes_left: u64,
+    },
+    /// A guest was disconnected for reaching the playtime cap (sent without a request, the server can invite the next guest)
+    #[serde(rename = "playtime_over")]
+    PlaytimeOver {
+        /// Guest ID
+        guest_id: u64,
+        /// Steam ID of the guest
+        steam_id: u64,
+    },
+    /// The host wants to stop and asks another client to take the session over (sent without a request)
+    #[serde(rename = "handoff")]
+    Handoff {
+        /// Game ID
+        game: u32,
+        /// Number of guests in the session
+        guests: usize,
+    },
+    /// The game of a host request is running, this client can host now (sent without a request)
+    #[serde(rename = "host_ready")]
+    HostReady {
+        /// Game ID
+        game: u32,
+    },
+    /// Redacted log lines the host agreed to share (answer to a diagnostics request)
+    #[serde(rename = "diagnostics")]
+    Diagnostics {
+        /// Minutes of log
+        minutes: u64,
+        /// Log lines, oldest first
+        lines: Vec<String>,
+    },
+    /// The gaming PC was sent Wake-on-LAN packets (answer of an agent to a wake request)
+    #[serde(rename = "waking")]
+    Waking {
+        /// MAC address of the gaming PC
+        mac: String,
+    },
+    /// The gaming PC is already running (answer of the inviter to a wake request)
+    #[serde(rename = "awake")]
+    Awake,
+    /// Free guest slots for the server's waitlist (sent without a request when it changes)
+    #[serde(rename = "slots")]
+    Slots {
+        /// Slots the waitlist can fill now
+        free: u32,
+        /// Slots configured by the host
+        total: u32,
+        /// Slots the host can take at its current load (lower than total while it is overloaded)
+        limit: u32,
+    },
+    /// CPU, GPU and video encoder load of the host while streaming (sent without a request)
+    #[serde(rename = "load")]
+    Load {
+        #[serde(flatten)]
+        load: HostLoad,
+    },
+    /// Summary of a Remote Play session that ended (sent without a request, [recap] send)
+    #[serde(rename = "recap")]
+    Recap {
+        #[serde(flatten)]
+        recap: SessionRecap,
+    },
+    /// NAT of the host found by the STUN probe (sent without a request)
+    #[serde(rename = "network")]
+    Network {
+        /// How the router maps the host's connections
+        mapping: NatMapping,
+        /// Whether guests will likely stream through Steam relays
+        relay_likely: bool,
+    },
+    /// Whether Steam can create invites (sent without a request when it changes)
+    #[serde(rename = "steam")]
+    Steam {
+        /// False while the connection to Steam is being restarted
+        available: bool,
+    },
+    /// Chat message from the host (sent without a request)
+    #[serde(rename = "chat")]
+    Chat {
+        /// Message text
+        text: String,
+        /// Discord user ID of the message being replied to
+        #[serde(skip_serializing_if = "Option::is_none")]
+        to: Option<String>,
+    },
+    /// Request for the latest game compatibility list (sent without a request)
+    #[serde(rename = "compat_query")]
+    CompatQuery {
+        /// When the cached list was fetched
+        #[serde(skip_serializing_if = "Option::is_none")]
+        updated: Option<DateTime<Utc>>,
+    },
+    /// Session state after a reconnect or a resume from sleep (sent without a request)
+    #[serde(rename = "sync")]
+    Sync {
+        /// Whether new invites are refused
+        paused: bool,
+        /// Guest IDs of the active invite links
+        invites: Vec<u64>,
+        /// Guest IDs of the guests in the session
+        guests: Vec<u64>,
+    },
+    /// Pairing code the host enters in Discord
+    #[serde(rename = "pair")]
+    Pair {
+        /// Six digit code
+        code: String,
+    },
+    /// Request to become the active device again (sent without a request)
+    #[serde(rename = "takeover")]
+    Takeover,
+    /// Request to move the Discord link of the previous token to this one (sent without a request)
+    #[serde(rename = "relink")]
+    Relink {
+        /// Token the Discord account is linked with
+        previous: String,
+        /// Pairing code the host confirms in Discord
+        code: String,
     },
     /// Error response
     #[serde(rename = "error")]
@@ -99,15 +608,201 @@ pub enum ClientCmd {
     },
 }
 
+impl ClientCmd {
+    /// Join failure with the hint that matches the reason
+    pub fn join_failed(
+        guest_id: u64,
+        steam_id: u64,
+        persona: Option<String>,
+        reason: JoinFailure,
+    ) -> Self {
+        ClientCmd::JoinFailed {
+            guest_id,
+            steam_id,
+            persona,
+            reason,
+            hint: reason.hint().to_owned(),
+        }
+    }
+}
+
+/// Kind of invite link
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum InviteKind {
+    /// Joins with a controller slot
+    #[default]
+    Player,
+    /// Only watches the stream (no controller slot)
+    Spectator,
+}
+
+impl InviteKind {
+    /// Whether Steam can create invites of this kind
+    pub fn is_supported(self) -> bool {
+        // Steam has no API for spectator-only Remote Play Together invites yet
+        self == InviteKind::Player
+    }
+}
+
+/// Approval state of a joining guest
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalState {
+    /// Waiting for the host
+    Pending,
+    /// Allowed to play
+    Approved,
+    /// Disconnected by the host (or the approval timed out)
+    Denied,
+}
+
+/// Reason of an invite link revocation
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RevokeReason {
+    /// Revoked by the host
+    Host,
+    /// The link reached its maximum number of uses
+    Exhausted,
+    /// Revoked at the request of the server
+    Server,
+    /// The game of the link was closed
+    GameExited,
+    /// Steam no longer accepts the link (a new one is posted)
+    Invalid,
+    /// The time-boxed link expired
+    Expired,
+}
+
+/// How the router maps the host's connections (found with STUN)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NatMapping {
+    /// No NAT, the host has a public address
+    Open,
+    /// The same external port for every destination (guests connect directly)
+    Cone,
+    /// A different external port for every destination
+    Symmetric,
+    /// No STUN server answered (UDP is blocked)
+    Blocked,
+    /// Only one STUN server answered, so cone and symmetric cannot be told apart
+    Unknown,
+}
+
+impl NatMapping {
+    /// Whether guests will likely stream through Steam relays
+    pub fn relay_likely(self) -> bool {
+        matches!(self, Self::Symmetric | Self::Blocked)
+    }
+}
+
+/// Utilization of the host while streaming (percent)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct HostLoad {
+    /// All CPU cores
+    pub cpu: f32,
+    /// Busiest GPU (None if the GPU cannot be queried)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gpu: Option<f32>,
+    /// Busiest video encoder (None if the GPU cannot be queried)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoder: Option<f32>,
+}
+
+/// Summary of a Remote Play session (from the first guest joining to the last one leaving)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SessionRecap {
+    /// When the first guest joined
+    pub started: DateTime<Utc>,
+    /// Length of the session (seconds)
+    pub duration_secs: u64,
+    /// Different guests who joined
+    pub guests: usize,
+    /// Most guests in the session at once
+    pub peak: usize,
+    /// Times a guest left the session
+    pub disconnects: usize,
+    /// Average round trip time to the servers during the session (None without a heartbeat)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+}
+
+/// What the Steam store page of a game lists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct StoreInfo {
+    /// The game lists Remote Play Together
+    pub remote_play_together: bool,
+    /// Controller support of the game
+    pub controller: ControllerSupport,
+}
+
+/// Controller support listed on the Steam store
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ControllerSupport {
+    /// Playable with a controller only
+    Full,
+    /// Some parts need keyboard and mouse
+    Partial,
+    /// Keyboard and mouse only
+    None,
+}
+
+/// Why a guest could not join (from the launch result reported by Steam)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JoinFailure {
+    /// The guest could not reach the host
+    Unreachable,
+    /// The guest has no Steam client or Steam Link app
+    ClientMissing,
+    /// The Steam client or Steam Link app of the guest is too old
+    ClientOutdated,
+    /// The game did not start on the host
+    LaunchFailed,
+    /// The connection timed out
+    Timeout,
+    /// A result without a known fix
+    Unknown,
+}
+
+impl JoinFailure {
+    /// Maps a launch result reported by Steam (ERemoteClientLaunchResult)
+    pub fn from_result(result: i32) -> Self {
+        match result {
+            3 | 6 => Self::Unreachable,
+            4 => Self::ClientMissing,
+            9 => Self::ClientOutdated,
+            5 => Self::LaunchFailed,
+            7 => Self::Timeout,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// How the host or the guest can fix it
+    pub fn hint(self) -> &'static str {
+        match self {
+            Self::Unreachable => "The guest could not reach the host. The host's NAT may be strict: enable UPnP on the router or forward UDP ports 27031-27036 and TCP ports 27036-27037.",
+            Self::ClientMissing => "The guest needs the Steam client or the Steam Link app to join.",
+            Self::ClientOutdated => "The guest needs to update the Steam client or the Steam Link app.",
+            Self::LaunchFailed => "The game did not start on the host. The host should check that the game is still running and try again.",
+            Self::Timeout => "The connection timed out. A firewall or VPN on either side may be blocking Remote Play.",
+            Self::Unknown => "Steam could not start the session. Both sides should restart Steam and try a new link.",
+        }
+    }
+}
+
 /// User information
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct User {
     pub id: String,
     pub name: String,
 }
 
 /// Error statuses
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ErrorStatus {
     /// The command is invalid
@@ -116,4 +811,47 @@ pub enum ErrorStatus {
     InvalidApp,
     /// The app does not support remote play
     UnsupportedApp,
+    /// The host paused invite creation
+    Paused,
+    /// The invite link does not exist (or was already revoked)
+    UnknownInvite,
+    /// The host does not allow the command ([permissions] in the config)
+    PermissionDenied,
+    /// Steam cannot create this kind of invite
+    UnsupportedInvite,
+    /// Steam keeps failing and the connection to it is being restarted
+    SteamUnavailable,
+    /// Every guest slot is taken (the waitlist has to wait for a slot message)
+    NoFreeSlot,
+    /// The agent could not wake the gaming PC (no MAC address or a network error)
+    WakeFailed,
+    /// The host did not share the log (declined, or log sharing or file logging is disabled)
+    DiagnosticsDeclined,
+    /// The server sent more requests than the host allows ([permissions] max_per_minute)
+    RateLimited,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_version_servers_get_known_error_statuses() {
+        let paused = ClientMessage::error(RequestId("4".to_owned()), ErrorStatus::Paused);
+        assert_eq!(
+            Protocol::V1.encode(&paused).unwrap().as_deref(),
+            Some(r#"{"id":"4","cmd":"error","code":"invalid_cmd"}"#)
+        );
+        assert_eq!(
+            Protocol::V2.encode(&paused).unwrap().as_deref(),
+            Some(r#"{"id":"4","cmd":"error","code":"paused"}"#)
+        );
+    }
+
+    #[test]
+    fn unknown_versions_are_not_spoken() {
+        assert_eq!(Protocol::parse(" 1 "), Some(Protocol::V1));
+        assert_eq!(Protocol::parse("2"), Some(Protocol::V2));
+        assert_eq!(Protocol::parse("3"), None);
+    }
 }
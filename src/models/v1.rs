@@ -0,0 +1,144 @@
+//! First version of the protocol (invites and announcements only)
+//! Servers deployed before the notifications, waitlists and pairing still speak it
+
+use serde::{Deserialize, Serialize};
+
+use super::{InviteKind, User};
+
+/// A data structure to represent a request to the daemon
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServerMessage {
+    /// Request ID
+    pub id: String,
+    /// Request user
+    pub user: Option<User>,
+    /// Request type
+    #[serde(flatten)]
+    pub cmd: ServerCmd,
+}
+
+/// Request Type
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "cmd")]
+pub enum ServerCmd {
+    /// Announce message
+    #[serde(rename = "message")]
+    Message {
+        /// Message text
+        text: String,
+        /// Text to copy to clipboard
+        copy: Option<String>,
+    },
+    /// Generate a game id
+    #[serde(rename = "game")]
+    GameId,
+    /// Generate a link request
+    #[serde(rename = "link")]
+    Link {
+        /// Game ID
+        game: u32,
+    },
+    /// Exit request
+    #[serde(rename = "exit")]
+    Exit,
+    #[serde(other)]
+    Invalid,
+}
+
+/// A data structure to represent a response from the daemon
+/// Borrows from the message of the current version, so nothing is copied to send it
+#[derive(Debug, Serialize)]
+pub struct ClientMessage<'a> {
+    /// Request ID
+    pub id: &'a str,
+    /// Request type
+    #[serde(flatten)]
+    pub cmd: ClientCmd<'a>,
+}
+
+/// Request Type
+#[derive(Debug, Serialize)]
+#[serde(tag = "cmd")]
+pub enum ClientCmd<'a> {
+    /// Generate a game id
+    #[serde(rename = "game")]
+    GameId {
+        /// Game ID
+        game: u32,
+    },
+    /// Generate a link request
+    #[serde(rename = "link")]
+    Link {
+        /// Invite URL
+        url: &'a str,
+    },
+    /// Error response
+    #[serde(rename = "error")]
+    Error {
+        /// Error code
+        code: ErrorStatus,
+    },
+}
+
+/// Error statuses
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorStatus {
+    /// The command is invalid
+    InvalidCmd,
+    /// The app is not running
+    InvalidApp,
+    /// The app does not support remote play
+    UnsupportedApp,
+}
+
+impl From<ServerMessage> for super::ServerMessage {
+    fn from(msg: ServerMessage) -> Self {
+        let cmd = match msg.cmd {
+            ServerCmd::Message { text, copy } => super::ServerCmd::Message { text, copy },
+            ServerCmd::GameId => super::ServerCmd::GameId,
+            // Links were always one-time friend invites
+            ServerCmd::Link { game } => super::ServerCmd::Link {
+                game,
+                max_uses: None,
+                kind: InviteKind::default(),
+                ttl_secs: None,
+            },
+            ServerCmd::Exit => super::ServerCmd::Exit,
+            ServerCmd::Invalid => super::ServerCmd::Invalid,
+        };
+        Self {
+            id: msg.id,
+            user: msg.user,
+            key: None,
+            cmd,
+        }
+    }
+}
+
+impl From<&super::ErrorStatus> for ErrorStatus {
+    fn from(code: &super::ErrorStatus) -> Self {
+        match code {
+            super::ErrorStatus::InvalidApp => Self::InvalidApp,
+            super::ErrorStatus::UnsupportedApp => Self::UnsupportedApp,
+            // The server only knows that the command did not work
+            _ => Self::InvalidCmd,
+        }
+    }
+}
+
+impl<'a> ClientMessage<'a> {
+    /**
+     * Views a message of the current version as one of the first version
+     * @return The message (None if the first version has no command for it)
+     */
+    pub fn view(msg: &'a super::ClientMessage) -> Option<Self> {
+        let cmd = match &msg.cmd {
+            &super::ClientCmd::GameId { game, .. } => ClientCmd::GameId { game },
+            super::ClientCmd::Link { url, .. } => ClientCmd::Link { url },
+            super::ClientCmd::Error { code } => ClientCmd::Error { code: code.into() },
+            _ => return None,
+        };
+        Some(Self { id: &msg.id, cmd })
+    }
+}
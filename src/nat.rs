@@ -0,0 +1,464 @@
+use anyhow::{anyhow, Context as _, Result};
+use igd_next::{search_gateway, PortMappingProtocol, SearchOptions};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, ToSocketAddrs as _, UdpSocket},
+    ops::RangeInclusive,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    task::{self, JoinHandle},
+    time::{interval, MissedTickBehavior},
+};
+
+use crate::{
+    config::NatConfig,
+    console::{self, Glyph},
+    handlers::Handler,
+    lan,
+    logger::Level,
+    models::NatMapping,
+};
+
+/// UDP ports of Remote Play (https://help.steampowered.com/faqs/view/2EA8-4D75-DA21-31EB)
+const UDP_PORTS: RangeInclusive<u16> = 27031..=27036;
+/// TCP ports of Remote Play
+const TCP_PORTS: RangeInclusive<u16> = 27036..=27037;
+/// Lifetime of a port mapping (renewed at half of it)
+const LEASE: Duration = Duration::from_secs(60 * 60);
+/// Name of the mappings shown on the router
+const DESCRIPTION: &str = "Remote Play Inviter";
+/// Port of NAT-PMP on the router
+const NAT_PMP_PORT: u16 = 5351;
+/// STUN message types and attributes (RFC 5389)
+const STUN_BINDING_REQUEST: u16 = 0x0001;
+const STUN_BINDING_RESPONSE: u16 = 0x0101;
+const STUN_MAPPED_ADDRESS: u16 = 0x0001;
+const STUN_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+const STUN_MAGIC_COOKIE: u32 = 0x2112_A442;
+
+/// NAT type as seen by the guests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NatType {
+    /// The PC has a public address
+    Open,
+    /// Behind a router that opens ports on request
+    Moderate,
+    /// Behind a router that cannot open ports (or several routers)
+    Strict,
+}
+
+/// Port mapping protocol of the router
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Method {
+    Upnp,
+    NatPmp,
+}
+
+/// What the router tells about the network
+struct Probe {
+    /// Address of this PC on the local network
+    local: Option<IpAddr>,
+    /// UPnP router and the external address it reports
+    upnp: Option<(SocketAddr, Option<IpAddr>)>,
+    /// External address reported by NAT-PMP
+    nat_pmp: Option<Ipv4Addr>,
+}
+
+impl Probe {
+    fn run(wait: Duration) -> Self {
+        let local = lan::local_ip();
+        let upnp = search_gateway(SearchOptions {
+            timeout: Some(wait),
+            ..Default::default()
+        })
+        .ok()
+        .map(|gateway| (gateway.addr, gateway.get_external_ip().ok()));
+        let nat_pmp = gateway_v4(local, upnp.map(|(addr, _)| addr))
+            .and_then(|gateway| nat_pmp_external(gateway, wait).ok());
+        Self {
+            local,
+            upnp,
+            nat_pmp,
+        }
+    }
+
+    /// External address reported by the router
+    fn external(&self) -> Option<IpAddr> {
+        self.upnp
+            .and_then(|(_, external)| external)
+            .or(self.nat_pmp.map(IpAddr::V4))
+    }
+
+    fn nat_type(&self) -> NatType {
+        match (self.local, self.external()) {
+            (Some(local), _) if is_public(local) => NatType::Open,
+            // Another router (or the carrier) is in front of this one
+            (_, Some(external)) if !is_public(external) => NatType::Strict,
+            (_, Some(_)) => NatType::Moderate,
+            (_, None) => NatType::Strict,
+        }
+    }
+}
+
+/// Whether an address can be reached from the internet
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            // 100.64.0.0/10 is used by carrier-grade NAT
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => !(ip.is_loopback() || ip.is_unspecified()),
+    }
+}
+
+/// Router address for NAT-PMP (the UPnP router, or the first address of the local network)
+fn gateway_v4(local: Option<IpAddr>, upnp: Option<SocketAddr>) -> Option<Ipv4Addr> {
+    if let Some(SocketAddr::V4(addr)) = upnp {
+        return Some(*addr.ip());
+    }
+    match local? {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            Some(Ipv4Addr::new(a, b, c, 1))
+        }
+        IpAddr::V6(_) => None,
+    }
+}
+
+/**
+ * Sends a NAT-PMP request to the router (RFC 6886)
+ * @return The response
+ */
+fn nat_pmp_request(gateway: Ipv4Addr, request: &[u8], wait: Duration) -> Result<Vec<u8>> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.set_read_timeout(Some(wait))?;
+    socket.connect(SocketAddrV4::new(gateway, NAT_PMP_PORT))?;
+    socket.send(request)?;
+    let mut buf = [0; 16];
+    let n = socket.recv(&mut buf).context("No NAT-PMP response")?;
+    let response = buf[..n].to_vec();
+    // Version 0, opcode of the request + 128, result code 0
+    if n < 4 || response[0] != 0 || response[1] != request[1] + 128 {
+        anyhow::bail!("Invalid NAT-PMP response");
+    }
+    let result = u16::from_be_bytes([response[2], response[3]]);
+    if result != 0 {
+        anyhow::bail!("NAT-PMP request refused (result code {result})");
+    }
+    Ok(response)
+}
+
+/// External address reported by NAT-PMP
+fn nat_pmp_external(gateway: Ipv4Addr, wait: Duration) -> Result<Ipv4Addr> {
+    let response = nat_pmp_request(gateway, &[0, 0], wait)?;
+    if response.len() < 12 {
+        anyhow::bail!("Invalid NAT-PMP response");
+    }
+    Ok(Ipv4Addr::new(
+        response[8],
+        response[9],
+        response[10],
+        response[11],
+    ))
+}
+
+/// Maps a port to this PC with NAT-PMP
+fn nat_pmp_map(
+    gateway: Ipv4Addr,
+    protocol: PortMappingProtocol,
+    port: u16,
+    wait: Duration,
+) -> Result<()> {
+    let opcode = match protocol {
+        PortMappingProtocol::UDP => 1,
+        PortMappingProtocol::TCP => 2,
+    };
+    let mut request = vec![0, opcode, 0, 0];
+    request.extend_from_slice(&port.to_be_bytes());
+    request.extend_from_slice(&port.to_be_bytes());
+    request.extend_from_slice(&(LEASE.as_secs() as u32).to_be_bytes());
+    nat_pmp_request(gateway, &request, wait).map(|_| ())
+}
+
+/// Ports to open on the router
+fn ports() -> impl Iterator<Item = (PortMappingProtocol, u16)> {
+    UDP_PORTS
+        .map(|port| (PortMappingProtocol::UDP, port))
+        .chain(TCP_PORTS.map(|port| (PortMappingProtocol::TCP, port)))
+}
+
+/**
+ * Opens the Remote Play ports on the router (UPnP, or NAT-PMP as a fallback)
+ * @return The protocol that worked
+ */
+fn map_ports(wait: Duration) -> Result<Method> {
+    let local = lan::local_ip().context("No local network address")?;
+    let upnp_error = match search_gateway(SearchOptions {
+        timeout: Some(wait),
+        ..Default::default()
+    }) {
+        Ok(gateway) => {
+            let result: Result<()> = ports().try_for_each(|(protocol, port)| {
+                gateway
+                    .add_port(
+                        protocol,
+                        port,
+                        SocketAddr::new(local, port),
+                        LEASE.as_secs() as u32,
+                        DESCRIPTION,
+                    )
+                    .with_context(|| format!("UPnP refused {protocol} port {port}"))
+            });
+            match result {
+                Ok(()) => return Ok(Method::Upnp),
+                Err(err) => err,
+            }
+        }
+        Err(err) => anyhow::Error::new(err).context("No UPnP router found"),
+    };
+
+    let Some(gateway) = gateway_v4(Some(local), None) else {
+        return Err(upnp_error);
+    };
+    ports()
+        .try_for_each(|(protocol, port)| nat_pmp_map(gateway, protocol, port, wait))
+        .map_err(|err| anyhow!("{upnp_error}, {err}"))?;
+    Ok(Method::NatPmp)
+}
+
+/**
+ * Asks a STUN server for the external address of a socket (RFC 5389 binding request)
+ * @return The address the server saw
+ */
+fn stun_mapped(socket: &UdpSocket, server: &str) -> Result<SocketAddr> {
+    let server = server
+        .to_socket_addrs()
+        .with_context(|| format!("Unable to resolve STUN server {server}"))?
+        .find(SocketAddr::is_ipv4)
+        .with_context(|| format!("No IPv4 address for STUN server {server}"))?;
+    let transaction: [u8; 12] = rand::random();
+    let mut request = Vec::with_capacity(20);
+    request.extend_from_slice(&STUN_BINDING_REQUEST.to_be_bytes());
+    request.extend_from_slice(&0u16.to_be_bytes());
+    request.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+    request.extend_from_slice(&transaction);
+    socket.send_to(&request, server)?;
+
+    let mut buf = [0; 512];
+    loop {
+        let (n, from) = socket
+            .recv_from(&mut buf)
+            .with_context(|| format!("No answer from STUN server {server}"))?;
+        let response = &buf[..n];
+        // Answers of other servers (or late retries) are skipped
+        if from != server
+            || n < 20
+            || response[..2] != STUN_BINDING_RESPONSE.to_be_bytes()
+            || response[8..20] != transaction
+        {
+            continue;
+        }
+        return parse_mapped_address(&response[20..])
+            .with_context(|| format!("No mapped address from STUN server {server}"));
+    }
+}
+
+/// Finds the (XOR-)MAPPED-ADDRESS attribute of a STUN response (IPv4 only)
+fn parse_mapped_address(mut attributes: &[u8]) -> Option<SocketAddr> {
+    let mut mapped = None;
+    while attributes.len() >= 4 {
+        let kind = u16::from_be_bytes([attributes[0], attributes[1]]);
+        let len = u16::from_be_bytes([attributes[2], attributes[3]]) as usize;
+        let value = attributes.get(4..4 + len)?;
+        // Family 0x01 is IPv4
+        if value.len() >= 8 && value[1] == 0x01 {
+            let port = u16::from_be_bytes([value[2], value[3]]);
+            let ip = u32::from_be_bytes([value[4], value[5], value[6], value[7]]);
+            match kind {
+                STUN_XOR_MAPPED_ADDRESS => {
+                    return Some(SocketAddr::new(
+                        IpAddr::V4(Ipv4Addr::from(ip ^ STUN_MAGIC_COOKIE)),
+                        port ^ (STUN_MAGIC_COOKIE >> 16) as u16,
+                    ));
+                }
+                STUN_MAPPED_ADDRESS => {
+                    mapped = Some(SocketAddr::new(IpAddr::V4(Ipv4Addr::from(ip)), port));
+                }
+                _ => (),
+            }
+        }
+        // Attributes are padded to 4 bytes
+        attributes = attributes
+            .get(4 + len.next_multiple_of(4)..)
+            .unwrap_or_default();
+    }
+    mapped
+}
+
+/// Classifies the NAT by comparing the external addresses seen by the STUN servers
+fn stun_probe(servers: &[String], wait: Duration) -> NatMapping {
+    let Ok(socket) = UdpSocket::bind("0.0.0.0:0") else {
+        return NatMapping::Blocked;
+    };
+    if socket.set_read_timeout(Some(wait)).is_err() {
+        return NatMapping::Blocked;
+    }
+    let local = lan::local_ip();
+    let mapped: Vec<SocketAddr> = servers
+        .iter()
+        .filter_map(|server| stun_mapped(&socket, server).ok())
+        .collect();
+    match mapped.as_slice() {
+        [] => NatMapping::Blocked,
+        [first, ..] if Some(first.ip()) == local => NatMapping::Open,
+        [_] => NatMapping::Unknown,
+        [first, rest @ ..] if rest.iter().all(|addr| addr == first) => NatMapping::Cone,
+        _ => NatMapping::Symmetric,
+    }
+}
+
+/// Description of a NAT mapping for the host
+pub fn describe(mapping: NatMapping) -> &'static str {
+    match mapping {
+        NatMapping::Open => "open (guests connect directly)",
+        NatMapping::Cone => "cone (guests connect directly)",
+        NatMapping::Symmetric => "symmetric (guests will likely stream through Steam relays)",
+        NatMapping::Blocked => "UDP blocked (guests will likely stream through Steam relays)",
+        NatMapping::Unknown => "unknown (only one STUN server answered)",
+    }
+}
+
+/// Classifies the NAT with STUN in the background and reports it to the servers
+pub fn spawn_probe(
+    handler: Arc<Handler>,
+    servers: Vec<String>,
+    wait: Duration,
+) -> Option<JoinHandle<()>> {
+    if servers.is_empty() {
+        return None;
+    }
+    Some(tokio::spawn(async move {
+        if let Ok(mapping) = task::spawn_blocking(move || stun_probe(&servers, wait)).await {
+            if mapping.relay_likely() {
+                let _ = console::println!(
+                    @level Level::Warn,
+                    "{} NAT: {}",
+                    Glyph::Warn,
+                    describe(mapping)
+                );
+            }
+            handler.set_network(mapping).await;
+        }
+    }))
+}
+
+/// Keeps the Remote Play ports open on the router while the inviter runs
+pub fn spawn(config: NatConfig, wait: Duration) -> Option<JoinHandle<()>> {
+    if !config.port_mapping {
+        return None;
+    }
+    Some(tokio::spawn(async move {
+        let mut ticker = interval(LEASE / 2);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut mapped = false;
+        loop {
+            ticker.tick().await;
+            match task::spawn_blocking(move || map_ports(wait)).await {
+                Ok(Ok(method)) => {
+                    if !mapped {
+                        let _ = console::println!(
+                            "{} Opened the Remote Play ports on the router ({})",
+                            Glyph::Ok,
+                            match method {
+                                Method::Upnp => "UPnP",
+                                Method::NatPmp => "NAT-PMP",
+                            }
+                        );
+                    }
+                    mapped = true;
+                }
+                Ok(Err(err)) => {
+                    let _ = console::eprintln!(
+                        "{} Unable to open the Remote Play ports on the router: {:#}",
+                        Glyph::Warn,
+                        err
+                    );
+                    mapped = false;
+                }
+                Err(_) => (),
+            }
+        }
+    }))
+}
+
+/// `diagnose` subcommand
+pub async fn diagnose(config: &NatConfig, wait: Duration) -> Result<()> {
+    console::println!("{} Checking the network...", Glyph::Wait)?;
+    let probe = task::spawn_blocking(move || Probe::run(wait)).await?;
+    let servers = config.stun_servers.clone();
+    let mapping =
+        task::spawn_blocking(move || (!servers.is_empty()).then(|| stun_probe(&servers, wait)))
+            .await?;
+
+    match probe.local {
+        Some(local) => console::println!("{} Local address: {local}", Glyph::Ok)?,
+        None => console::println!(@level Level::Error, "{} No local network address", Glyph::Err)?,
+    }
+    match probe.upnp {
+        Some((addr, Some(external))) => console::println!(
+            "{} UPnP router: {addr} (external address: {external})",
+            Glyph::Ok
+        )?,
+        Some((addr, None)) => console::println!(
+            @level Level::Warn,
+            "{} UPnP router: {addr} (external address unknown)",
+            Glyph::Warn
+        )?,
+        None => console::println!(@level Level::Error, "{} No UPnP router found", Glyph::Err)?,
+    }
+    match probe.nat_pmp {
+        Some(external) => console::println!(
+            "{} NAT-PMP router (external address: {external})",
+            Glyph::Ok
+        )?,
+        None => console::println!(@level Level::Error, "{} No NAT-PMP router found", Glyph::Err)?,
+    }
+    if let Some(mapping) = mapping {
+        let (glyph, level) = if mapping.relay_likely() {
+            (Glyph::Warn, Level::Warn)
+        } else {
+            (Glyph::Ok, Level::Info)
+        };
+        console::println!(@level level, "{} STUN: {}", glyph, describe(mapping))?;
+    }
+
+    match probe.nat_type() {
+        NatType::Open => {
+            console::println!("{} NAT type: open (guests connect directly)", Glyph::Ok)?
+        }
+        NatType::Moderate => console::printdoc! {"
+            {ok} NAT type: moderate
+              Set port_mapping = true in [nat] of the config so the router opens the Remote Play ports.
+            ",
+            ok = Glyph::Ok,
+        }?,
+        NatType::Strict => console::printdoc! {"
+            {warn} NAT type: strict (guests may fail to connect or go through Steam relays)
+              Enable UPnP or NAT-PMP on the router, or forward UDP {udp_start}-{udp_end} and TCP {tcp_start}-{tcp_end} to this PC.
+              If the external address is private, another router or the provider is in front of this one.
+            ",
+            warn = Glyph::Warn,
+            udp_start = UDP_PORTS.start(),
+            udp_end = UDP_PORTS.end(),
+            tcp_start = TCP_PORTS.start(),
+            tcp_end = TCP_PORTS.end(),
+        }?,
+    }
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex as StdMutex,
+    time::{Duration, Instant},
+};
+use steam_stuff::Steam;
+use tokio::{sync::Mutex, time::sleep};
+
+use crate::config::PersonasConfig;
+
+/// How often Steam is asked again while it fetches a name
+const POLL: Duration = Duration::from_millis(200);
+/// Window of the lookup rate limit
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Steam names of the guests
+pub struct Personas {
+    config: PersonasConfig,
+    /// How long Steam may take to fetch a name
+    wait: Duration,
+    /// Name of each Steam ID and when it was fetched
+    names: StdMutex<HashMap<u64, (String, Instant)>>,
+    /// Recent lookups (for the rate limit)
+    lookups: StdMutex<VecDeque<Instant>>,
+}
+
+impl Personas {
+    pub fn new(config: PersonasConfig, wait: Duration) -> Self {
+        Self {
+            config,
+            wait,
+            names: StdMutex::new(HashMap::new()),
+            lookups: StdMutex::new(VecDeque::new()),
+        }
+    }
+
+    fn max_age(&self) -> Duration {
+        Duration::from_secs(self.config.cache_secs)
+    }
+
+    /// Cached name of a Steam user (no lookup)
+    pub fn cached(&self, steam_id: u64) -> Option<String> {
+        if !self.config.enabled {
+            return None;
+        }
+        let names = self.names.lock().unwrap();
+        let (name, fetched) = names.get(&steam_id)?;
+        (fetched.elapsed() < self.max_age()).then(|| name.clone())
+    }
+
+    /// Number of cached names
+    pub fn count(&self) -> usize {
+        self.names.lock().unwrap().len()
+    }
+
+    /// Whether another lookup fits in the rate limit (the lookup is counted)
+    fn take_lookup(&self) -> bool {
+        let mut lookups = self.lookups.lock().unwrap();
+        while lookups
+            .front()
+            .is_some_and(|start| start.elapsed() >= RATE_WINDOW)
+        {
+            lookups.pop_front();
+        }
+        if lookups.len() >= self.config.lookups_per_minute {
+            return false;
+        }
+        lookups.push_back(Instant::now());
+        true
+    }
+
+    fn store(&self, steam_id: u64, name: &str) {
+        let max_age = self.max_age();
+        let mut names = self.names.lock().unwrap();
+        names.retain(|_, (_, fetched)| fetched.elapsed() < max_age);
+        names.insert(steam_id, (name.to_owned(), Instant::now()));
+    }
+
+    /**
+     * Looks up the name of a Steam user, waiting for Steam to fetch it
+     * @return The name (None if disabled, rate limited or Steam did not answer in time)
+     */
+    pub async fn resolve(&self, steam: &Mutex<dyn Steam>, steam_id: u64) -> Option<String> {
+        if steam_id == 0 {
+            return None;
+        }
+        if let Some(name) = self.cached(steam_id) {
+            return Some(name);
+        }
+        if !self.config.enabled || !self.take_lookup() {
+            return None;
+        }
+        let deadline = Instant::now() + self.wait;
+        loop {
+            // The Steam callbacks deliver the name in the background
+            if let Some(name) = steam.lock().await.persona_name(steam_id) {
+                self.store(steam_id, &name);
+                return Some(name);
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+            sleep(POLL).await;
+        }
+    }
+}
+
+/// Guest in a player list: `[guest_id]claimer (Steam name)`
+pub fn guest_label(guest_id: u64, name: Option<&str>, persona: Option<&str>) -> String {
+    match persona {
+        Some(persona) => format!("[{guest_id}]{} ({persona})", name.unwrap_or("?")),
+        None => format!("[{guest_id}]{}", name.unwrap_or("?")),
+    }
+}
+
+/// Steam user as a console field value: `76561198000000000 (Steam name)`
+pub fn steam_user(steam_id: u64, persona: &Option<String>) -> String {
+    match persona {
+        Some(persona) => format!("{steam_id} ({persona})"),
+        None => steam_id.to_string(),
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    env::{self, consts},
+    fs,
+    path::PathBuf,
+};
+
+use crate::config::DeviceConfig;
+
+/// Details about this PC sent in the connection handshake
+/// The bot shows where the game is hosted from and the server can target platform-specific fixes
+pub struct Platform {
+    /// Operating system (e.g. "windows", "macos", "linux")
+    pub os: &'static str,
+    /// CPU architecture (e.g. "x86_64", "aarch64")
+    pub arch: &'static str,
+    /// Name of the PC (None when anonymized)
+    pub host: Option<String>,
+    /// Version of the Steam client (None if it cannot be found)
+    pub steam_version: Option<String>,
+}
+
+impl Platform {
+    pub fn detect(device: &DeviceConfig) -> Self {
+        let host = match &device.name {
+            _ if device.anonymize => None,
+            Some(name) => Some(name.clone()),
+            None => Some(gethostname::gethostname().to_string_lossy().into_owned()),
+        };
+        Self {
+            os: consts::OS,
+            arch: consts::ARCH,
+            host: host.filter(|host| !host.is_empty()),
+            steam_version: steam_version(),
+        }
+    }
+
+    /// Query parameters of the handshake (starting with "&")
+    pub fn query(&self) -> String {
+        let mut query = format!("&os={}&arch={}", self.os, self.arch);
+        if let Some(host) = &self.host {
+            query.push_str(&format!("&host={}", encode_query(host)));
+        }
+        if let Some(version) = &self.steam_version {
+            query.push_str(&format!("&steam={}", encode_query(version)));
+        }
+        query
+    }
+}
+
+/// Percent-encodes a query parameter value
+pub fn encode_query(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// Package manifests of the Steam client in the default install locations
+fn steam_manifests() -> Vec<PathBuf> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    match consts::OS {
+        "windows" => ["ProgramFiles(x86)", "ProgramFiles"]
+            .iter()
+            .filter_map(env::var_os)
+            .flat_map(|dir| {
+                let package = PathBuf::from(dir).join("Steam").join("package");
+                [
+                    package.join("steam_client_win64.manifest"),
+                    package.join("steam_client_win32.manifest"),
+                ]
+            })
+            .collect(),
+        "macos" => home
+            .map(|home| {
+                home.join("Library/Application Support/Steam/package/steam_client_osx.manifest")
+            })
+            .into_iter()
+            .collect(),
+        _ => home
+            .map(|home| {
+                [".steam/steam", ".local/share/Steam"]
+                    .iter()
+                    .map(|dir| {
+                        home.join(dir)
+                            .join("package/steam_client_ubuntu12.manifest")
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+/// Version of the installed Steam client (the "version" of its package manifest)
+fn steam_version() -> Option<String> {
+    steam_manifests().iter().find_map(|path| {
+        let content = fs::read_to_string(path).ok()?;
+        content.lines().find_map(|line| {
+            // "version"		"1719867434"
+            let mut fields = line.split('"').filter(|field| !field.trim().is_empty());
+            match (fields.next(), fields.next()) {
+                (Some("version"), Some(version)) => Some(version.to_owned()),
+                _ => None,
+            }
+        })
+    })
+}
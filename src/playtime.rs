@@ -0,0 +1,66 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::{
+    sync::broadcast::error::RecvError,
+    task::{AbortHandle, JoinHandle, JoinSet},
+    time::sleep,
+};
+
+use crate::{
+    config::PlaytimeConfig,
+    console::{self, Glyph},
+    events::Event,
+    handlers::Handler,
+};
+
+/// Disconnects the guests who reached the playtime cap, warning them a few minutes before
+pub fn spawn(handler: Arc<Handler>, config: PlaytimeConfig) -> Option<JoinHandle<()>> {
+    if config.max_minutes == 0 {
+        return None;
+    }
+    let max = Duration::from_secs(config.max_minutes * 60);
+    let warn = Duration::from_secs(config.warn_minutes * 60).min(max);
+
+    let mut events = handler.subscribe();
+    Some(tokio::spawn(async move {
+        // The timers are stopped with this task
+        let mut timers = JoinSet::new();
+        let mut guests = HashMap::<u64, AbortHandle>::new();
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(Event::GuestJoined { guest_id, steam_id, name, .. }) => {
+                        // Priority guests play as long as they like
+                        if handler.is_priority(Some(steam_id), name.as_deref().as_slice()) {
+                            continue;
+                        }
+                        let handler = handler.clone();
+                        let timer = timers.spawn(async move {
+                            sleep(max - warn).await;
+                            if !warn.is_zero() {
+                                handler.warn_playtime(guest_id, steam_id, warn).await;
+                                sleep(warn).await;
+                            }
+                            if let Err(err) = handler.end_playtime(guest_id).await {
+                                let _ = console::eprintln!("{} {}", Glyph::Err, err);
+                            }
+                        });
+                        if let Some(old) = guests.insert(guest_id, timer) {
+                            old.abort();
+                        }
+                    }
+                    Ok(Event::GuestLeft { guest_id, .. }) => {
+                        if let Some(timer) = guests.remove(&guest_id) {
+                            timer.abort();
+                        }
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                },
+                // Reap the finished timers
+                Some(_) = timers.join_next() => {
+                    guests.retain(|_, timer| !timer.is_finished());
+                }
+            }
+        }
+    }))
+}
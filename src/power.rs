@@ -0,0 +1,36 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    task::JoinHandle,
+    time::{interval, MissedTickBehavior},
+};
+
+use crate::handlers::Handler;
+
+/// How often the clocks are compared
+const TICK: Duration = Duration::from_secs(5);
+/// Wall clock time beyond the tick that counts as a suspend
+const SUSPEND_THRESHOLD: Duration = Duration::from_secs(15);
+
+/// Detects system suspend/resume on all platforms
+/// The timer stops while the system sleeps but the wall clock keeps going,
+/// so a tick that arrives long after the previous one means the system was suspended
+pub fn spawn_resume_watcher(handler: Arc<Handler>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = interval(TICK);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut last = SystemTime::now();
+        loop {
+            ticker.tick().await;
+            let now = SystemTime::now();
+            // A clock set backwards is not a suspend
+            let elapsed = now.duration_since(last).unwrap_or_default();
+            last = now;
+            if elapsed > TICK + SUSPEND_THRESHOLD {
+                handler.on_resume().await;
+            }
+        }
+    })
+}
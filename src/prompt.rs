@@ -0,0 +1,344 @@
+use anyhow::{bail, Context as _, Result};
+#[cfg(feature = "tui")]
+use rustyline::{error::ReadlineError, DefaultEditor};
+#[cfg(feature = "tui")]
+use std::path::PathBuf;
+use std::{sync::Arc, thread, time::Duration};
+
+#[cfg(feature = "tui")]
+use crate::config;
+use crate::{
+    audit::{Origin, Source},
+    bans::BanDuration,
+    console::{self, Glyph},
+    handlers::Handler,
+    ipc,
+    models::InviteKind,
+};
+
+/// A console command
+struct Spec {
+    /// Name followed by the aliases
+    names: &'static [&'static str],
+    /// Arguments
+    usage: &'static str,
+    /// Description
+    help: &'static str,
+}
+
+/// Console commands
+const COMMANDS: &[Spec] = &[
+    Spec {
+        names: &["help", "h", "?"],
+        usage: "[command]",
+        help: "Show the commands (or the details of one)",
+    },
+    Spec {
+        names: &["status", "s"],
+        usage: "",
+        help: "Show the connection, players and invites",
+    },
+    Spec {
+        names: &["approve", "y", "yes"],
+        usage: "[guest_id]",
+        help: "Let a guest waiting for approval play (the longest waiting one by default)",
+    },
+    Spec {
+        names: &["deny", "n", "no"],
+        usage: "[guest_id]",
+        help: "Disconnect a guest waiting for approval (the longest waiting one by default)",
+    },
+    Spec {
+        names: &["invite", "i"],
+        usage: "[minutes]",
+        help: "Create an invite link for the running game (revoked after the minutes if given)",
+    },
+    Spec {
+        names: &["revoke"],
+        usage: "<guest_id>",
+        help: "Revoke an invite link",
+    },
+    Spec {
+        names: &["kick", "k"],
+        usage: "<guest_id> [duration]",
+        help: "Disconnect a guest (and ban for 30m, 12h, 7d or permanent)",
+    },
+    Spec {
+        names: &["bans"],
+        usage: "",
+        help: "List the banned guests",
+    },
+    Spec {
+        names: &["unban"],
+        usage: "<steam_id>",
+        help: "Lift the ban of a guest",
+    },
+    Spec {
+        names: &["pause"],
+        usage: "",
+        help: "Refuse new invites",
+    },
+    Spec {
+        names: &["resume"],
+        usage: "",
+        help: "Accept new invites again",
+    },
+    Spec {
+        names: &["quiet"],
+        usage: "<on|off>",
+        help: "Lift the current quiet hours until they end (off) or keep them again (on)",
+    },
+    Spec {
+        names: &["handoff"],
+        usage: "",
+        help: "Stop hosting and let another PC linked with the bot take the session over",
+    },
+    Spec {
+        names: &["takeover"],
+        usage: "",
+        help: "Get the invites on this PC when the UUID is in use on another device",
+    },
+    Spec {
+        names: &["share"],
+        usage: "",
+        help: "Send the log the server asked for (names, IDs and links hidden)",
+    },
+    Spec {
+        names: &["decline"],
+        usage: "",
+        help: "Refuse the server's request for the log",
+    },
+    Spec {
+        names: &["reply", "r"],
+        usage: "<message>",
+        help: "Reply to the last chat message",
+    },
+];
+
+/// Parsed console command
+enum ConsoleCommand {
+    Help(Option<&'static Spec>),
+    Status,
+    Decide {
+        guest_id: Option<u64>,
+        approve: bool,
+    },
+    Invite(Option<u64>),
+    Revoke(u64),
+    Kick {
+        guest_id: u64,
+        ban: Option<BanDuration>,
+    },
+    Bans,
+    Unban(u64),
+    Pause(bool),
+    /// Keep (false) or lift (true) the current quiet hours
+    Quiet(bool),
+    Handoff,
+    Takeover,
+    /// Send (true) or refuse (false) the requested log
+    Diagnostics(bool),
+    Reply(String),
+}
+
+/// Finds a command by its name or alias
+fn find(name: &str) -> Option<&'static Spec> {
+    COMMANDS
+        .iter()
+        .find(|spec| spec.names.iter().any(|n| n.eq_ignore_ascii_case(name)))
+}
+
+/**
+ * Parses a console line
+ * @return The command (None for an empty line)
+ */
+fn parse(line: &str) -> Result<Option<ConsoleCommand>> {
+    // Slash-command style ("/kick 3") works too
+    let line = line.trim().trim_start_matches('/');
+    if line.is_empty() {
+        return Ok(None);
+    }
+    let (name, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+    let spec = find(name).with_context(|| format!("Unknown command: {name} (see help)"))?;
+    let id = |arg: &str| {
+        arg.parse::<u64>()
+            .with_context(|| format!("Invalid ID: {arg}"))
+    };
+
+    let args: Vec<&str> = rest.split_whitespace().collect();
+    let command = match (spec.names[0], args.as_slice()) {
+        ("help", []) => ConsoleCommand::Help(None),
+        ("help", [name]) => ConsoleCommand::Help(Some(
+            find(name).with_context(|| format!("Unknown command: {name}"))?,
+        )),
+        ("status", []) => ConsoleCommand::Status,
+        (name @ ("approve" | "deny"), []) => ConsoleCommand::Decide {
+            guest_id: None,
+            approve: name == "approve",
+        },
+        (name @ ("approve" | "deny"), [guest_id]) => ConsoleCommand::Decide {
+            guest_id: Some(id(guest_id)?),
+            approve: name == "approve",
+        },
+        ("invite", []) => ConsoleCommand::Invite(None),
+        ("invite", [minutes]) => ConsoleCommand::Invite(Some(
+            minutes
+                .parse()
+                .ok()
+                .filter(|&minutes| minutes > 0)
+                .with_context(|| format!("Invalid number of minutes: {minutes}"))?,
+        )),
+        ("revoke", [guest_id]) => ConsoleCommand::Revoke(id(guest_id)?),
+        ("kick", [guest_id]) => ConsoleCommand::Kick {
+            guest_id: id(guest_id)?,
+            ban: None,
+        },
+        ("kick", [guest_id, duration]) => ConsoleCommand::Kick {
+            guest_id: id(guest_id)?,
+            ban: Some(duration.parse()?),
+        },
+        ("bans", []) => ConsoleCommand::Bans,
+        ("unban", [steam_id]) => ConsoleCommand::Unban(id(steam_id)?),
+        (name @ ("pause" | "resume"), []) => ConsoleCommand::Pause(name == "pause"),
+        ("quiet", [state @ ("on" | "off")]) => ConsoleCommand::Quiet(*state == "off"),
+        ("handoff", []) => ConsoleCommand::Handoff,
+        ("takeover", []) => ConsoleCommand::Takeover,
+        (name @ ("share" | "decline"), []) => ConsoleCommand::Diagnostics(name == "share"),
+        // The message keeps its spacing
+        ("reply", [_, ..]) => ConsoleCommand::Reply(rest.to_owned()),
+        _ => bail!("Usage: {} {}", spec.names[0], spec.usage),
+    };
+    Ok(Some(command))
+}
+
+/// Prints the command list or the details of a command
+fn print_help(spec: Option<&Spec>) -> Result<()> {
+    let Some(spec) = spec else {
+        console::println!("Commands (type help <command> for details):")?;
+        for spec in COMMANDS {
+            let usage = format!("{} {}", spec.names[0], spec.usage);
+            console::println!("  {:<28} {}", usage.trim_end(), spec.help)?;
+        }
+        return Ok(());
+    };
+    console::println!("Usage: {} {}", spec.names[0], spec.usage)?;
+    console::println!("  {}", spec.help)?;
+    if spec.names.len() > 1 {
+        console::println!("  Aliases: {}", spec.names[1..].join(", "))?;
+    }
+    Ok(())
+}
+
+/// Runs a console command
+async fn execute(handler: &Handler, command: ConsoleCommand) -> Result<()> {
+    let origin = || Origin::new(Source::Console, None);
+    match command {
+        ConsoleCommand::Help(spec) => print_help(spec),
+        ConsoleCommand::Status => ipc::print_report(&handler.status().await),
+        ConsoleCommand::Decide { guest_id, approve } => {
+            let guest_id = match guest_id {
+                Some(guest_id) => guest_id,
+                None => handler
+                    .first_pending()
+                    .await
+                    .context("No guest is waiting for approval")?,
+            };
+            handler.decide_guest(guest_id, approve, origin()).await
+        }
+        ConsoleCommand::Invite(minutes) => {
+            let ttl = minutes.map(|minutes| Duration::from_secs(minutes * 60));
+            handler
+                .create_local_invite("local", None, None, InviteKind::Player, ttl)
+                .await?;
+            Ok(())
+        }
+        ConsoleCommand::Revoke(guest_id) => handler.revoke_invite(guest_id, origin()).await,
+        ConsoleCommand::Kick { guest_id, ban } => handler.kick_guest(guest_id, ban, origin()).await,
+        ConsoleCommand::Bans => ipc::print_ban_list(&handler.bans().await),
+        ConsoleCommand::Unban(steam_id) => handler.unban(steam_id, origin()).await,
+        ConsoleCommand::Pause(paused) => handler.set_paused(paused, origin()),
+        ConsoleCommand::Quiet(lifted) => handler.lift_quiet(lifted, origin()),
+        ConsoleCommand::Handoff => handler.request_handoff(origin()).await,
+        ConsoleCommand::Takeover => handler.take_over(),
+        ConsoleCommand::Diagnostics(share) => handler.decide_diagnostics(share, origin()).await,
+        ConsoleCommand::Reply(text) => handler.send_chat(&text).await,
+    }
+}
+
+/// Path of the command history
+#[cfg(feature = "tui")]
+fn history_path() -> Result<PathBuf> {
+    Ok(config::get_exe_path()?.with_extension("history"))
+}
+
+/// Reads commands from the console (line editing and history on a terminal, plain lines when piped)
+pub fn spawn(handler: Arc<Handler>) {
+    let runtime = tokio::runtime::Handle::current();
+    // Reading blocks, so it runs on its own thread
+    thread::spawn(move || {
+        read_lines(|line| {
+            let result = match parse(line) {
+                Ok(Some(command)) => runtime.block_on(execute(&handler, command)),
+                Ok(None) => Ok(()),
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                let _ = console::eprintln!("{} {}", Glyph::Err, err);
+            }
+        });
+    });
+}
+
+/// Passes the lines of the line editor to a function until the input ends
+#[cfg(feature = "tui")]
+fn read_lines(mut run: impl FnMut(&str)) {
+    // Keep Ctrl+C working as usual
+    let editor_config = rustyline::Config::builder()
+        .auto_add_history(true)
+        .enable_signals(true)
+        .build();
+    let mut editor = match DefaultEditor::with_config(editor_config) {
+        Ok(editor) => editor,
+        Err(err) => {
+            let _ = console::eprintln!("{} Console commands disabled: {}", Glyph::Err, err);
+            return;
+        }
+    };
+    let history = history_path().ok();
+    if let Some(path) = &history {
+        // No history yet on the first run
+        let _ = editor.load_history(path);
+    }
+
+    loop {
+        let line = match editor.readline("") {
+            Ok(line) => line,
+            // Piped input ended
+            Err(ReadlineError::Eof) => break,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(err) => {
+                let _ = console::eprintln!("{} Console commands disabled: {}", Glyph::Err, err);
+                break;
+            }
+        };
+        run(&line);
+        if let Some(path) = &history {
+            let _ = editor.save_history(path);
+        }
+    }
+}
+
+/// Passes the lines of the standard input to a function until it ends (no line editing without the tui feature)
+#[cfg(not(feature = "tui"))]
+fn read_lines(mut run: impl FnMut(&str)) {
+    for line in std::io::stdin().lines() {
+        match line {
+            Ok(line) => run(&line),
+            Err(err) => {
+                let _ = console::eprintln!("{} Console commands disabled: {}", Glyph::Err, err);
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use anyhow::{Context as _, Result};
+use chrono::NaiveTime;
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    task::JoinHandle,
+    time::{interval, MissedTickBehavior},
+};
+
+use crate::{
+    config::QuietHoursConfig,
+    console::{self, Glyph},
+    handlers::Handler,
+};
+
+/// How often the clock is checked
+const TICK: Duration = Duration::from_secs(30);
+
+/// Daily time range in which invites are paused and notifications held back
+#[derive(Debug, Clone, Copy)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    /// Reads the times of the configuration (None if disabled)
+    pub fn new(config: &QuietHoursConfig) -> Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let time = |value: &str| {
+            NaiveTime::parse_from_str(value, "%H:%M")
+                .with_context(|| format!("Invalid quiet hours time: {value} (expected HH:MM)"))
+        };
+        Ok(Some(Self {
+            start: time(&config.start)?,
+            end: time(&config.end)?,
+        }))
+    }
+
+    /// Whether the time is in the quiet hours (which may span midnight)
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+/// Enters and leaves the quiet hours by the clock
+pub fn spawn(handler: Arc<Handler>) -> Option<JoinHandle<()>> {
+    handler.quiet_hours()?;
+    Some(tokio::spawn(async move {
+        let mut ticker = interval(TICK);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            if let Err(err) = handler.update_quiet(None) {
+                let _ = console::eprintln!("{} {}", Glyph::Err, err);
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quiet_hours(start: &str, end: &str) -> QuietHours {
+        QuietHours::new(&QuietHoursConfig {
+            enabled: true,
+            start: start.to_owned(),
+            end: end.to_owned(),
+        })
+        .unwrap()
+        .unwrap()
+    }
+
+    fn time(value: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(value, "%H:%M").unwrap()
+    }
+
+    #[test]
+    fn quiet_hours_can_wrap_midnight() {
+        let night = quiet_hours("23:00", "07:00");
+        assert!(night.contains(time("23:00")));
+        assert!(night.contains(time("00:00")));
+        assert!(night.contains(time("06:59")));
+        assert!(!night.contains(time("07:00")));
+        assert!(!night.contains(time("12:00")));
+        assert!(!night.contains(time("22:59")));
+    }
+
+    #[test]
+    fn quiet_hours_within_a_day() {
+        let afternoon = quiet_hours("13:00", "15:30");
+        assert!(!afternoon.contains(time("12:59")));
+        assert!(afternoon.contains(time("13:00")));
+        assert!(afternoon.contains(time("15:29")));
+        assert!(!afternoon.contains(time("15:30")));
+    }
+
+    #[test]
+    fn invalid_times_are_refused() {
+        let config = QuietHoursConfig {
+            enabled: true,
+            start: "25:00".to_owned(),
+            end: "07:00".to_owned(),
+        };
+        assert!(QuietHours::new(&config).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use chrono::{DateTime, Utc};
+use std::{
+    collections::{BTreeSet, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{sync::broadcast::error::RecvError, task::JoinHandle, time::Instant};
+
+use crate::{
+    config::RecapConfig,
+    console::{self, Glyph},
+    events::Event,
+    handlers::Handler,
+    models::SessionRecap,
+    stats::{self, SessionStats},
+};
+
+/// Remote Play session in progress
+struct Session {
+    started: DateTime<Utc>,
+    start: Instant,
+    /// Game running when the first guest joined
+    game: Option<u32>,
+    /// Steam IDs of every guest who joined
+    guests: HashSet<u64>,
+    /// Names of every guest who joined
+    players: BTreeSet<String>,
+    /// Guest IDs of the guests in the session now
+    present: HashSet<u64>,
+    peak: usize,
+    disconnects: usize,
+    /// Heartbeat round trips when the session started
+    rtt_before: (Duration, u32),
+}
+
+/// Sums the heartbeat round trips of every server
+fn rtt_total(handler: &Handler) -> (Duration, u32) {
+    handler
+        .endpoints()
+        .iter()
+        .map(|endpoint| endpoint.rtt_total())
+        .fold((Duration::ZERO, 0), |(sum, count), (rtt, samples)| {
+            (sum + rtt, count + samples)
+        })
+}
+
+impl Session {
+    async fn start(handler: &Handler) -> Self {
+        Self {
+            started: Utc::now(),
+            start: Instant::now(),
+            game: handler.running_game().await.ok(),
+            guests: HashSet::new(),
+            players: BTreeSet::new(),
+            present: HashSet::new(),
+            peak: 0,
+            disconnects: 0,
+            rtt_before: rtt_total(handler),
+        }
+    }
+
+    fn finish(self, handler: &Handler) -> SessionStats {
+        let (sum, count) = rtt_total(handler);
+        let samples = count - self.rtt_before.1;
+        let recap = SessionRecap {
+            started: self.started,
+            duration_secs: self.start.elapsed().as_secs(),
+            guests: self.guests.len(),
+            peak: self.peak,
+            disconnects: self.disconnects,
+            latency_ms: (samples > 0)
+                .then(|| ((sum - self.rtt_before.0) / samples).as_millis() as u64),
+        };
+        SessionStats {
+            recap,
+            game: self.game,
+            players: self.players.into_iter().collect(),
+        }
+    }
+}
+
+/// Prints, sends and saves a summary whenever the last guest leaves the Remote Play session
+pub fn spawn(handler: Arc<Handler>, config: RecapConfig) -> Option<JoinHandle<()>> {
+    if !config.enabled && !config.send && !config.stats {
+        return None;
+    }
+    let mut events = handler.subscribe();
+    Some(tokio::spawn(async move {
+        let mut session: Option<Session> = None;
+        loop {
+            match events.recv().await {
+                Ok(Event::GuestJoined {
+                    guest_id,
+                    steam_id,
+                    name,
+                    persona,
+                    ..
+                }) => {
+                    if session.is_none() {
+                        session = Some(Session::start(&handler).await);
+                    }
+                    let Some(current) = &mut session else {
+                        continue;
+                    };
+                    current.guests.insert(steam_id);
+                    current
+                        .players
+                        .insert(name.or(persona).unwrap_or_else(|| steam_id.to_string()));
+                    current.present.insert(guest_id);
+                    current.peak = current.peak.max(current.present.len());
+                }
+                Ok(Event::GuestLeft { guest_id, .. }) => {
+                    let Some(current) = &mut session else {
+                        continue;
+                    };
+                    if !current.present.remove(&guest_id) {
+                        continue;
+                    }
+                    current.disconnects += 1;
+                    if !current.present.is_empty() {
+                        continue;
+                    }
+                    if let Some(ended) = session.take() {
+                        let stats = ended.finish(&handler);
+                        if config.enabled {
+                            let _ = print(&stats.recap);
+                        }
+                        if config.stats {
+                            stats::record(&stats);
+                        }
+                        if config.send {
+                            handler.report_recap(stats.recap);
+                        }
+                    }
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }))
+}
+
+/// Length of a session for the console (e.g. 1h 05m)
+fn describe(secs: u64) -> String {
+    match (secs / 3600, secs / 60 % 60) {
+        (0, 0) => format!("{secs}s"),
+        (0, minutes) => format!("{minutes}m"),
+        (hours, minutes) => format!("{hours}h {minutes:02}m"),
+    }
+}
+
+fn print(recap: &SessionRecap) -> anyhow::Result<()> {
+    console::printdoc! {"
+
+        {players} Session recap: {duration}
+          Guests: {guests} ({peak} at once)
+          Disconnects: {disconnects}
+          Server latency: {latency}
+
+        ",
+        players = Glyph::Players,
+        duration = describe(recap.duration_secs),
+        guests = recap.guests,
+        peak = recap.peak,
+        disconnects = recap.disconnects,
+        latency = recap
+            .latency_ms
+            .map_or_else(|| "not measured".to_owned(), |ms| format!("{ms} ms"))
+    }
+}
@@ -0,0 +1,178 @@
+use anyhow::{Context as _, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::Write as _,
+    path::Path,
+    sync::{Arc, Mutex, OnceLock},
+    time::Instant,
+};
+use tokio::{task::JoinHandle, time};
+
+use crate::{
+    console::{self, Glyph},
+    files,
+    handlers::Handler,
+};
+
+/// Callback of the Remote Play session from Steam
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "callback", rename_all = "snake_case")]
+pub enum Callback {
+    /// An invite link was created
+    Invited {
+        invitee: u64,
+        guest_id: u64,
+        url: String,
+    },
+    /// A guest joined
+    Started { invitee: u64, guest_id: u64 },
+    /// A guest left
+    Stopped { invitee: u64, guest_id: u64 },
+    /// Steam could not create an invite link (ERemoteClientLaunchResult)
+    Failed {
+        invitee: u64,
+        guest_id: u64,
+        result: i32,
+    },
+}
+
+/// Line of a recording
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Record {
+    /// Milliseconds since the recording started
+    pub at_ms: u64,
+    #[serde(flatten)]
+    pub callback: Callback,
+}
+
+/// File the callbacks are written to (--record-steam)
+struct Recorder {
+    start: Instant,
+    file: Mutex<File>,
+}
+
+static RECORDER: OnceLock<Recorder> = OnceLock::new();
+/// Callbacks fed to the handler instead of the ones of Steam (--replay-steam)
+static REPLAY: OnceLock<Vec<Record>> = OnceLock::new();
+
+/// Writes every Steam callback to a file from now on (JSON Lines, it holds Steam IDs and links)
+pub fn start_recording(path: &Path) -> Result<()> {
+    let file = files::append(path)
+        .with_context(|| format!("Unable to open the recording file: {}", path.display()))?;
+    let _ = RECORDER.set(Recorder {
+        start: Instant::now(),
+        file: Mutex::new(file),
+    });
+    Ok(())
+}
+
+/// Adds a callback to the recording (nothing while not recording)
+pub fn record(callback: &Callback) {
+    let Some(recorder) = RECORDER.get() else {
+        return;
+    };
+    let record = Record {
+        at_ms: recorder.start.elapsed().as_millis() as u64,
+        callback: callback.clone(),
+    };
+    let Ok(line) = serde_json::to_string(&record) else {
+        return;
+    };
+    let mut file = recorder.file.lock().unwrap_or_else(|err| err.into_inner());
+    if let Err(err) = writeln!(file, "{line}") {
+        let _ = console::eprintln!("{} Unable to record a Steam callback: {}", Glyph::Err, err);
+    }
+}
+
+/// Feeds the callbacks of a recording to the handler instead of the ones of Steam
+pub fn start_replay(path: &Path) -> Result<()> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Unable to read the recording file: {}", path.display()))?;
+    let records = parse(&text).with_context(|| format!("Invalid recording: {}", path.display()))?;
+    let _ = REPLAY.set(records);
+    Ok(())
+}
+
+/// Whether the Steam callbacks come from a recording
+pub fn is_replaying() -> bool {
+    REPLAY.get().is_some()
+}
+
+/**
+ * Reads a recording
+ * @return The callbacks in the order they are replayed (by time, then by line)
+ */
+pub fn parse(text: &str) -> Result<Vec<Record>> {
+    let mut records = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).with_context(|| format!("Line {}", index + 1))
+        })
+        .collect::<Result<Vec<Record>>>()?;
+    // Stable, so callbacks of the same millisecond keep their order
+    records.sort_by_key(|record| record.at_ms);
+    Ok(records)
+}
+
+/// Delivers the callbacks one by one with the recorded delays between them
+pub async fn play(records: &[Record], mut deliver: impl FnMut(Callback)) {
+    let start = time::Instant::now();
+    for record in records {
+        time::sleep_until(start + time::Duration::from_millis(record.at_ms)).await;
+        deliver(record.callback.clone());
+    }
+}
+
+/// Replays the recording given with --replay-steam (None without one)
+pub fn spawn(handler: Arc<Handler>) -> Option<JoinHandle<()>> {
+    let records = REPLAY.get()?;
+    Some(tokio::spawn(async move {
+        let _ = console::println!(
+            "{} Replaying {} recorded Steam callbacks",
+            Glyph::Retry,
+            records.len()
+        );
+        play(records, |callback| handler.on_steam_callback(callback)).await;
+        let _ = console::println!("{} Replay finished", Glyph::Ok);
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Guest leaving in the same millisecond the join was reported (the order must be kept)
+    const JOIN_LEAVE_RACE: &str = r#"
+{"at_ms":0,"callback":"invited","invitee":0,"guest_id":7,"url":"https://s.team/p/AAAA-BBBB/CCCC"}
+{"at_ms":20,"callback":"stopped","invitee":42,"guest_id":7}
+{"at_ms":10,"callback":"started","invitee":42,"guest_id":7}
+{"at_ms":20,"callback":"started","invitee":42,"guest_id":7}
+"#;
+
+    #[test]
+    fn parse_orders_by_time_then_line() {
+        let records = parse(JOIN_LEAVE_RACE).unwrap();
+        let order: Vec<_> = records.iter().map(|record| record.at_ms).collect();
+        assert_eq!(order, [0, 10, 20, 20]);
+        assert!(matches!(records[2].callback, Callback::Stopped { .. }));
+        assert!(matches!(records[3].callback, Callback::Started { .. }));
+    }
+
+    #[test]
+    fn parse_reports_the_line() {
+        let err = parse("{\"at_ms\":0,\"callback\":\"left\"}").unwrap_err();
+        assert!(format!("{err:#}").starts_with("Line 1"));
+    }
+
+    #[tokio::test]
+    async fn play_delivers_in_order() {
+        let records = parse(JOIN_LEAVE_RACE).unwrap();
+        let mut delivered = Vec::new();
+        play(&records, |callback| delivered.push(callback)).await;
+        let expected: Vec<_> = records.into_iter().map(|record| record.callback).collect();
+        assert_eq!(delivered, expected);
+    }
+}
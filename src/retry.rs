@@ -1,20 +1,49 @@
+use rand::Rng;
+use std::time::Duration;
+
+/// Longest wait between reconnects
+const MAX_SEC: u64 = 60;
+/// Longest wait the server can ask for
+const MAX_HINT_SEC: u64 = 15 * 60;
+
 /// Retry seconds
-pub struct RetrySec(u64);
+/// Waits are randomized so that clients do not reconnect in lockstep after a server restart
+pub struct RetrySec {
+    sec: u64,
+    /// Wait requested by the server for the next reconnect
+    hint: Option<u64>,
+}
 
 impl RetrySec {
     /// Creates a new RetrySec with an initial value of 1 second
     pub fn new() -> Self {
-        Self(1)
+        Self { sec: 1, hint: None }
+    }
+
+    /**
+     * Doubles the retry seconds, capping at 60 seconds
+     * @return Wait before the next reconnect (between half and all of the retry seconds)
+     */
+    pub fn next(&mut self) -> Duration {
+        self.sec = (self.sec * 2).min(MAX_SEC);
+        let millis = self.sec * 1000;
+        let mut rng = rand::thread_rng();
+        match self.hint.take() {
+            // Spread the clients out after the requested wait
+            Some(hint) => {
+                Duration::from_secs(hint) + Duration::from_millis(rng.gen_range(0..=millis))
+            }
+            None => Duration::from_millis(rng.gen_range(millis / 2..=millis)),
+        }
     }
 
-    /// Doubles the retry seconds, capping at 60 seconds
-    pub fn next(&mut self) -> u64 {
-        self.0 = self.0.min(60) * 2;
-        self.0
+    /// Waits at least this long before the next reconnect (Retry-After or a server backoff hint)
+    pub fn hint(&mut self, sec: u64) {
+        self.hint = Some(sec.min(MAX_HINT_SEC));
     }
 
     /// Resets the retry seconds to the initial value of 1 second
     pub fn reset(&mut self) {
-        self.0 = 1;
+        self.sec = 1;
     }
 }
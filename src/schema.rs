@@ -0,0 +1,34 @@
+use anyhow::{Context as _, Result};
+use schemars::schema_for;
+use serde_json::json;
+use std::io::{self, Write as _};
+
+use crate::models::{ClientMessage, ServerMessage};
+
+/// Side of the protocol whose messages are described
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Requests the server sends to the client (`ServerMessage`)
+    Server,
+    /// Answers and notifications the client sends to the server (`ClientMessage`)
+    Client,
+}
+
+/// `schema` subcommand: prints the JSON Schema of the protocol messages
+/// Without a direction, both schemas are printed as `{"server": ..., "client": ...}`
+pub fn print(direction: Option<Direction>) -> Result<()> {
+    let schema = match direction {
+        Some(Direction::Server) => serde_json::to_value(schema_for!(ServerMessage)),
+        Some(Direction::Client) => serde_json::to_value(schema_for!(ClientMessage)),
+        None => serde_json::to_value(schema_for!(ServerMessage)).and_then(|server| {
+            Ok(json!({
+                "server": server,
+                "client": serde_json::to_value(schema_for!(ClientMessage))?,
+            }))
+        }),
+    }
+    .context("Unable to serialize the schema")?;
+    let text = serde_json::to_string_pretty(&schema).context("Unable to serialize the schema")?;
+    // Plain output, so it can be redirected to a file
+    writeln!(io::stdout(), "{text}").context("Unable to print the schema")
+}
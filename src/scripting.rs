@@ -0,0 +1,197 @@
+use anyhow::{anyhow, Context as _, Result};
+use rhai::{
+    module_resolvers::DummyModuleResolver, CallFnOptions, Dynamic, Engine, EvalAltResult, Scope,
+    AST,
+};
+use std::{path::Path, sync::Arc, thread};
+use tokio::{runtime, sync::broadcast::error::RecvError};
+
+use crate::{
+    audit::{Origin, Source},
+    config::{self, ScriptsConfig},
+    console::{self, Glyph},
+    handlers::Handler,
+    models::InviteKind,
+};
+
+/// Deepest function call nesting allowed in a script
+const MAX_CALL_LEVELS: usize = 32;
+
+/// A loaded user script
+struct Script {
+    /// File name (shown in the console)
+    name: String,
+    /// Compiled script
+    ast: AST,
+    /// Global variables, kept between events
+    scope: Scope<'static>,
+}
+
+/// Runs the user scripts on handler events
+/// A script reacts to an event by defining `fn on_<event type>(event)`, e.g. `on_guest_joined`
+pub fn spawn(handler: &Arc<Handler>, config: ScriptsConfig) {
+    if !config.enabled || config.files.is_empty() {
+        return;
+    }
+
+    let engine = engine(handler, &config);
+    let mut scripts = Vec::new();
+    for file in &config.files {
+        match load(&engine, file) {
+            Ok(script) => {
+                let _ = console::println!("{} Loaded script: {}", Glyph::Ok, script.name);
+                scripts.push(script);
+            }
+            Err(err) => {
+                let _ = console::eprintln!("{} {:#}", Glyph::Err, err);
+            }
+        }
+    }
+    if scripts.is_empty() {
+        return;
+    }
+
+    let mut events = handler.subscribe();
+    // Scripts run synchronously, so they get their own thread
+    thread::spawn(move || loop {
+        let event = match events.blocking_recv() {
+            Ok(event) => event,
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        };
+        let event = match rhai::serde::to_dynamic(&event) {
+            Ok(event) => event,
+            Err(_) => continue,
+        };
+        let Some(kind) = event
+            .read_lock::<rhai::Map>()
+            .and_then(|map| map.get("type").map(|kind| kind.to_string()))
+        else {
+            continue;
+        };
+
+        let callback = format!("on_{kind}");
+        for script in &mut scripts {
+            if !script
+                .ast
+                .iter_functions()
+                .any(|f| f.name == callback && f.params.len() == 1)
+            {
+                continue;
+            }
+            // The top level statements already ran when the script was loaded
+            let options = CallFnOptions::new().eval_ast(false).rewind_scope(false);
+            let result = engine.call_fn_with_options::<Dynamic>(
+                options,
+                &mut script.scope,
+                &script.ast,
+                &callback,
+                (event.clone(),),
+            );
+            if let Err(err) = result {
+                let _ = console::eprintln!(
+                    "{} Script {} failed in {}: {}",
+                    Glyph::Err,
+                    script.name,
+                    callback,
+                    err
+                );
+            }
+        }
+    });
+}
+
+/// Compiles a script and runs its top level statements
+fn load(engine: &Engine, file: &Path) -> Result<Script> {
+    // Relative paths are resolved next to the executable
+    let path = match config::get_exe_path()?.parent() {
+        Some(dir) if file.is_relative() => dir.join(file),
+        _ => file.to_path_buf(),
+    };
+    let name = path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+
+    let ast = engine
+        .compile_file(path.clone())
+        .map_err(|err| anyhow!("{err}"))
+        .with_context(|| format!("Unable to load script: {:?}", path))?;
+    let mut scope = Scope::new();
+    engine
+        .run_ast_with_scope(&mut scope, &ast)
+        .map_err(|err| anyhow!("{err}"))
+        .with_context(|| format!("Script {name} failed"))?;
+    Ok(Script { name, ast, scope })
+}
+
+/// Converts an error for the script
+fn script_error(err: anyhow::Error) -> Box<EvalAltResult> {
+    format!("{err:#}").into()
+}
+
+/// Creates the script engine with the API available to the scripts
+/// Scripts can only reach the inviter through these functions (no file or network access)
+fn engine(handler: &Arc<Handler>, config: &ScriptsConfig) -> Engine {
+    let mut engine = Engine::new();
+    // `import` would load script files from the disk
+    engine.set_module_resolver(DummyModuleResolver::new());
+    // Keep a runaway script from stalling the other scripts
+    engine.set_max_operations(config.max_operations);
+    engine.set_max_call_levels(MAX_CALL_LEVELS);
+    engine.on_print(|text| {
+        let _ = console::println!("[script] {text}");
+    });
+    engine.on_debug(|text, _, _| {
+        let _ = console::println!("[script] {text}");
+    });
+
+    let runtime = runtime::Handle::current();
+
+    // notify(text): Shows a message to the host
+    engine.register_fn("notify", |text: &str| {
+        let _ = console::println!("Script: {text}");
+        let _ = console::headline(text);
+    });
+
+    // status(): Connection, players and invites (same as the status command)
+    let (h, rt) = (handler.clone(), runtime.clone());
+    engine.register_fn("status", move || -> Result<Dynamic, Box<EvalAltResult>> {
+        rhai::serde::to_dynamic(rt.block_on(h.status()))
+    });
+
+    // create_invite(): Creates an invite link for the running game and returns its URL
+    let (h, rt) = (handler.clone(), runtime.clone());
+    engine.register_fn(
+        "create_invite",
+        move || -> Result<String, Box<EvalAltResult>> {
+            let invite = h.create_local_invite("script", None, None, InviteKind::Player, None);
+            let (_, _, url) = rt.block_on(invite).map_err(script_error)?;
+            Ok(url)
+        },
+    );
+
+    // send_chat(text): Sends a chat message to Discord
+    let (h, rt) = (handler.clone(), runtime.clone());
+    engine.register_fn(
+        "send_chat",
+        move |text: &str| -> Result<(), Box<EvalAltResult>> {
+            rt.block_on(h.send_chat(text)).map_err(script_error)
+        },
+    );
+
+    // kick(guest_id): Disconnects a guest (bans for the configured [bans] on_kick duration)
+    let (h, rt) = (handler.clone(), runtime);
+    engine.register_fn(
+        "kick",
+        move |guest_id: i64| -> Result<(), Box<EvalAltResult>> {
+            let guest_id =
+                u64::try_from(guest_id).map_err(|_| format!("Invalid guest ID: {guest_id}"))?;
+            let origin = Origin::new(Source::Script, None);
+            rt.block_on(h.kick_guest(guest_id, None, origin))
+                .map_err(script_error)
+        },
+    );
+
+    engine
+}
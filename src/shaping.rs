@@ -0,0 +1,360 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    future, mem,
+    time::Duration,
+};
+use tokio::time::{self, Instant};
+
+use crate::{
+    config::ShapingConfig,
+    models::{ClientCmd, ClientMessage, RevokeReason},
+};
+
+/// Messages of each priority that wait for the connection
+const QUEUE_CAPACITY: usize = 32;
+
+/// How urgently a message has to reach the server (lowest first)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Telemetry and state syncs (may be held back and batched while the upstream is saturated)
+    Background,
+    /// Invite events and host actions (approvals, revocations, chat replies)
+    Event,
+    /// Responses to server requests (sent right away)
+    Control,
+}
+
+impl Priority {
+    /// Priority of a message
+    pub fn of(cmd: &ClientCmd) -> Self {
+        match cmd {
+            ClientCmd::GameId { .. } | ClientCmd::Link { .. } | ClientCmd::Error { .. } => {
+                Priority::Control
+            }
+            // The client already refuses further joins, the server only updates its view
+            ClientCmd::Revoked {
+                reason: RevokeReason::Exhausted,
+                ..
+            } => Priority::Background,
+            ClientCmd::Sync { .. }
+            | ClientCmd::CompatQuery { .. }
+            | ClientCmd::Load { .. }
+            | ClientCmd::Recap { .. } => Priority::Background,
+            _ => Priority::Event,
+        }
+    }
+}
+
+/// Messages waiting to be sent without a request, served by priority
+#[derive(Default)]
+pub struct NoticeQueue {
+    queues: BTreeMap<Priority, VecDeque<ClientMessage>>,
+}
+
+impl NoticeQueue {
+    /**
+     * Queues a message
+     * @return Whether it was queued (dropped if the server is unreachable for a long time)
+     */
+    pub fn push(&mut self, msg: ClientMessage) -> bool {
+        let queue = self.queues.entry(Priority::of(&msg.cmd)).or_default();
+        if queue.len() >= QUEUE_CAPACITY {
+            return false;
+        }
+        queue.push_back(msg);
+        true
+    }
+
+    /// Number of queued messages
+    pub fn count(&self) -> usize {
+        self.queues.values().map(VecDeque::len).sum()
+    }
+
+    /// Takes the oldest message of the highest priority
+    pub fn pop(&mut self) -> Option<ClientMessage> {
+        self.queues
+            .values_mut()
+            .rev()
+            .find_map(|queue| queue.pop_front())
+    }
+}
+
+/// Traffic shaper for the connection to the server
+/// While guests are streaming and the round trip time shows a saturated upstream,
+/// background messages are batched and heartbeats are sent less often
+pub struct Shaper {
+    config: ShapingConfig,
+    /// Whether a Remote Play session is active
+    streaming: bool,
+    /// Smoothed round trip time of the heartbeats
+    rtt: Option<Duration>,
+    /// When the unanswered heartbeat was sent
+    ping_sent: Option<Instant>,
+    /// When the next heartbeat is due
+    next_ping: Instant,
+    /// Held back messages
+    batch: Vec<ClientMessage>,
+    /// When the held back messages are sent at the latest
+    deadline: Option<Instant>,
+}
+
+impl Shaper {
+    pub fn new(config: ShapingConfig) -> Self {
+        let next_ping = Instant::now() + Duration::from_secs(config.heartbeat_secs);
+        Self {
+            config,
+            streaming: false,
+            rtt: None,
+            ping_sent: None,
+            next_ping,
+            batch: Vec::new(),
+            deadline: None,
+        }
+    }
+
+    /// Forgets the measurements of the previous connection
+    pub fn reset_link(&mut self) {
+        self.rtt = None;
+        self.ping_sent = None;
+        self.next_ping = Instant::now() + Duration::from_secs(self.config.heartbeat_secs);
+    }
+
+    /// Updates whether a Remote Play session is active
+    pub fn set_streaming(&mut self, streaming: bool) {
+        self.streaming = streaming;
+    }
+
+    /// Whether the upstream looks saturated
+    fn is_saturated(&self) -> bool {
+        let limit = Duration::from_millis(self.config.saturation_rtt_ms);
+        // An overdue heartbeat counts as a slow one
+        let waiting = self.ping_sent.map(|sent| sent.elapsed());
+        self.rtt.max(waiting).is_some_and(|rtt| rtt > limit)
+    }
+
+    /// Whether background traffic is being held back
+    pub fn is_throttling(&self) -> bool {
+        self.config.enabled && self.streaming && self.is_saturated()
+    }
+
+    /**
+     * Queues a message without a request
+     * @return Messages to send now (held back ones are sent along when not throttling)
+     */
+    pub fn submit(&mut self, msg: ClientMessage) -> Vec<ClientMessage> {
+        if !self.is_throttling() {
+            let mut out = self.flush();
+            out.push(msg);
+            return out;
+        }
+        if Priority::of(&msg.cmd) > Priority::Background {
+            return vec![msg];
+        }
+        self.batch.push(msg);
+        self.deadline
+            .get_or_insert_with(|| Instant::now() + Duration::from_secs(self.config.batch_secs));
+        Vec::new()
+    }
+
+    /// When the held back messages are due
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Takes the held back messages
+    pub fn flush(&mut self) -> Vec<ClientMessage> {
+        self.deadline = None;
+        mem::take(&mut self.batch)
+    }
+
+    /// When the next heartbeat is due
+    pub fn next_ping(&self) -> Instant {
+        self.next_ping
+    }
+
+    /// Records a sent heartbeat and schedules the next one (less often while throttling)
+    pub fn ping_sent(&mut self) {
+        let now = Instant::now();
+        // Keep the time of an unanswered heartbeat so a stalled link shows up as slow
+        self.ping_sent.get_or_insert(now);
+        let secs = if self.is_throttling() {
+            self.config.throttled_heartbeat_secs
+        } else {
+            self.config.heartbeat_secs
+        };
+        self.next_ping = now + Duration::from_secs(secs);
+    }
+
+    /**
+     * Records the answer to a heartbeat
+     * @return Round trip time of the heartbeat (None for an unsolicited pong)
+     */
+    pub fn pong_received(&mut self) -> Option<Duration> {
+        let sample = self.ping_sent.take()?.elapsed();
+        self.rtt = Some(match self.rtt {
+            Some(rtt) => (rtt * 3 + sample) / 4,
+            None => sample,
+        });
+        Some(sample)
+    }
+}
+
+/// Waits until the deadline (forever if there is none)
+pub async fn wait(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => time::sleep_until(deadline).await,
+        None => future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shaper(batch_secs: u64) -> Shaper {
+        Shaper::new(ShapingConfig {
+            saturation_rtt_ms: 1,
+            batch_secs,
+            ..ShapingConfig::default()
+        })
+    }
+
+    /// Measures a slow heartbeat
+    fn saturate(shaper: &mut Shaper) {
+        shaper.ping_sent();
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(shaper.pong_received().is_some());
+    }
+
+    fn revoked(guest_id: u64, reason: RevokeReason) -> ClientMessage {
+        ClientMessage::notice(ClientCmd::Revoked { guest_id, reason })
+    }
+
+    fn guest_ids(messages: &[ClientMessage]) -> Vec<u64> {
+        messages
+            .iter()
+            .map(|msg| match msg.cmd {
+                ClientCmd::Revoked { guest_id, .. } => guest_id,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn batches_only_while_streaming_and_saturated() {
+        let mut shaper = shaper(10);
+        // Saturated without a stream
+        saturate(&mut shaper);
+        assert!(!shaper.is_throttling());
+        assert_eq!(
+            guest_ids(&shaper.submit(revoked(1, RevokeReason::Exhausted))),
+            [1]
+        );
+
+        // Streaming on an idle link
+        let mut shaper = self::shaper(10);
+        shaper.set_streaming(true);
+        assert!(!shaper.is_throttling());
+        assert_eq!(
+            guest_ids(&shaper.submit(revoked(2, RevokeReason::Exhausted))),
+            [2]
+        );
+
+        // Streaming on a saturated link: background messages wait, events do not
+        saturate(&mut shaper);
+        assert!(shaper.is_throttling());
+        assert!(shaper
+            .submit(revoked(3, RevokeReason::Exhausted))
+            .is_empty());
+        assert_eq!(
+            guest_ids(&shaper.submit(revoked(4, RevokeReason::Host))),
+            [4]
+        );
+
+        // Once the stream ends the held back messages go along with the next one
+        shaper.set_streaming(false);
+        assert_eq!(
+            guest_ids(&shaper.submit(revoked(5, RevokeReason::Exhausted))),
+            [3, 5]
+        );
+        assert!(shaper.deadline().is_none());
+    }
+
+    #[test]
+    fn disabled_shaper_never_batches() {
+        let mut shaper = Shaper::new(ShapingConfig {
+            enabled: false,
+            saturation_rtt_ms: 1,
+            ..ShapingConfig::default()
+        });
+        shaper.set_streaming(true);
+        saturate(&mut shaper);
+        assert!(!shaper.is_throttling());
+        assert_eq!(
+            guest_ids(&shaper.submit(revoked(1, RevokeReason::Exhausted))),
+            [1]
+        );
+    }
+
+    #[tokio::test]
+    async fn held_back_messages_are_flushed_at_the_deadline() {
+        let mut shaper = shaper(0);
+        shaper.set_streaming(true);
+        saturate(&mut shaper);
+        assert!(shaper
+            .submit(revoked(1, RevokeReason::Exhausted))
+            .is_empty());
+        let deadline = shaper.deadline().unwrap();
+        // Later messages do not push the deadline back
+        assert!(shaper
+            .submit(revoked(2, RevokeReason::Exhausted))
+            .is_empty());
+        assert_eq!(shaper.deadline(), Some(deadline));
+
+        time::timeout(Duration::from_secs(1), wait(shaper.deadline()))
+            .await
+            .unwrap();
+        assert_eq!(guest_ids(&shaper.flush()), [1, 2]);
+        assert!(shaper.deadline().is_none());
+        assert!(shaper.flush().is_empty());
+    }
+
+    #[tokio::test]
+    async fn no_deadline_waits_forever() {
+        assert!(time::timeout(Duration::from_millis(10), wait(None))
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn heartbeats_slow_down_while_throttling() {
+        let config = ShapingConfig::default();
+        let mut shaper = shaper(10);
+        shaper.set_streaming(true);
+        shaper.ping_sent();
+        let interval = shaper.next_ping() - Instant::now();
+        assert!(interval <= Duration::from_secs(config.heartbeat_secs));
+        assert!(interval > Duration::from_secs(config.heartbeat_secs - 1));
+
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(shaper.pong_received().is_some());
+        assert!(shaper.is_throttling());
+        shaper.ping_sent();
+        let interval = shaper.next_ping() - Instant::now();
+        assert!(interval > Duration::from_secs(config.throttled_heartbeat_secs - 1));
+
+        // Back to the normal interval on a new connection
+        shaper.reset_link();
+        assert!(!shaper.is_throttling());
+        shaper.ping_sent();
+        let interval = shaper.next_ping() - Instant::now();
+        assert!(interval <= Duration::from_secs(config.heartbeat_secs));
+    }
+
+    #[test]
+    fn unsolicited_pongs_are_ignored() {
+        let mut shaper = shaper(10);
+        assert!(shaper.pong_received().is_none());
+        assert!(!shaper.is_saturated());
+    }
+}
@@ -0,0 +1,170 @@
+use anyhow::{Context as _, Result};
+use serde::Deserialize;
+use serde_json::json;
+use std::{collections::HashMap, time::Duration};
+use tokio::{sync::Mutex, task};
+
+use crate::{
+    config::ShortenerConfig,
+    console::{self, Glyph},
+    transport,
+};
+
+/// Path of the shortener on the project's server
+const SERVICE_PATH: &str = "/shorten";
+
+/// Short link returned by the shortener
+#[derive(Debug, Clone, Deserialize)]
+struct ShortLink {
+    /// Short URL
+    url: String,
+    /// ID used to delete the short link (None if the shortener cannot delete links)
+    #[serde(default)]
+    id: Option<String>,
+}
+
+/// Shortens invite URLs before they are shared
+pub struct Shortener {
+    /// Shortener API (None: disabled)
+    api: Option<String>,
+    /// Bearer token of the shortener API
+    token: Option<String>,
+    /// How long the shortener may take to answer
+    timeout: Duration,
+    /// Short link of each invite URL
+    cache: Mutex<HashMap<String, ShortLink>>,
+}
+
+impl Shortener {
+    /// endpoint_url: URL of the server the client registers with (None: the default server)
+    pub fn new(config: &ShortenerConfig, endpoint_url: Option<&str>, timeout: Duration) -> Self {
+        let api = if config.enabled {
+            let api = config
+                .api
+                .clone()
+                .or_else(|| service_api(endpoint_url.unwrap_or(crate::DEFAULT_URL)));
+            if api.is_none() {
+                let _ = console::eprintln!(
+                    "{} The invite links are not shortened: set shortener.api for a server on a Unix domain socket",
+                    Glyph::Warn
+                );
+            }
+            api
+        } else {
+            None
+        };
+        Self {
+            api,
+            token: config.token.clone(),
+            timeout,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /**
+     * Shortens an invite URL (the URL itself is kept if the shortener fails)
+     * @return The URL to share
+     */
+    pub async fn shorten(&self, url: &str) -> String {
+        let Some(api) = &self.api else {
+            return url.to_owned();
+        };
+        if let Some(link) = self.cache.lock().await.get(url) {
+            return link.url.clone();
+        }
+
+        let request = ureq::post(api).timeout(self.timeout);
+        let request = match &self.token {
+            Some(token) => request.set("Authorization", &format!("Bearer {token}")),
+            None => request,
+        };
+        let body = json!({ "url": url });
+        let result = task::spawn_blocking(move || -> Result<ShortLink> {
+            request
+                .send_json(body)
+                .context("Failed to shorten the invite link")?
+                .into_json()
+                .context("Invalid response from the shortener")
+        })
+        .await;
+        match result {
+            Ok(Ok(link)) => {
+                let short = link.url.clone();
+                self.cache.lock().await.insert(url.to_owned(), link);
+                short
+            }
+            Ok(Err(err)) => {
+                let _ = console::eprintln!("{} {:#}", Glyph::Warn, err);
+                url.to_owned()
+            }
+            Err(_) => url.to_owned(),
+        }
+    }
+
+    /// Deletes the short link of a revoked invite so it stops redirecting
+    pub async fn revoke(&self, url: &str) {
+        let (Some(api), Some(link)) = (&self.api, self.cache.lock().await.remove(url)) else {
+            return;
+        };
+        let Some(id) = link.id else {
+            return;
+        };
+        let request =
+            ureq::delete(&format!("{}/{id}", api.trim_end_matches('/'))).timeout(self.timeout);
+        let request = match &self.token {
+            Some(token) => request.set("Authorization", &format!("Bearer {token}")),
+            None => request,
+        };
+        // The revocation does not wait for the shortener
+        task::spawn_blocking(move || {
+            if let Err(err) = request.call() {
+                let _ = console::eprintln!(
+                    "{} Failed to delete the short link {}: {}",
+                    Glyph::Warn,
+                    link.url,
+                    err
+                );
+            }
+        });
+    }
+}
+
+/**
+ * Shortener of the server the client registers with
+ * (e.g. "wss://example.com/ws?v=1" -> "https://example.com/shorten")
+ * @return None for a server on a Unix domain socket (it has no HTTP origin)
+ */
+fn service_api(endpoint_url: &str) -> Option<String> {
+    if transport::unix_socket(endpoint_url).is_some() {
+        return None;
+    }
+    let url = endpoint_url
+        .replacen("wss://", "https://", 1)
+        .replacen("ws://", "http://", 1);
+    let origin = match url.find("://") {
+        Some(index) => match url[index + 3..].find('/') {
+            Some(end) => &url[..index + 3 + end],
+            None => &url,
+        },
+        None => &url,
+    };
+    Some(format!("{origin}{SERVICE_PATH}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortener_follows_the_endpoint() {
+        assert_eq!(
+            service_api("wss://example.com:8443/ws?v=1&token=x").as_deref(),
+            Some("https://example.com:8443/shorten")
+        );
+        assert_eq!(
+            service_api("ws://localhost").as_deref(),
+            Some("http://localhost/shorten")
+        );
+        assert_eq!(service_api("ws+unix:///run/inviter.sock:/ws"), None);
+    }
+}
@@ -0,0 +1,66 @@
+use rand::Rng as _;
+use std::{
+    ops::Range,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+use tokio::{task::JoinHandle, time};
+
+use crate::{
+    console::{self, Glyph},
+    handlers::Handler,
+    logger::Level,
+    replay::Callback,
+};
+
+/// Steam IDs of the fake guests start here (below the accounts of real users)
+const FIRST_STEAM_ID: u64 = 76_561_190_000_000_000;
+/// Different fake guests, so some of them come back like regulars
+const STEAM_IDS: u64 = 50;
+/// Guest IDs of the fake guests start here (far above the ones of Steam)
+const FIRST_GUEST_ID: u64 = 1 << 48;
+/// How long a fake guest stays in seconds
+const STAY_SECS: Range<u64> = 30..300;
+
+/// Fake guest joins per minute (--simulate-guests)
+static RATE: OnceLock<u32> = OnceLock::new();
+
+/// Synthesizes guest traffic from now on
+pub fn start(per_minute: u32) {
+    let _ = RATE.set(per_minute);
+}
+
+/// Feeds fake guest joins and leaves to the handler as Steam callbacks (None without --simulate-guests)
+pub fn spawn(handler: Arc<Handler>) -> Option<JoinHandle<()>> {
+    let per_minute = *RATE.get()?;
+    let interval = Duration::from_secs(60) / per_minute;
+    Some(tokio::spawn(async move {
+        let _ = console::println!(
+            @level Level::Warn,
+            "{} Simulating {} guest joins per minute",
+            Glyph::Warn,
+            per_minute
+        );
+        let mut guest_id = FIRST_GUEST_ID;
+        loop {
+            // Random gaps around the rate, so joins and leaves overlap
+            let (gap, invitee, stay) = {
+                let mut rng = rand::thread_rng();
+                (
+                    interval.mul_f64(rng.gen_range(0.5..1.5)),
+                    FIRST_STEAM_ID + rng.gen_range(0..STEAM_IDS),
+                    Duration::from_secs(rng.gen_range(STAY_SECS)),
+                )
+            };
+            time::sleep(gap).await;
+            guest_id += 1;
+            handler.on_steam_callback(Callback::Started { invitee, guest_id });
+
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                time::sleep(stay).await;
+                handler.on_steam_callback(Callback::Stopped { invitee, guest_id });
+            });
+        }
+    }))
+}
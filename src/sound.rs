@@ -0,0 +1,136 @@
+use anyhow::{Context as _, Result};
+use rodio::{
+    source::{SineWave, Source as _},
+    Decoder, OutputStream, Sink,
+};
+use std::{fs::File, io::BufReader, sync::Arc, thread, time::Duration};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{
+    config::{self, SoundConfig, SoundsConfig},
+    console::{self, Glyph},
+    events::Event,
+    handlers::{ConnectionState, Handler},
+};
+
+/// Sound alerts
+#[derive(Clone, Copy)]
+enum Alert {
+    /// A guest joined
+    Join,
+    /// A guest left
+    Leave,
+    /// The connection to the server was lost
+    Disconnect,
+}
+
+impl Alert {
+    /// Notes of the built-in chime (frequency in Hz)
+    fn notes(self) -> &'static [f32] {
+        match self {
+            Alert::Join => &[660.0, 880.0],
+            Alert::Leave => &[880.0, 660.0],
+            Alert::Disconnect => &[330.0, 330.0],
+        }
+    }
+
+    /// Settings of the alert
+    fn config(self, config: &SoundsConfig) -> &SoundConfig {
+        match self {
+            Alert::Join => &config.join,
+            Alert::Leave => &config.leave,
+            Alert::Disconnect => &config.disconnect,
+        }
+    }
+}
+
+/// Starts playing sound alerts for handler events
+pub fn spawn(handler: &Arc<Handler>, config: SoundsConfig) {
+    if !config.enabled {
+        return;
+    }
+
+    let mut events = handler.subscribe();
+    let handler = handler.clone();
+    // The audio output cannot be moved between threads, so it lives on its own thread
+    thread::spawn(move || {
+        let (_stream, output) = match OutputStream::try_default() {
+            Ok(output) => output,
+            Err(err) => {
+                let _ = console::eprintln!("{} Sound alerts disabled: {}", Glyph::Err, err);
+                return;
+            }
+        };
+
+        let mut connected = false;
+        loop {
+            let event = match events.blocking_recv() {
+                Ok(event) => event,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            let alert = match event {
+                Event::GuestJoined { .. } => Alert::Join,
+                Event::GuestLeft { .. } => Alert::Leave,
+                Event::ConnectionChanged { state } => {
+                    // Only alert when an established connection is lost
+                    let lost = connected && state != ConnectionState::Connected;
+                    connected = state == ConnectionState::Connected;
+                    if !lost {
+                        continue;
+                    }
+                    Alert::Disconnect
+                }
+                _ => continue,
+            };
+            // Silent while a fullscreen game has the focus and during the quiet hours
+            if !alert.config(&config).enabled || console::is_do_not_disturb() || handler.is_quiet()
+            {
+                continue;
+            }
+
+            let sink = match Sink::try_new(&output) {
+                Ok(sink) => sink,
+                Err(_) => continue,
+            };
+            sink.set_volume(config.volume.clamp(0.0, 1.0));
+            if let Err(err) = append(&sink, alert, &config) {
+                let _ = console::eprintln!("{} {}", Glyph::Err, err);
+                // Fall back to the built-in chime
+                append_chime(&sink, alert);
+            }
+            sink.sleep_until_end();
+        }
+    });
+}
+
+/// Queues the user's sound file, or the built-in chime if none is set
+fn append(sink: &Sink, alert: Alert, config: &SoundsConfig) -> Result<()> {
+    let Some(file) = &alert.config(config).file else {
+        append_chime(sink, alert);
+        return Ok(());
+    };
+
+    // Relative paths are resolved next to the executable
+    let path = match config::get_exe_path()?.parent() {
+        Some(dir) if file.is_relative() => dir.join(file),
+        _ => file.clone(),
+    };
+    let source =
+        File::open(&path).with_context(|| format!("Unable to open sound file: {:?}", path))?;
+    let decoder = Decoder::new(BufReader::new(source))
+        .with_context(|| format!("Unable to decode sound file: {:?}", path))?;
+    sink.append(decoder);
+    Ok(())
+}
+
+/// Queues the built-in chime
+fn append_chime(sink: &Sink, alert: Alert) {
+    for &frequency in alert.notes() {
+        sink.append(
+            SineWave::new(frequency)
+                .take_duration(Duration::from_millis(120))
+                .fade_in(Duration::from_millis(10)),
+        );
+    }
+}
@@ -0,0 +1,284 @@
+use anyhow::{Context as _, Result};
+use chrono::{Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::{self, Write as _},
+    path::PathBuf,
+};
+
+use crate::{
+    compat::CompatList,
+    config,
+    console::{self, Glyph},
+    files,
+    models::SessionRecap,
+};
+
+/// Length of the periods a summary covers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Period {
+    /// ISO week (Monday to Sunday)
+    #[default]
+    Week,
+    /// Calendar month
+    Month,
+}
+
+/// File format of `stats export`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+/// Stats of a Remote Play session as saved on this PC
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionStats {
+    #[serde(flatten)]
+    pub recap: SessionRecap,
+    /// Game that was running when the first guest joined
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game: Option<u32>,
+    /// Discord or Steam names of the guests
+    #[serde(default)]
+    pub players: Vec<String>,
+}
+
+/// How often something appeared in the sessions of a period
+#[derive(Debug, Serialize)]
+pub struct Count {
+    pub name: String,
+    pub sessions: usize,
+}
+
+/// Sessions of one week or month
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    /// Week (e.g. 2024-W27) or month (e.g. 2024-07)
+    pub period: String,
+    pub sessions: usize,
+    /// Hours of hosting
+    pub hours: f64,
+    /// Different guests
+    pub guests: usize,
+    /// Games by the number of sessions
+    pub games: Vec<Count>,
+    /// Guests by the number of sessions they joined (at most 3)
+    pub top_guests: Vec<Count>,
+}
+
+/// Path of the session stats (one JSON object per line)
+fn stats_path() -> Result<PathBuf> {
+    Ok(config::get_exe_path()?.with_extension("stats.jsonl"))
+}
+
+/// Appends the stats of a session that ended
+pub fn record(stats: &SessionStats) {
+    let result: Result<()> = 'tryblock: {
+        let path = match stats_path() {
+            Ok(path) => path,
+            Err(err) => break 'tryblock Err(err),
+        };
+        let line = match serde_json::to_string(stats).context("Unable to serialize session stats") {
+            Ok(line) => line,
+            Err(err) => break 'tryblock Err(err),
+        };
+        files::append(&path)
+            .and_then(|mut file| writeln!(file, "{line}"))
+            .with_context(|| format!("Unable to write session stats: {:?}", path))
+    };
+    if let Err(err) = result {
+        let _ = console::eprintln!("{} {}", Glyph::Err, err);
+    }
+}
+
+/**
+ * Groups the sessions by week or month
+ * @return Summaries, oldest period first
+ */
+pub fn summarize(sessions: &[SessionStats], period: Period, games: &CompatList) -> Vec<Summary> {
+    let mut periods = BTreeMap::<String, Vec<&SessionStats>>::new();
+    for session in sessions {
+        let started = session.recap.started.with_timezone(&Local);
+        let key = match period {
+            Period::Week => started.format("%G-W%V"),
+            Period::Month => started.format("%Y-%m"),
+        };
+        periods.entry(key.to_string()).or_default().push(session);
+    }
+
+    periods
+        .into_iter()
+        .map(|(period, sessions)| {
+            let secs: u64 = sessions.iter().map(|s| s.recap.duration_secs).sum();
+            let game_names = sessions.iter().filter_map(|s| s.game).map(|app_id| {
+                games
+                    .find(app_id)
+                    .and_then(|entry| entry.name.clone())
+                    .unwrap_or_else(|| format!("App {app_id}"))
+            });
+            let players = sessions.iter().flat_map(|s| s.players.iter().cloned());
+            let guests = ranked(players.clone(), usize::MAX).len();
+            Summary {
+                period,
+                sessions: sessions.len(),
+                hours: (secs as f64 / 360.0).round() / 10.0,
+                guests,
+                games: ranked(game_names, usize::MAX),
+                top_guests: ranked(players, 3),
+            }
+        })
+        .collect()
+}
+
+/// Counts the names, most frequent first (then by name)
+fn ranked(names: impl Iterator<Item = String>, limit: usize) -> Vec<Count> {
+    let mut counts = HashMap::<String, usize>::new();
+    for name in names {
+        *counts.entry(name).or_default() += 1;
+    }
+    let mut counts: Vec<Count> = counts
+        .into_iter()
+        .map(|(name, sessions)| Count { name, sessions })
+        .collect();
+    counts.sort_by(|a, b| {
+        b.sessions
+            .cmp(&a.sessions)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    counts.truncate(limit);
+    counts
+}
+
+/// Names with their counts for a table cell (e.g. `alice (3), bob`)
+fn describe(counts: &[Count]) -> String {
+    if counts.is_empty() {
+        return "-".to_owned();
+    }
+    counts
+        .iter()
+        .map(|count| match count.sessions {
+            1 => count.name.clone(),
+            sessions => format!("{} ({sessions})", count.name),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Reads the recorded sessions (none if nothing was recorded yet)
+fn load() -> Result<Vec<SessionStats>> {
+    let path = stats_path()?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Unable to read session stats: {:?}", path))
+        }
+    };
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// `stats` subcommand
+pub fn show(period: Period, json: bool) -> Result<()> {
+    let sessions = load()?;
+    // Only for the game names, so a missing list is fine
+    let games = CompatList::load().unwrap_or_default();
+    let summaries = summarize(&sessions, period, &games);
+
+    if json {
+        let text =
+            serde_json::to_string_pretty(&summaries).context("Unable to serialize the stats")?;
+        // Plain output, so it can be redirected to a file
+        return console::data(&text);
+    }
+    if summaries.is_empty() {
+        console::println!("{} No Remote Play sessions recorded yet", Glyph::Ok)?;
+        return Ok(());
+    }
+    console::println!(
+        "{:<10} {:>8} {:>6} {:>6}  {:<30}  Top guests",
+        match period {
+            Period::Week => "Week",
+            Period::Month => "Month",
+        },
+        "Sessions",
+        "Hours",
+        "Guests",
+        "Games"
+    )?;
+    for summary in &summaries {
+        console::println!(
+            "{:<10} {:>8} {:>6.1} {:>6}  {:<30}  {}",
+            summary.period,
+            summary.sessions,
+            summary.hours,
+            summary.guests,
+            describe(&summary.games),
+            describe(&summary.top_guests)
+        )?;
+    }
+    Ok(())
+}
+
+/// Quotes a CSV field if needed
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+/**
+ * Writes the sessions as CSV (one row per session)
+ * @return CSV text with a header row
+ */
+pub fn to_csv(sessions: &[SessionStats]) -> String {
+    let mut csv =
+        "started,duration_secs,guests,peak,disconnects,latency_ms,game,players\n".to_owned();
+    for session in sessions {
+        let recap = &session.recap;
+        let row = [
+            recap.started.to_rfc3339(),
+            recap.duration_secs.to_string(),
+            recap.guests.to_string(),
+            recap.peak.to_string(),
+            recap.disconnects.to_string(),
+            recap
+                .latency_ms
+                .map(|ms| ms.to_string())
+                .unwrap_or_default(),
+            session
+                .game
+                .map(|game| game.to_string())
+                .unwrap_or_default(),
+            session.players.join("; "),
+        ];
+        let row: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// `stats export` subcommand: prints the sessions for a spreadsheet or a script
+pub fn export(format: ExportFormat, since: Option<NaiveDate>) -> Result<()> {
+    let mut sessions = load()?;
+    if let Some(since) = since {
+        sessions
+            .retain(|session| session.recap.started.with_timezone(&Local).date_naive() >= since);
+    }
+    let text = match format {
+        ExportFormat::Csv => to_csv(&sessions),
+        ExportFormat::Json => {
+            serde_json::to_string_pretty(&sessions).context("Unable to serialize the stats")? + "\n"
+        }
+    };
+    // Plain output, so it can be redirected to a file
+    write!(io::stdout(), "{text}").context("Unable to print the stats")
+}
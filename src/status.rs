@@ -0,0 +1,64 @@
+//! Connection state, pause state and guest count shared by the status displays (tray, Discord)
+
+use std::{collections::BTreeSet, sync::Arc};
+use tokio::sync::{broadcast::error::RecvError, watch};
+
+use crate::{
+    events::Event,
+    handlers::{ConnectionState, Handler},
+};
+
+/// What the status displays show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HostStatus {
+    /// State of the connection to the server
+    pub state: ConnectionState,
+    /// Whether new invites are refused
+    pub paused: bool,
+    /// Number of guests in the session
+    pub guests: usize,
+}
+
+impl Default for HostStatus {
+    fn default() -> Self {
+        Self {
+            state: ConnectionState::Connecting,
+            paused: false,
+            guests: 0,
+        }
+    }
+}
+
+/// Follows the handler events and publishes the status whenever it changes
+pub fn spawn_tracker(handler: &Arc<Handler>) -> watch::Receiver<HostStatus> {
+    let (status_tx, status_rx) = watch::channel(HostStatus::default());
+    let mut events = handler.subscribe();
+    tokio::spawn(async move {
+        let mut guests = BTreeSet::<u64>::new();
+
+        while !status_tx.is_closed() {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            status_tx.send_if_modified(|status| {
+                let old = *status;
+                match event {
+                    Event::GuestJoined { guest_id, .. } => {
+                        guests.insert(guest_id);
+                    }
+                    Event::GuestLeft { guest_id, .. } => {
+                        guests.remove(&guest_id);
+                    }
+                    Event::ConnectionChanged { state } => status.state = state,
+                    Event::PauseChanged { paused } => status.paused = paused,
+                    _ => (),
+                }
+                status.guests = guests.len();
+                *status != old
+            });
+        }
+    });
+    status_rx
+}
@@ -0,0 +1,128 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+use tokio::{sync::Notify, task::JoinHandle, time::sleep};
+
+use crate::{
+    console::{self, Glyph},
+    handlers::Handler,
+    retry::RetrySec,
+};
+
+/// Failures in a row that mark Steam as degraded
+const THRESHOLD: u32 = 3;
+
+/// Kind of Steam operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SteamOp {
+    /// Creating an invite link
+    Invite,
+    /// Starting the session of a guest on the host
+    Launch,
+}
+
+/// Consecutive failures of the Steam operations
+#[derive(Default)]
+pub struct SteamHealth {
+    failures: Mutex<HashMap<SteamOp, u32>>,
+    degraded: AtomicBool,
+    /// Wakes the recovery task
+    recover: Notify,
+}
+
+impl SteamHealth {
+    /// Whether Steam failed too often (invites are unavailable until it recovers)
+    pub fn is_degraded(&self) -> bool {
+        self.degraded.load(Ordering::Relaxed)
+    }
+
+    /**
+     * Records a failed operation
+     * @return Whether Steam just became degraded
+     */
+    pub fn failure(&self, op: SteamOp) -> bool {
+        let mut failures = self.failures.lock().unwrap();
+        let count = failures.entry(op).or_default();
+        *count += 1;
+        if *count < THRESHOLD || self.degraded.swap(true, Ordering::Relaxed) {
+            return false;
+        }
+        self.recover.notify_one();
+        true
+    }
+
+    /// Records a successful operation
+    pub fn success(&self, op: SteamOp) {
+        self.failures.lock().unwrap().remove(&op);
+    }
+
+    /// Clears the failures after Steam was started again
+    fn recovered(&self) {
+        self.failures.lock().unwrap().clear();
+        self.degraded.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Restarts the Steam connection with backoff whenever Steam becomes degraded
+pub fn spawn_recovery(handler: Arc<Handler>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            handler.steam_health().recover.notified().await;
+            let mut retry_sec = RetrySec::new();
+            loop {
+                sleep(retry_sec.next()).await;
+                match restart(&handler).await {
+                    Ok(()) => break,
+                    Err(err) => {
+                        let _ = console::eprintln!(
+                            "{} Unable to restart the Steam connection: {}",
+                            Glyph::Retry,
+                            err
+                        );
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Restarts the Steam connection and clears the failures if it worked
+async fn restart(handler: &Arc<Handler>) -> anyhow::Result<()> {
+    handler.restart_steam().await?;
+    handler.steam_health().recovered();
+    handler.on_steam_recovered();
+    Ok(())
+}
+
+#[cfg(all(test, feature = "no-steam"))]
+mod tests {
+    use super::*;
+    use crate::{bans::BanList, compat::CompatList, config::Config};
+    use steam_stuff::Backend;
+
+    #[tokio::test]
+    async fn degraded_steam_recovers_after_a_restart() {
+        let handler = Arc::new(Handler::new(
+            Arc::new(tokio::sync::Mutex::new(Backend::new().unwrap())),
+            &Config::default(),
+            BanList::default(),
+            CompatList::default(),
+            None,
+            Vec::new(),
+        ));
+        for _ in 1..THRESHOLD {
+            assert!(!handler.steam_health().failure(SteamOp::Invite));
+        }
+        assert!(handler.steam_health().failure(SteamOp::Invite));
+        assert!(handler.steam_health().is_degraded());
+
+        restart(&handler).await.unwrap();
+        assert!(!handler.steam_health().is_degraded());
+        // The failure count starts over
+        assert!(!handler.steam_health().failure(SteamOp::Invite));
+    }
+}
@@ -0,0 +1,193 @@
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::PathBuf, sync::Mutex, time::Duration};
+use tokio::task;
+
+use crate::{
+    config::{self, StoreConfig},
+    logger::{self, Level},
+    models::{ControllerSupport, StoreInfo},
+};
+
+/// App details of the Steam store
+const APPDETAILS_API: &str = "https://store.steampowered.com/api/appdetails";
+/// Store categories of the features the client cares about
+const REMOTE_PLAY_TOGETHER: u32 = 44;
+const FULL_CONTROLLER_SUPPORT: u32 = 28;
+const PARTIAL_CONTROLLER_SUPPORT: u32 = 18;
+
+#[derive(Deserialize)]
+struct AppDetails {
+    success: bool,
+    #[serde(default)]
+    data: Option<AppData>,
+}
+
+#[derive(Deserialize)]
+struct AppData {
+    #[serde(default)]
+    categories: Vec<Category>,
+}
+
+#[derive(Deserialize)]
+struct Category {
+    id: u32,
+}
+
+/// Store lookup saved on disk
+#[derive(Clone, Serialize, Deserialize)]
+struct CachedApp {
+    fetched: DateTime<Utc>,
+    /// The store has a page for the game (false for tools and delisted games)
+    listed: bool,
+    remote_play_together: bool,
+    controller: ControllerSupport,
+}
+
+impl CachedApp {
+    fn info(&self) -> Option<StoreInfo> {
+        self.listed.then_some(StoreInfo {
+            remote_play_together: self.remote_play_together,
+            controller: self.controller,
+        })
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Cache {
+    /// Lookups by app ID
+    #[serde(default)]
+    games: BTreeMap<String, CachedApp>,
+}
+
+/// Remote Play Together and controller support of the games from the Steam store, cached on disk
+pub struct Store {
+    config: StoreConfig,
+    /// How long the store may take to answer
+    timeout: Duration,
+    cache: Mutex<Cache>,
+}
+
+impl Store {
+    pub fn new(config: &StoreConfig, timeout: Duration) -> Self {
+        let cache = match load() {
+            Ok(cache) => cache,
+            Err(err) => {
+                logger::write(Level::Warn, format_args!("{err:#}"));
+                Cache::default()
+            }
+        };
+        Self {
+            config: config.clone(),
+            timeout,
+            cache: Mutex::new(cache),
+        }
+    }
+
+    /**
+     * Store metadata of a game, looked up if it is not cached or outdated
+     * @return None if disabled or the store has no page for the game (the outdated metadata while the store is unreachable)
+     */
+    pub async fn lookup(&self, app_id: u32) -> Option<StoreInfo> {
+        if !self.config.enabled {
+            return None;
+        }
+        let key = app_id.to_string();
+        let max_age = chrono::Duration::days(self.config.max_age_days as i64);
+        let cached = self.cache.lock().unwrap().games.get(&key).cloned();
+        if let Some(cached) = &cached {
+            if Utc::now() - cached.fetched < max_age {
+                return cached.info();
+            }
+        }
+
+        let timeout = self.timeout;
+        let result = task::spawn_blocking(move || fetch(app_id, timeout))
+            .await
+            .context("Store lookup task failed")
+            .and_then(|result| result);
+        match result {
+            Ok(app) => {
+                let info = app.info();
+                let mut cache = self.cache.lock().unwrap();
+                cache.games.insert(key, app);
+                if let Err(err) = save(&cache) {
+                    logger::write(Level::Warn, format_args!("{err:#}"));
+                }
+                info
+            }
+            Err(err) => {
+                // Offline: keep using the outdated metadata
+                logger::write(
+                    Level::Warn,
+                    format_args!("Unable to look up game_id={app_id} on the Steam store: {err:#}"),
+                );
+                cached.and_then(|cached| cached.info())
+            }
+        }
+    }
+
+    /// Whether games without Remote Play Together are refused
+    pub fn refuses_unsupported(&self) -> bool {
+        self.config.enabled && self.config.refuse_unsupported
+    }
+}
+
+/// Path of the cached lookups
+fn cache_path() -> Result<PathBuf> {
+    Ok(config::get_exe_path()?.with_extension("store.toml"))
+}
+
+/// Reads the cached lookups (empty if there are none)
+fn load() -> Result<Cache> {
+    let path = cache_path()?;
+    if !path.exists() {
+        return Ok(Cache::default());
+    }
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Unable to read store cache: {:?}", path))?;
+    toml::from_str(&content).context("Unable to parse store cache")
+}
+
+fn save(cache: &Cache) -> Result<()> {
+    let path = cache_path()?;
+    let content = toml::to_string(cache).context("Unable to serialize store cache")?;
+    fs::write(&path, content).with_context(|| format!("Unable to write store cache: {:?}", path))
+}
+
+/// Asks the Steam store for the categories of a game
+fn fetch(app_id: u32, timeout: Duration) -> Result<CachedApp> {
+    let mut details: BTreeMap<String, AppDetails> = ureq::get(APPDETAILS_API)
+        .query("appids", &app_id.to_string())
+        .query("filters", "categories")
+        .timeout(timeout)
+        .call()
+        .context("Steam store request failed")?
+        .into_json()
+        .context("Invalid Steam store response")?;
+    let details = details
+        .remove(&app_id.to_string())
+        .context("Steam store response has no details of the game")?;
+    let categories: Vec<u32> = match details.data.filter(|_| details.success) {
+        Some(data) => data
+            .categories
+            .into_iter()
+            .map(|category| category.id)
+            .collect(),
+        None => Vec::new(),
+    };
+    let controller = if categories.contains(&FULL_CONTROLLER_SUPPORT) {
+        ControllerSupport::Full
+    } else if categories.contains(&PARTIAL_CONTROLLER_SUPPORT) {
+        ControllerSupport::Partial
+    } else {
+        ControllerSupport::None
+    };
+    Ok(CachedApp {
+        fetched: Utc::now(),
+        listed: details.success,
+        remote_play_together: categories.contains(&REMOTE_PLAY_TOGETHER),
+        controller,
+    })
+}
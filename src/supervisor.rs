@@ -0,0 +1,302 @@
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+
+#[cfg(feature = "deck")]
+use crate::deck;
+#[cfg(feature = "discord-rpc")]
+use crate::discord_rpc;
+#[cfg(feature = "integration")]
+use crate::integration;
+#[cfg(all(target_os = "macos", feature = "macos"))]
+use crate::macos;
+#[cfg(feature = "scripting")]
+use crate::scripting;
+#[cfg(feature = "simulate")]
+use crate::simulate;
+#[cfg(feature = "sounds")]
+use crate::sound;
+#[cfg(any(all(windows, feature = "tray"), feature = "discord-rpc"))]
+use crate::status;
+#[cfg(all(windows, feature = "notifications"))]
+use crate::toast;
+#[cfg(all(windows, feature = "tray"))]
+use crate::tray;
+use crate::{
+    compat,
+    config::Config,
+    console::{self, Glyph},
+    container,
+    error::{ClientResult, Recovery},
+    focus,
+    handlers::Handler,
+    health, healthz, ipc, lan, launch, load, nat, playtime, power, prompt, quiet, recap, replay,
+    steam_health, systemd, waitlist, watchdog,
+};
+
+/// Long-lived task
+struct Service {
+    /// Name shown when the task failed
+    name: &'static str,
+    task: JoinHandle<()>,
+}
+
+/// Owns the long-lived tasks of the inviter
+/// Starts them in order (the servers are connected last) and stops them in reverse order on exit
+/// The threads (console input, sounds, game controller, scripts) end with the process
+#[derive(Default)]
+pub struct Supervisor {
+    /// Background services in the order they were started
+    services: Vec<Service>,
+    /// Connection of each server
+    connections: Vec<JoinHandle<ClientResult<()>>>,
+    /// Whether this instance owns the control socket
+    control_socket: bool,
+}
+
+impl Supervisor {
+    /// Starts the services that run next to the server connections
+    pub async fn start(handler: &Arc<Handler>, config: &Config) -> Self {
+        let mut supervisor = Self::default();
+
+        // Steam callbacks first, the other services react to them
+        supervisor.add("steam-callbacks", handler.run_steam_callbacks());
+        supervisor.add("steam-replay", replay::spawn(handler.clone()));
+        #[cfg(feature = "simulate")]
+        supervisor.add("guest-simulator", simulate::spawn(handler.clone()));
+
+        // Serve the local control API (status / invite subcommands)
+        match ipc::serve(handler.clone()) {
+            Ok(task) => {
+                supervisor.control_socket = task.is_some();
+                supervisor.add("control-api", task);
+            }
+            Err(err) => {
+                let _ = console::eprintln!("{} {}", Glyph::Err, err);
+            }
+        }
+
+        // Answer approvals with a game controller on the Steam Deck
+        #[cfg(feature = "deck")]
+        if config.approval.enabled && console::is_deck() {
+            deck::spawn_controller(handler.clone());
+        }
+
+        // Reconnect and re-sync right after the system resumes from sleep
+        supervisor.add(
+            "resume-watcher",
+            power::spawn_resume_watcher(handler.clone()),
+        );
+
+        // Revoke stale invites when the game is closed (and post one when a game is started)
+        supervisor.add(
+            "game-watcher",
+            launch::spawn_game_watcher(handler.clone(), config.invites.auto_invite),
+        );
+
+        // Open the Remote Play ports on the router
+        supervisor.add(
+            "port-mapping",
+            nat::spawn(config.nat.clone(), config.timeouts.gateway()),
+        );
+
+        // Classify the NAT so the servers know if guests will need relays
+        supervisor.add(
+            "stun-probe",
+            nat::spawn_probe(
+                handler.clone(),
+                config.nat.stun_servers.clone(),
+                config.timeouts.gateway(),
+            ),
+        );
+
+        // Rotate the guests who reached the playtime cap
+        supervisor.add(
+            "playtime",
+            playtime::spawn(handler.clone(), config.playtime.clone()),
+        );
+
+        // Sum up each Remote Play session when the last guest leaves
+        supervisor.add("recap", recap::spawn(handler.clone(), config.recap.clone()));
+
+        // Keep the servers' waitlists up to date with the free slots
+        supervisor.add(
+            "waitlist",
+            waitlist::spawn(handler.clone(), &config.waitlist),
+        );
+
+        // Replace the invite links Steam no longer accepts
+        supervisor.add(
+            "invite-checker",
+            health::spawn_invite_checker(handler.clone(), config.invites.health_check_secs),
+        );
+
+        // Pause the invites and hold back the notifications during the quiet hours
+        supervisor.add("quiet-hours", quiet::spawn(handler.clone()));
+
+        // Revoke the time-boxed invites when they expire
+        supervisor.add("invite-expiry", health::spawn_expiry(handler.clone()));
+
+        // Restart the connection to Steam when it keeps failing
+        supervisor.add(
+            "steam-recovery",
+            steam_health::spawn_recovery(handler.clone()),
+        );
+
+        // Watch for tasks and queues that keep growing in long sessions
+        supervisor.add(
+            "watchdog",
+            watchdog::spawn(handler.clone(), config.watchdog.interval_secs),
+        );
+
+        // Report the readiness to systemd and ping its watchdog while the handler responds
+        supervisor.add("systemd-notify", systemd::spawn(handler.clone()));
+
+        // Report the CPU, GPU and encoder load while guests are playing
+        supervisor.add(
+            "host-load",
+            load::spawn(handler.clone(), config.load.clone()),
+        );
+
+        // Keep the game compatibility list up to date
+        supervisor.add("compat-refresher", compat::spawn_refresher(handler.clone()));
+
+        // Hold back messages while a fullscreen game has the focus
+        supervisor.add(
+            "focus-watcher",
+            focus::spawn_watcher(config.do_not_disturb.clone()),
+        );
+
+        // Play sound alerts on guest join/leave and disconnect
+        #[cfg(feature = "sounds")]
+        sound::spawn(handler, config.sounds.clone());
+
+        // Switch OBS scenes on guest join/leave
+        #[cfg(feature = "integration")]
+        supervisor.add("obs", integration::spawn_obs(handler, config.obs.clone()));
+
+        // Kick or ban joining guests from the toast notification
+        #[cfg(all(windows, feature = "notifications"))]
+        toast::spawn(handler, config.notifications.clone());
+
+        // Connection state and guest count for the status displays
+        #[cfg(any(all(windows, feature = "tray"), feature = "discord-rpc"))]
+        let status = status::spawn_tracker(handler);
+
+        // Status in the notification area
+        #[cfg(all(windows, feature = "tray"))]
+        tray::spawn(&status, config.tray.clone());
+
+        // Status on the host's Discord profile
+        #[cfg(feature = "discord-rpc")]
+        discord_rpc::spawn(&status, config.discord_rpc.clone());
+
+        // Menu bar status, notifications and sleep/wake handling
+        #[cfg(all(target_os = "macos", feature = "macos"))]
+        macos::attach(handler, config.macos.clone());
+
+        // Run the user scripts on handler events
+        #[cfg(feature = "scripting")]
+        scripting::spawn(handler, config.scripts.clone());
+
+        // Serve the invite page on the local network
+        if config.lan.enabled {
+            match lan::serve(handler.clone(), config.lan.clone(), config.timeouts.lan()).await {
+                Ok(task) => supervisor.add("lan-page", task),
+                Err(err) => {
+                    let _ = console::eprintln!("{} {}", Glyph::Err, err);
+                }
+            }
+        }
+
+        // Answer the health probes of the container orchestrator
+        match healthz::serve(
+            handler.clone(),
+            &config.health,
+            container::is_enabled(),
+            config.timeouts.lan(),
+        )
+        .await
+        {
+            Ok(task) => supervisor.add("health-endpoint", task),
+            Err(err) => {
+                let _ = console::eprintln!("{} {}", Glyph::Err, err);
+            }
+        }
+
+        // Read commands from the console (approvals, moderation, chat replies), there is no terminal in a container
+        if !container::is_enabled() {
+            prompt::spawn(handler.clone());
+        }
+
+        supervisor
+    }
+
+    /// Takes over a long-lived task (None: the service is disabled)
+    fn add(&mut self, name: &'static str, task: impl Into<Option<JoinHandle<()>>>) {
+        if let Some(task) = task.into() {
+            self.services.push(Service { name, task });
+        }
+    }
+
+    /// Takes over the connection of a server
+    pub fn add_connection(&mut self, task: JoinHandle<ClientResult<()>>) {
+        self.connections.push(task);
+    }
+
+    /**
+     * Waits until every connection stopped (exit requested, an outdated client or out of retries) or Ctrl+C is pressed
+     * @return The exit code of the connections (0 if they stopped without an error, None if Ctrl+C was pressed)
+     */
+    pub async fn wait(&mut self) -> Option<i32> {
+        tokio::select! {
+            results = futures::future::join_all(self.connections.iter_mut()) => {
+                // The worst stop decides
+                let mut worst = None;
+                for result in results {
+                    if let Ok(Err(err)) = result {
+                        let _ = console::eprintln!("{} {}", Glyph::Err, err);
+                        worst = worst.max(Some(err.recovery()));
+                    }
+                }
+                self.connections.clear();
+                Some(worst.map_or(0, Recovery::exit_code))
+            }
+            _ = stop_requested() => None,
+        }
+    }
+
+    /// Stops the connections first, then the services in reverse order of their start
+    pub async fn shutdown(self) {
+        for task in self.connections {
+            task.abort();
+            let _ = task.await;
+        }
+        for service in self.services.into_iter().rev() {
+            service.task.abort();
+            if let Err(err) = service.task.await {
+                if err.is_panic() {
+                    let _ =
+                        console::eprintln!("{} {} stopped unexpectedly", Glyph::Err, service.name);
+                }
+            }
+        }
+        if self.control_socket {
+            ipc::remove_socket();
+        }
+    }
+}
+
+/// Waits for Ctrl+C or SIGTERM (sent by docker stop, systemctl stop and the other process supervisors)
+pub async fn stop_requested() {
+    #[cfg(unix)]
+    if let Ok(mut terminate) =
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+    {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => (),
+            _ = terminate.recv() => (),
+        }
+        return;
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
@@ -0,0 +1,128 @@
+use std::{env, sync::Arc, time::Duration};
+use tokio::{
+    sync::broadcast::error::RecvError,
+    task::JoinHandle,
+    time::{interval, timeout, Interval, MissedTickBehavior},
+};
+
+use crate::{
+    events::Event,
+    handlers::{ConnectionState, Handler},
+    logger::{self, Level},
+};
+
+/// Tells systemd about the state of the service (sd_notify)
+#[cfg(unix)]
+fn notify(state: &str) {
+    let Some(path) = env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    if let Err(err) = send(&path, state) {
+        logger::write(Level::Warn, format_args!("Unable to notify systemd: {err}"));
+    }
+}
+
+#[cfg(unix)]
+fn send(path: &std::ffi::OsStr, state: &str) -> std::io::Result<()> {
+    use std::os::unix::{
+        ffi::OsStrExt as _,
+        net::{SocketAddr, UnixDatagram},
+    };
+
+    let socket = UnixDatagram::unbound()?;
+    let addr = match path.as_bytes().strip_prefix(b"@") {
+        // Abstract socket
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt as _;
+            SocketAddr::from_abstract_name(name)?
+        }
+        _ => SocketAddr::from_pathname(path)?,
+    };
+    socket.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(())
+}
+
+/// There is no systemd here
+#[cfg(not(unix))]
+fn notify(_state: &str) {}
+
+/// Interval of the watchdog pings, half the timeout of the unit (None without WatchdogSec)
+fn watchdog_interval() -> Option<Duration> {
+    // Meant for another process of the service
+    if let Ok(pid) = env::var("WATCHDOG_PID") {
+        if pid.parse() != Ok(std::process::id()) {
+            return None;
+        }
+    }
+    let usec: u64 = env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec) / 2)
+}
+
+/// Waits for the next watchdog ping (forever without a watchdog)
+async fn tick(ticker: &mut Option<Interval>) {
+    match ticker {
+        Some(ticker) => {
+            ticker.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Status line shown by systemctl status
+fn status(state: ConnectionState) -> &'static str {
+    match state {
+        ConnectionState::Connecting => "STATUS=Connecting to the server",
+        ConnectionState::Connected => "STATUS=Connected to the server",
+        ConnectionState::Reconnecting => "STATUS=Reconnecting to the server",
+        ConnectionState::Pairing => "STATUS=Waiting for the pairing in Discord",
+    }
+}
+
+/**
+ * Reports to systemd (Type=notify): ready after the first connection, then the watchdog pings while the handler responds
+ * @return None when not started by systemd
+ */
+pub fn spawn(handler: Arc<Handler>) -> Option<JoinHandle<()>> {
+    env::var_os("NOTIFY_SOCKET")?;
+    let period = watchdog_interval();
+    let mut events = handler.subscribe();
+    Some(tokio::spawn(async move {
+        let mut ticker = period.map(|period| {
+            let mut ticker = interval(period);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            ticker
+        });
+        let mut ready = false;
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(Event::ConnectionChanged { state }) => {
+                        // Pairing needs a working connection too
+                        if !ready
+                            && matches!(state, ConnectionState::Connected | ConnectionState::Pairing)
+                        {
+                            ready = true;
+                            notify(&format!("READY=1\n{}", status(state)));
+                        } else {
+                            notify(status(state));
+                        }
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => (),
+                    Err(RecvError::Closed) => break,
+                },
+                _ = tick(&mut ticker) => {
+                    // The same probe as the usage watchdog: a wedged handler (a lock that is never
+                    // released) stops the pings, so systemd restarts the client
+                    match timeout(period.unwrap_or_default(), handler.usage()).await {
+                        Ok(_) => notify("WATCHDOG=1"),
+                        Err(_) => logger::write(
+                            Level::Warn,
+                            format_args!("The handler did not respond, the systemd watchdog is not pinged"),
+                        ),
+                    }
+                }
+            }
+        }
+    }))
+}
@@ -0,0 +1,120 @@
+use std::sync::Arc;
+use tauri_winrt_notification::{IconCrop, Toast};
+use tokio::sync::{broadcast::error::RecvError, mpsc};
+
+use crate::{
+    audit::{Origin, Source},
+    config::NotificationsConfig,
+    console::{self, Glyph},
+    events::Event,
+    handlers::Handler,
+    personas,
+};
+
+/// Buttons of the guest join toast
+enum ToastAction {
+    /// Disconnect the guest
+    Kick(u64),
+    /// Disconnect and ban the guest
+    Ban(u64),
+}
+
+impl ToastAction {
+    /// Parses the activation argument of a button (`kick:<guest_id>` / `ban:<guest_id>`)
+    fn parse(argument: &str) -> Option<Self> {
+        let (action, guest_id) = argument.split_once(':')?;
+        let guest_id = guest_id.parse().ok()?;
+        match action {
+            "kick" => Some(ToastAction::Kick(guest_id)),
+            "ban" => Some(ToastAction::Ban(guest_id)),
+            _ => None,
+        }
+    }
+}
+
+/// Shows a toast with Kick / Ban buttons when a guest joins
+pub fn spawn(handler: &Arc<Handler>, config: NotificationsConfig) {
+    if !config.enabled {
+        return;
+    }
+
+    // Button clicks arrive on a WinRT thread and are handled on the runtime
+    let (action_tx, mut action_rx) = mpsc::unbounded_channel::<ToastAction>();
+    let action_handler = handler.clone();
+    tokio::spawn(async move {
+        while let Some(action) = action_rx.recv().await {
+            let origin = Origin::new(Source::Notification, None);
+            let result = match action {
+                ToastAction::Kick(guest_id) => {
+                    // Still bans for the configured [bans] on_kick duration
+                    action_handler.kick_guest(guest_id, None, origin).await
+                }
+                ToastAction::Ban(guest_id) => {
+                    action_handler
+                        .kick_guest(guest_id, Some(config.ban_duration), origin)
+                        .await
+                }
+            };
+            if let Err(err) = result {
+                let _ = console::eprintln!("{} {}", Glyph::Err, err);
+            }
+        }
+    });
+
+    let mut events = handler.subscribe();
+    let handler = handler.clone();
+    tokio::spawn(async move {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            let Event::GuestJoined {
+                guest_id,
+                steam_id,
+                name,
+                persona,
+                endpoint,
+            } = event
+            else {
+                continue;
+            };
+            if handler.is_quiet() {
+                continue;
+            }
+
+            let via = endpoint
+                .map(|endpoint| format!("via {endpoint}"))
+                .unwrap_or_default();
+            let action_tx = action_tx.clone();
+            let mut toast = Toast::new(Toast::POWERSHELL_APP_ID)
+                .title(&format!(
+                    "{} joined the game",
+                    name.as_deref().or(persona.as_deref()).unwrap_or("A guest")
+                ))
+                .text1(&format!(
+                    "guest_id={guest_id}, steam_id={}",
+                    personas::steam_user(steam_id, &persona)
+                ))
+                .text2(&via);
+            if let Some(avatar) = handler.avatar(steam_id).await {
+                toast = toast.icon(&avatar, IconCrop::Circular, "Avatar");
+            }
+            let result = toast
+                .add_button("Kick", &format!("kick:{guest_id}"))
+                .add_button("Ban", &format!("ban:{guest_id}"))
+                .on_activated(move |argument| {
+                    // Clicking the toast body has no argument
+                    if let Some(action) = argument.as_deref().and_then(ToastAction::parse) {
+                        let _ = action_tx.send(action);
+                    }
+                    Ok(())
+                })
+                .show();
+            if let Err(err) = result {
+                let _ = console::eprintln!("{} Failed to show notification: {}", Glyph::Err, err);
+            }
+        }
+    });
+}
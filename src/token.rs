@@ -0,0 +1,238 @@
+use anyhow::{bail, Context as _, Result};
+use rand::{seq::SliceRandom as _, thread_rng};
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::PathBuf};
+use uuid::Uuid;
+
+use crate::{
+    config::{self, Config},
+    console::{self, Glyph},
+    files, ipc,
+};
+
+/// Characters of a pairing code (no look-alikes such as 0/O and 1/I)
+const CODE_CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+/// Length of a pairing code
+const CODE_LEN: usize = 8;
+
+/// Move of the Discord link to a new token, waiting for the server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingRelink {
+    /// Token the Discord account is linked with
+    pub previous: String,
+    /// Code the host confirms in Discord
+    pub code: String,
+}
+
+impl PendingRelink {
+    /// Path of the pending move
+    fn path() -> Result<PathBuf> {
+        Ok(config::get_exe_path()?.with_extension("relink.toml"))
+    }
+
+    /// Reads the pending move (None if there is none)
+    pub fn load() -> Result<Option<Self>> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Unable to read pending relink: {:?}", path))?;
+        toml::from_str(&content)
+            .map(Some)
+            .context("Unable to parse pending relink")
+    }
+
+    /// Saves the pending move
+    fn save(&self) -> Result<()> {
+        let path = Self::path()?;
+        let content = toml::to_string(self).context("Unable to serialize pending relink")?;
+        files::write(&path, content)
+            .with_context(|| format!("Unable to write pending relink: {:?}", path))
+    }
+
+    /// Forgets the pending move (the server confirmed it)
+    pub fn clear() -> Result<()> {
+        let path = Self::path()?;
+        if path.exists() {
+            fs::remove_file(&path)
+                .with_context(|| format!("Unable to remove pending relink: {:?}", path))?;
+        }
+        Ok(())
+    }
+}
+
+/// Hides all but the start and the end of a token
+fn redact(token: &str) -> String {
+    let chars: Vec<char> = token.chars().collect();
+    if chars.len() < 16 {
+        return "*".repeat(chars.len());
+    }
+    let start: String = chars[..8].iter().collect();
+    let end: String = chars[chars.len() - 4..].iter().collect();
+    format!("{start}-****-****-****-********{end}")
+}
+
+/// Random pairing code (e.g. "K7QM-2XPD")
+fn pairing_code() -> String {
+    let mut rng = thread_rng();
+    let code: String = (0..CODE_LEN)
+        .map(|_| *CODE_CHARS.choose(&mut rng).unwrap_or(&b'A') as char)
+        .collect();
+    format!("{}-{}", &code[..CODE_LEN / 2], &code[CODE_LEN / 2..])
+}
+
+/**
+ * Asks the host a yes/no question on the console
+ * @return Whether the host answered yes
+ */
+fn confirm(question: &str) -> Result<bool> {
+    console::println!("{} {} [y/N]", Glyph::Wait, question)?;
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .context("Unable to read the answer")?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Refuses to change the token under a running instance
+fn ensure_stopped() -> Result<()> {
+    if ipc::is_running() {
+        bail!("Stop the running inviter first (it keeps using the current token)");
+    }
+    Ok(())
+}
+
+/// `token show` subcommand
+pub fn show(config: &Config) -> Result<()> {
+    console::println!("{} Token: {}", Glyph::Ok, redact(&config.uuid))?;
+    if config::is_uuid_sealed()? {
+        console::println!("{} The token is encrypted for this PC", Glyph::Ok)?;
+    }
+    if let Some(relink) = PendingRelink::load()? {
+        console::println!(
+            "{} Waiting to move the Discord link from {} (pairing code: {})",
+            Glyph::Wait,
+            redact(&relink.previous),
+            relink.code
+        )?;
+    }
+    Ok(())
+}
+
+/// `token export` subcommand
+pub fn export(config: &Config, file: Option<PathBuf>) -> Result<()> {
+    if !confirm("Anyone with the token can create invites as you. Export it?")? {
+        bail!("Export cancelled");
+    }
+    match file {
+        Some(file) => {
+            files::write(&file, format!("{}\n", config.uuid))
+                .with_context(|| format!("Unable to write token file: {:?}", file))?;
+            console::println!("{} Token written to {:?}", Glyph::Ok, file)?;
+        }
+        None => console::println!("{}", config.uuid)?,
+    }
+    Ok(())
+}
+
+/// `token import` subcommand
+pub fn import(config: &Config, source: &str) -> Result<()> {
+    ensure_stopped()?;
+    // A file written by `token export`, or the token itself
+    let text = match fs::read_to_string(source) {
+        Ok(text) => text,
+        Err(_) => source.to_owned(),
+    };
+    let token = Uuid::parse_str(text.trim())
+        .context("Invalid token (expected the output of token export)")?
+        .to_string();
+    if token == config.uuid {
+        console::println!("{} This token is already in use", Glyph::Ok)?;
+        return Ok(());
+    }
+    let question = format!(
+        "Replace the token {} with {}? This PC loses its current Discord link.",
+        redact(&config.uuid),
+        redact(&token)
+    );
+    if !confirm(&question)? {
+        bail!("Import cancelled");
+    }
+    config::write_uuid(&config.uuid, &token)?;
+    // A move to the replaced token can no longer happen
+    PendingRelink::clear()?;
+    console::println!("{} Token imported: {}", Glyph::Ok, redact(&token))?;
+    Ok(())
+}
+
+/// `token rotate` subcommand
+pub fn rotate(config: &Config, relink: bool) -> Result<()> {
+    ensure_stopped()?;
+    if !confirm("Replace the token with a new one?")? {
+        bail!("Rotation cancelled");
+    }
+    let token = Uuid::new_v4().to_string();
+    if relink {
+        // Keep the oldest token if a move is still pending, the server only knows that one
+        let previous = match PendingRelink::load()? {
+            Some(pending) => pending.previous,
+            None => config.uuid.clone(),
+        };
+        let pending = PendingRelink {
+            previous,
+            code: pairing_code(),
+        };
+        pending.save()?;
+        config::write_uuid(&config.uuid, &token)?;
+        console::printdoc! {"
+            {ok} Token rotated: {token}
+            {wait} Start the inviter and confirm the move in Discord with the pairing code: {code}
+            ",
+            ok = Glyph::Ok,
+            token = redact(&token),
+            wait = Glyph::Wait,
+            code = pending.code,
+        }?;
+    } else {
+        config::write_uuid(&config.uuid, &token)?;
+        PendingRelink::clear()?;
+        console::printdoc! {"
+            {ok} Token rotated: {token}
+            {wait} Start the inviter and link it with the Discord bot again.
+            ",
+            ok = Glyph::Ok,
+            token = redact(&token),
+            wait = Glyph::Wait,
+        }?;
+    }
+    Ok(())
+}
+
+/// `token encrypt` subcommand
+pub fn encrypt(config: &Config) -> Result<()> {
+    if config::is_uuid_sealed()? {
+        console::println!("{} The token is already encrypted", Glyph::Ok)?;
+        return Ok(());
+    }
+    config::write_sealed_uuid(&config.uuid, true)?;
+    console::printdoc! {"
+        {ok} Token encrypted: a copy of the config file no longer works on another PC.
+        {wait} Keep a backup with token export, the token cannot be recovered if this PC is reinstalled.
+        ",
+        ok = Glyph::Ok,
+        wait = Glyph::Wait,
+    }?;
+    Ok(())
+}
+
+/// `token decrypt` subcommand
+pub fn decrypt(config: &Config) -> Result<()> {
+    if !config::is_uuid_sealed()? {
+        console::println!("{} The token is not encrypted", Glyph::Ok)?;
+        return Ok(());
+    }
+    config::write_sealed_uuid(&config.uuid, false)?;
+    console::println!("{} Token stored as plain text", Glyph::Ok)?;
+    Ok(())
+}
@@ -0,0 +1,224 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display},
+    io,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::{Arc, OnceLock},
+    task::{Context, Poll},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{lookup_host, TcpStream},
+};
+use tokio_tungstenite::{Connector, MaybeTlsStream};
+
+use crate::{
+    config::TlsBackend,
+    console::{self, Glyph},
+    doh::Resolver,
+};
+
+/// Scheme of a server on a Unix domain socket (e.g. "ws+unix:///run/inviter/bot.sock")
+pub const UNIX_SCHEME: &str = "ws+unix://";
+
+/// IP version of a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IpFamily {
+    #[serde(rename = "ipv4")]
+    V4,
+    #[serde(rename = "ipv6")]
+    V6,
+}
+
+impl IpFamily {
+    pub fn of(addr: &SocketAddr) -> Self {
+        match addr {
+            SocketAddr::V4(_) => IpFamily::V4,
+            SocketAddr::V6(_) => IpFamily::V6,
+        }
+    }
+}
+
+impl Display for IpFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            IpFamily::V4 => "IPv4",
+            IpFamily::V6 => "IPv6",
+        })
+    }
+}
+
+/// IP version the connections are limited to (--ipv4-only or --ipv6-only, for debugging)
+static ONLY_FAMILY: OnceLock<IpFamily> = OnceLock::new();
+
+/// Limits the connections to an IP version
+pub fn set_only_family(family: IpFamily) {
+    let _ = ONLY_FAMILY.set(family);
+}
+
+/**
+ * Connects to a host over TCP, trying each address in the order of the resolver (IPv6 and IPv4)
+ * @return The connection to the first address that answered
+ */
+pub async fn connect_tcp(
+    host: &str,
+    port: u16,
+    resolver: Option<&Resolver>,
+) -> io::Result<TcpStream> {
+    // The host of an IPv6 URL is in brackets ("[::1]")
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let addrs: Vec<SocketAddr> = match resolver {
+        Some(resolver) if host.parse::<IpAddr>().is_err() => match resolver.resolve(host).await {
+            Ok(ips) => ips
+                .into_iter()
+                .map(|ip| SocketAddr::new(ip, port))
+                .collect(),
+            // The system resolver may be the one the provider was configured to avoid
+            Err(err) if !resolver.system_fallback() => {
+                return Err(io::Error::other(format!("{err:#}")));
+            }
+            Err(err) => {
+                let _ = console::eprintln!("{} {:#}. Using the system resolver", Glyph::Warn, err);
+                lookup_host((host, port)).await?.collect()
+            }
+        },
+        _ => lookup_host((host, port)).await?.collect(),
+    };
+    let only = ONLY_FAMILY.get().copied();
+    let mut last_error = None;
+    for addr in addrs {
+        if only.is_some_and(|family| family != IpFamily::of(&addr)) {
+            continue;
+        }
+        match TcpStream::connect(addr).await {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_error = Some(err),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::AddrNotAvailable,
+            match only {
+                Some(family) => format!("{host} has no {family} address"),
+                None => format!("{host} has no address"),
+            },
+        )
+    }))
+}
+
+/// TLS connector of the server connections
+pub fn connector(backend: TlsBackend) -> anyhow::Result<Connector> {
+    match backend {
+        TlsBackend::Rustls => {
+            let mut roots = rustls::RootCertStore::empty();
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            let config = rustls::ClientConfig::builder()
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+            Ok(Connector::Rustls(Arc::new(config)))
+        }
+        #[cfg(feature = "native-tls")]
+        TlsBackend::Native => Ok(Connector::NativeTls(native_tls::TlsConnector::new()?)),
+        #[cfg(not(feature = "native-tls"))]
+        TlsBackend::Native => {
+            anyhow::bail!("backend = \"native\" in [tls] needs a build with the native-tls feature")
+        }
+    }
+}
+
+/// IP version of a connection to a server (None for Unix domain sockets)
+pub fn family_of(stream: &MaybeTlsStream<Transport>) -> Option<IpFamily> {
+    let transport = match stream {
+        MaybeTlsStream::Plain(transport) => transport,
+        MaybeTlsStream::Rustls(stream) => stream.get_ref().0,
+        #[cfg(feature = "native-tls")]
+        MaybeTlsStream::NativeTls(stream) => stream.get_ref().get_ref().get_ref(),
+        _ => return None,
+    };
+    match transport {
+        Transport::Tcp(stream) => stream.peer_addr().ok().map(|addr| IpFamily::of(&addr)),
+        #[cfg(unix)]
+        Transport::Unix(_) => None,
+    }
+}
+
+/// Connection to a server, over TCP or a Unix domain socket (same-host deployments)
+pub enum Transport {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixStream),
+}
+
+/**
+ * Splits a Unix domain socket URL ("ws+unix:///path/to.sock:/request/path")
+ * @return The socket path and the request path (None for other URLs)
+ */
+pub fn unix_socket(url: &str) -> Option<(&str, &str)> {
+    let rest = url.strip_prefix(UNIX_SCHEME)?;
+    Some(match rest.find(":/") {
+        Some(index) => (&rest[..index], &rest[index + 1..]),
+        None => (rest, "/"),
+    })
+}
+
+/// Connects to a Unix domain socket
+#[cfg(unix)]
+pub async fn connect_unix(path: &str) -> io::Result<Transport> {
+    Ok(Transport::Unix(
+        tokio::net::UnixStream::connect(path).await?,
+    ))
+}
+
+/// Connects to a Unix domain socket
+#[cfg(not(unix))]
+pub async fn connect_unix(_path: &str) -> io::Result<Transport> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Unix domain sockets are not supported on this platform",
+    ))
+}
+
+impl AsyncRead for Transport {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(unix)]
+            Transport::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Transport {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Transport::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(unix)]
+            Transport::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(unix)]
+            Transport::Unix(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(unix)]
+            Transport::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
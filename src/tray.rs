@@ -0,0 +1,110 @@
+//! Notification area icon with the connection state and guest count (Windows)
+
+use std::{mem, ptr};
+use tokio::sync::watch;
+use windows_sys::Win32::{
+    Foundation::HWND,
+    System::{Console::GetConsoleWindow, LibraryLoader::GetModuleHandleW},
+    UI::{
+        Shell::{
+            Shell_NotifyIconW, NIF_ICON, NIF_TIP, NIM_ADD, NIM_DELETE, NIM_MODIFY, NOTIFYICONDATAW,
+        },
+        WindowsAndMessaging::{LoadIconW, IDI_APPLICATION},
+    },
+};
+
+use crate::{
+    config::TrayConfig,
+    console::{self, Glyph},
+    handlers::ConnectionState,
+    status::HostStatus,
+};
+
+/// ID of the icon among the icons of the console window
+const ICON_ID: u32 = 1;
+/// Resource ID of the application icon (embedded by build.rs)
+const APP_ICON: usize = 1;
+
+/// Icon in the notification area (removed when dropped)
+struct TrayIcon {
+    data: NOTIFYICONDATAW,
+}
+
+impl TrayIcon {
+    /**
+     * Adds the icon to the notification area
+     * @return None if the shell refused it (e.g. no taskbar)
+     */
+    fn add(window: HWND, tip: &str) -> Option<Self> {
+        let mut data: NOTIFYICONDATAW = unsafe { mem::zeroed() };
+        data.cbSize = mem::size_of::<NOTIFYICONDATAW>() as u32;
+        data.hWnd = window;
+        data.uID = ICON_ID;
+        data.uFlags = NIF_ICON | NIF_TIP;
+        data.hIcon = unsafe {
+            match LoadIconW(GetModuleHandleW(ptr::null()), APP_ICON as _) {
+                0 => LoadIconW(0, IDI_APPLICATION),
+                icon => icon,
+            }
+        };
+        copy_tip(&mut data.szTip, tip);
+        (unsafe { Shell_NotifyIconW(NIM_ADD, &data) } != 0).then_some(Self { data })
+    }
+
+    /// Changes the tooltip
+    fn set_tip(&mut self, tip: &str) {
+        copy_tip(&mut self.data.szTip, tip);
+        unsafe { Shell_NotifyIconW(NIM_MODIFY, &self.data) };
+    }
+}
+
+impl Drop for TrayIcon {
+    fn drop(&mut self) {
+        unsafe { Shell_NotifyIconW(NIM_DELETE, &self.data) };
+    }
+}
+
+/// Shows the connection state and guest count in the tooltip of a notification area icon
+pub fn spawn(status: &watch::Receiver<HostStatus>, config: TrayConfig) {
+    if !config.enabled {
+        return;
+    }
+    // The icon belongs to the console window (there is none when started without a console)
+    let window = unsafe { GetConsoleWindow() };
+    if window == 0 {
+        return;
+    }
+    let mut status = status.clone();
+    let Some(mut icon) = TrayIcon::add(window, &status_tip(*status.borrow_and_update())) else {
+        let _ = console::eprintln!("{} Failed to add the notification area icon", Glyph::Warn);
+        return;
+    };
+
+    tokio::spawn(async move {
+        while status.changed().await.is_ok() {
+            icon.set_tip(&status_tip(*status.borrow_and_update()));
+        }
+    });
+}
+
+/// Tooltip of the icon
+fn status_tip(status: HostStatus) -> String {
+    let text = match status.state {
+        ConnectionState::Connecting => "Connecting".to_owned(),
+        ConnectionState::Reconnecting => "Offline".to_owned(),
+        ConnectionState::Pairing => "Pairing".to_owned(),
+        ConnectionState::Connected if status.paused => "Paused".to_owned(),
+        ConnectionState::Connected if status.guests == 1 => "1 guest".to_owned(),
+        ConnectionState::Connected => format!("{} guests", status.guests),
+    };
+    format!("Remote Play Inviter: {text}")
+}
+
+/// Copies a tooltip into the fixed size buffer (cut off and null terminated)
+fn copy_tip(buffer: &mut [u16], tip: &str) {
+    let len = buffer.len() - 1;
+    buffer.fill(0);
+    for (dst, src) in buffer[..len].iter_mut().zip(tip.encode_utf16()) {
+        *dst = src;
+    }
+}
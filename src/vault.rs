@@ -0,0 +1,225 @@
+use anyhow::{Context as _, Result};
+
+/// Prefix of an encrypted token in the config file (followed by the encrypted bytes in hex)
+const SEALED_PREFIX: &str = "sealed:";
+
+/// Whether a token in the config file is encrypted
+pub fn is_sealed(value: &str) -> bool {
+    value.starts_with(SEALED_PREFIX)
+}
+
+/**
+ * Encrypts the token with a key bound to this PC and user
+ * @return The value to store in the config file
+ */
+pub fn seal(token: &str) -> Result<String> {
+    let sealed = protect(token.as_bytes())?;
+    let hex: String = sealed.iter().map(|byte| format!("{byte:02x}")).collect();
+    Ok(format!("{SEALED_PREFIX}{hex}"))
+}
+
+/// Decrypts a token stored by `seal` (fails on another PC or for another user)
+pub fn unseal(value: &str) -> Result<String> {
+    let hex = value
+        .strip_prefix(SEALED_PREFIX)
+        .context("The token is not encrypted")?;
+    if hex.len() % 2 != 0 {
+        anyhow::bail!("The encrypted token is damaged");
+    }
+    let digit = |byte: u8| char::from(byte).to_digit(16);
+    let sealed = hex
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| Some(((digit(pair[0])? << 4) | digit(pair[1])?) as u8))
+        .collect::<Option<Vec<u8>>>()
+        .context("The encrypted token is damaged")?;
+    String::from_utf8(unprotect(&sealed)?).context("The decrypted token is not text")
+}
+
+/// Encrypts with DPAPI (only the current Windows user can decrypt)
+#[cfg(windows)]
+fn protect(data: &[u8]) -> Result<Vec<u8>> {
+    use std::{io, ptr};
+    use windows_sys::Win32::Security::Cryptography::{
+        CryptProtectData, CRYPTPROTECT_UI_FORBIDDEN, CRYPT_INTEGER_BLOB,
+    };
+
+    let input = CRYPT_INTEGER_BLOB {
+        cbData: data.len() as u32,
+        pbData: data.as_ptr() as *mut u8,
+    };
+    let mut output = CRYPT_INTEGER_BLOB {
+        cbData: 0,
+        pbData: ptr::null_mut(),
+    };
+    let ok = unsafe {
+        CryptProtectData(
+            &input,
+            ptr::null(),
+            ptr::null(),
+            ptr::null(),
+            ptr::null(),
+            CRYPTPROTECT_UI_FORBIDDEN,
+            &mut output,
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error()).context("Unable to encrypt the token");
+    }
+    Ok(take_blob(output))
+}
+
+/// Decrypts with DPAPI
+#[cfg(windows)]
+fn unprotect(data: &[u8]) -> Result<Vec<u8>> {
+    use std::{io, ptr};
+    use windows_sys::Win32::Security::Cryptography::{
+        CryptUnprotectData, CRYPTPROTECT_UI_FORBIDDEN, CRYPT_INTEGER_BLOB,
+    };
+
+    let input = CRYPT_INTEGER_BLOB {
+        cbData: data.len() as u32,
+        pbData: data.as_ptr() as *mut u8,
+    };
+    let mut output = CRYPT_INTEGER_BLOB {
+        cbData: 0,
+        pbData: ptr::null_mut(),
+    };
+    let ok = unsafe {
+        CryptUnprotectData(
+            &input,
+            ptr::null_mut(),
+            ptr::null(),
+            ptr::null(),
+            ptr::null(),
+            CRYPTPROTECT_UI_FORBIDDEN,
+            &mut output,
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error())
+            .context("Unable to decrypt the token (was the config copied from another PC?)");
+    }
+    Ok(take_blob(output))
+}
+
+/// Copies the output of DPAPI and frees it
+#[cfg(windows)]
+fn take_blob(blob: windows_sys::Win32::Security::Cryptography::CRYPT_INTEGER_BLOB) -> Vec<u8> {
+    use windows_sys::Win32::Foundation::LocalFree;
+
+    let data = unsafe { std::slice::from_raw_parts(blob.pbData, blob.cbData as usize) }.to_vec();
+    unsafe { LocalFree(blob.pbData as _) };
+    data
+}
+
+/// Key that encrypts the token, kept in the OS keychain
+#[cfg(all(not(windows), feature = "keychain"))]
+mod keychain {
+    use anyhow::{Context as _, Result};
+    use keyring::Entry;
+    use ring::{
+        aead::{self, Aad, LessSafeKey, Nonce, UnboundKey, NONCE_LEN},
+        rand::{SecureRandom as _, SystemRandom},
+    };
+
+    /// Keychain entry of the key
+    const SERVICE: &str = "remoteplay-inviter";
+    const ACCOUNT: &str = "config-key";
+
+    /**
+     * Reads the key from the keychain
+     * @param create Whether to generate the key if there is none yet
+     */
+    fn key(create: bool) -> Result<LessSafeKey> {
+        let entry = Entry::new(SERVICE, ACCOUNT).context("Unable to open the keychain")?;
+        let secret = match entry.get_secret() {
+            Ok(secret) => secret,
+            Err(keyring::Error::NoEntry) if create => {
+                let mut secret = vec![0; aead::CHACHA20_POLY1305.key_len()];
+                SystemRandom::new()
+                    .fill(&mut secret)
+                    .ok()
+                    .context("Unable to generate the key")?;
+                entry
+                    .set_secret(&secret)
+                    .context("Unable to store the key in the keychain")?;
+                secret
+            }
+            Err(keyring::Error::NoEntry) => {
+                anyhow::bail!(
+                    "The key is not in the keychain (was the config copied from another PC?)"
+                )
+            }
+            Err(err) => return Err(err).context("Unable to read the key from the keychain"),
+        };
+        let key = UnboundKey::new(&aead::CHACHA20_POLY1305, &secret)
+            .ok()
+            .context("The key in the keychain is invalid")?;
+        Ok(LessSafeKey::new(key))
+    }
+
+    /// Encrypts with the key (the output is the nonce followed by the ciphertext)
+    pub fn protect(data: &[u8]) -> Result<Vec<u8>> {
+        let key = key(true)?;
+        let mut nonce = [0; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .ok()
+            .context("Unable to generate a nonce")?;
+        let mut sealed = data.to_vec();
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::empty(),
+            &mut sealed,
+        )
+        .ok()
+        .context("Unable to encrypt the token")?;
+        Ok([nonce.as_slice(), &sealed].concat())
+    }
+
+    /// Decrypts the output of `protect`
+    pub fn unprotect(data: &[u8]) -> Result<Vec<u8>> {
+        if data.len() < NONCE_LEN {
+            anyhow::bail!("The encrypted token is damaged");
+        }
+        let (nonce, sealed) = data.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce)
+            .ok()
+            .context("The encrypted token is damaged")?;
+        let mut sealed = sealed.to_vec();
+        let plain = key(false)?
+            .open_in_place(nonce, Aad::empty(), &mut sealed)
+            .ok()
+            .context("Unable to decrypt the token (was the config copied from another PC?)")?;
+        Ok(plain.to_vec())
+    }
+}
+
+#[cfg(all(not(windows), feature = "keychain"))]
+use keychain::{protect, unprotect};
+
+#[cfg(all(not(windows), not(feature = "keychain")))]
+fn protect(_data: &[u8]) -> Result<Vec<u8>> {
+    anyhow::bail!("Encrypting the token needs the keychain feature on this platform")
+}
+
+#[cfg(all(not(windows), not(feature = "keychain")))]
+fn unprotect(_data: &[u8]) -> Result<Vec<u8>> {
+    anyhow::bail!(
+        "The token is encrypted, but this build has no keychain support (keychain feature)"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn damaged_tokens_are_refused() {
+        for value in ["sealed:abc", "sealed:zz", "sealed:+f", "sealed:aé0"] {
+            let err = unseal(value).unwrap_err();
+            assert_eq!(err.to_string(), "The encrypted token is damaged", "{value}");
+        }
+    }
+}
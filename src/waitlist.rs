@@ -0,0 +1,30 @@
+use std::sync::Arc;
+use tokio::{sync::broadcast::error::RecvError, task::JoinHandle};
+
+use crate::{config::WaitlistConfig, events::Event, handlers::Handler};
+
+/// Reports the free guest slots to the servers' waitlists whenever they change
+pub fn spawn(handler: Arc<Handler>, config: &WaitlistConfig) -> Option<JoinHandle<()>> {
+    if config.slots == 0 {
+        return None;
+    }
+    let mut events = handler.subscribe();
+    Some(tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(
+                    Event::GuestJoined { .. }
+                    | Event::GuestPending { .. }
+                    | Event::GuestDenied { .. }
+                    | Event::GuestLeft { .. }
+                    | Event::InviteCreated { .. }
+                    | Event::InviteRevoked { .. },
+                )
+                // Missed events may have changed the slots
+                | Err(RecvError::Lagged(_)) => handler.report_slots().await,
+                Ok(_) => {}
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }))
+}
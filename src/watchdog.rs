@@ -0,0 +1,124 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    task::JoinHandle,
+    time::{interval, MissedTickBehavior},
+};
+
+use crate::{
+    console::{self, Glyph},
+    handlers::Handler,
+    logger::{self, Level},
+};
+
+/// Checks kept to find the lowest recent value of each count
+const WINDOW: usize = 12;
+/// Counts below this are never reported
+const MIN_REPORTED: usize = 64;
+
+/// Counts that must stay bounded while the app runs
+pub struct Usage {
+    /// Live tokio tasks
+    pub tasks: usize,
+    /// Receivers of the handler events
+    pub subscribers: usize,
+    /// Active invite links
+    pub invites: usize,
+    /// Known guest names
+    pub guests: usize,
+    /// Guests playing
+    pub players: usize,
+    /// Invites that used up their joins
+    pub exhausted: usize,
+    /// Cached Steam names
+    pub personas: usize,
+    /// Requests waiting for the host's approval
+    pub approvals: usize,
+    /// Messages waiting to be sent to the servers
+    pub notices: usize,
+}
+
+impl Usage {
+    /// Name and value of each count
+    fn entries(&self) -> [(&'static str, usize); 9] {
+        [
+            ("tasks", self.tasks),
+            ("subscribers", self.subscribers),
+            ("invites", self.invites),
+            ("guests", self.guests),
+            ("players", self.players),
+            ("exhausted", self.exhausted),
+            ("personas", self.personas),
+            ("approvals", self.approvals),
+            ("notices", self.notices),
+        ]
+    }
+}
+
+/// Recent values of a count
+#[derive(Default)]
+struct Trend {
+    samples: VecDeque<usize>,
+    /// Value of the last warning (0: none)
+    warned: usize,
+}
+
+impl Trend {
+    /**
+     * Adds a value
+     * @return The lowest recent value if the count kept growing (at least doubled within the window)
+     */
+    fn push(&mut self, value: usize) -> Option<usize> {
+        self.samples.push_back(value);
+        if self.samples.len() > WINDOW {
+            self.samples.pop_front();
+        }
+        if value * 2 <= self.warned {
+            self.warned = 0;
+        }
+        let low = self.samples.iter().copied().min().unwrap_or(value);
+        if self.samples.len() < WINDOW
+            || value < MIN_REPORTED
+            || value < low * 2
+            || value < self.warned * 2
+        {
+            return None;
+        }
+        self.warned = value;
+        Some(low)
+    }
+}
+
+/// Logs the task and queue counts and warns when one keeps growing (a leak in a long session)
+pub fn spawn(handler: Arc<Handler>, secs: u64) -> Option<JoinHandle<()>> {
+    if secs == 0 {
+        return None;
+    }
+    Some(tokio::spawn(async move {
+        let mut trends = HashMap::<&'static str, Trend>::new();
+        let mut ticker = interval(Duration::from_secs(secs));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let entries = handler.usage().await.entries();
+            let line = entries
+                .iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            logger::write(Level::Info, format_args!("Usage: {line}"));
+
+            for (name, value) in entries {
+                if let Some(low) = trends.entry(name).or_default().push(value) {
+                    let _ = console::eprintln!(
+                        "{} The number of {name} keeps growing ({low} -> {value}). Please report it with the log file",
+                        Glyph::Warn
+                    );
+                }
+            }
+        }
+    }))
+}
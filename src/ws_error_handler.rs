@@ -1,13 +1,179 @@
-use crate::{console, ConnectionErrorMessage, ConnectionErrorType, VERSION};
+use crate::{
+    config,
+    console::{self, Glyph},
+    error::{ClientError, ClientResult, ErrorKind as _},
+    ConnectionErrorMessage, ConnectionErrorType, VERSION,
+};
 use anyhow::{anyhow, Context as _, Result};
-use tokio_tungstenite::tungstenite::Error as WsError;
+use chrono::{DateTime, Utc};
+use std::io;
+use tokio_tungstenite::tungstenite::{
+    handshake::client::Response,
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    Error as WsError,
+};
+
+/// Close code for a client that was kicked or banned by the server
+const CLOSE_KICKED: u16 = 4003;
+/// Close code for a client that has to reconnect to another server (the reason is the new URL)
+const CLOSE_MOVED: u16 = 4010;
+
+/// What to do after the server refused the connection
+pub enum RefusedAction {
+    /// Stop reconnecting (the client is outdated or was refused with a message)
+    Stop,
+    /// Reconnect asking for a pairing code to link the token again
+    Relink,
+}
+
+/// What to do after the server closed the connection
+pub enum CloseAction {
+    /// Reconnect after the usual wait (without the error for a planned shutdown)
+    Reconnect { planned: bool },
+    /// Stop reconnecting to the server
+    Stop(String),
+    /// Reconnect to the server of another URL after a short wait
+    Redirect(String),
+}
+
+/// Reads the close code and reason sent by the server
+pub fn close_action(frame: Option<&CloseFrame>) -> CloseAction {
+    let Some(frame) = frame else {
+        return CloseAction::Reconnect { planned: false };
+    };
+    let reason = frame.reason.trim();
+    match frame.code {
+        CloseCode::Normal | CloseCode::Away | CloseCode::Restart | CloseCode::Again => {
+            CloseAction::Reconnect { planned: true }
+        }
+        CloseCode::Policy => CloseAction::Stop(reason.to_owned()),
+        CloseCode::Library(CLOSE_KICKED) => CloseAction::Stop(reason.to_owned()),
+        CloseCode::Library(CLOSE_MOVED)
+            if reason.starts_with("ws://") || reason.starts_with("wss://") =>
+        {
+            CloseAction::Redirect(reason.to_owned())
+        }
+        _ => CloseAction::Reconnect { planned: false },
+    }
+}
+
+/**
+ * Reads the Retry-After header of a refused connection
+ * @return Seconds to wait before reconnecting
+ */
+pub fn retry_after(err: &WsError) -> Option<u64> {
+    let WsError::Http(res) = err else {
+        return None;
+    };
+    let value = res.headers().get("Retry-After")?.to_str().ok()?.trim();
+    // Either a number of seconds or an HTTP date
+    if let Ok(sec) = value.parse::<u64>() {
+        return Some(sec);
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    let sec = (date.with_timezone(&Utc) - Utc::now()).num_seconds();
+    Some(sec.max(0) as u64)
+}
+
+/// Edge protection (CDN or proxy in front of the server) that refused the connection
+enum EdgeBlock {
+    /// A login page of an authenticating proxy (Cloudflare Access)
+    Login,
+    /// A browser challenge of the bot protection (by the named provider)
+    Challenge(&'static str),
+}
+
+/// Recognizes the responses of common edge protections
+fn edge_block(res: &Response) -> Option<EdgeBlock> {
+    let header = |name: &str| {
+        res.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+    };
+    if header("location").contains("cloudflareaccess.com")
+        || res.headers().contains_key("cf-access-domain")
+    {
+        return Some(EdgeBlock::Login);
+    }
+    if res.status() != 403 && res.status() != 503 {
+        return None;
+    }
+    let body = res
+        .body()
+        .as_deref()
+        .map(String::from_utf8_lossy)
+        .unwrap_or_default();
+    let server = header("server");
+    if header("cf-mitigated") == "challenge"
+        || (server == "cloudflare"
+            && (body.contains("challenge-platform") || body.contains("Just a moment")))
+    {
+        Some(EdgeBlock::Challenge("Cloudflare"))
+    } else if server.starts_with("akamaighost") && body.contains("Access Denied") {
+        Some(EdgeBlock::Challenge("Akamai"))
+    } else if res.headers().contains_key("x-amz-cf-id") && res.status() == 403 {
+        Some(EdgeBlock::Challenge("Amazon CloudFront"))
+    } else {
+        None
+    }
+}
+
+/// Explains how to get through the edge protection in front of a self-hosted server
+fn print_edge_guidance(block: &EdgeBlock, status: u16) -> ClientResult<()> {
+    let path = config::get_exe_path()
+        .config()?
+        .with_extension("endpoint.toml");
+    match block {
+        EdgeBlock::Login => console::urgent_printdoc! {"
+
+            {error} The server is behind Cloudflare Access, which asks for a login ({status})
+              Ask the server owner for a service token and add it to {path}:
+                [headers]
+                CF-Access-Client-Id = \"<client id>.access\"
+                CF-Access-Client-Secret = \"<client secret>\"
+
+            ",
+            error = Glyph::Err,
+            path = path.display()
+        },
+        EdgeBlock::Challenge(provider) => console::urgent_printdoc! {"
+
+            {error} {provider} blocked the connection with a browser challenge ({status})
+              The inviter cannot solve challenges. The server owner can either
+                - skip the bot protection for the /ws path (e.g. a WAF skip rule), or
+                - give you a service token header to add under [headers] in {path}
+
+            ",
+            error = Glyph::Err,
+            path = path.display()
+        },
+    }
+    .console()
+}
+
+/**
+ * Handle WebSocket errors
+ * @return Ok when the server refused the client (outdated, unlinked or refused with a message)
+ */
+pub fn handle_ws_error(err: WsError) -> ClientResult<RefusedAction> {
+    // A CDN or proxy in front of the server refused the connection: retrying does not help
+    if let WsError::Http(res) = &err {
+        if let Some(block) = edge_block(res) {
+            print_edge_guidance(&block, res.status().as_u16())?;
+            let _ = console::bell();
+            return Err(ClientError::Config(anyhow!(
+                "Connection blocked before reaching the server ({})",
+                res.status()
+            )));
+        }
+    }
 
-/// Handle WebSocket errors
-pub fn handle_ws_error(err: WsError) -> Result<()> {
     match err {
         // In case of Bad Request
         WsError::Http(res) if res.status() == 400 => {
-            let result: Result<()> = 'tryblock: {
+            let result: Result<RefusedAction> = 'tryblock: {
                 // Get the response body
                 let header = match res
                     .headers()
@@ -35,18 +201,46 @@ pub fn handle_ws_error(err: WsError) -> Result<()> {
                     // If the version is outdated
                     ConnectionErrorType::Outdated { required, download } => {
                         // Display the content
-                        if let Err(err) = console::printdoc! {"
+                        if let Err(err) = console::urgent_printdoc! {"
 
-                            ↑ Update required: {VERSION} to {required}
+                            {update} Update required: {VERSION} to {required}
                               Download: {download}
 
-                            "}
+                            ", update = Glyph::Update}
                         {
                             break 'tryblock Err(err);
                         }
+                        let _ = console::bell();
 
-                        // Open the browser
-                        let _ = webbrowser::open(&download);
+                        // Open the browser (unless it would steal the focus from the game)
+                        if !console::is_do_not_disturb() {
+                            let _ = webbrowser::open(&download);
+                        }
+                    }
+                    // The token was unlinked in Discord: pair it again instead of giving up
+                    ConnectionErrorType::Unlinked { link } => {
+                        if let Err(err) = console::urgent_printdoc! {"
+
+                            {warn} This client is no longer linked with a Discord account
+                              Enter the pairing code shown next in Discord to link it again{page}
+
+                            ",
+                            warn = Glyph::Warn,
+                            page = link
+                                .as_ref()
+                                .map(|link| format!("\n  Link page: {link}"))
+                                .unwrap_or_default()
+                        } {
+                            break 'tryblock Err(err);
+                        }
+                        let _ = console::bell();
+
+                        if let Some(link) = link {
+                            if !console::is_do_not_disturb() {
+                                let _ = webbrowser::open(&link);
+                            }
+                        }
+                        break 'tryblock Ok(RefusedAction::Relink);
                     }
                     // For other errors
                     _ => {
@@ -59,13 +253,14 @@ pub fn handle_ws_error(err: WsError) -> Result<()> {
                                 .join("\n");
 
                             // Display the error message
-                            if let Err(err) = console::printdoc! {
+                            if let Err(err) = console::urgent_printdoc! {
                                 "
 
-                                    ☓ Connection error:
+                                    {error} Connection error:
                                     {message}
 
-                                    "
+                                    ",
+                                error = Glyph::Err
                             } {
                                 break 'tryblock Err(err);
                             }
@@ -73,19 +268,135 @@ pub fn handle_ws_error(err: WsError) -> Result<()> {
                     }
                 }
 
-                Ok(())
+                Ok(RefusedAction::Stop)
             };
 
-            if let Err(err) = result {
+            match result {
+                Ok(action) => return Ok(action),
                 // If parsing fails
-                console::eprintln!("☓ {err}")?;
+                Err(err) => console::eprintln!("{} {err}", Glyph::Err).console()?,
             }
         }
+        // The server limits how often clients connect (the Retry-After wait is applied by the caller)
+        WsError::Http(res) if res.status() == 429 => {
+            return Err(ClientError::Network(anyhow!(
+                "The server is busy ({}). Waiting before reconnecting",
+                res.status()
+            )))
+        }
+        // The token was revoked or the client was banned: retrying does not help
+        WsError::Http(res) if res.status() == 401 || res.status() == 403 => {
+            let program = std::env::current_exe()
+                .map(|path| path.display().to_string())
+                .unwrap_or_else(|_| "remoteplay-inviter".to_owned());
+            console::urgent_printdoc! {"
+
+                {error} Connection refused by the server ({status})
+                  The token of this client was revoked or the client was banned.
+                  To register again with a new token (the other settings are kept):
+                    1. Close this window
+                    2. Run: {program} token rotate
+                    3. Start the inviter again and link it with the Discord bot
+
+                ",
+                error = Glyph::Err,
+                status = res.status(),
+                program = program
+            }
+            .console()?;
+            let _ = console::bell();
+            return Err(ClientError::Config(anyhow!(
+                "Connection refused by the server ({})",
+                res.status()
+            )));
+        }
         // For other HTTP errors
-        WsError::Http(res) => Err(anyhow!("HTTP error: {}", res.status()))?,
+        WsError::Http(res) => {
+            return Err(ClientError::Network(anyhow!(
+                "HTTP error: {}",
+                res.status()
+            )))
+        }
+        // The server has no address of the IP version the connections are limited to
+        WsError::Io(io_err) if io_err.kind() == io::ErrorKind::AddrNotAvailable => {
+            return Err(ClientError::Network(anyhow!(
+                "Failed to connect to the server: {io_err}"
+            )))
+        }
         // For other errors
-        _ => Err(err).context("Failed to connect to the server")?,
+        _ => Err(err)
+            .context("Failed to connect to the server")
+            .network()?,
     }
 
-    Ok(())
+    Ok(RefusedAction::Stop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_tungstenite::tungstenite::http;
+
+    fn close(code: u16, reason: &str) -> CloseAction {
+        close_action(Some(&CloseFrame {
+            code: CloseCode::from(code),
+            reason: reason.to_owned().into(),
+        }))
+    }
+
+    fn refused(retry_after: Option<&str>) -> WsError {
+        let mut res = http::Response::builder().status(503);
+        if let Some(value) = retry_after {
+            res = res.header("Retry-After", value);
+        }
+        WsError::Http(res.body(None).unwrap())
+    }
+
+    #[test]
+    fn kicked_clients_stop_reconnecting() {
+        assert!(matches!(close(4003, " banned "), CloseAction::Stop(reason) if reason == "banned"));
+        assert!(matches!(close(1008, "outdated"), CloseAction::Stop(_)));
+    }
+
+    #[test]
+    fn moved_clients_follow_the_new_url() {
+        assert!(matches!(
+            close(4010, "wss://other.example.com/ws"),
+            CloseAction::Redirect(url) if url == "wss://other.example.com/ws"
+        ));
+        // Only WebSocket URLs are followed
+        assert!(matches!(
+            close(4010, "https://example.com"),
+            CloseAction::Reconnect { planned: false }
+        ));
+    }
+
+    #[test]
+    fn other_closes_reconnect() {
+        assert!(matches!(
+            close(1001, ""),
+            CloseAction::Reconnect { planned: true }
+        ));
+        assert!(matches!(
+            close(4999, ""),
+            CloseAction::Reconnect { planned: false }
+        ));
+        assert!(matches!(
+            close_action(None),
+            CloseAction::Reconnect { planned: false }
+        ));
+    }
+
+    #[test]
+    fn retry_after_takes_seconds_or_a_date() {
+        assert_eq!(retry_after(&refused(Some(" 120 "))), Some(120));
+        let later = (Utc::now() + chrono::Duration::seconds(90)).to_rfc2822();
+        let sec = retry_after(&refused(Some(&later))).unwrap();
+        assert!((85..=90).contains(&sec), "{sec}");
+        let past = (Utc::now() - chrono::Duration::hours(1)).to_rfc2822();
+        assert_eq!(retry_after(&refused(Some(&past))), Some(0));
+        assert_eq!(retry_after(&refused(Some("soon"))), None);
+        assert_eq!(retry_after(&refused(None)), None);
+        assert_eq!(retry_after(&WsError::ConnectionClosed), None);
+    }
 }
@@ -1,6 +1,10 @@
 use cmake;
 
 fn main() {
+    // The stub backend needs no native library
+    if std::env::var_os("CARGO_FEATURE_NO_STEAM").is_some() {
+        return;
+    }
     let dst = cmake::build("cmake");
     println!("cargo:rustc-link-search=native={}", dst.display());
     println!("cargo:rustc-link-lib=static=cmake");
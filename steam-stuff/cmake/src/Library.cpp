@@ -35,6 +35,15 @@ bool SteamStuff_CanRemotePlayTogether(uint64_t gameID)
 	return GClientContext()->AppManager()->BCanRemotePlayTogether(CGameID(uint64(gameID)).AppID());
 }
 
+const char* SteamStuff_GetPersonaName(uint64_t steamID)
+{
+	CSteamID user = CSteamID(uint64(steamID));
+	// True while Steam is still fetching the name of a user who is not a friend
+	if (GClientContext()->SteamFriends()->RequestUserInformation(user, true))
+		return nullptr;
+	return GClientContext()->SteamFriends()->GetFriendPersonaName(user);
+}
+
 
 // RemotePlayInviteHandler functions
 
@@ -48,6 +57,11 @@ void SteamStuff_CancelInvite(uint64_t invitee, uint64_t guestID)
 	GRemotePlayInviteHandler()->CancelInvite(CSteamID(uint64(invitee)), guestID);
 }
 
+bool SteamStuff_IsSessionActive()
+{
+	return GClientContext()->RemoteClientManager()->BIsStreamingSessionActive();
+}
+
 void SteamStuff_SetOnRemoteInvited(OnRemoteInvited cb)
 {
 	GRemotePlayInviteHandler()->m_onRemoteInvited = cb;
@@ -63,6 +77,11 @@ void SteamStuff_SetOnRemoteStopped(OnRemoteStopped cb)
 	GRemotePlayInviteHandler()->m_onRemoteStopped = cb;
 }
 
+void SteamStuff_SetOnRemoteFailed(OnRemoteFailed cb)
+{
+	GRemotePlayInviteHandler()->m_onRemoteFailed = cb;
+}
+
 
 #ifdef __cplusplus
 }
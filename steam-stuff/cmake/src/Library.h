@@ -12,12 +12,15 @@ void SteamStuff_Shutdown();
 void SteamStuff_RunCallbacks();
 uint64_t SteamStuff_GetRunningGameID();
 bool SteamStuff_CanRemotePlayTogether(uint64_t gameID);
+const char* SteamStuff_GetPersonaName(uint64_t steamID);
 
 uint64_t SteamStuff_SendInvite(uint64_t invitee, uint64_t gameID);
 void SteamStuff_CancelInvite(uint64_t invitee, uint64_t guestID);
+bool SteamStuff_IsSessionActive();
 void SteamStuff_SetOnRemoteInvited(OnRemoteInvited cb);
 void SteamStuff_SetOnRemoteStarted(OnRemoteStarted cb);
 void SteamStuff_SetOnRemoteStopped(OnRemoteStopped cb);
+void SteamStuff_SetOnRemoteFailed(OnRemoteFailed cb);
 
 #ifdef __cplusplus
 }
@@ -7,7 +7,8 @@ RemotePlayInviteHandler::RemotePlayInviteHandler() :
 	m_remoteStartedCb(this, &RemotePlayInviteHandler::OnRemotePlayStarted),
 	m_remoteStoppedCb(this, &RemotePlayInviteHandler::OnRemotePlayStopped),
 	m_onRemoteInvited(nullptr),
-	m_onRemoteStopped(nullptr)
+	m_onRemoteStopped(nullptr),
+	m_onRemoteFailed(nullptr)
 {
 }
 
@@ -48,6 +49,14 @@ void RemotePlayInviteHandler::OnRemotePlayInvited(RemotePlayInviteResult_t* cb)
 			m_onRemoteInvited(cb->m_player.m_playerID.ConvertToUint64(), cb->m_player.m_guestID, cb->m_szConnectURL);
 		}
 	}
+	else
+	{
+		// Call the invite failed callback
+		if (m_onRemoteFailed)
+		{
+			m_onRemoteFailed(cb->m_player.m_playerID.ConvertToUint64(), cb->m_player.m_guestID, (int32_t)cb->m_eResult);
+		}
+	}
 }
 
 void RemotePlayInviteHandler::OnRemotePlayStarted(StreamingClientConnected_t* cb)
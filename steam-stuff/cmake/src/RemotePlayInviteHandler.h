@@ -49,6 +49,7 @@ public:
 	OnRemoteInvited m_onRemoteInvited;
 	OnRemoteStarted m_onRemoteStarted;
 	OnRemoteStopped m_onRemoteStopped;
+	OnRemoteFailed m_onRemoteFailed;
 
 private:
 	STEAM_CALLBACK(RemotePlayInviteHandler, OnRemotePlayInvited, RemotePlayInviteResult_t, m_remoteInvitedCb);
@@ -26,4 +26,12 @@ typedef void (*OnRemoteStarted)(uint64_t invitee, uint64_t guestID);
 */
 typedef void (*OnRemoteStopped)(uint64_t invitee, uint64_t guestID);
 
+/**
+	@brief Callback for when Steam could not create a Remote Play invite.
+	@param invitee The Steam ID of the invitee.
+	@param guestID The guest ID of the invitee.
+	@param result The launch result reported by Steam.
+*/
+typedef void (*OnRemoteFailed)(uint64_t invitee, uint64_t guestID, int32_t result);
+
 #endif // CMAKE_TYPES_H
@@ -0,0 +1,39 @@
+use crate::GameID;
+use anyhow::Result;
+
+/// Callback with the invitee, the guest ID and the invite link
+pub type OnInvited = Box<dyn Fn(u64, u64, &str) + Send + Sync>;
+/// Callback with the invitee and the guest ID
+pub type OnSession = Box<dyn Fn(u64, u64) + Send + Sync>;
+/// Callback with the invitee, the guest ID and the launch result
+pub type OnFailed = Box<dyn Fn(u64, u64, i32) + Send + Sync>;
+
+/// Remote Play features of the Steam client
+pub trait Steam: Send {
+    /// Shuts Steam down and initializes it again (the callbacks must be set again)
+    fn reinit(&mut self) -> Result<()>;
+
+    fn run_callbacks(&self);
+
+    fn get_running_game_id(&self) -> GameID;
+
+    fn can_remote_play_together(&self, game_id: u64) -> bool;
+
+    /// Persona name of a Steam user (None while Steam is still fetching it)
+    fn persona_name(&self, steam_id: u64) -> Option<String>;
+
+    /// Asks for an invite link (0 if Steam refused, the link comes with the invited callback)
+    fn send_invite(&self, invitee: u64, game_id: u64) -> u64;
+
+    fn cancel_invite(&self, invitee: u64, guest_id: u64);
+
+    fn is_session_active(&self) -> bool;
+
+    fn set_on_remote_invited(&self, callback: OnInvited);
+
+    fn set_on_remote_started(&self, callback: OnSession);
+
+    fn set_on_remote_stopped(&self, callback: OnSession);
+
+    fn set_on_remote_failed(&self, callback: OnFailed);
+}
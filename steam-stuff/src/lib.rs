@@ -1,9 +1,25 @@
+mod backend;
 mod game_id;
+#[cfg(not(feature = "no-steam"))]
 mod native;
+#[cfg(feature = "no-steam")]
+mod no_steam;
+#[cfg(not(feature = "no-steam"))]
 mod steam_stuff;
 
+pub use backend::{OnFailed, OnInvited, OnSession, Steam};
 pub use game_id::{GameID, GameUID};
+#[cfg(feature = "no-steam")]
+pub use no_steam::NoSteam;
+#[cfg(not(feature = "no-steam"))]
 pub use steam_stuff::SteamStuff;
 
+/// Steam backend of this build (the stub without Steam with the no-steam feature)
+#[cfg(not(feature = "no-steam"))]
+pub type Backend = SteamStuff;
+#[cfg(feature = "no-steam")]
+pub type Backend = NoSteam;
+
 // extern crate to link C++ library
+#[cfg(not(feature = "no-steam"))]
 extern crate link_cplusplus;
@@ -9,15 +9,22 @@ pub type OnRemoteStarted = ::std::option::Option<unsafe extern "C" fn(invitee: u
 #[doc = "@brief Callback for when a Remote Play session is closed.\n@param invitee The Steam ID of the invitee.\n@param guestID The guest ID of the invitee."]
 pub type OnRemoteStopped = ::std::option::Option<unsafe extern "C" fn(invitee: u64, guestID: u64)>;
 
+#[doc = "@brief Callback for when Steam could not create a Remote Play invite.\n@param invitee The Steam ID of the invitee.\n@param guestID The guest ID of the invitee.\n@param result The launch result reported by Steam."]
+pub type OnRemoteFailed =
+    ::std::option::Option<unsafe extern "C" fn(invitee: u64, guestID: u64, result: i32)>;
+
 extern "C" {
     pub fn SteamStuff_Init() -> bool;
     pub fn SteamStuff_Shutdown();
     pub fn SteamStuff_RunCallbacks();
     pub fn SteamStuff_GetRunningGameID() -> u64;
     pub fn SteamStuff_CanRemotePlayTogether(gameID: u64) -> bool;
+    pub fn SteamStuff_GetPersonaName(steamID: u64) -> *const ::std::os::raw::c_char;
     pub fn SteamStuff_SendInvite(invitee: u64, gameID: u64) -> u64;
     pub fn SteamStuff_CancelInvite(invitee: u64, guestID: u64);
+    pub fn SteamStuff_IsSessionActive() -> bool;
     pub fn SteamStuff_SetOnRemoteInvited(cb: OnRemoteInvited);
     pub fn SteamStuff_SetOnRemoteStarted(cb: OnRemoteStarted);
     pub fn SteamStuff_SetOnRemoteStopped(cb: OnRemoteStopped);
+    pub fn SteamStuff_SetOnRemoteFailed(cb: OnRemoteFailed);
 }
@@ -0,0 +1,55 @@
+use crate::{
+    backend::{OnFailed, OnInvited, OnSession},
+    GameID, Steam,
+};
+use anyhow::Result;
+
+/// Stand-in for Steam on platforms where the Steamworks library does not link (no-steam feature)
+/// No game is ever running and every invite is refused, so the client only relays the server
+pub struct NoSteam {
+    _private: (),
+}
+
+impl NoSteam {
+    pub fn new() -> Result<Self> {
+        Ok(NoSteam { _private: () })
+    }
+}
+
+impl Steam for NoSteam {
+    fn reinit(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn run_callbacks(&self) {}
+
+    fn get_running_game_id(&self) -> GameID {
+        GameID::new(0, 0, 0)
+    }
+
+    fn can_remote_play_together(&self, _game_id: u64) -> bool {
+        false
+    }
+
+    fn persona_name(&self, _steam_id: u64) -> Option<String> {
+        None
+    }
+
+    fn send_invite(&self, _invitee: u64, _game_id: u64) -> u64 {
+        0
+    }
+
+    fn cancel_invite(&self, _invitee: u64, _guest_id: u64) {}
+
+    fn is_session_active(&self) -> bool {
+        false
+    }
+
+    fn set_on_remote_invited(&self, _callback: OnInvited) {}
+
+    fn set_on_remote_started(&self, _callback: OnSession) {}
+
+    fn set_on_remote_stopped(&self, _callback: OnSession) {}
+
+    fn set_on_remote_failed(&self, _callback: OnFailed) {}
+}
@@ -1,4 +1,7 @@
-use crate::{native, GameID};
+use crate::{
+    backend::{OnFailed, OnInvited, OnSession},
+    native, GameID, Steam,
+};
 use anyhow::Result;
 use std::ffi::CStr;
 use std::os::raw::c_char;
@@ -8,45 +11,94 @@ static ON_REMOTE_INVITED: Mutex<Option<Arc<dyn Fn(u64, u64, &str) + Send + Sync>
     Mutex::new(None);
 static ON_REMOTE_STARTED: Mutex<Option<Arc<dyn Fn(u64, u64) + Send + Sync>>> = Mutex::new(None);
 static ON_REMOTE_STOPPED: Mutex<Option<Arc<dyn Fn(u64, u64) + Send + Sync>>> = Mutex::new(None);
+static ON_REMOTE_FAILED: Mutex<Option<Arc<dyn Fn(u64, u64, i32) + Send + Sync>>> = Mutex::new(None);
 
 pub struct SteamStuff {
-    _private: (),
+    /// SteamStuff_Init succeeded and SteamStuff_Shutdown was not called since
+    initialized: bool,
 }
 
 impl SteamStuff {
     pub fn new() -> Result<Self> {
         if unsafe { native::SteamStuff_Init() } {
-            Ok(SteamStuff { _private: () })
+            Ok(SteamStuff { initialized: true })
         } else {
             Err(anyhow::anyhow!("Failed to initialize SteamStuff"))
         }
     }
 
-    pub fn run_callbacks(&self) {
-        unsafe { native::SteamStuff_RunCallbacks() }
+    /// Shuts SteamStuff down once (a failed reinit already left it shut down)
+    fn shutdown(&mut self) {
+        if std::mem::take(&mut self.initialized) {
+            unsafe { native::SteamStuff_Shutdown() }
+        }
+    }
+}
+
+impl Steam for SteamStuff {
+    fn reinit(&mut self) -> Result<()> {
+        self.shutdown();
+        if unsafe { native::SteamStuff_Init() } {
+            self.initialized = true;
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("Failed to initialize SteamStuff"))
+        }
     }
 
-    pub fn get_running_game_id(&self) -> GameID {
+    // The native calls use the Steam interfaces released by the shutdown, so after a failed
+    // reinit they are skipped until a reinit succeeds
+
+    fn run_callbacks(&self) {
+        if self.initialized {
+            unsafe { native::SteamStuff_RunCallbacks() }
+        }
+    }
+
+    fn get_running_game_id(&self) -> GameID {
+        if !self.initialized {
+            return GameID::new(0, 0, 0);
+        }
         unsafe { GameID::from(native::SteamStuff_GetRunningGameID()) }
     }
 
-    pub fn can_remote_play_together(&self, game_id: u64) -> bool {
-        unsafe { native::SteamStuff_CanRemotePlayTogether(game_id) }
+    fn can_remote_play_together(&self, game_id: u64) -> bool {
+        self.initialized && unsafe { native::SteamStuff_CanRemotePlayTogether(game_id) }
     }
 
-    pub fn send_invite(&self, invitee: u64, game_id: u64) -> u64 {
+    fn persona_name(&self, steam_id: u64) -> Option<String> {
+        if !self.initialized {
+            return None;
+        }
+        let name = unsafe { native::SteamStuff_GetPersonaName(steam_id) };
+        if name.is_null() {
+            return None;
+        }
+        let name = unsafe { CStr::from_ptr(name) }
+            .to_string_lossy()
+            .into_owned();
+        (!name.is_empty()).then_some(name)
+    }
+
+    fn send_invite(&self, invitee: u64, game_id: u64) -> u64 {
+        if !self.initialized {
+            return 0;
+        }
         unsafe { native::SteamStuff_SendInvite(invitee, game_id) }
     }
 
-    pub fn cancel_invite(&self, invitee: u64, guest_id: u64) {
-        unsafe { native::SteamStuff_CancelInvite(invitee, guest_id) }
+    fn cancel_invite(&self, invitee: u64, guest_id: u64) {
+        if self.initialized {
+            unsafe { native::SteamStuff_CancelInvite(invitee, guest_id) }
+        }
     }
 
-    pub fn set_on_remote_invited<F>(&self, callback: F)
-    where
-        F: Fn(u64, u64, &str) + Send + Sync + 'static,
-    {
-        let cb = Arc::new(callback);
+    fn is_session_active(&self) -> bool {
+        self.initialized && unsafe { native::SteamStuff_IsSessionActive() }
+    }
+
+    fn set_on_remote_invited(&self, callback: OnInvited) {
+        let cb: Arc<dyn Fn(u64, u64, &str) + Send + Sync> = Arc::from(callback);
         let mut guard = ON_REMOTE_INVITED.lock().unwrap();
         *guard = Some(cb.clone());
 
@@ -62,11 +114,8 @@ impl SteamStuff {
         unsafe { native::SteamStuff_SetOnRemoteInvited(Some(trampoline)) }
     }
 
-    pub fn set_on_remote_started<F>(&self, callback: F)
-    where
-        F: Fn(u64, u64) + Send + Sync + 'static,
-    {
-        let cb = Arc::new(callback);
+    fn set_on_remote_started(&self, callback: OnSession) {
+        let cb: Arc<dyn Fn(u64, u64) + Send + Sync> = Arc::from(callback);
         let mut guard = ON_REMOTE_STARTED.lock().unwrap();
         *guard = Some(cb.clone());
 
@@ -80,11 +129,8 @@ impl SteamStuff {
         unsafe { native::SteamStuff_SetOnRemoteStarted(Some(trampoline)) }
     }
 
-    pub fn set_on_remote_stopped<F>(&self, callback: F)
-    where
-        F: Fn(u64, u64) + Send + Sync + 'static,
-    {
-        let cb = Arc::new(callback);
+    fn set_on_remote_stopped(&self, callback: OnSession) {
+        let cb: Arc<dyn Fn(u64, u64) + Send + Sync> = Arc::from(callback);
         let mut guard = ON_REMOTE_STOPPED.lock().unwrap();
         *guard = Some(cb.clone());
 
@@ -97,10 +143,25 @@ impl SteamStuff {
 
         unsafe { native::SteamStuff_SetOnRemoteStopped(Some(trampoline)) }
     }
+
+    fn set_on_remote_failed(&self, callback: OnFailed) {
+        let cb: Arc<dyn Fn(u64, u64, i32) + Send + Sync> = Arc::from(callback);
+        let mut guard = ON_REMOTE_FAILED.lock().unwrap();
+        *guard = Some(cb.clone());
+
+        unsafe extern "C" fn trampoline(invitee: u64, guest_id: u64, result: i32) {
+            let cb = ON_REMOTE_FAILED.lock().unwrap();
+            if let Some(cb) = &*cb {
+                cb(invitee, guest_id, result);
+            }
+        }
+
+        unsafe { native::SteamStuff_SetOnRemoteFailed(Some(trampoline)) }
+    }
 }
 
 impl Drop for SteamStuff {
     fn drop(&mut self) {
-        unsafe { native::SteamStuff_Shutdown() }
+        self.shutdown();
     }
 }